@@ -28,7 +28,7 @@ async fn main() -> anyhow::Result<()> {
         .stderr(Stdio::inherit())
         .args(["-hwaccel", "auto"])
         .input_with_pipe(&mut input).unwrap()
-            .format("flv")
+            .force_format("flv")
             .done()
         .output_with_pipe(&mut output).unwrap()
             .format("webm")
@@ -52,7 +52,7 @@ async fn main() -> anyhow::Result<()> {
                     Err(err) => panic!("{err}"),
                 };
 
-                input_listener.write(&buffer).unwrap();
+                input_listener.write_all(&buffer[..len]).unwrap();
 
                 if len < buffer.len() { break };
             }
@@ -66,11 +66,8 @@ async fn main() -> anyhow::Result<()> {
             move || {
                 loop {
                     let progress = ffmpeg_progress.as_mut().expect("FFmpeg is not started yet").blocking_recv().unwrap();
-                    match progress.progress {
-                        Some(progress) => if let essi_ffmpeg::FFmpegProgressStatus::End = progress {
-                            break;
-                        },
-                        _ => { }
+                    if let Some(essi_ffmpeg::FFmpegProgressStatus::End) = progress.progress {
+                        break;
                     }
                 }
     
@@ -87,13 +84,13 @@ async fn main() -> anyhow::Result<()> {
             while is_encoding.load(Ordering::Acquire) {
                 let mut buffer = [0u8; 64];
 
-                match output_listener.read(&mut buffer) {
+                let len = match output_listener.read(&mut buffer) {
                     Ok(len) => len,
                     Err(ref err) if err.kind() == ErrorKind::WouldBlock => { continue },
                     Err(err) => panic!("{err}"),
                 };
 
-                output_video.write(&buffer).unwrap();
+                output_video.write_all(&buffer[..len]).unwrap();
             }
         });
     });
@@ -30,7 +30,7 @@ async fn main() -> anyhow::Result<()> {
         .stderr(Stdio::inherit())
         .args(["-hwaccel", "cuda"])
         .input(buffer).unwrap()
-            .format("flv")
+            .force_format("flv")
             .done()
         .output(&mut output_mp4).unwrap()
             .codec_audio("copy")
@@ -29,7 +29,7 @@ async fn main() -> anyhow::Result<()> {
     let mut ffmpeg = essi_ffmpeg::FFmpeg::new()
         .stderr(Stdio::inherit())
         .input(buffer).unwrap()
-            .format("flv")
+            .force_format("flv")
             .done()
         .output(&mut output_mp4).unwrap()
             .codec_audio("copy")
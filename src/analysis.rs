@@ -0,0 +1,386 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::Context;
+
+use crate::{FFmpeg, FFmpegError};
+
+/// Peaks within this distance of 0 dBFS are counted as clipping
+const CLIPPING_THRESHOLD_DB: f64 = -0.01;
+
+/// Loudness & level statistics of the first audio stream, see [`FFmpeg::audio_stats`]
+#[derive(Debug, Clone, Default)]
+pub struct AudioStats {
+    /// EBU R128 integrated loudness in LUFS
+    pub integrated_loudness: Option<f64>,
+    /// Loudness range in LU
+    pub loudness_range: Option<f64>,
+    /// Low end of the loudness range in LUFS
+    pub loudness_range_low: Option<f64>,
+    /// High end of the loudness range in LUFS
+    pub loudness_range_high: Option<f64>,
+    /// True peak in dBTP
+    pub true_peak: Option<f64>,
+    /// Mean amplitude across all channels, should be close to 0
+    pub dc_offset: Option<f64>,
+    /// Sample peak in dBFS
+    pub peak_level: Option<f64>,
+    /// RMS level in dBFS
+    pub rms_level: Option<f64>,
+    /// How many times full scale was hit, summed across all channels
+    pub clipping_count: u64,
+    pub channels: Vec<AudioChannelStats>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AudioChannelStats {
+    pub dc_offset: Option<f64>,
+    /// Sample peak in dBFS
+    pub peak_level: Option<f64>,
+    /// RMS level in dBFS
+    pub rms_level: Option<f64>,
+    /// How many times the min or max sample value was reached
+    pub peak_count: u64,
+    /// Same as `peak_count` if the peak is at full scale, otherwise 0
+    pub clipping_count: u64,
+}
+
+/// The summary of the ebur128 filter, see [`FFmpeg::loudness_report`]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LoudnessReport {
+    /// Integrated loudness in LUFS
+    pub integrated: f64,
+    /// Gating threshold of the integrated loudness in LUFS
+    pub integrated_threshold: f64,
+    /// Loudness range in LU
+    pub loudness_range: f64,
+    /// Gating threshold of the loudness range in LUFS
+    pub loudness_range_threshold: f64,
+    /// Low end of the loudness range in LUFS
+    pub loudness_range_low: f64,
+    /// High end of the loudness range in LUFS
+    pub loudness_range_high: f64,
+    /// True peak in dBTP
+    pub true_peak: f64,
+}
+
+/// Settings of the silencedetect filter, see [`FFmpeg::detect_silence`] & [`FFmpeg::split_on_silence`]
+#[derive(Debug, Clone)]
+pub struct SilenceParams {
+    /// Anything quieter than this is silence, in dBFS
+    pub noise: f64,
+    /// Shortest pause that counts as silence
+    pub min_silence: Duration,
+    /// Silence kept before & after every spoken segment so words aren't cut off
+    pub padding: Duration,
+    /// Spoken segments shorter than this are dropped, e.g. clicks & coughs
+    pub min_speech: Duration,
+}
+
+impl Default for SilenceParams {
+    fn default() -> Self {
+        Self {
+            noise: -30.0,
+            min_silence: Duration::from_millis(500),
+            padding: Duration::from_millis(200),
+            min_speech: Duration::from_millis(100),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Silence {
+    pub start: Duration,
+    /// [`Option::None`] if the silence lasts until the end of the input on older FFmpeg versions
+    pub end: Option<Duration>,
+}
+
+impl FFmpeg {
+    /// Find every silence in the first audio stream of `input`
+    pub fn detect_silence(input: impl Into<PathBuf>, params: &SilenceParams) -> anyhow::Result<Vec<Silence>> {
+        Ok(Self::silences_and_duration(input.into(), params)?.0)
+    }
+
+    /// The silences of `input` & its duration if the container knows it
+    pub(crate) fn silences_and_duration(input: PathBuf, params: &SilenceParams) -> anyhow::Result<(Vec<Silence>, Option<Duration>)> {
+        let (status, stderr) = Self::quiet()?
+            .input_with_file(input)
+            .done()
+            .args(["-map", "0:a:0", "-af", &format!("silencedetect=noise={}dB:d={}", params.noise, params.min_silence.as_secs_f64()), "-f", "null", "-"])
+            .run_collect_stderr()?;
+
+        if !status.success() {
+            return Err(FFmpegError { status, stderr: Some(stderr) }.into());
+        }
+
+        Ok((parse_silences(&stderr), parse_input_duration(&stderr)))
+    }
+
+    /// Measure the EBU R128 loudness of the first audio stream of `input`, only the ebur128 filter is run unlike [`FFmpeg::audio_stats`]
+    pub fn loudness_report(input: impl Into<PathBuf>) -> anyhow::Result<LoudnessReport> {
+        let (status, stderr) = Self::quiet()?
+            .input_with_file(input.into())
+            .done()
+            .args(["-map", "0:a:0", "-af", "ebur128=peak=true:framelog=quiet", "-f", "null", "-"])
+            .run_collect_stderr()?;
+
+        if !status.success() {
+            return Err(FFmpegError { status, stderr: Some(stderr) }.into());
+        }
+
+        LoudnessReport::parse(&stderr).context("Can't find the ebur128 summary")
+    }
+
+    /// Run the astats & ebur128 filters over the first audio stream of `input`, for broadcast compliance checks
+    ///
+    /// This decodes the whole input, so it takes a while for long files
+    pub fn audio_stats(input: impl Into<PathBuf>) -> anyhow::Result<AudioStats> {
+        let (status, stderr) = Self::quiet()?
+            .input_with_file(input.into())
+            .done()
+            .args(["-map", "0:a:0", "-af", "astats,ebur128=peak=true:framelog=quiet", "-f", "null", "-"])
+            .run_collect_stderr()?;
+
+        if !status.success() {
+            return Err(FFmpegError { status, stderr: Some(stderr) }.into());
+        }
+
+        Ok(AudioStats::parse(&stderr))
+    }
+}
+
+impl LoudnessReport {
+    fn parse(stderr: &str) -> Option<Self> {
+        // [Parsed_ebur128_0 @ 0x55d0c1a2c100] Summary:
+        let (_, summary) = stderr.split_once("Summary:")?;
+
+        let mut values = std::collections::HashMap::new();
+        // e.g. "True peak", the summary is logged as a single multiline message, so only the first line is prefixed
+        //   Loudness range:
+        //     LRA:         5.2 LU
+        let mut section = "";
+
+        for line in summary.lines().map(str::trim) {
+            let Some((key, value)) = line.split_once(':') else { continue };
+
+            if value.trim().is_empty() {
+                section = key;
+                continue;
+            }
+
+            if let Some(value) = value.split_whitespace().next().and_then(|value| value.parse::<f64>().ok()) {
+                values.insert((section, key), value);
+            }
+        }
+
+        Some(Self {
+            integrated: *values.get(&("Integrated loudness", "I"))?,
+            integrated_threshold: *values.get(&("Integrated loudness", "Threshold"))?,
+            loudness_range: *values.get(&("Loudness range", "LRA"))?,
+            loudness_range_threshold: *values.get(&("Loudness range", "Threshold"))?,
+            loudness_range_low: *values.get(&("Loudness range", "LRA low"))?,
+            loudness_range_high: *values.get(&("Loudness range", "LRA high"))?,
+            true_peak: *values.get(&("True peak", "Peak"))?,
+        })
+    }
+}
+
+impl AudioStats {
+    fn parse(stderr: &str) -> Self {
+        let mut stats = AudioStats::default();
+
+        // Some(index) while reading a channel of astats, None while reading the overall part
+        let mut channel = None;
+
+        let number = |value: &str| value.split_whitespace().next().and_then(|value| value.parse::<f64>().ok());
+
+        for line in stderr.lines() {
+            // [Parsed_astats_0 @ 0x5581c6f5a8c0] DC offset: 0.000012
+            if let Some((_, stat)) = line.split_once("Parsed_astats").and_then(|(_, rest)| rest.split_once("] ")) {
+                if stat.trim() == "Overall" {
+                    channel = None;
+                    continue;
+                }
+
+                let Some((key, value)) = stat.split_once(':') else { continue };
+
+                if key == "Channel" {
+                    stats.channels.push(AudioChannelStats::default());
+                    channel = Some(stats.channels.len() - 1);
+                    continue;
+                }
+
+                match (channel.and_then(|index| stats.channels.get_mut(index)), key) {
+                    (Some(channel), "DC offset") => channel.dc_offset = number(value),
+                    (Some(channel), "Peak level dB") => channel.peak_level = number(value),
+                    (Some(channel), "RMS level dB") => channel.rms_level = number(value),
+                    (Some(channel), "Peak count") => channel.peak_count = number(value).unwrap_or_default() as u64,
+                    (None, "DC offset") => stats.dc_offset = number(value),
+                    (None, "Peak level dB") => stats.peak_level = number(value),
+                    (None, "RMS level dB") => stats.rms_level = number(value),
+                    _ => { }
+                }
+
+            }
+        }
+
+        if let Some(loudness) = LoudnessReport::parse(stderr) {
+            stats.integrated_loudness = Some(loudness.integrated);
+            stats.loudness_range = Some(loudness.loudness_range);
+            stats.loudness_range_low = Some(loudness.loudness_range_low);
+            stats.loudness_range_high = Some(loudness.loudness_range_high);
+            stats.true_peak = Some(loudness.true_peak);
+        }
+
+        for channel in stats.channels.iter_mut() {
+            if channel.peak_level.is_some_and(|peak| peak >= CLIPPING_THRESHOLD_DB) {
+                channel.clipping_count = channel.peak_count;
+            }
+        }
+
+        stats.clipping_count = stats.channels.iter().map(|channel| channel.clipping_count).sum();
+
+        stats
+    }
+}
+
+fn parse_silences(stderr: &str) -> Vec<Silence> {
+    let mut silences: Vec<Silence> = Vec::new();
+
+    // Slightly negative at the start of some inputs
+    let seconds = |value: &str| value.trim().parse::<f64>().ok().map(|value| Duration::from_secs_f64(value.max(0.0)));
+
+    for line in stderr.lines() {
+        // [silencedetect @ 0x5602c1b4a9c0] silence_start: 1.50068
+        // [silencedetect @ 0x5602c1b4a9c0] silence_end: 3.20317 | silence_duration: 1.70249
+        let Some((_, event)) = line.split_once("[silencedetect @ ").and_then(|(_, rest)| rest.split_once("] ")) else { continue };
+
+        if let Some(start) = event.strip_prefix("silence_start:").and_then(seconds) {
+            silences.push(Silence { start, end: None });
+        } else if let Some(end) = event.strip_prefix("silence_end:").and_then(|value| seconds(value.split('|').next()?)) {
+            if let Some(silence) = silences.last_mut().filter(|silence| silence.end.is_none()) {
+                silence.end = Some(end);
+            }
+        }
+    }
+
+    silences
+}
+
+/// `Duration: 00:01:02.50, start: ...` of the first input
+pub(crate) fn parse_input_duration(stderr: &str) -> Option<Duration> {
+    let (_, rest) = stderr.split_once("Duration: ")?;
+    let (time, _) = rest.split_once(',')?;
+
+    let mut parts = time.trim().splitn(3, ':').map(|part| part.parse::<f64>());
+    let (hours, minutes, seconds) = (parts.next()?.ok()?, parts.next()?.ok()?, parts.next()?.ok()?);
+
+    Some(Duration::from_secs_f64(hours * 3600.0 + minutes * 60.0 + seconds))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn audio_stats_parsing() {
+        let stats = AudioStats::parse("\
+[Parsed_astats_0 @ 0x55d0c1a2b3c0] Channel: 1
+[Parsed_astats_0 @ 0x55d0c1a2b3c0] DC offset: 0.000120
+[Parsed_astats_0 @ 0x55d0c1a2b3c0] Min level: -1.000000
+[Parsed_astats_0 @ 0x55d0c1a2b3c0] Max level: 1.000000
+[Parsed_astats_0 @ 0x55d0c1a2b3c0] Peak level dB: 0.000000
+[Parsed_astats_0 @ 0x55d0c1a2b3c0] RMS level dB: -18.200000
+[Parsed_astats_0 @ 0x55d0c1a2b3c0] Peak count: 12
+[Parsed_astats_0 @ 0x55d0c1a2b3c0] Channel: 2
+[Parsed_astats_0 @ 0x55d0c1a2b3c0] DC offset: -0.000050
+[Parsed_astats_0 @ 0x55d0c1a2b3c0] Peak level dB: -3.100000
+[Parsed_astats_0 @ 0x55d0c1a2b3c0] RMS level dB: -20.400000
+[Parsed_astats_0 @ 0x55d0c1a2b3c0] Peak count: 2
+[Parsed_astats_0 @ 0x55d0c1a2b3c0] Overall
+[Parsed_astats_0 @ 0x55d0c1a2b3c0] DC offset: 0.000035
+[Parsed_astats_0 @ 0x55d0c1a2b3c0] Peak level dB: 0.000000
+[Parsed_astats_0 @ 0x55d0c1a2b3c0] RMS level dB: -19.200000
+[Parsed_astats_0 @ 0x55d0c1a2b3c0] Peak count: 7.000000
+[Parsed_ebur128_1 @ 0x55d0c1a2c100] Summary:
+
+  Integrated loudness:
+    I:         -23.1 LUFS
+    Threshold: -33.4 LUFS
+
+  Loudness range:
+    LRA:         6.3 LU
+    Threshold:  -43.4 LUFS
+    LRA low:    -27.0 LUFS
+    LRA high:   -20.7 LUFS
+
+  True peak:
+    Peak:        0.4 dBFS
+");
+
+        assert_eq!(stats.integrated_loudness, Some(-23.1));
+        assert_eq!(stats.loudness_range, Some(6.3));
+        assert_eq!(stats.loudness_range_low, Some(-27.0));
+        assert_eq!(stats.loudness_range_high, Some(-20.7));
+        assert_eq!(stats.true_peak, Some(0.4));
+        assert_eq!(stats.dc_offset, Some(0.000035));
+        assert_eq!(stats.rms_level, Some(-19.2));
+
+        assert_eq!(stats.channels.len(), 2);
+        assert_eq!(stats.channels[0].clipping_count, 12);
+        assert_eq!(stats.channels[1].peak_count, 2);
+        assert_eq!(stats.channels[1].clipping_count, 0);
+        assert_eq!(stats.clipping_count, 12);
+    }
+
+    #[test]
+    fn loudness_report_parsing() {
+        let report = LoudnessReport::parse("\
+[Parsed_ebur128_0 @ 0x55d0c1a2c100] Summary:
+
+  Integrated loudness:
+    I:         -23.1 LUFS
+    Threshold: -33.4 LUFS
+
+  Loudness range:
+    LRA:         6.3 LU
+    Threshold:  -43.4 LUFS
+    LRA low:    -27.0 LUFS
+    LRA high:   -20.7 LUFS
+
+  True peak:
+    Peak:       -1.2 dBFS
+");
+
+        assert_eq!(report, Some(LoudnessReport {
+            integrated: -23.1,
+            integrated_threshold: -33.4,
+            loudness_range: 6.3,
+            loudness_range_threshold: -43.4,
+            loudness_range_low: -27.0,
+            loudness_range_high: -20.7,
+            true_peak: -1.2,
+        }));
+        assert_eq!(LoudnessReport::parse("[Parsed_ebur128_0 @ 0x55d0c1a2c100] Summary:\n"), None);
+    }
+
+    #[test]
+    fn silence_parsing() {
+        let stderr = "\
+Input #0, wav, from 'speech.wav':
+  Duration: 00:01:02.50, bitrate: 705 kb/s
+[silencedetect @ 0x5602c1b4a9c0] silence_start: -0.00102
+[silencedetect @ 0x5602c1b4a9c0] silence_end: 1.2 | silence_duration: 1.20102
+[silencedetect @ 0x5602c1b4a9c0] silence_start: 10.5
+[silencedetect @ 0x5602c1b4a9c0] silence_end: 11.25 | silence_duration: 0.75
+[silencedetect @ 0x5602c1b4a9c0] silence_start: 60
+";
+
+        assert_eq!(parse_silences(stderr), [
+            Silence { start: Duration::ZERO, end: Some(Duration::from_millis(1200)) },
+            Silence { start: Duration::from_millis(10500), end: Some(Duration::from_millis(11250)) },
+            Silence { start: Duration::from_secs(60), end: None },
+        ]);
+        assert_eq!(parse_input_duration(stderr), Some(Duration::from_millis(62500)));
+        assert_eq!(parse_input_duration("  Duration: N/A, start: 0.000000"), None);
+    }
+}
@@ -0,0 +1,176 @@
+use std::{io::{Cursor, Read}, path::Path};
+
+use anyhow::Context;
+use flate2::read::GzDecoder;
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const XZ_MAGIC: &[u8] = b"\xFD7zXZ\x00";
+const GZ_MAGIC: &[u8] = b"\x1F\x8B";
+
+/// What a download is, told apart by its magic bytes since the URL doesn't always end with the extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ArchiveFormat {
+    /// A single gzip compressed binary, e.g. the ffmpeg-static builds
+    Gz,
+    TarGz,
+    /// e.g. the BtbN Linux builds
+    TarXz,
+    /// e.g. the BtbN Windows & gyan.dev builds
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// The format & the decompressed tar or binary, a zip is returned as is
+    fn detect(data: &[u8]) -> anyhow::Result<(Self, Vec<u8>)> {
+        if data.starts_with(ZIP_MAGIC) {
+            return Ok((Self::Zip, data.to_vec()));
+        }
+
+        if data.starts_with(XZ_MAGIC) {
+            let mut tar = Vec::new();
+            lzma_rs::xz_decompress(&mut Cursor::new(data), &mut tar).map_err(|err| anyhow::anyhow!("Can't decompress the xz archive: {err}"))?;
+
+            return Ok((Self::TarXz, tar));
+        }
+
+        if data.starts_with(GZ_MAGIC) {
+            let mut decompressed = Vec::new();
+            GzDecoder::new(data).read_to_end(&mut decompressed)?;
+
+            // Every tar header has the ustar magic at 257
+            return match decompressed.get(257..262) == Some(b"ustar") {
+                true => Ok((Self::TarGz, decompressed)),
+                false => Ok((Self::Gz, decompressed)),
+            };
+        }
+
+        anyhow::bail!("The download isn't a gzip, tar.gz, tar.xz or zip archive")
+    }
+}
+
+/// Whether `path` in an archive is the binary `name`, with or without `.exe`
+fn is_binary(path: &Path, name: &str) -> bool {
+    path.file_name().is_some_and(|file_name| file_name == name || file_name.to_string_lossy() == format!("{name}.exe"))
+}
+
+/// The binaries called `names` in `data`, in the same order
+///
+/// Archives are searched for an entry named like the binary (e.g. `ffmpeg-master-latest-win64-gpl/bin/ffmpeg.exe`), the one closest to the root wins.
+/// A plain gzip can only hold a single binary
+pub(crate) fn extract_binaries(data: &[u8], names: &[&str]) -> anyhow::Result<Vec<Vec<u8>>> {
+    let (format, data) = ArchiveFormat::detect(data)?;
+
+    // (depth, binary) of the best match of every name
+    let mut found: Vec<Option<(usize, Vec<u8>)>> = vec![None; names.len()];
+
+    let mut consider = |path: &Path, read: &mut dyn FnMut() -> std::io::Result<Vec<u8>>| -> std::io::Result<()> {
+        let depth = path.components().count();
+
+        for (name, found) in names.iter().zip(found.iter_mut()) {
+            if is_binary(path, name) && found.as_ref().map_or(true, |(best, _)| depth < *best) {
+                *found = Some((depth, read()?));
+            }
+        }
+
+        Ok(())
+    };
+
+    match format {
+        ArchiveFormat::Gz => {
+            if names.len() != 1 {
+                anyhow::bail!("A gzip download holds a single binary, {names:?} can't all be extracted from it");
+            }
+
+            return Ok(vec![data]);
+        },
+        ArchiveFormat::TarGz | ArchiveFormat::TarXz => {
+            let mut archive = tar::Archive::new(Cursor::new(data));
+
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                if !entry.header().entry_type().is_file() { continue };
+
+                let path = entry.path()?.into_owned();
+
+                consider(&path, &mut || {
+                    let mut binary = Vec::new();
+                    entry.read_to_end(&mut binary)?;
+
+                    Ok(binary)
+                })?;
+            }
+        },
+        ArchiveFormat::Zip => {
+            let mut archive = zip::ZipArchive::new(Cursor::new(data))?;
+
+            for index in 0..archive.len() {
+                let mut entry = archive.by_index(index)?;
+                if !entry.is_file() { continue };
+
+                let Some(path) = entry.enclosed_name().map(Path::to_path_buf) else { continue };
+
+                consider(&path, &mut || {
+                    let mut binary = Vec::new();
+                    entry.read_to_end(&mut binary)?;
+
+                    Ok(binary)
+                })?;
+            }
+        },
+    }
+
+    names.iter()
+        .zip(found)
+        .map(|(name, found)| found.map(|(_, binary)| binary).with_context(|| format!("There's no {name} in the {format:?} archive")))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::*;
+
+    fn tar(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+
+        for (path, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o755);
+            header.set_cksum();
+
+            builder.append_data(&mut header, path, *data).unwrap();
+        }
+
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn archive_extraction() {
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gz.write_all(b"single").unwrap();
+        let gz = gz.finish().unwrap();
+
+        assert_eq!(extract_binaries(&gz, &["ffmpeg"]).unwrap(), [b"single"]);
+        assert!(extract_binaries(&gz, &["ffmpeg", "ffprobe"]).is_err());
+
+        let tar = tar(&[
+            ("ffmpeg-master-latest-linux64-gpl/doc/ffmpeg", b"manual"),
+            ("ffmpeg-master-latest-linux64-gpl/bin/ffprobe", b"probe"),
+            ("ffmpeg-master-latest-linux64-gpl/ffmpeg", b"binary"),
+        ]);
+        let mut xz = Vec::new();
+        lzma_rs::xz_compress(&mut Cursor::new(&tar), &mut xz).unwrap();
+
+        assert_eq!(extract_binaries(&xz, &["ffmpeg", "ffprobe"]).unwrap(), [&b"binary"[..], b"probe"]);
+        assert!(extract_binaries(&xz, &["ffplay"]).is_err());
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("ffmpeg-7.0-essentials_build/bin/ffmpeg.exe", zip::write::FileOptions::default()).unwrap();
+        zip.write_all(b"windows").unwrap();
+        let zip = zip.finish().unwrap().into_inner();
+
+        assert_eq!(extract_binaries(&zip, &["ffmpeg"]).unwrap(), [b"windows"]);
+    }
+}
@@ -0,0 +1,260 @@
+use std::{path::Path, process::ExitStatus, time::Duration};
+
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, process::{Child, ChildStdin, ChildStdout}};
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{process, stderr, temp::TempDir, timeline, DropBehavior, FFmpegBuilder, FFmpegError, FFmpegOutcome, InteractiveCommand, Normal, TempOutput};
+
+/// Same as [`crate::FFmpegCommand`] but built on [`tokio::process`], so waiting doesn't block a runtime worker
+///
+/// Started with [`FFmpegBuilder::start_async`]
+pub struct AsyncFFmpegCommand {
+    /// Only [`Option::None`] while being dropped
+    child: Option<Child>,
+    pid: u32,
+    drop_behavior: DropBehavior,
+    stderr_collector: Option<stderr::StderrCollector>,
+    /// Taken once [`timeline::TimelineEvent::Exited`] is sent
+    timeline: Option<UnboundedSender<timeline::TimelineEvent>>,
+    _temp_files: Vec<TempOutput>,
+    work_dir: Option<TempDir>,
+}
+
+impl FFmpegBuilder<Normal> {
+    /// Start a new FFmpeg child process with [`tokio::process`], has to be called inside a tokio runtime
+    ///
+    /// A piped stderr is always collected, with [`stderr::DEFAULT_RING_BUFFER_CAPACITY`] if [`FFmpegBuilder::stderr_ring_buffer`] isn't set,
+    /// so FFmpeg never blocks on a full stderr pipe
+    pub fn start_async(mut self) -> anyhow::Result<AsyncFFmpegCommand> {
+        if self.start_options.zmq_address.is_some() {
+            anyhow::bail!("FFmpegBuilder::zmq_address isn't supported by start_async, filter commands can only be sent with FFmpegCommand");
+        }
+
+        self.inner_command.args(self.checked_args()?);
+
+        if self.start_options.detached {
+            process::detach(&mut self.inner_command);
+        } else {
+            process::prepare(&mut self.inner_command, self.start_options.share_process_group);
+        }
+
+        let stderr_lines = self.stderr_line_handler();
+
+        let mut child = tokio::process::Command::from(self.inner_command).spawn()?;
+        let pid = child.id().unwrap_or_default();
+
+        process::spawned(pid, self.start_options.share_process_group && !self.start_options.detached);
+
+        if let Some(pid_file) = &self.start_options.pid_file {
+            if let Err(error) = std::fs::write(pid_file, pid.to_string()) {
+                // Nothing would be left to stop it otherwise, tokio reaps the killed child once it's dropped
+                let _ = child.start_kill();
+
+                return Err(anyhow::Error::new(error).context(format!("Can't write the pid file {pid_file:?}")));
+            }
+        }
+
+        if let Some((data, mut stdin)) = self.start_options.stdin_data.take().and_then(|data| Some((data, child.stdin.take()?))) {
+            tokio::spawn(async move { stdin.write_all(&data).await });
+        }
+
+        if let Some((tx, mut stdout)) = self.start_options.stdout_buffer.take().and_then(|tx| Some((tx, child.stdout.take()?))) {
+            tokio::spawn(async move {
+                let mut data = Vec::new();
+
+                let _ = tx.send(stdout.read_to_end(&mut data).await.map(|_| data));
+            });
+        }
+
+        let capacity = self.start_options.stderr_ring_buffer.unwrap_or(stderr::DEFAULT_RING_BUFFER_CAPACITY);
+
+        let stderr_collector = child.stderr.take().map(|stderr| stderr::StderrCollector::spawn_async(stderr, capacity, self.start_options.benchmark, stderr_lines));
+
+        if let Some(timeline) = &self.start_options.timeline {
+            let _ = timeline.send(timeline::TimelineEvent::Spawned { pid });
+        }
+
+        Ok(AsyncFFmpegCommand {
+            child: Some(child),
+            pid,
+            drop_behavior: self.start_options.drop_behavior,
+            stderr_collector,
+            timeline: self.start_options.timeline.take(),
+            _temp_files: std::mem::take(&mut self.start_options.temp_files),
+            work_dir: self.start_options.work_dir.take(),
+        })
+    }
+}
+
+impl AsyncFFmpegCommand {
+    fn child(&mut self) -> &mut Child {
+        self.child.as_mut().expect("The child is only taken on drop")
+    }
+
+    /// OS assigned process identifier
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// Same as [`crate::FFmpegCommand::work_dir`]
+    pub fn work_dir(&self) -> Option<&Path> {
+        self.work_dir.as_ref().map(TempDir::path)
+    }
+
+    pub async fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        let status = self.child().wait().await?;
+
+        if let Some(stderr_collector) = &self.stderr_collector {
+            stderr_collector.finish_async().await;
+        }
+
+        self.on_exit(status);
+
+        Ok(status)
+    }
+
+    /// Wait for FFmpeg to exit & turn a non-zero exit status into an [`FFmpegError`]
+    pub async fn wait_checked(&mut self) -> anyhow::Result<FFmpegOutcome> {
+        let status = self.wait().await?;
+
+        if !status.success() {
+            return Err(FFmpegError { status, stderr: self.recent_stderr() }.into());
+        }
+
+        Ok(FFmpegOutcome {
+            status,
+            benchmark: self.stderr_collector.as_ref().and_then(|stderr_collector| stderr_collector.benchmark()),
+        })
+    }
+
+    /// Check if FFmpeg has exited without blocking
+    ///
+    /// The end of stderr might still be on its way, [`AsyncFFmpegCommand::wait`] waits for it
+    pub fn try_wait(&mut self) -> std::io::Result<Option<ExitStatus>> {
+        let status = self.child().try_wait()?;
+
+        if let Some(status) = status {
+            self.on_exit(status);
+        }
+
+        Ok(status)
+    }
+
+    fn on_exit(&mut self, status: ExitStatus) {
+        if let Some(timeline) = self.timeline.take() {
+            let _ = timeline.send(timeline::TimelineEvent::Exited { status });
+        }
+    }
+
+    /// Same as [`crate::FFmpegCommand::recent_stderr`], every stderr that isn't taken is collected
+    pub fn recent_stderr(&self) -> Option<String> {
+        self.stderr_collector.as_ref().map(|stderr_collector| stderr_collector.contents())
+    }
+
+    /// Wait for FFmpeg to exit, at most for `timeout`
+    ///
+    /// Returns [`Option::None`] if FFmpeg is still running
+    pub async fn wait_timeout(&mut self, timeout: Duration) -> std::io::Result<Option<ExitStatus>> {
+        match tokio::time::timeout(timeout, self.wait()).await {
+            Ok(status) => status.map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Send an interactive command through stdin
+    ///
+    /// Stdin must be piped & not taken
+    pub async fn send_key(&mut self, command: InteractiveCommand) -> std::io::Result<()> {
+        let Some(stdin) = self.child().stdin.as_mut() else {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotConnected, "FFmpeg stdin is not available"));
+        };
+
+        stdin.write_all(&command.to_keystrokes()).await?;
+        stdin.flush().await
+    }
+
+    /// Gracefully stop FFmpeg & wait for it to finish writing the outputs, same as [`crate::FFmpegCommand::stop`]
+    pub async fn stop(mut self) -> std::io::Result<()> {
+        match self.send_key(InteractiveCommand::Quit).await {
+            // Closing stdin makes sure FFmpeg doesn't wait for more input
+            Ok(_) => drop(self.take_stdin()),
+            Err(_) => process::interrupt(self.pid).or_else(|_| process::kill(self.pid))?,
+        }
+
+        self.wait().await?;
+
+        Ok(())
+    }
+
+    pub async fn force_stop(mut self) -> std::io::Result<()> {
+        self.child().kill().await
+    }
+
+    /// Used for piping input or command to FFmpeg
+    pub fn take_stdin(&mut self) -> Option<ChildStdin> {
+        self.child().stdin.take()
+    }
+
+    /// Used for piping output from FFmpeg
+    pub fn take_stdout(&mut self) -> Option<ChildStdout> {
+        self.child().stdout.take()
+    }
+}
+
+impl Drop for AsyncFFmpegCommand {
+    fn drop(&mut self) {
+        let Some(mut child) = self.child.take() else { return };
+
+        if !matches!(child.try_wait(), Ok(None)) { return };
+
+        match self.drop_behavior {
+            DropBehavior::Kill => { let _ = child.start_kill(); },
+            DropBehavior::GracefulStop { timeout } => {
+                let _ = process::interrupt(self.pid);
+
+                // Can't wait here, the runtime finishes the stop
+                match tokio::runtime::Handle::try_current() {
+                    Ok(runtime) => {
+                        runtime.spawn(async move {
+                            if tokio::time::timeout(timeout, child.wait()).await.is_err() {
+                                let _ = child.kill().await;
+                            }
+                        });
+                    },
+                    Err(_) => { let _ = child.start_kill(); },
+                }
+            },
+            DropBehavior::Detach => { },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Context;
+
+    use super::*;
+    use crate::FFmpeg;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stderr_is_drained() -> anyhow::Result<()> {
+        // More than the pipe buffer, FFmpeg would block writing it if nobody reads stderr
+        let mut command = FFmpeg::new_with_program("sh")
+            .args(["-c", "head -c 200000 /dev/zero | tr '\\0' a >&2; exit 1"])
+            .stderr_ring_buffer(1024)
+            .start_async()?;
+
+        let status = command.wait_timeout(Duration::from_secs(10)).await?.context("FFmpeg blocked on stderr")?;
+        assert!(!status.success());
+
+        let error = command.wait_checked().await.err().unwrap();
+        assert_eq!(error.downcast_ref::<FFmpegError>().and_then(|error| error.stderr.as_ref()).map(String::len), Some(1024));
+
+        let zmq = FFmpeg::new_with_program("sh").zmq_address("127.0.0.1:5555").start_async();
+        assert!(zmq.is_err());
+
+        Ok(())
+    }
+}
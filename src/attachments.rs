@@ -0,0 +1,117 @@
+//! Attachment streams of Matroska files, usually the fonts of ASS subtitles, see [`FFmpeg::dump_attachments`]
+
+use std::{path::{Path, PathBuf}};
+
+use crate::{probe::{schema::FFprobeOutput, FFprobe}, FFmpeg};
+
+const FONT_EXTENSIONS: [&str; 5] = ["ttf", "otf", "ttc", "woff", "woff2"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// Index of the stream in the input
+    pub index: usize,
+    /// The `filename` tag
+    pub filename: Option<String>,
+    /// The `mimetype` tag, e.g. `application/x-truetype-font`
+    pub mimetype: Option<String>,
+}
+
+impl Attachment {
+    /// Whether it's a font by its mimetype or extension, muxers don't agree on the font mimetypes
+    pub fn is_font(&self) -> bool {
+        let mimetype = self.mimetype.as_deref().is_some_and(|mimetype| mimetype.contains("font"));
+        let extension = self.filename.as_deref()
+            .and_then(|filename| Path::new(filename).extension())
+            .is_some_and(|extension| FONT_EXTENSIONS.iter().any(|font| extension.eq_ignore_ascii_case(font)));
+
+        mimetype || extension
+    }
+
+    /// Where it's dumped in `dir`, only the file name of the tag is used so it can't point outside of `dir`
+    fn path_in(&self, dir: &Path) -> PathBuf {
+        let name = self.filename.as_deref()
+            .and_then(|filename| Path::new(filename).file_name())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| format!("attachment_{}", self.index).into());
+
+        dir.join(name)
+    }
+}
+
+/// Every attachment stream in the probed streams
+pub fn attachments(probe: &FFprobeOutput) -> Vec<Attachment> {
+    probe.streams.iter()
+        .filter(|stream| stream.codec_type.as_deref() == Some("attachment"))
+        .map(|stream| Attachment {
+            index: stream.index as usize,
+            filename: stream.tags.get("filename").cloned(),
+            mimetype: stream.tags.get("mimetype").cloned(),
+        })
+        .collect()
+}
+
+impl FFprobe {
+    /// The attachments of `input`, empty if there aren't any, see [`attachments`]
+    pub fn attachments(input: impl Into<PathBuf>) -> anyhow::Result<Vec<Attachment>> {
+        Ok(attachments(&Self::run(["-show_streams"], input.into())?))
+    }
+}
+
+impl FFmpeg {
+    /// Write every attachment of `input` into `dir` with `-dump_attachment`, returns where each one was written
+    ///
+    /// Give the fonts to [`crate::FFmpegBuilder::burn_subtitles_with_fonts`] so styled ASS subtitles render with the right fonts
+    pub fn dump_attachments(input: impl Into<PathBuf>, dir: impl AsRef<Path>) -> anyhow::Result<Vec<(Attachment, PathBuf)>> {
+        let input = input.into();
+        let dir = dir.as_ref();
+
+        let attachments = FFprobe::attachments(&input)?;
+        if attachments.is_empty() { return Ok(Vec::new()) };
+
+        std::fs::create_dir_all(dir)?;
+
+        let dumped = attachments.into_iter().map(|attachment| {
+            let path = attachment.path_in(dir);
+            (attachment, path)
+        }).collect::<Vec<_>>();
+
+        let dump_args = dumped.iter().flat_map(|(attachment, path)| [format!("-dump_attachment:{}", attachment.index), path.display().to_string()]);
+
+        Self::quiet()?
+            .arg("-y")
+            .input_with_file(input)
+            .args(dump_args)
+            .done()
+            // Attachments are written while the input is opened, nothing has to be decoded
+            .args(["-t", "0", "-f", "null", "-"])
+            .run_checked()?;
+
+        Ok(dumped)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn attachment_detection() {
+        let probe: FFprobeOutput = serde_json::from_str(r#"{
+            "streams": [
+                { "index": 0, "codec_type": "video" },
+                { "index": 1, "codec_type": "subtitle", "codec_name": "ass" },
+                { "index": 2, "codec_type": "attachment", "tags": { "filename": "../Roboto.TTF", "mimetype": "application/octet-stream" } },
+                { "index": 3, "codec_type": "attachment", "tags": { "mimetype": "font/otf" } },
+                { "index": 4, "codec_type": "attachment", "tags": { "filename": "cover.jpg", "mimetype": "image/jpeg" } }
+            ]
+        }"#).unwrap();
+
+        let attachments = attachments(&probe);
+
+        assert_eq!(attachments.iter().map(|attachment| attachment.index).collect::<Vec<_>>(), [2, 3, 4]);
+        assert_eq!(attachments.iter().map(Attachment::is_font).collect::<Vec<_>>(), [true, true, false]);
+
+        assert_eq!(attachments[0].path_in(Path::new("fonts")), Path::new("fonts/Roboto.TTF"));
+        assert_eq!(attachments[1].path_in(Path::new("fonts")), Path::new("fonts/attachment_3"));
+    }
+}
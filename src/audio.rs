@@ -0,0 +1,445 @@
+use std::{collections::VecDeque, io::Read, path::{Path, PathBuf}, panic::AssertUnwindSafe, process::Stdio, sync::Mutex, time::Duration};
+
+use anyhow::Context;
+
+use crate::{probe::FFprobe, stderr::DEFAULT_RING_BUFFER_CAPACITY, FFmpeg, FFmpegError};
+
+/// Lower the music while the original audio is loud, see [`BackgroundMusicOptions::ducking`]
+#[derive(Debug, Clone, Copy)]
+pub struct Ducking {
+    /// Level of the original audio (0.0 - 1.0) that starts lowering the music
+    pub threshold: f32,
+    /// How much the music is lowered above the threshold, e.g. 8 means 8:1
+    pub ratio: f32,
+    pub attack: Duration,
+    pub release: Duration,
+}
+
+impl Default for Ducking {
+    fn default() -> Self {
+        Self {
+            threshold: 0.05,
+            ratio: 8.0,
+            attack: Duration::from_millis(20),
+            release: Duration::from_millis(300),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BackgroundMusicOptions {
+    /// Volume multiplier of the music
+    pub volume: f32,
+    /// [`Option::None`] to keep the music at the same volume the whole time
+    pub ducking: Option<Ducking>,
+}
+
+impl Default for BackgroundMusicOptions {
+    fn default() -> Self {
+        Self { volume: 0.3, ducking: Some(Ducking::default()) }
+    }
+}
+
+impl FFmpeg {
+    /// Mix `music` under the audio of `video`, the music is looped or cut to the length of the video
+    ///
+    /// The video stream is copied, the audio is encoded with AAC
+    pub fn add_background_music(video: impl Into<PathBuf>, music: impl Into<PathBuf>, output: impl Into<PathBuf>, options: BackgroundMusicOptions) -> anyhow::Result<()> {
+        let video = video.into();
+
+        let mut graph = format!("[1:a]volume={}[music];", options.volume);
+
+        // amix would fail without the original audio, the music becomes the only audio instead
+        match (has_audio_stream(&video)?, options.ducking) {
+            (false, _) => graph.push_str("[music]anull[a]"),
+            (true, None) => graph.push_str("[0:a][music]amix=inputs=2:duration=first:dropout_transition=0:normalize=0[a]"),
+            (true, Some(ducking)) => graph.push_str(&format!(
+                "[0:a]asplit=2[original][sidechain];\
+                [music][sidechain]sidechaincompress=threshold={}:ratio={}:attack={}:release={}[ducked];\
+                [original][ducked]amix=inputs=2:duration=first:dropout_transition=0:normalize=0[a]",
+                ducking.threshold,
+                ducking.ratio,
+                ducking.attack.as_secs_f64() * 1000.0,
+                ducking.release.as_secs_f64() * 1000.0,
+            )),
+        }
+
+        Self::quiet()?
+            .input_with_file(video)
+            .done()
+            .args(["-stream_loop", "-1"])
+            .input_with_file(music.into())
+            .done()
+            .args(["-filter_complex", &graph, "-map", "0:v?", "-map", "[a]", "-c:v", "copy", "-c:a", "aac", "-shortest"])
+            .output_as_file(output.into())
+            .done()
+            .run_checked()?;
+
+        Ok(())
+    }
+}
+
+/// EBU R128 loudness to normalize to, see [`FFmpeg::normalize_loudness`]
+#[derive(Debug, Clone, Copy)]
+pub struct LoudnessTarget {
+    /// Integrated loudness in LUFS, -70.0 to -5.0
+    pub i: f32,
+    /// True peak in dBTP, -9.0 to 0.0
+    pub tp: f32,
+    /// Loudness range in LU, 1.0 to 50.0
+    pub lra: f32,
+}
+
+impl Default for LoudnessTarget {
+    /// EBU R128 broadcast
+    fn default() -> Self {
+        Self { i: -23.0, tp: -1.0, lra: 7.0 }
+    }
+}
+
+/// Loudness measured by the loudnorm filter
+#[derive(Debug, Clone, Copy)]
+pub struct LoudnessMeasurement {
+    /// Integrated loudness in LUFS
+    pub integrated: f64,
+    /// True peak in dBTP
+    pub true_peak: f64,
+    /// Loudness range in LU
+    pub lra: f64,
+    pub threshold: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct LoudnessNormalization {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub before: LoudnessMeasurement,
+    pub after: LoudnessMeasurement,
+}
+
+/// The JSON printed by loudnorm with `print_format=json`, every value is a string
+#[derive(serde::Deserialize)]
+struct LoudnormReport {
+    input_i: String,
+    input_tp: String,
+    input_lra: String,
+    input_thresh: String,
+    output_i: String,
+    output_tp: String,
+    output_lra: String,
+    output_thresh: String,
+    target_offset: String,
+}
+
+impl LoudnormReport {
+    /// The report is the last JSON object in stderr
+    fn parse(stderr: &str) -> anyhow::Result<Self> {
+        let start = stderr.rfind('{').context("loudnorm didn't print its report")?;
+        let end = stderr[start..].find('}').context("loudnorm didn't print its report")?;
+
+        Ok(serde_json::from_str(&stderr[start..=start + end])?)
+    }
+
+    fn number(value: &str) -> f64 {
+        value.trim().parse::<f64>().unwrap_or(f64::NAN)
+    }
+
+    fn input(&self) -> LoudnessMeasurement {
+        LoudnessMeasurement {
+            integrated: Self::number(&self.input_i),
+            true_peak: Self::number(&self.input_tp),
+            lra: Self::number(&self.input_lra),
+            threshold: Self::number(&self.input_thresh),
+        }
+    }
+
+    fn output(&self) -> LoudnessMeasurement {
+        LoudnessMeasurement {
+            integrated: Self::number(&self.output_i),
+            true_peak: Self::number(&self.output_tp),
+            lra: Self::number(&self.output_lra),
+            threshold: Self::number(&self.output_thresh),
+        }
+    }
+}
+
+/// `48000` from `Stream #0:0: Audio: pcm_s16le ([1][0][0][0] / 0x0001), 48000 Hz, stereo, s16, 1536 kb/s`
+fn sample_rate(stderr: &str) -> Option<u32> {
+    let line = stderr.lines().find(|line| line.trim_start().starts_with("Stream #0:") && line.contains(": Audio:"))?;
+
+    line.split(", ").find_map(|part| part.strip_suffix(" Hz")?.parse::<u32>().ok())
+}
+
+impl FFmpeg {
+    /// Two pass loudnorm, the first pass measures `input` & the second one normalizes it into `output`
+    ///
+    /// Only the first audio stream is normalized, the video is copied if there's any
+    pub fn normalize_loudness(input: impl Into<PathBuf>, output: impl Into<PathBuf>, target: LoudnessTarget) -> anyhow::Result<LoudnessNormalization> {
+        let (input, output) = (input.into(), output.into());
+        let loudnorm = format!("loudnorm=I={}:TP={}:LRA={}", target.i, target.tp, target.lra);
+
+        let (status, stderr) = Self::quiet()?
+            .input_with_file(input.clone())
+            .done()
+            .args(["-map", "0:a:0", "-af", &format!("{loudnorm}:print_format=json"), "-f", "null", "-"])
+            .run_collect_stderr()?;
+
+        if !status.success() {
+            return Err(FFmpegError { status, stderr: Some(stderr) }.into());
+        }
+
+        let measured = LoudnormReport::parse(&stderr)?;
+
+        // loudnorm upsamples to 192kHz, go back to the original sample rate
+        let sample_rate = sample_rate(&stderr).unwrap_or(48000);
+
+        let (status, stderr) = Self::quiet()?
+            .input_with_file(input.clone())
+            .done()
+            .args([
+                "-map".to_string(), "0:v?".to_string(),
+                "-map".to_string(), "0:a:0".to_string(),
+                "-c:v".to_string(), "copy".to_string(),
+                "-af".to_string(), format!(
+                    "{loudnorm}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true:print_format=json",
+                    measured.input_i, measured.input_tp, measured.input_lra, measured.input_thresh, measured.target_offset,
+                ),
+                "-ar".to_string(), sample_rate.to_string(),
+            ])
+            .output_as_file(output.clone())
+            .done()
+            .run_collect_stderr()?;
+
+        if !status.success() {
+            return Err(FFmpegError { status, stderr: Some(stderr) }.into());
+        }
+
+        Ok(LoudnessNormalization {
+            input,
+            output,
+            before: measured.input(),
+            after: LoudnormReport::parse(&stderr)?.output(),
+        })
+    }
+
+    /// [`FFmpeg::normalize_loudness`] for every `(input, output)` pair, running up to `concurrency` FFmpeg at a time
+    ///
+    /// Results are in the same order as `files`
+    pub fn normalize_batch<I, P, Q>(files: I, target: LoudnessTarget, concurrency: usize) -> Vec<anyhow::Result<LoudnessNormalization>>
+    where
+        I: IntoIterator<Item = (P, Q)>,
+        P: Into<PathBuf>,
+        Q: Into<PathBuf>,
+    {
+        let files = files.into_iter().map(|(input, output)| (input.into(), output.into())).collect();
+
+        run_batch(files, concurrency, |(input, output)| Self::normalize_loudness(input, output, target))
+    }
+}
+
+/// Run `job` for every item on up to `concurrency` threads, a panicking job is turned into an error instead of taking down the caller
+///
+/// Results are in the same order as `items`
+fn run_batch<T, R, F>(items: Vec<T>, concurrency: usize, job: F) -> Vec<anyhow::Result<R>>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> anyhow::Result<R> + Sync,
+{
+    let results = Mutex::new((0..items.len()).map(|_| None).collect::<Vec<_>>());
+    let queue = Mutex::new(items.into_iter().enumerate().collect::<VecDeque<_>>());
+
+    std::thread::scope(|scope| {
+        for _ in 0..concurrency.max(1) {
+            scope.spawn(|| loop {
+                let Some((index, item)) = queue.lock().ok().and_then(|mut queue| queue.pop_front()) else { break };
+
+                let result = std::panic::catch_unwind(AssertUnwindSafe(|| job(item)))
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("The job panicked")));
+
+                if let Ok(mut results) = results.lock() {
+                    results[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|result| result.unwrap_or_else(|| Err(anyhow::anyhow!("The job never ran"))))
+        .collect()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AudioConvertOptions {
+    /// Encoder to use, picked from the output extension if [`Option::None`]
+    pub codec: Option<String>,
+    /// Bitrate in kbit/s, the encoder default if [`Option::None`], ignored by lossless codecs
+    pub bitrate: Option<u32>,
+}
+
+impl FFmpeg {
+    /// Convert between audio formats (e.g. flac to mp3) without losing the tags & cover art
+    ///
+    /// The codec is picked from the `output` extension: mp3, flac, m4a, ogg, opus or wav
+    ///
+    /// Cover art can only be kept in mp3, flac & m4a, it is dropped for the rest
+    pub fn convert_audio(input: impl Into<PathBuf>, output: impl Into<PathBuf>, options: AudioConvertOptions) -> anyhow::Result<()> {
+        let output = output.into();
+        let extension = output.extension().map(|extension| extension.to_string_lossy().to_lowercase()).unwrap_or_default();
+
+        let (default_codec, cover_art) = match extension.as_str() {
+            "mp3" => ("libmp3lame", true),
+            "flac" => ("flac", true),
+            "m4a" | "aac" | "mp4" => ("aac", true),
+            "ogg" | "oga" => ("libvorbis", false),
+            "opus" => ("libopus", false),
+            "wav" => ("pcm_s16le", false),
+            _ => anyhow::bail!("Can't pick an audio codec for {output:?}, set AudioConvertOptions::codec"),
+        };
+
+        let mut builder = Self::quiet()?
+            .input_with_file(input.into())
+            .done()
+            .args(["-map", "0:a", "-map_metadata", "0", "-c:a", options.codec.as_deref().unwrap_or(default_codec)]);
+
+        if let Some(bitrate) = options.bitrate {
+            builder = builder.args(["-b:a".to_string(), format!("{bitrate}k")]);
+        }
+
+        // Cover art is a single frame video stream, copy it as is & keep it marked as cover art
+        if cover_art {
+            builder = builder.args(["-map", "0:v?", "-c:v", "copy", "-disposition:v", "attached_pic"]);
+        }
+
+        // Version 4 (the default) isn't supported by a lot of players
+        if extension == "mp3" {
+            builder = builder.args(["-id3v2_version", "3"]);
+        }
+
+        builder
+            .output_as_file(output)
+            .done()
+            .run_checked()?;
+
+        Ok(())
+    }
+}
+
+/// Cover art embedded in an audio file, see [`FFmpeg::extract_album_art`]
+#[derive(Debug, Clone)]
+pub struct AlbumArt {
+    /// The image file as is, e.g. a whole JPEG
+    pub data: Vec<u8>,
+    /// e.g. `mjpeg` or `png`
+    pub codec: String,
+}
+
+impl AlbumArt {
+    /// File extension matching [`AlbumArt::codec`]
+    pub fn extension(&self) -> &str {
+        match self.codec.as_str() {
+            "mjpeg" => "jpg",
+            "png" => "png",
+            "bmp" => "bmp",
+            "gif" => "gif",
+            "webp" => "webp",
+            "tiff" => "tiff",
+            codec => codec,
+        }
+    }
+}
+
+impl FFmpeg {
+    /// Get the cover art of `input` without re-encoding it
+    ///
+    /// Returns [`Option::None`] if there's no stream marked as an attached picture
+    pub fn extract_album_art(input: impl Into<PathBuf>) -> anyhow::Result<Option<AlbumArt>> {
+        let input = input.into();
+
+        let probe = FFprobe::probe_raw(&input)?;
+        let Some(stream) = probe.streams.iter().find(|stream| stream.disposition.as_ref().is_some_and(|disposition| disposition.attached_pic == 1)) else {
+            return Ok(None);
+        };
+
+        let mut command = Self::quiet()?
+            .input_with_file(input)
+            .done()
+            .args(["-map".to_string(), format!("0:{}", stream.index)])
+            .args(["-c", "copy", "-frames:v", "1", "-f", "image2pipe", "pipe:1"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr_ring_buffer(DEFAULT_RING_BUFFER_CAPACITY)
+            .start()?;
+
+        let mut data = Vec::new();
+        if let Some(mut stdout) = command.take_stdout() {
+            stdout.read_to_end(&mut data)?;
+        }
+
+        command.wait_checked()?;
+
+        Ok(Some(AlbumArt { data, codec: stream.codec_name.clone().unwrap_or_default() }))
+    }
+}
+
+/// Checked from the stream list FFmpeg prints when it's given an input without any output
+///
+/// e.g. `Stream #0:1[0x2](und): Audio: aac (LC) (mp4a / 0x6134706D), 48000 Hz, stereo, fltp, 128 kb/s (default)`
+pub(crate) fn has_audio_stream(input: &Path) -> anyhow::Result<bool> {
+    let (_, stderr) = FFmpeg::try_new()?
+        .args(["-hide_banner", "-nostdin"])
+        .input_with_file(input.to_path_buf())
+        .done()
+        .run_collect_stderr()?;
+
+    Ok(stderr.lines().any(|line| line.trim_start().starts_with("Stream #0:") && line.contains(": Audio:")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn loudnorm_report_parsing() {
+        let stderr = r#"
+  Stream #0:0: Audio: pcm_s16le ([1][0][0][0] / 0x0001), 44100 Hz, stereo, s16, 1411 kb/s
+[Parsed_loudnorm_0 @ 0x55d0c1a2b3c0]
+{
+	"input_i" : "-27.61",
+	"input_tp" : "-4.47",
+	"input_lra" : "18.06",
+	"input_thresh" : "-39.20",
+	"output_i" : "-16.58",
+	"output_tp" : "-1.50",
+	"output_lra" : "14.78",
+	"output_thresh" : "-27.71",
+	"normalization_type" : "dynamic",
+	"target_offset" : "0.58"
+}
+[out#0/null @ 0x55d0c1a2c100] video:0kB audio:1034kB subtitle:0kB other streams:0kB global headers:0kB muxing overhead: unknown
+"#;
+
+        let report = LoudnormReport::parse(stderr).unwrap();
+
+        assert_eq!(report.input().integrated, -27.61);
+        assert_eq!(report.input().true_peak, -4.47);
+        assert_eq!(report.output().lra, 14.78);
+        assert_eq!(report.target_offset, "0.58");
+        assert_eq!(sample_rate(stderr), Some(44100));
+    }
+
+    #[test]
+    fn batch_survives_a_panic() {
+        let results = run_batch(vec![1, 2, 3, 4], 2, |item| match item {
+            2 => panic!("broken input"),
+            3 => anyhow::bail!("failed"),
+            item => Ok(item * 10),
+        });
+
+        let results = results.iter().map(|result| result.as_ref().map_err(|error| error.to_string())).collect::<Vec<_>>();
+        assert_eq!(results, [Ok(&10), Err("The job panicked".to_string()), Err("failed".to_string()), Ok(&40)]);
+    }
+}
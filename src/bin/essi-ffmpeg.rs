@@ -0,0 +1,181 @@
+//! Run the helpers of the library from the command line, see `essi-ffmpeg help`
+
+use std::{path::PathBuf, process::Stdio, time::Duration};
+
+use anyhow::Context;
+use essi_ffmpeg::{probe::FFprobe, DownloadComponents, FFmpeg, FFmpegBuilder, Normal};
+
+const USAGE: &str = "\
+Usage: essi-ffmpeg [--dry-run] <command>
+
+Commands:
+    download                                        Download FFmpeg if it isn't in the system
+    probe <input>                                   Print the ffprobe output as json
+    remux <input> <output>                          Copy every stream into another container
+    thumbnail <input> <output> [--at <seconds>]     Save a single frame, at 10% of the input by default
+    hls <input> <playlist> [--segment <seconds>]    Encode into a VOD HLS playlist, 6 second segments by default
+
+Options:
+    --dry-run    Print the FFmpeg arguments instead of running them";
+
+struct Args {
+    dry_run: bool,
+    positional: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+impl Args {
+    fn parse() -> anyhow::Result<Self> {
+        let mut args = Args { dry_run: false, positional: Vec::new(), options: Vec::new() };
+        let mut raw = std::env::args().skip(1);
+
+        while let Some(arg) = raw.next() {
+            match arg.as_str() {
+                "--dry-run" => args.dry_run = true,
+                option if option.starts_with("--") => {
+                    let value = raw.next().with_context(|| format!("{option} needs a value"))?;
+
+                    args.options.push((option.to_string(), value));
+                },
+                _ => args.positional.push(arg),
+            }
+        }
+
+        Ok(args)
+    }
+
+    fn positional(&self, index: usize, name: &str) -> anyhow::Result<&str> {
+        self.positional.get(index).map(String::as_str).with_context(|| format!("Missing <{name}>\n\n{USAGE}"))
+    }
+
+    fn seconds(&self, option: &str) -> anyhow::Result<Option<f64>> {
+        let Some((_, value)) = self.options.iter().find(|(name, _)| name == option) else { return Ok(None) };
+
+        Ok(Some(value.parse().with_context(|| format!("{option} has to be a number of seconds"))?))
+    }
+}
+
+/// Print or run `builder`
+fn run(builder: FFmpegBuilder<Normal>, dry_run: bool) -> anyhow::Result<()> {
+    if dry_run {
+        builder.inspect_args(|args| println!("ffmpeg {}", args.iter().map(|arg| arg.to_string_lossy()).collect::<Vec<_>>().join(" ")));
+
+        return Ok(());
+    }
+
+    builder
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .start()?
+        .wait_checked()?;
+
+    Ok(())
+}
+
+fn duration_of(input: &str) -> anyhow::Result<Duration> {
+    let duration = FFprobe::probe_raw(input)?
+        .format
+        .and_then(|format| format.duration)
+        .and_then(|duration| duration.parse::<f64>().ok())
+        .with_context(|| format!("Can't find the duration of {input:?}"))?;
+
+    Ok(Duration::from_secs_f64(duration))
+}
+
+fn ffmpeg(dry_run: bool) -> anyhow::Result<FFmpegBuilder<Normal>> {
+    // Printing the arguments shouldn't need FFmpeg
+    if dry_run {
+        return Ok(FFmpeg::new_with_program("ffmpeg").args(["-hide_banner", "-nostdin"]));
+    }
+
+    let program = FFmpeg::get_program()?.context("Can't find FFmpeg in your system, run `essi-ffmpeg download` first")?;
+
+    Ok(FFmpeg::new_with_program(program).args(["-hide_banner", "-nostdin"]))
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse()?;
+
+    match args.positional.first().map(String::as_str) {
+        Some("download") => {
+            let Some((handle, mut progress)) = FFmpeg::auto_download_components(DownloadComponents::FFMPEG | DownloadComponents::FFPROBE).await? else {
+                println!("FFmpeg & ffprobe are already available");
+                return Ok(());
+            };
+
+            tokio::spawn(async move {
+                while let Some(state) = progress.recv().await {
+                    println!("{state:?}");
+                }
+            });
+
+            handle.await??;
+        },
+        Some("probe") => {
+            let probe = FFprobe::probe_raw(args.positional(1, "input")?)?;
+
+            println!("{}", serde_json::to_string_pretty(&probe)?);
+        },
+        Some("remux") => {
+            let builder = ffmpeg(args.dry_run)?
+                .input_with_file(args.positional(1, "input")?.into())
+                .done()
+                .args(["-map", "0", "-c", "copy"])
+                .output_as_file(args.positional(2, "output")?.into())
+                .done();
+
+            run(builder, args.dry_run)?;
+        },
+        Some("thumbnail") => {
+            let input = args.positional(1, "input")?;
+
+            let at = match args.seconds("--at")? {
+                Some(at) => at,
+                None if args.dry_run => 0.0,
+                None => duration_of(input)?.as_secs_f64() / 10.0,
+            };
+
+            let builder = ffmpeg(args.dry_run)?
+                .args(["-ss", &at.to_string()])
+                .input_with_file(input.into())
+                .done()
+                .args(["-frames:v", "1", "-update", "1"])
+                .output_as_file(args.positional(2, "output")?.into())
+                .done();
+
+            run(builder, args.dry_run)?;
+        },
+        Some("hls") => {
+            let playlist = PathBuf::from(args.positional(2, "playlist")?);
+            let segment = args.seconds("--segment")?.unwrap_or(6.0);
+
+            let segments = playlist.with_file_name(format!(
+                "{}_%05d.ts",
+                playlist.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default(),
+            ));
+
+            if let Some(parent) = playlist.parent().filter(|parent| !parent.as_os_str().is_empty() && !args.dry_run) {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            let builder = ffmpeg(args.dry_run)?
+                .input_with_file(args.positional(1, "input")?.into())
+                .done()
+                .args(["-map", "0:v:0", "-map", "0:a:0?", "-c:v", "libx264", "-c:a", "aac"])
+                // Keyframes on every segment boundary so the segments have the asked length
+                .args(["-force_key_frames".to_string(), format!("expr:gte(t,n_forced*{segment})")])
+                .args(["-f", "hls", "-hls_time", &segment.to_string(), "-hls_playlist_type", "vod"])
+                .args(["-hls_segment_filename".to_string(), segments.display().to_string()])
+                .output_as_file(playlist)
+                .done();
+
+            run(builder, args.dry_run)?;
+        },
+        Some("help") | None => println!("{USAGE}"),
+        Some(command) => anyhow::bail!("Unknown command {command:?}\n\n{USAGE}"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use std::{io::{self, Read}, process::ChildStdout, sync::mpsc::{self, Receiver, Sender}};
+
+/// The encoded bytes of an output made by [`crate::FFmpegBuilder::output_to_vec`]
+///
+/// Collected by a background thread once the command is started
+#[derive(Debug)]
+pub struct OutputBuffer {
+    rx: Receiver<io::Result<Vec<u8>>>,
+}
+
+impl OutputBuffer {
+    pub(crate) fn new() -> (Self, Sender<io::Result<Vec<u8>>>) {
+        let (tx, rx) = mpsc::channel();
+
+        (Self { rx }, tx)
+    }
+
+    /// Wait until FFmpeg closes the output & take everything it wrote
+    ///
+    /// Fails if the command was never started
+    pub fn wait(self) -> io::Result<Vec<u8>> {
+        self.rx.recv().map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "The command writing the output was never started"))?
+    }
+
+    /// The bytes if FFmpeg is done writing the output, otherwise `self` is given back
+    pub fn try_take(self) -> Result<io::Result<Vec<u8>>, Self> {
+        match self.rx.try_recv() {
+            Ok(result) => Ok(result),
+            Err(mpsc::TryRecvError::Empty) => Err(self),
+            Err(mpsc::TryRecvError::Disconnected) => Ok(Err(io::Error::new(io::ErrorKind::BrokenPipe, "The command writing the output was never started"))),
+        }
+    }
+}
+
+/// Read `stdout` to the end in a background thread
+pub(crate) fn collect(mut stdout: ChildStdout, tx: Sender<io::Result<Vec<u8>>>) {
+    std::thread::spawn(move || {
+        let mut data = Vec::new();
+
+        let _ = tx.send(stdout.read_to_end(&mut data).map(|_| data));
+    });
+}
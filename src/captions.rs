@@ -0,0 +1,116 @@
+//! Broadcast closed captions (CEA-608/708), see [`FFmpeg::extract_captions`]
+
+use std::path::PathBuf;
+
+use crate::{
+    compose::escape_filter_path,
+    probe::{schema::FFprobeOutput, FFprobe},
+    FFmpeg, TestSource,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptionFormat {
+    /// SubRip, `.srt`
+    Srt,
+    /// WebVTT, `.vtt`
+    WebVtt,
+}
+
+impl CaptionFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Srt => "srt",
+            Self::WebVtt => "vtt",
+        }
+    }
+
+    fn codec(&self) -> &'static str {
+        match self {
+            Self::Srt => "srt",
+            Self::WebVtt => "webvtt",
+        }
+    }
+}
+
+/// Where the captions of an input are carried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptionSource {
+    /// A/53 side data of the first video stream, common in MPEG-TS broadcasts, decoded with `movie=...[out0+subcc]`
+    VideoStream,
+    /// An `eia_608` subtitle stream with this index, e.g. in MOV or MXF
+    SubtitleStream(usize),
+}
+
+/// Every caption source in the probed streams
+///
+/// Captions in the video stream are only found if ffprobe sees them in the first few seconds
+pub fn detect_captions(probe: &FFprobeOutput) -> Vec<CaptionSource> {
+    let video = probe.streams.iter()
+        .any(|stream| stream.codec_type.as_deref() == Some("video") && stream.closed_captions == Some(1))
+        .then_some(CaptionSource::VideoStream);
+
+    let streams = probe.streams.iter()
+        .filter(|stream| stream.codec_type.as_deref() == Some("subtitle") && stream.codec_name.as_deref() == Some("eia_608"))
+        .map(|stream| CaptionSource::SubtitleStream(stream.index as usize));
+
+    video.into_iter().chain(streams).collect()
+}
+
+impl FFprobe {
+    /// The closed captions of `input`, empty if there aren't any, see [`detect_captions`]
+    pub fn closed_captions(input: impl Into<PathBuf>) -> anyhow::Result<Vec<CaptionSource>> {
+        Ok(detect_captions(&Self::run(["-show_streams"], input.into())?))
+    }
+}
+
+impl FFmpeg {
+    /// Convert the closed captions in `source` of `input` to a subtitle file
+    ///
+    /// Find the sources with [`FFprobe::closed_captions`]
+    pub fn extract_captions(input: impl Into<PathBuf>, source: CaptionSource, output: impl Into<PathBuf>, format: CaptionFormat) -> anyhow::Result<()> {
+        let input = input.into();
+
+        let builder = Self::quiet()?;
+
+        let builder = match source {
+            CaptionSource::VideoStream => builder
+                .input_lavfi(TestSource::Custom(format!("movie={}[out0+subcc]", escape_filter_path(&input))))
+                .done()
+                .args(["-map", "0:s:0"]),
+            CaptionSource::SubtitleStream(index) => builder
+                .input_with_file(input)
+                .done()
+                .args(["-map", &format!("0:{index}")]),
+        };
+
+        builder
+            .args(["-c:s", format.codec()])
+            .output_as_file(output.into())
+            .done()
+            .run_checked()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn caption_detection() {
+        let probe: FFprobeOutput = serde_json::from_str(r#"{
+            "streams": [
+                { "index": 0, "codec_type": "video", "codec_name": "mpeg2video", "closed_captions": 1 },
+                { "index": 1, "codec_type": "audio", "codec_name": "ac3" },
+                { "index": 2, "codec_type": "subtitle", "codec_name": "eia_608" },
+                { "index": 3, "codec_type": "subtitle", "codec_name": "dvb_subtitle" }
+            ]
+        }"#).unwrap();
+
+        assert_eq!(detect_captions(&probe), [CaptionSource::VideoStream, CaptionSource::SubtitleStream(2)]);
+
+        let probe: FFprobeOutput = serde_json::from_str(r#"{ "streams": [{ "index": 0, "codec_type": "video", "closed_captions": 0 }] }"#).unwrap();
+        assert!(detect_captions(&probe).is_empty());
+    }
+}
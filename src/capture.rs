@@ -0,0 +1,185 @@
+//! Record the screen
+
+use crate::{FFmpegBuilder, Input, Normal};
+
+/// The FFmpeg device used for [`ScreenCapture`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenGrabber {
+    /// Windows GDI, works everywhere but is slow on large screens
+    GdiGrab,
+    /// Windows Desktop Duplication, the frames are on the GPU (d3d11), use `hwdownload,format=bgra` to filter them on the CPU
+    DdaGrab,
+    /// X11 on Linux & BSD
+    X11Grab,
+    /// macOS, can't capture a region
+    AvFoundation,
+}
+
+impl ScreenGrabber {
+    /// gdigrab on Windows, avfoundation on macOS & x11grab everywhere else
+    pub fn platform_default() -> Self {
+        if cfg!(windows) {
+            Self::GdiGrab
+        } else if cfg!(target_os = "macos") {
+            Self::AvFoundation
+        } else {
+            Self::X11Grab
+        }
+    }
+}
+
+/// A rectangle of the screen in pixels, from the top left corner of the (virtual) desktop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScreenRegion {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Options of [`FFmpegBuilder::input_screen`]
+#[derive(Debug, Clone)]
+pub struct ScreenCapture {
+    grabber: ScreenGrabber,
+    display: Option<String>,
+    region: Option<ScreenRegion>,
+    draw_cursor: bool,
+    framerate: u32,
+}
+
+impl Default for ScreenCapture {
+    fn default() -> Self {
+        Self::new(ScreenGrabber::platform_default())
+    }
+}
+
+impl ScreenCapture {
+    /// The whole screen at 30 fps with the cursor
+    pub fn new(grabber: ScreenGrabber) -> Self {
+        Self { grabber, display: None, region: None, draw_cursor: true, framerate: 30 }
+    }
+
+    /// Which screen to capture
+    ///
+    /// e.g. `desktop` or `title=Window title` for gdigrab, the monitor index for ddagrab,
+    /// `:0.0` for x11grab (`$DISPLAY` by default) & the device index for avfoundation
+    pub fn display(mut self, display: impl Into<String>) -> Self {
+        self.display = Some(display.into());
+
+        self
+    }
+
+    /// Only capture `region` instead of the whole screen
+    pub fn region(mut self, region: ScreenRegion) -> Self {
+        self.region = Some(region);
+
+        self
+    }
+
+    pub fn draw_cursor(mut self, draw_cursor: bool) -> Self {
+        self.draw_cursor = draw_cursor;
+
+        self
+    }
+
+    pub fn framerate(mut self, framerate: u32) -> Self {
+        self.framerate = framerate;
+
+        self
+    }
+
+    pub fn grabber(&self) -> ScreenGrabber {
+        self.grabber
+    }
+
+    /// Every argument of the input, including `-i`
+    fn to_args(&self) -> anyhow::Result<Vec<String>> {
+        let cursor = u8::from(self.draw_cursor).to_string();
+        let framerate = self.framerate.to_string();
+        let mut args = Vec::new();
+
+        match self.grabber {
+            ScreenGrabber::GdiGrab => {
+                args.extend(["-f", "gdigrab", "-framerate", &framerate, "-draw_mouse", &cursor].map(String::from));
+
+                if let Some(ScreenRegion { x, y, width, height }) = self.region {
+                    args.extend(["-offset_x".to_string(), x.to_string(), "-offset_y".to_string(), y.to_string()]);
+                    args.extend(["-video_size".to_string(), format!("{width}x{height}")]);
+                }
+
+                args.extend(["-i".to_string(), self.display.clone().unwrap_or_else(|| "desktop".to_string())]);
+            },
+            ScreenGrabber::DdaGrab => {
+                let mut source = format!("ddagrab=output_idx={}:framerate={framerate}:draw_mouse={cursor}", self.display.as_deref().unwrap_or("0"));
+
+                if let Some(ScreenRegion { x, y, width, height }) = self.region {
+                    source.push_str(&format!(":video_size={width}x{height}:offset_x={x}:offset_y={y}"));
+                }
+
+                args.extend(["-f".to_string(), "lavfi".to_string(), "-i".to_string(), source]);
+            },
+            ScreenGrabber::X11Grab => {
+                let display = self.display.clone().or_else(|| std::env::var("DISPLAY").ok()).unwrap_or_else(|| ":0.0".to_string());
+
+                args.extend(["-f", "x11grab", "-framerate", &framerate, "-draw_mouse", &cursor].map(String::from));
+
+                let input = match self.region {
+                    Some(ScreenRegion { x, y, width, height }) => {
+                        args.extend(["-video_size".to_string(), format!("{width}x{height}")]);
+
+                        format!("{display}+{x},{y}")
+                    },
+                    None => display,
+                };
+
+                args.extend(["-i".to_string(), input]);
+            },
+            ScreenGrabber::AvFoundation => {
+                if self.region.is_some() {
+                    anyhow::bail!("avfoundation can't capture a region, crop the output instead");
+                }
+
+                args.extend(["-f", "avfoundation", "-framerate", &framerate, "-capture_cursor", &cursor].map(String::from));
+                args.extend(["-i".to_string(), format!("{}:none", self.display.as_deref().unwrap_or("1"))]);
+            },
+        }
+
+        Ok(args)
+    }
+}
+
+impl FFmpegBuilder<Normal> {
+    /// Record the screen as an input, fails if the grabber doesn't support the options
+    pub fn input_screen(mut self, capture: &ScreenCapture) -> anyhow::Result<FFmpegBuilder<Input>> {
+        let args = capture.to_args()?;
+
+        self.inserting_offset = Some(self.inner_args.len());
+
+        self.inner_args.extend(args.into_iter().map(Into::into));
+
+        Ok(self.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn region_flags() -> anyhow::Result<()> {
+        let region = ScreenRegion { x: 100, y: 50, width: 1280, height: 720 };
+
+        let args = ScreenCapture::new(ScreenGrabber::GdiGrab).region(region).draw_cursor(false).framerate(60).to_args()?;
+        assert_eq!(args, ["-f", "gdigrab", "-framerate", "60", "-draw_mouse", "0", "-offset_x", "100", "-offset_y", "50", "-video_size", "1280x720", "-i", "desktop"]);
+
+        let args = ScreenCapture::new(ScreenGrabber::X11Grab).display(":1.0").region(region).to_args()?;
+        assert_eq!(args, ["-f", "x11grab", "-framerate", "30", "-draw_mouse", "1", "-video_size", "1280x720", "-i", ":1.0+100,50"]);
+
+        let args = ScreenCapture::new(ScreenGrabber::DdaGrab).region(region).to_args()?;
+        assert_eq!(args.last().unwrap(), "ddagrab=output_idx=0:framerate=30:draw_mouse=1:video_size=1280x720:offset_x=100:offset_y=50");
+
+        assert!(ScreenCapture::new(ScreenGrabber::AvFoundation).region(region).to_args().is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,826 @@
+use std::{ffi::{OsStr, OsString}, ops::Range, path::{Path, PathBuf}, time::Duration};
+
+use anyhow::Context;
+
+use crate::{is_output_marker, probe::{schema::Stream, FFprobe, MediaInfo}, stream::{Bsf, StreamSelector}, FFmpeg, FFmpegBuilder, Normal, IO};
+
+#[derive(Debug, Clone, Default)]
+pub struct MuxOptions {
+    /// `-shortest`, stop at the end of the shorter input instead of the longer one
+    pub shortest: bool,
+}
+
+impl MuxOptions {
+    /// `video` & `audio` are the inputs with the index of the stream taken from each
+    fn build(&self, builder: FFmpegBuilder<Normal>, video: (PathBuf, i64), audio: (PathBuf, i64), output: PathBuf) -> FFmpegBuilder<Normal> {
+        // genpts fills in missing timestamps, common with raw streams & some screen recorders
+        let mut builder = builder
+            .args(["-fflags", "+genpts"])
+            .input_with_file(video.0)
+            .done()
+            .args(["-fflags", "+genpts"])
+            .input_with_file(audio.0)
+            .done()
+            .args(["-map".to_string(), format!("0:{}", video.1), "-map".to_string(), format!("1:{}", audio.1)])
+            .args(["-c", "copy", "-avoid_negative_ts", "make_zero"]);
+
+        if self.shortest {
+            builder = builder.args(["-shortest"]);
+        }
+
+        builder.output_as_file(output).done()
+    }
+}
+
+/// The first stream of `codec_type`, cover art doesn't count as a video stream
+fn mux_stream<'a>(streams: &'a [Stream], codec_type: &str) -> Option<&'a Stream> {
+    streams
+        .iter()
+        .find(|stream| stream.codec_type.as_deref() == Some(codec_type) && !stream.disposition.as_ref().is_some_and(|disposition| disposition.attached_pic == 1))
+}
+
+/// Whether the container of `extension` can hold `codec`, [`Option::None`] if it isn't known
+fn container_supports(extension: &str, codec: &str) -> Option<bool> {
+    let supported: &[&str] = match extension {
+        "mkv" | "mka" | "nut" => return Some(true),
+        "mp4" | "m4v" | "m4a" => &["h264", "hevc", "av1", "vp9", "mpeg4", "mpeg2video", "aac", "mp3", "alac", "ac3", "eac3", "opus", "flac"],
+        "mov" => &["h264", "hevc", "prores", "mpeg4", "mjpeg", "dnxhd", "aac", "mp3", "alac", "ac3", "eac3", "pcm_s16le", "pcm_s24le"],
+        "webm" => &["vp8", "vp9", "av1", "vorbis", "opus"],
+        "ts" | "m2ts" => &["h264", "hevc", "mpeg2video", "aac", "mp3", "ac3", "eac3", "mp2"],
+        _ => return None,
+    };
+
+    Some(supported.contains(&codec))
+}
+
+impl FFmpeg {
+    /// Put the first video stream of `video` & the first audio stream of `audio` into `output` without re-encoding
+    ///
+    /// Fails before running FFmpeg if the container of `output` can't hold one of the codecs, e.g. opus in mov
+    pub fn mux(video: impl Into<PathBuf>, audio: impl Into<PathBuf>, output: impl Into<PathBuf>, options: MuxOptions) -> anyhow::Result<()> {
+        let (video, audio, output) = (video.into(), audio.into(), output.into());
+        let extension = output.extension().map(|extension| extension.to_string_lossy().to_lowercase()).unwrap_or_default();
+
+        let mut indices = Vec::new();
+
+        for (input, codec_type) in [(&video, "video"), (&audio, "audio")] {
+            let streams = FFprobe::probe_raw(input)?.streams;
+
+            let Some(stream) = mux_stream(&streams, codec_type) else { anyhow::bail!("{input:?} doesn't have a {codec_type} stream") };
+            let codec = stream.codec_name.as_deref().unwrap_or_default();
+
+            if container_supports(&extension, codec) == Some(false) {
+                anyhow::bail!("{codec} from {input:?} can't be muxed into {output:?} without re-encoding");
+            }
+
+            indices.push(stream.index);
+        }
+
+        options
+            .build(Self::quiet()?, (video, indices[0]), (audio, indices[1]), output)
+            .run_checked()?;
+
+        Ok(())
+    }
+
+    /// Copy every stream of `input` into the container of `output` without re-encoding
+    ///
+    /// The bitstream filters needed between mp4/mov & MPEG-TS are added, see [`remux_bitstream_filters`]
+    pub fn remux(input: impl Into<PathBuf>, output: impl Into<PathBuf>) -> anyhow::Result<()> {
+        let (input, output) = (input.into(), output.into());
+        let extension = |path: &Path| path.extension().map(|extension| extension.to_string_lossy().to_lowercase()).unwrap_or_default();
+
+        let streams = FFprobe::probe_raw(&input)?.streams;
+        let filters = remux_bitstream_filters(&extension(&input), &extension(&output), &streams);
+
+        let mut builder = Self::quiet()?
+            .input_with_file(input)
+            .done()
+            .output_as_file(output)
+            .map_all()
+            .args(["-c", "copy"]);
+
+        for (stream, bsf) in filters {
+            builder = builder.bitstream_filter(stream, bsf);
+        }
+
+        builder
+            .done()
+            .run_checked()?;
+
+        Ok(())
+    }
+}
+
+/// The bitstream filters to copy `streams` from a `from` file into a `to` file, by extension
+///
+/// H.264 & HEVC into MPEG-TS need Annex B start codes, AAC out of MPEG-TS needs its ADTS headers removed
+pub fn remux_bitstream_filters(from: &str, to: &str, streams: &[Stream]) -> Vec<(StreamSelector, Bsf)> {
+    let is_ts = |extension: &str| matches!(extension, "ts" | "m2ts" | "mts");
+    let is_mp4 = |extension: &str| matches!(extension, "mp4" | "m4v" | "m4a" | "mov");
+
+    streams
+        .iter()
+        .filter_map(|stream| {
+            let bsf = match stream.codec_name.as_deref()? {
+                "h264" if is_ts(to) && !is_ts(from) => Bsf::H264Mp4ToAnnexB,
+                "hevc" if is_ts(to) && !is_ts(from) => Bsf::HevcMp4ToAnnexB,
+                "aac" if is_mp4(to) && (is_ts(from) || from == "aac") => Bsf::AacAdtsToAsc,
+                _ => return None,
+            };
+
+            Some((StreamSelector::Index(stream.index as usize), bsf))
+        })
+        .collect()
+}
+
+/// Escape `text` so it can be used as is in a drawtext filter inside a filter graph
+pub(crate) fn escape_filter_text(text: &str) -> String {
+    let escape = |text: &str, special: &[char]| {
+        text.chars().fold(String::with_capacity(text.len()), |mut escaped, c| {
+            if special.contains(&c) {
+                escaped.push('\\');
+            }
+
+            escaped.push(c);
+            escaped
+        })
+    };
+
+    // Once for the filter option, once for the filter graph
+    escape(&escape(text, &['\\', '\'', ':']), &['\\', '\'', '[', ']', ',', ';'])
+}
+
+/// Escape `path` so it can be used as a file option of a filter inside a filter graph, e.g. `subtitles=` or `movie=`
+///
+/// Windows paths use forward slashes, FFmpeg accepts them & the drive colon still has to be escaped
+pub fn escape_filter_path(path: impl AsRef<Path>) -> String {
+    filter_path(&path.as_ref().to_string_lossy(), cfg!(windows))
+}
+
+fn filter_path(path: &str, windows: bool) -> String {
+    if !windows { return escape_filter_text(path) };
+
+    // `\\?\C:\...` can't be used with forward slashes, it's the same path without the prefix
+    let path = path.strip_prefix(r"\\?\").unwrap_or(path);
+
+    escape_filter_text(&path.replace('\\', "/"))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StackLayout {
+    /// Side by side, `hstack`
+    #[default]
+    Horizontal,
+    /// On top of each other, `vstack`
+    Vertical,
+}
+
+/// Options of [`FFmpeg::compare`]
+#[derive(Debug, Clone)]
+pub struct ComparisonOptions {
+    pub layout: StackLayout,
+    /// Height of both sides for [`StackLayout::Horizontal`], width for [`StackLayout::Vertical`]
+    pub size: u32,
+    /// Drawn on the top left of each side
+    pub labels: Option<(String, String)>,
+    /// Only compare this part, cut at the same time from both inputs
+    pub range: Option<Range<Duration>>,
+}
+
+impl Default for ComparisonOptions {
+    fn default() -> Self {
+        Self {
+            layout: StackLayout::Horizontal,
+            size: 720,
+            labels: None,
+            range: None,
+        }
+    }
+}
+
+impl ComparisonOptions {
+    /// The `-filter_complex` graph, outputs `[v]`
+    fn filter_graph(&self) -> String {
+        // The font size is relative to the height, so it's about the same for a 16:9 video in both layouts
+        let (scale, stack, font_size) = match self.layout {
+            StackLayout::Horizontal => (format!("scale=-2:{}", self.size), "hstack", self.size / 20),
+            StackLayout::Vertical => (format!("scale={}:-2", self.size), "vstack", self.size * 9 / 16 / 20),
+        };
+
+        let labels = self.labels.as_ref().map(|(a, b)| [a, b]);
+
+        let mut graph = Vec::new();
+
+        for index in 0..2 {
+            let mut chain = format!("[{index}:v]{scale},setsar=1");
+
+            if let Some(labels) = labels {
+                let label = TextOverlay {
+                    size: font_size,
+                    margin: 16,
+                    box_color: Some("black@0.5".to_string()),
+                    ..TextOverlay::new(labels[index])
+                };
+
+                chain.push_str(&format!(",{}", label.filter()));
+            }
+
+            graph.push(format!("{chain}[s{index}]"));
+        }
+
+        graph.push(format!("[s0][s1]{stack}=inputs=2:shortest=1[v]"));
+
+        graph.join(";")
+    }
+}
+
+impl FFmpeg {
+    /// Render `a` & `b` next to each other for a visual quality comparison, the audio of `a` is kept if there's one
+    pub fn compare(a: impl Into<PathBuf>, b: impl Into<PathBuf>, output: impl Into<PathBuf>, options: ComparisonOptions) -> anyhow::Result<()> {
+        let mut builder = Self::quiet()?;
+
+        for input in [a.into(), b.into()] {
+            if let Some(range) = &options.range {
+                builder = builder.args(["-ss".to_string(), range.start.as_secs_f64().to_string(), "-t".to_string(), range.end.saturating_sub(range.start).as_secs_f64().to_string()]);
+            }
+
+            builder = builder.input_with_file(input).done();
+        }
+
+        builder
+            .args(["-filter_complex", &options.filter_graph(), "-map", "[v]", "-map", "0:a?"])
+            .output_as_file(output.into())
+            .done()
+            .run_checked()?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverlayPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+    /// Top left corner of the overlay in pixels, ignores the margin
+    At(u32, u32),
+}
+
+impl OverlayPosition {
+    /// x & y expressions, `outer` & `inner` are the names of the width & height variables of the filter
+    fn expressions(&self, margin: u32, outer: (&str, &str), inner: (&str, &str)) -> (String, String) {
+        let ((outer_w, outer_h), (inner_w, inner_h)) = (outer, inner);
+
+        let right = format!("{outer_w}-{inner_w}-{margin}");
+        let bottom = format!("{outer_h}-{inner_h}-{margin}");
+
+        match self {
+            OverlayPosition::TopLeft => (margin.to_string(), margin.to_string()),
+            OverlayPosition::TopRight => (right, margin.to_string()),
+            OverlayPosition::BottomLeft => (margin.to_string(), bottom),
+            OverlayPosition::BottomRight => (right, bottom),
+            OverlayPosition::Center => (format!("({outer_w}-{inner_w})/2"), format!("({outer_h}-{inner_h})/2")),
+            OverlayPosition::At(x, y) => (x.to_string(), y.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverlaySize {
+    /// Width relative to the main video (e.g. 0.25), the aspect ratio is kept
+    Relative(f64),
+    /// Width & height in pixels
+    Fixed(u32, u32),
+}
+
+/// Options of [`FFmpeg::picture_in_picture`]
+#[derive(Debug, Clone)]
+pub struct PictureInPicture {
+    pub position: OverlayPosition,
+    pub size: OverlaySize,
+    /// Distance from the edges of the main video in pixels
+    pub margin: u32,
+    /// Width in pixels & color (e.g. `white` or `#ff0000`) of a border around the overlay
+    pub border: Option<(u32, String)>,
+    /// Only show the overlay during this part of the main video, the overlay starts playing when it's shown
+    pub range: Option<Range<Duration>>,
+}
+
+impl Default for PictureInPicture {
+    fn default() -> Self {
+        Self {
+            position: OverlayPosition::BottomRight,
+            size: OverlaySize::Relative(0.25),
+            margin: 24,
+            border: None,
+            range: None,
+        }
+    }
+}
+
+impl PictureInPicture {
+    /// The `-filter_complex` graph, outputs `[v]`
+    ///
+    /// `main_width` is only needed for [`OverlaySize::Relative`]
+    fn filter_graph(&self, main_width: Option<u32>) -> anyhow::Result<String> {
+        let scale = match self.size {
+            OverlaySize::Fixed(width, height) => format!("scale={width}:{height}"),
+            OverlaySize::Relative(ratio) => {
+                let Some(main_width) = main_width else { anyhow::bail!("The width of the main video is unknown, use OverlaySize::Fixed") };
+
+                // Even so it works with every pixel format
+                format!("scale={}:-2", (main_width as f64 * ratio / 2.0).round() as u32 * 2)
+            },
+        };
+
+        let mut overlay_chain = format!("[1:v]{scale},setsar=1");
+
+        if let Some((width, color)) = &self.border {
+            overlay_chain.push_str(&format!(",pad=iw+{}:ih+{}:{width}:{width}:color={color}", width * 2, width * 2));
+        }
+
+        if let Some(range) = &self.range {
+            overlay_chain.push_str(&format!(",setpts=PTS+{}/TB", range.start.as_secs_f64()));
+        }
+
+        let (x, y) = self.position.expressions(self.margin, ("W", "H"), ("w", "h"));
+
+        // pass keeps the main video going after the overlay ends instead of freezing its last frame
+        let mut overlay = format!("[0:v][pip]overlay=x={x}:y={y}:eof_action=pass");
+
+        if let Some(range) = &self.range {
+            overlay.push_str(&format!(":enable='between(t,{},{})'", range.start.as_secs_f64(), range.end.as_secs_f64()));
+        }
+
+        Ok(format!("{overlay_chain}[pip];{overlay}[v]"))
+    }
+}
+
+impl FFmpeg {
+    /// Show `overlay` on top of `main`, the audio of `main` is kept if there's one
+    pub fn picture_in_picture(main: impl Into<PathBuf>, overlay: impl Into<PathBuf>, output: impl Into<PathBuf>, options: PictureInPicture) -> anyhow::Result<()> {
+        let main = main.into();
+
+        let main_width = match options.size {
+            OverlaySize::Relative(_) => FFprobe::probe_raw(&main)?
+                .streams
+                .iter()
+                .find(|stream| stream.codec_type.as_deref() == Some("video"))
+                .and_then(|stream| stream.width)
+                .and_then(|width| u32::try_from(width).ok()),
+            OverlaySize::Fixed(..) => None,
+        };
+
+        Self::quiet()?
+            .input_with_file(main)
+            .done()
+            .input_with_file(overlay.into())
+            .done()
+            .args(["-filter_complex", &options.filter_graph(main_width)?, "-map", "[v]", "-map", "0:a?"])
+            .output_as_file(output.into())
+            .done()
+            .run_checked()?;
+
+        Ok(())
+    }
+}
+
+/// Text drawn on the video with the drawtext filter, see [`FFmpegBuilder::draw_text`]
+#[derive(Debug, Clone)]
+pub struct TextOverlay {
+    /// Drawn as is, there's no need to escape anything
+    pub text: String,
+    /// Path to a font file or the name of an installed font (e.g. `Arial`), a platform default if [`Option::None`]
+    pub font: Option<String>,
+    /// Font size in pixels
+    pub size: u32,
+    /// e.g. `white`, `#ffcc00` or `black@0.5`
+    pub color: String,
+    pub position: OverlayPosition,
+    /// Distance from the edges of the video in pixels
+    pub margin: u32,
+    /// Color of a box drawn behind the text
+    pub box_color: Option<String>,
+    /// Color of a shadow drawn down & right of the text
+    pub shadow: Option<String>,
+    /// Only draw the text during this part of the video
+    pub enable_range: Option<Range<Duration>>,
+}
+
+impl TextOverlay {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            font: None,
+            size: 48,
+            color: "white".to_string(),
+            position: OverlayPosition::TopLeft,
+            margin: 24,
+            box_color: None,
+            shadow: None,
+            enable_range: None,
+        }
+    }
+
+    /// The drawtext filter, escaped for a filter graph
+    fn filter(&self) -> String {
+        let (x, y) = self.position.expressions(self.margin, ("w", "h"), ("text_w", "text_h"));
+
+        let mut filter = format!(
+            "drawtext=text={}:expansion=none:fontsize={}:fontcolor={}:x={x}:y={y}",
+            escape_filter_text(&self.text),
+            self.size,
+            escape_filter_text(&self.color),
+        );
+
+        match self.font.as_deref().map(resolve_font).or_else(default_font) {
+            Some(Font::File(path)) => filter.push_str(&format!(":fontfile={}", escape_filter_path(path))),
+            Some(Font::Name(name)) => filter.push_str(&format!(":font={}", escape_filter_text(&name))),
+            None => { },
+        }
+
+        if let Some(color) = &self.box_color {
+            filter.push_str(&format!(":box=1:boxcolor={}:boxborderw={}", escape_filter_text(color), (self.size / 4).max(1)));
+        }
+
+        if let Some(color) = &self.shadow {
+            let offset = (self.size / 24).max(1);
+
+            filter.push_str(&format!(":shadowcolor={}:shadowx={offset}:shadowy={offset}", escape_filter_text(color)));
+        }
+
+        if let Some(range) = &self.enable_range {
+            filter.push_str(&format!(":enable='between(t,{},{})'", range.start.as_secs_f64(), range.end.as_secs_f64()));
+        }
+
+        filter
+    }
+}
+
+enum Font {
+    File(PathBuf),
+    /// Looked up by fontconfig
+    Name(String),
+}
+
+/// Folders with the system wide & user fonts
+fn font_folders() -> Vec<PathBuf> {
+    if cfg!(windows) {
+        let windows = std::env::var_os("WINDIR").map(PathBuf::from).unwrap_or_else(|| PathBuf::from(r"C:\Windows"));
+        let mut folders = vec![windows.join("Fonts")];
+
+        if let Some(local) = std::env::var_os("LOCALAPPDATA") {
+            folders.push(Path::new(&local).join(r"Microsoft\Windows\Fonts"));
+        }
+
+        folders
+    } else if cfg!(target_os = "macos") {
+        let mut folders = vec![PathBuf::from("/System/Library/Fonts"), PathBuf::from("/System/Library/Fonts/Supplemental"), PathBuf::from("/Library/Fonts")];
+
+        if let Some(home) = std::env::var_os("HOME") {
+            folders.push(Path::new(&home).join("Library/Fonts"));
+        }
+
+        folders
+    } else {
+        Vec::new()
+    }
+}
+
+/// A font installed as `<font>.ttf`, `.ttc` or `.otf` in [`font_folders`]
+fn find_font_file(font: &str) -> Option<PathBuf> {
+    font_folders().into_iter().find_map(|folder| {
+        ["ttf", "ttc", "otf"].into_iter().map(|extension| folder.join(format!("{font}.{extension}"))).find(|file| file.is_file())
+    })
+}
+
+/// FFmpeg builds for Windows & macOS usually don't have fontconfig, so font names are looked up as files there
+///
+/// Names that aren't found are still given to FFmpeg, so it reports the missing font instead of the text silently using another one
+fn resolve_font(font: &str) -> Font {
+    let path = Path::new(font);
+
+    // Let FFmpeg report a missing file instead of looking it up as a name
+    if path.is_file() || path.extension().is_some() || path.components().count() > 1 {
+        return Font::File(path.to_path_buf());
+    }
+
+    find_font_file(font).map_or_else(|| Font::Name(font.to_string()), Font::File)
+}
+
+/// drawtext needs a font file if FFmpeg is built without fontconfig
+fn default_font() -> Option<Font> {
+    if cfg!(windows) {
+        find_font_file("arial").map(Font::File)
+    } else if cfg!(target_os = "macos") {
+        find_font_file("Helvetica").map(Font::File)
+    } else {
+        None
+    }
+}
+
+/// How [`FFmpegBuilder::fit_canvas`] deals with a different aspect ratio
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FitMode {
+    /// Scale to fit inside, the empty space is filled with `color` (e.g. `black`)
+    Letterbox { color: String },
+    /// Scale to cover the whole canvas, the overflow is cut off equally from both sides
+    Crop,
+    /// Scale to the exact size, distorting the image
+    Stretch,
+}
+
+impl Default for FitMode {
+    fn default() -> Self {
+        FitMode::Letterbox { color: "black".to_string() }
+    }
+}
+
+impl FitMode {
+    pub(crate) fn filter(&self, width: u32, height: u32) -> String {
+        let chain = match self {
+            FitMode::Letterbox { color } => format!(
+                "scale={width}:{height}:force_original_aspect_ratio=decrease:force_divisible_by=2,pad={width}:{height}:(ow-iw)/2:(oh-ih)/2:color={}",
+                escape_filter_text(color),
+            ),
+            FitMode::Crop => format!("scale={width}:{height}:force_original_aspect_ratio=increase,crop={width}:{height}"),
+            FitMode::Stretch => format!("scale={width}:{height}"),
+        };
+
+        // Players would stretch it back otherwise
+        format!("{chain},setsar=1")
+    }
+}
+
+impl FFmpegBuilder<IO> {
+    /// Draw `overlay` on the video of this output
+    pub fn draw_text(self, overlay: &TextOverlay) -> Self {
+        self.video_filter(overlay.filter())
+    }
+
+    /// Burn the subtitles of `path` into the video, e.g. a `.srt` or `.ass` file
+    pub fn burn_subtitles(self, path: impl AsRef<Path>) -> Self {
+        self.video_filter(format!("subtitles={}", escape_filter_path(path)))
+    }
+
+    /// Same as [`FFmpegBuilder::burn_subtitles`] but the fonts in `fonts_dir` are used before the system fonts, e.g. the ones from [`FFmpeg::dump_attachments`]
+    pub fn burn_subtitles_with_fonts(self, path: impl AsRef<Path>, fonts_dir: impl AsRef<Path>) -> Self {
+        self.video_filter(format!("subtitles=filename={}:fontsdir={}", escape_filter_path(path), escape_filter_path(fonts_dir)))
+    }
+
+    /// Fade the video in from black & the audio in from silence
+    ///
+    /// The inputs this output maps are probed with ffprobe, so only the streams it actually has get a fade
+    pub fn fade_in(self, duration: Duration) -> anyhow::Result<Self> {
+        let infos = self.probe_mapped()?;
+
+        Ok(self.fade(&infos, "in", Duration::ZERO, duration))
+    }
+
+    /// Fade the video out to black & the audio out to silence at the end of the inputs this output maps
+    ///
+    /// The end is probed with ffprobe, trimming (e.g. `-t`) isn't taken into account, use [`FFmpegBuilder::fade_out_at`] for that
+    pub fn fade_out(self, duration: Duration) -> anyhow::Result<Self> {
+        let infos = self.probe_mapped()?;
+        let lengths = infos.iter().filter_map(|info| info.duration);
+
+        // FFmpeg stops at the longest stream, unless told to stop at the shortest
+        let length = match self.output_section().iter().any(|arg| arg == "-shortest") {
+            true => lengths.min(),
+            false => lengths.max(),
+        };
+        let length = length.context("Can't find the duration of the inputs of this output")?;
+
+        Ok(self.fade(&infos, "out", length.saturating_sub(duration), duration))
+    }
+
+    /// Same as [`FFmpegBuilder::fade_out`] but starting at `start` of the output
+    pub fn fade_out_at(self, start: Duration, duration: Duration) -> anyhow::Result<Self> {
+        let infos = self.probe_mapped()?;
+
+        Ok(self.fade(&infos, "out", start, duration))
+    }
+    /// Make the video exactly `width` x `height`, e.g. to give uploads with different aspect ratios the same size
+    pub fn fit_canvas(self, width: u32, height: u32, mode: FitMode) -> Self {
+        self.video_filter(mode.filter(width, height))
+    }
+
+    /// Only the streams that are in the inputs this output maps, FFmpeg fails on a filter for a stream the output doesn't have
+    fn fade(self, infos: &[MediaInfo], kind: &str, start: Duration, duration: Duration) -> Self {
+        let section = self.output_section();
+        let video = !section.iter().any(|arg| arg == "-vn") && infos.iter().any(|info| info.video().is_some());
+        let audio = !section.iter().any(|arg| arg == "-an") && infos.iter().any(|info| info.audio().is_some());
+
+        self.fade_streams(kind, start, duration, video, audio)
+    }
+
+    fn fade_streams(mut self, kind: &str, start: Duration, duration: Duration, video: bool, audio: bool) -> Self {
+        let (start, duration) = (start.as_secs_f64(), duration.as_secs_f64());
+
+        if video {
+            self = self.video_filter(format!("fade=t={kind}:st={start}:d={duration}"));
+        }
+
+        if audio {
+            self = self.audio_filter(format!("afade=t={kind}:st={start}:d={duration}"));
+        }
+
+        self
+    }
+
+    /// The options of this output that were already added
+    fn output_section(&self) -> &[OsString] {
+        let at = self.inserting_offset.unwrap_or(self.inner_args.len());
+        // Starts after the path of the previous input or output
+        let start = self.inner_args[..at].iter().rposition(|arg| arg == "-i" || is_output_marker(arg)).map_or(0, |index| index + 2);
+
+        &self.inner_args[start.min(at)..at]
+    }
+
+    /// The inputs this output takes streams from with `-map`
+    ///
+    /// Every input when it doesn't have any `-map` (FFmpeg picks the streams itself) or maps the output of a filter graph
+    fn mapped_inputs(&self) -> Vec<&OsStr> {
+        let inputs = self.inner_args.windows(2).filter(|pair| pair[0] == "-i").map(|pair| pair[1].as_os_str()).collect::<Vec<_>>();
+        let mut mapped = Vec::new();
+
+        for map in self.output_section().windows(2).filter(|pair| pair[0] == "-map").map(|pair| pair[1].to_string_lossy()) {
+            // Leaving streams out, e.g. `-map -0:s`
+            if map.starts_with('-') { continue }
+
+            let index = map.split(':').next().and_then(|index| index.parse::<usize>().ok());
+
+            match index.and_then(|index| inputs.get(index)) {
+                Some(input) if !mapped.contains(input) => mapped.push(*input),
+                Some(_) => {},
+                None => return inputs,
+            }
+        }
+
+        match mapped.is_empty() {
+            true => inputs,
+            false => mapped,
+        }
+    }
+
+    fn probe_mapped(&self) -> anyhow::Result<Vec<MediaInfo>> {
+        let inputs = self.mapped_inputs();
+
+        if inputs.is_empty() {
+            anyhow::bail!("A fade needs an input to know which streams there are");
+        }
+
+        inputs.into_iter().map(|input| FFprobe::probe(input).with_context(|| format!("Can't probe {input:?}"))).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mux_args() {
+        let streams: Vec<Stream> = serde_json::from_str(r#"[
+            { "index": 0, "codec_name": "mjpeg", "codec_type": "video", "disposition": { "attached_pic": 1 } },
+            { "index": 1, "codec_name": "h264", "codec_type": "video" },
+            { "index": 2, "codec_name": "aac", "codec_type": "audio" }
+        ]"#).unwrap();
+
+        assert_eq!(mux_stream(&streams, "video").map(|stream| stream.index), Some(1));
+        assert_eq!(mux_stream(&streams, "audio").map(|stream| stream.index), Some(2));
+        assert!(mux_stream(&streams, "subtitle").is_none());
+
+        let builder = MuxOptions { shortest: true }.build(FFmpeg::new_with_program("ffmpeg"), ("video.mp4".into(), 1), ("audio.m4a".into(), 0), "out.mp4".into());
+
+        assert_eq!(builder.inner_args, [
+            "-fflags", "+genpts", "-i", "video.mp4", "-fflags", "+genpts", "-i", "audio.m4a",
+            "-map", "0:1", "-map", "1:0", "-c", "copy", "-avoid_negative_ts", "make_zero", "-shortest", "-y", "out.mp4",
+        ]);
+    }
+
+    #[test]
+    fn fade_mapped_streams() {
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("intro.mp4".into())
+            .done()
+            .output_as_file("intro.mp4".into())
+            .done()
+            .input_with_file("video.mp4".into())
+            .done()
+            .input_with_file("music.m4a".into())
+            .done()
+            .output_as_file("out.m4a".into())
+            .args(["-map", "2:a", "-map", "-0:s", "-vn"]);
+
+        assert_eq!(builder.output_section(), ["-map", "2:a", "-map", "-0:s", "-vn"]);
+        assert_eq!(builder.mapped_inputs(), ["music.m4a"]);
+
+        let builder = builder.fade_streams("out", Duration::from_secs(9), Duration::from_secs(1), false, true);
+
+        assert!(!builder.inner_args.iter().any(|arg| arg == "-vf"));
+        assert!(builder.inner_args.windows(2).any(|args| args == ["-af", "afade=t=out:st=9:d=1"]));
+
+        // A filter graph can take from any input
+        let builder = builder.done().output_as_file("out.mp4".into()).args(["-map", "[v]"]);
+
+        assert_eq!(builder.mapped_inputs(), ["intro.mp4", "video.mp4", "music.m4a"]);
+    }
+
+    #[test]
+    fn remux_filters() {
+        let streams: Vec<Stream> = serde_json::from_str(r#"[
+            { "index": 0, "codec_name": "h264", "codec_type": "video" },
+            { "index": 1, "codec_name": "aac", "codec_type": "audio" },
+            { "index": 2, "codec_name": "hevc", "codec_type": "video" }
+        ]"#).unwrap();
+
+        assert_eq!(remux_bitstream_filters("mp4", "ts", &streams), [
+            (StreamSelector::Index(0), Bsf::H264Mp4ToAnnexB),
+            (StreamSelector::Index(2), Bsf::HevcMp4ToAnnexB),
+        ]);
+        assert_eq!(remux_bitstream_filters("ts", "mov", &streams), [(StreamSelector::Index(1), Bsf::AacAdtsToAsc)]);
+        assert!(remux_bitstream_filters("mkv", "mp4", &streams).is_empty());
+    }
+
+    #[test]
+    fn windows_filter_paths() {
+        assert_eq!(filter_path(r"C:\Users\me\subs [en].srt", true), r"C\\:/Users/me/subs \[en\].srt");
+        assert_eq!(filter_path(r"\\?\D:\fonts\a.ttf", true), r"D\\:/fonts/a.ttf");
+        assert_eq!(filter_path(r"/home/me/odd\name.srt", false), r"/home/me/odd\\\\name.srt");
+    }
+
+    #[test]
+    fn comparison_filter_graph() {
+        assert_eq!(escape_filter_text("x264: crf 'low'"), r"x264\\: crf \\\'low\\\'");
+
+        let options = ComparisonOptions {
+            layout: StackLayout::Vertical,
+            labels: Some(("Original".to_string(), "CRF 28".to_string())),
+            size: 1280,
+            ..Default::default()
+        };
+
+        let graph = options.filter_graph();
+        let chains = graph.split(';').collect::<Vec<_>>();
+
+        assert!(chains[1].starts_with("[1:v]scale=1280:-2,setsar=1,drawtext=text=CRF 28:expansion=none:fontsize=36:"));
+        assert_eq!(chains[2], "[s0][s1]vstack=inputs=2:shortest=1[v]");
+    }
+
+    #[test]
+    fn picture_in_picture_filter_graph() {
+        let options = PictureInPicture {
+            border: Some((4, "white".to_string())),
+            range: Some(Duration::from_secs(5)..Duration::from_millis(12500)),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            options.filter_graph(Some(1921)).unwrap(),
+            "[1:v]scale=480:-2,setsar=1,pad=iw+8:ih+8:4:4:color=white,setpts=PTS+5/TB[pip];\
+            [0:v][pip]overlay=x=W-w-24:y=H-h-24:eof_action=pass:enable='between(t,5,12.5)'[v]",
+        );
+        assert!(options.filter_graph(None).is_err());
+    }
+
+    #[test]
+    fn text_overlay_filter() {
+        let overlay = TextOverlay {
+            font: Some("/nonexistent/Font Bold.ttf".to_string()),
+            position: OverlayPosition::BottomRight,
+            box_color: Some("black@0.5".to_string()),
+            enable_range: Some(Duration::ZERO..Duration::from_secs(3)),
+            ..TextOverlay::new("100% done: it's 5:00")
+        };
+
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("in.mp4".into())
+            .done()
+            .output_as_file("out.mp4".into())
+            .video_filter("hflip")
+            .draw_text(&overlay)
+            .done();
+
+        assert_eq!(builder.inner_args[..4], ["-i", "in.mp4", "-vf", "hflip,drawtext=text=100% done\\\\: it\\\\\\'s 5\\\\:00:expansion=none:fontsize=48:\
+            fontcolor=white:x=w-text_w-24:y=h-text_h-24:fontfile=/nonexistent/Font Bold.ttf:box=1:boxcolor=black@0.5:boxborderw=12:enable='between(t,0,3)'"]);
+    }
+
+    #[test]
+    fn missing_font_is_kept() {
+        let overlay = TextOverlay { font: Some("No Such Font 1234".to_string()), ..TextOverlay::new("hi") };
+
+        assert!(overlay.filter().ends_with(":font=No Such Font 1234"));
+    }
+
+    #[test]
+    fn fit_canvas_filters() {
+        assert_eq!(
+            FitMode::default().filter(1080, 1920),
+            "scale=1080:1920:force_original_aspect_ratio=decrease:force_divisible_by=2,pad=1080:1920:(ow-iw)/2:(oh-ih)/2:color=black,setsar=1",
+        );
+        assert_eq!(FitMode::Crop.filter(1280, 720), "scale=1280:720:force_original_aspect_ratio=increase,crop=1280:720,setsar=1");
+    }
+}
@@ -0,0 +1,265 @@
+use std::{io::Write, path::PathBuf};
+
+use anyhow::Context;
+
+use crate::{compose::FitMode, probe::{schema::Stream, FFprobe}, temp::TempOutput, FFmpeg};
+
+/// How [`FFmpeg::concat`] joined the inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConcatStrategy {
+    /// `-f concat`, copies the streams of inputs with the same codecs & parameters
+    Demuxer,
+    /// `concat:a.ts|b.ts`, joins MPEG-TS files byte by byte
+    Protocol,
+    /// The `concat` filter, re-encodes everything so the inputs can be anything
+    Filter,
+}
+
+/// The strategy [`FFmpeg::concat`] chose & why
+#[derive(Debug, Clone)]
+pub struct ConcatReport {
+    pub strategy: ConcatStrategy,
+    pub reason: String,
+}
+
+/// What has to be the same for every input to copy the streams, e.g. `video h264 High 1920x1080 yuv420p 30/1fps sar 1:1 tb 1/90000`
+///
+/// Fields that ffprobe didn't report are left out
+fn stream_signature(stream: &Stream) -> Option<String> {
+    let codec = stream.codec_name.as_deref().unwrap_or("unknown");
+    let profile = stream.profile.as_deref().unwrap_or_default();
+    let time_base = stream.time_base.as_deref().map(|time_base| format!("tb {time_base}")).unwrap_or_default();
+
+    let fields = match stream.codec_type.as_deref()? {
+        "video" => vec![
+            "video".to_string(),
+            codec.to_string(),
+            profile.to_string(),
+            format!("{}x{}", stream.width.unwrap_or_default(), stream.height.unwrap_or_default()),
+            stream.pix_fmt.clone().unwrap_or_default(),
+            stream.r_frame_rate.as_deref().map(|rate| format!("{rate}fps")).unwrap_or_default(),
+            stream.sample_aspect_ratio.as_deref().map(|sar| format!("sar {sar}")).unwrap_or_default(),
+            time_base,
+        ],
+        "audio" => vec![
+            "audio".to_string(),
+            codec.to_string(),
+            profile.to_string(),
+            format!("{}Hz", stream.sample_rate.as_deref().unwrap_or_default()),
+            format!("{} channels", stream.channels.unwrap_or_default()),
+            time_base,
+        ],
+        "subtitle" => vec!["subtitle".to_string(), codec.to_string()],
+        _ => return None,
+    };
+
+    Some(fields.into_iter().filter(|field| !field.is_empty()).collect::<Vec<_>>().join(" "))
+}
+
+/// Choose a strategy from the format name & the streams of every input
+fn choose_strategy(inputs: &[(String, Vec<Stream>)], output_extension: &str) -> ConcatReport {
+    // Timestamps & codec parameters are stored differently by every container, the demuxer can't copy across them
+    if let Some((index, (format, _))) = inputs.iter().enumerate().skip(1).find(|(_, (format, _))| *format != inputs[0].0) {
+        return ConcatReport {
+            strategy: ConcatStrategy::Filter,
+            reason: format!("input {index} is {format} but input 0 is {}", inputs[0].0),
+        };
+    }
+
+    let signatures = inputs.iter().map(|(_, streams)| streams.iter().filter_map(stream_signature).collect::<Vec<_>>()).collect::<Vec<_>>();
+
+    let mismatch = signatures.iter().enumerate().skip(1).find(|(_, signature)| **signature != signatures[0]);
+
+    if let Some((index, signature)) = mismatch {
+        return ConcatReport {
+            strategy: ConcatStrategy::Filter,
+            reason: format!("input {index} has [{}] but input 0 has [{}]", signature.join(", "), signatures[0].join(", ")),
+        };
+    }
+
+    if inputs.iter().all(|(format, _)| format == "mpegts") && matches!(output_extension, "ts" | "m2ts" | "mts") {
+        return ConcatReport {
+            strategy: ConcatStrategy::Protocol,
+            reason: "every input is MPEG-TS with the same streams".to_string(),
+        };
+    }
+
+    ConcatReport {
+        strategy: ConcatStrategy::Demuxer,
+        reason: format!("every input has the same streams, [{}]", signatures.first().map(|signature| signature.join(", ")).unwrap_or_default()),
+    }
+}
+
+/// A concat demuxer list, paths are made absolute because they're relative to the list
+fn concat_list(inputs: &[PathBuf]) -> anyhow::Result<String> {
+    let current = std::env::current_dir()?;
+
+    Ok(inputs
+        .iter()
+        .map(|input| format!("file '{}'\n", current.join(input).display().to_string().replace('\'', r"'\''")))
+        .collect::<Vec<_>>()
+        .concat())
+}
+
+/// The concat filter graph & its output labels, the videos are letterboxed to the size of the first one
+fn concat_filter(inputs: &[Vec<Stream>]) -> anyhow::Result<(String, Vec<&'static str>)> {
+    let has = |codec_type: &str| inputs.iter().map(|streams| streams.iter().any(|stream| stream.codec_type.as_deref() == Some(codec_type))).collect::<Vec<_>>();
+    let (video, audio) = (has("video"), has("audio"));
+
+    if video.iter().any(|has| *has != video[0]) || audio.iter().any(|has| *has != audio[0]) {
+        anyhow::bail!("every input needs the same kind of streams");
+    }
+
+    let size = inputs[0].iter().find(|stream| stream.codec_type.as_deref() == Some("video")).and_then(|stream| Some((stream.width?, stream.height?)));
+    let fit = FitMode::default();
+
+    let mut graph = Vec::new();
+    let mut segments = Vec::new();
+
+    for index in 0..inputs.len() {
+        if let Some((width, height)) = size.filter(|_| video[0]) {
+            graph.push(format!("[{index}:v:0]{}[v{index}];", fit.filter(width as u32, height as u32)));
+            segments.push(format!("[v{index}]"));
+        }
+
+        if audio[0] {
+            segments.push(format!("[{index}:a:0]"));
+        }
+    }
+
+    let outputs = [(video[0], "[v]"), (audio[0], "[a]")].into_iter().filter(|(has, _)| *has).map(|(_, label)| label).collect::<Vec<_>>();
+
+    graph.push(format!("{}concat=n={}:v={}:a={}{}", segments.concat(), inputs.len(), u8::from(video[0]), u8::from(audio[0]), outputs.concat()));
+
+    Ok((graph.concat(), outputs))
+}
+
+impl FFmpeg {
+    /// Join `inputs` one after another into `output`, the fastest way that works for the inputs is chosen by probing them
+    ///
+    /// - Same container, codecs & parameters (profile, frame rate, aspect ratio & time base): the concat demuxer, without re-encoding
+    /// - Same as above & MPEG-TS in & out: the concat protocol
+    /// - Anything else: the concat filter, the videos are letterboxed to the size of the first input & everything is re-encoded
+    pub fn concat(inputs: impl IntoIterator<Item = impl Into<PathBuf>>, output: impl Into<PathBuf>) -> anyhow::Result<ConcatReport> {
+        let (inputs, output) = (inputs.into_iter().map(Into::into).collect::<Vec<PathBuf>>(), output.into());
+
+        if inputs.is_empty() {
+            anyhow::bail!("concat needs at least one input");
+        }
+
+        let probes = inputs
+            .iter()
+            .map(|input| {
+                let probe = FFprobe::probe_raw(input)?;
+                let format = probe.format.and_then(|format| format.format_name).unwrap_or_default();
+
+                Ok((format, probe.streams))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let extension = output.extension().map(|extension| extension.to_string_lossy().to_lowercase()).unwrap_or_default();
+        let report = choose_strategy(&probes, &extension);
+
+        let builder = Self::quiet()?;
+
+        let builder = match report.strategy {
+            ConcatStrategy::Protocol => {
+                let joined = inputs.iter().map(|input| input.display().to_string()).collect::<Vec<_>>().join("|");
+
+                builder.input_with_file(PathBuf::from(format!("concat:{joined}"))).done().args(["-map", "0", "-c", "copy"])
+            },
+            ConcatStrategy::Demuxer => {
+                let mut list = TempOutput::create()?;
+                list.write_all(concat_list(&inputs)?.as_bytes())?;
+
+                let mut builder = builder.args(["-f", "concat", "-safe", "0"]).input_with_file(list.path().to_path_buf()).done();
+
+                // Deleted with the command
+                builder.start_options.temp_files.push(list);
+
+                builder.args(["-map", "0", "-c", "copy"])
+            },
+            ConcatStrategy::Filter => {
+                let (graph, outputs) = concat_filter(&probes.into_iter().map(|(_, streams)| streams).collect::<Vec<_>>())
+                    .with_context(|| format!("Can't join the inputs, {}", report.reason))?;
+
+                let builder = inputs.iter().fold(builder, |builder, input| builder.input_with_file(input.clone()).done());
+
+                outputs.iter().fold(builder.args(["-filter_complex", &graph]), |builder, label| builder.args(["-map", label]))
+            },
+        };
+
+        builder
+            .output_as_file(output)
+            .done()
+            .run_checked()
+            .with_context(|| format!("Concat with {:?} failed, {}", report.strategy, report.reason))?;
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn strategy_selection() {
+        let streams = |json: &str| serde_json::from_str::<Vec<Stream>>(json).unwrap();
+
+        let hd = streams(r#"[
+            { "codec_type": "video", "codec_name": "h264", "profile": "High", "width": 1920, "height": 1080, "pix_fmt": "yuv420p",
+              "r_frame_rate": "30/1", "sample_aspect_ratio": "1:1", "time_base": "1/90000" },
+            { "codec_type": "audio", "codec_name": "aac", "profile": "LC", "sample_rate": "48000", "channels": 2, "time_base": "1/48000" },
+            { "codec_type": "data", "codec_name": "bin_data" }
+        ]"#);
+        let sd = streams(r#"[
+            { "codec_type": "video", "codec_name": "h264", "width": 1280, "height": 720, "pix_fmt": "yuv420p" },
+            { "codec_type": "audio", "codec_name": "aac", "sample_rate": "48000", "channels": 2 }
+        ]"#);
+
+        let mut hd_60fps = hd.clone();
+        hd_60fps[0].r_frame_rate = Some("60/1".to_string());
+
+        let report = choose_strategy(&[("mpegts".to_string(), hd.clone()), ("mpegts".to_string(), hd.clone())], "ts");
+        assert_eq!(report.strategy, ConcatStrategy::Protocol);
+
+        let report = choose_strategy(&[("mov,mp4,m4a,3gp,3g2,mj2".to_string(), hd.clone()), ("mov,mp4,m4a,3gp,3g2,mj2".to_string(), hd.clone())], "mp4");
+        assert_eq!(report.strategy, ConcatStrategy::Demuxer);
+
+        let report = choose_strategy(&[("mpegts".to_string(), hd.clone()), ("mov,mp4,m4a,3gp,3g2,mj2".to_string(), hd.clone())], "mp4");
+        assert_eq!(report.strategy, ConcatStrategy::Filter);
+        assert_eq!(report.reason, "input 1 is mov,mp4,m4a,3gp,3g2,mj2 but input 0 is mpegts");
+
+        let report = choose_strategy(&[("mpegts".to_string(), hd.clone()), ("mpegts".to_string(), hd_60fps)], "ts");
+        assert_eq!(report.strategy, ConcatStrategy::Filter);
+
+        let report = choose_strategy(&[("mpegts".to_string(), hd), ("mpegts".to_string(), sd)], "ts");
+        assert_eq!(report.strategy, ConcatStrategy::Filter);
+        assert!(report.reason.starts_with("input 1 has [video h264 1280x720 yuv420p, audio aac 48000Hz 2 channels] but input 0 has [video h264 High 1920x1080 yuv420p 30/1fps sar 1:1 tb 1/90000"));
+    }
+
+    #[test]
+    fn filter_graph() -> anyhow::Result<()> {
+        let video = serde_json::from_str::<Vec<Stream>>(r#"[{ "codec_type": "video", "width": 1280, "height": 720 }]"#)?;
+        let both = serde_json::from_str::<Vec<Stream>>(r#"[{ "codec_type": "video", "width": 640, "height": 480 }, { "codec_type": "audio" }]"#)?;
+
+        let (graph, outputs) = concat_filter(&[video.clone(), video.clone()])?;
+
+        assert_eq!(outputs, ["[v]"]);
+        assert!(graph.starts_with("[0:v:0]scale=1280:720:force_original_aspect_ratio=decrease"));
+        assert!(graph.ends_with("[v0][v1]concat=n=2:v=1:a=0[v]"));
+        assert!(concat_filter(&[video, both]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn demuxer_list_quoting() -> anyhow::Result<()> {
+        let list = concat_list(&[PathBuf::from("/media/it's.mp4")])?;
+
+        assert_eq!(list, "file '/media/it'\\''s.mp4'\n");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,192 @@
+use tokio::{sync::mpsc::{channel, Receiver}, task::JoinHandle};
+
+use crate::{download_binaries, probe::FFprobe, Checksum, DownloadComponents, DownloadedBinary, FFmpeg, FFmpegDownloadProgress};
+
+/// The platform a binary is downloaded for, see [`DownloadTarget::current`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DownloadTarget {
+    /// Same as [`std::env::consts::ARCH`], e.g. `x86_64` or `aarch64`
+    pub arch: &'static str,
+    /// Same as [`std::env::consts::OS`], e.g. `linux`, `windows` or `macos`
+    pub os: &'static str,
+}
+
+impl DownloadTarget {
+    /// The platform this was compiled for
+    pub fn current() -> Self {
+        Self { arch: std::env::consts::ARCH, os: std::env::consts::OS }
+    }
+
+    /// The Rust target triple, e.g. `x86_64-unknown-linux-gnu`
+    ///
+    /// The environment (`gnu`, `musl` or `msvc`) is the one this was compiled with
+    pub fn triple(&self) -> String {
+        let rest = match self.os {
+            "linux" if cfg!(target_env = "musl") => "unknown-linux-musl",
+            "linux" => "unknown-linux-gnu",
+            "windows" if cfg!(target_env = "gnu") => "pc-windows-gnu",
+            "windows" => "pc-windows-msvc",
+            "macos" => "apple-darwin",
+            os => os,
+        };
+
+        format!("{}-{rest}", self.arch)
+    }
+}
+
+/// How a [`FFmpegDownloadSource`] packs the binaries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArchiveLayout {
+    /// One download per binary, each a gzip of the binary or an archive with it
+    #[default]
+    PerBinary,
+    /// One archive with every binary in it, e.g. the full builds of BtbN or gyan.dev
+    Bundle,
+}
+
+/// Where [`FFmpeg::auto_download_from`] gets the binaries from, e.g. an internal mirror
+///
+/// ```
+/// use essi_ffmpeg::{ArchiveLayout, Checksum, DownloadComponents, DownloadTarget, FFmpegDownloadSource};
+///
+/// struct Mirror;
+///
+/// impl FFmpegDownloadSource for Mirror {
+///     fn url(&self, target: &DownloadTarget, _: DownloadComponents) -> Option<String> {
+///         Some(format!("https://mirror.example.com/ffmpeg/{}.tar.xz", target.triple()))
+///     }
+///
+///     fn layout(&self) -> ArchiveLayout {
+///         ArchiveLayout::Bundle
+///     }
+///
+///     fn checksum(&self, _: &DownloadTarget, _: DownloadComponents) -> Option<Checksum> {
+///         Some(Checksum::Sidecar)
+///     }
+/// }
+/// ```
+pub trait FFmpegDownloadSource {
+    /// The download of `components` for `target`, [`Option::None`] if there's no build for it
+    ///
+    /// With [`ArchiveLayout::PerBinary`] it's asked for one component at a time
+    fn url(&self, target: &DownloadTarget, components: DownloadComponents) -> Option<String>;
+
+    /// Whether every component has its own download, [`ArchiveLayout::PerBinary`] by default
+    fn layout(&self) -> ArchiveLayout {
+        ArchiveLayout::PerBinary
+    }
+
+    /// How the download of [`FFmpegDownloadSource::url`] is verified, not at all by default
+    fn checksum(&self, _target: &DownloadTarget, _components: DownloadComponents) -> Option<Checksum> {
+        None
+    }
+}
+
+/// The static builds of https://github.com/eugeneware/ffmpeg-static/releases/tag/b6.0, used by [`FFmpeg::auto_download`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EugenewareSource;
+
+impl FFmpegDownloadSource for EugenewareSource {
+    fn url(&self, target: &DownloadTarget, components: DownloadComponents) -> Option<String> {
+        let platform = match (target.os, target.arch) {
+            ("windows", "x86_64") => "win32-x64",
+            ("linux", "x86_64") => "linux-x64",
+            ("linux", "aarch64") => "linux-arm64",
+            ("macos", "x86_64") => "darwin-x64",
+            ("macos", "aarch64") => "darwin-arm64",
+            _ => return None,
+        };
+
+        let name = match components {
+            DownloadComponents::FFMPEG => "ffmpeg",
+            DownloadComponents::FFPROBE => "ffprobe",
+            _ => return None,
+        };
+
+        Some(format!("https://github.com/eugeneware/ffmpeg-static/releases/download/b6.0/{name}-{platform}.gz"))
+    }
+}
+
+/// The binaries of `components` that can't be found yet
+pub(crate) fn missing_binaries(components: DownloadComponents) -> anyhow::Result<Vec<(DownloadComponents, DownloadedBinary)>> {
+    let mut missing = Vec::new();
+
+    if components.contains(DownloadComponents::FFMPEG) && FFmpeg::get_program()?.is_none() {
+        missing.push((DownloadComponents::FFMPEG, ("ffmpeg", FFmpeg::get_program as fn() -> _)));
+    }
+
+    if components.contains(DownloadComponents::FFPROBE) && FFprobe::get_program()?.is_none() {
+        missing.push((DownloadComponents::FFPROBE, ("ffprobe", FFprobe::get_program as fn() -> _)));
+    }
+
+    Ok(missing)
+}
+
+impl FFmpeg {
+    /// Download the missing binaries of `components` from `source` for the current platform
+    ///
+    /// Returns [`Option::None`] if every one of them exists.
+    /// With [`ArchiveLayout::PerBinary`] they're downloaded one after another, so [`FFmpegDownloadProgress::Downloading`] starts from 0 again for every component
+    pub async fn auto_download_from(source: &impl FFmpegDownloadSource, components: DownloadComponents) -> anyhow::Result<Option<(JoinHandle<Result<(), anyhow::Error>>, Receiver<FFmpegDownloadProgress>)>> {
+        let missing = missing_binaries(components)?;
+        if missing.is_empty() { return Ok(None) };
+
+        let target = DownloadTarget::current();
+        let no_build = |components| anyhow::anyhow!("The download source has no build of {components:?} for {}", target.triple());
+
+        if source.layout() == ArchiveLayout::Bundle {
+            let components = missing.iter().fold(DownloadComponents::NONE, |all, (component, _)| all | *component);
+            let url = source.url(&target, components).ok_or_else(|| no_build(components))?;
+
+            return download_binaries(&url, missing.into_iter().map(|(_, binary)| binary).collect(), source.checksum(&target, components)).await.map(Some);
+        }
+
+        // Resolved up front, so a missing build fails before anything is downloaded
+        let downloads = missing.into_iter()
+            .map(|(component, binary)| {
+                let url = source.url(&target, component).ok_or_else(|| no_build(component))?;
+
+                Ok((url, binary, source.checksum(&target, component)))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let (progress_tx, progress_rx) = channel(256);
+
+        let handle = tokio::task::spawn(async move {
+            let count = downloads.len();
+
+            for (index, (url, binary, checksum)) in downloads.into_iter().enumerate() {
+                let (handle, mut progress) = download_binaries(&url, vec![binary], checksum).await?;
+
+                while let Some(state) = progress.recv().await {
+                    // Only the last component finishes the whole download
+                    if matches!(state, FFmpegDownloadProgress::Finished) && index + 1 < count { continue };
+
+                    let _ = progress_tx.send(state).await;
+                }
+
+                handle.await??;
+            }
+
+            Ok::<(), anyhow::Error>(())
+        });
+
+        Ok(Some((handle, progress_rx)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn eugeneware_urls() {
+        let linux = DownloadTarget { arch: "aarch64", os: "linux" };
+
+        assert_eq!(EugenewareSource.url(&linux, DownloadComponents::FFPROBE).unwrap(), "https://github.com/eugeneware/ffmpeg-static/releases/download/b6.0/ffprobe-linux-arm64.gz");
+        assert!(EugenewareSource.url(&linux, DownloadComponents::FFMPEG | DownloadComponents::FFPROBE).is_none());
+        assert!(EugenewareSource.url(&DownloadTarget { arch: "riscv64", os: "linux" }, DownloadComponents::FFMPEG).is_none());
+
+        assert_eq!(DownloadTarget { arch: "x86_64", os: "macos" }.triple(), "x86_64-apple-darwin");
+    }
+}
@@ -0,0 +1,716 @@
+//! Typed encoder specific options, see [`FFmpegBuilder::x26x_params`], [`FFmpegBuilder::svtav1`] & [`FFmpegBuilder::libopus`]
+
+use std::{fmt::Display, process::{Command, Stdio}};
+
+use anyhow::Context;
+
+use crate::{FFmpeg, FFmpegBuilder, IO};
+
+/// Fails if `key` or `value` would break a `key=value:...` parameter string
+fn check_param(key: &str, value: &str) -> anyhow::Result<()> {
+    if key.is_empty() || key.contains([':', '=']) {
+        anyhow::bail!("{key:?} isn't a valid parameter name");
+    }
+
+    if value.contains(':') {
+        anyhow::bail!("The value of {key} can't contain ':', got {value:?}");
+    }
+
+    Ok(())
+}
+
+/// Sets `key` to `value`, replacing the value it already had
+fn set_param(params: &mut Vec<(String, String)>, key: &str, value: impl Display) {
+    let value = value.to_string();
+
+    match params.iter_mut().find(|(existing, _)| existing == key) {
+        Some((_, existing)) => *existing = value,
+        None => params.push((key.to_string(), value)),
+    }
+}
+
+fn join_params(params: &[(String, String)]) -> String {
+    params.iter().map(|(key, value)| format!("{key}={value}")).collect::<Vec<_>>().join(":")
+}
+
+fn check_range(name: &str, value: Option<u8>, range: std::ops::RangeInclusive<u8>) -> anyhow::Result<()> {
+    match value {
+        Some(value) if !range.contains(&value) => anyhow::bail!("{name} goes from {} to {}, got {value}", range.start(), range.end()),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum X26xEncoder {
+    /// `libx264`, `-x264-params`
+    X264,
+    /// `libx265`, `-x265-params`
+    X265,
+}
+
+impl X26xEncoder {
+    pub fn codec(&self) -> &'static str {
+        match self {
+            Self::X264 => "libx264",
+            Self::X265 => "libx265",
+        }
+    }
+
+    fn params_option(&self) -> &'static str {
+        match self {
+            Self::X264 => "-x264-params",
+            Self::X265 => "-x265-params",
+        }
+    }
+
+    /// Highest `aq-mode`, x265 has an extra mode for dark scenes
+    fn max_aq_mode(&self) -> u8 {
+        match self {
+            Self::X264 => 3,
+            Self::X265 => 4,
+        }
+    }
+}
+
+/// `-x264-params` & `-x265-params`, rendered as `key=value:key=value`
+///
+/// Setting a parameter again replaces it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct X26xParams {
+    params: Vec<(String, String)>,
+}
+
+impl X26xParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Most frames between keyframes, the GOP size
+    pub fn keyint(self, frames: u32) -> Self {
+        self.set("keyint", frames)
+    }
+
+    /// Fewest frames between keyframes, a scene cut closer than this to the last keyframe isn't a keyframe
+    pub fn min_keyint(self, frames: u32) -> Self {
+        self.set("min-keyint", frames)
+    }
+
+    /// How eagerly keyframes are put on scene cuts, 0 for fixed GOPs, e.g. for segmenting
+    pub fn scenecut(self, threshold: u32) -> Self {
+        self.set("scenecut", threshold)
+    }
+
+    /// Adaptive quantization, 0 is off & 1 is variance AQ
+    pub fn aq_mode(self, mode: u8) -> Self {
+        self.set("aq-mode", mode)
+    }
+
+    pub fn aq_strength(self, strength: f32) -> Self {
+        self.set("aq-strength", strength)
+    }
+
+    /// Psychovisual rate distortion, keeps grain & detail at the cost of bitrate
+    pub fn psy_rd(self, strength: f32) -> Self {
+        self.set("psy-rd", strength)
+    }
+
+    /// Most consecutive B-frames
+    pub fn bframes(self, frames: u32) -> Self {
+        self.set("bframes", frames)
+    }
+
+    /// Reference frames
+    pub fn ref_frames(self, frames: u32) -> Self {
+        self.set("ref", frames)
+    }
+
+    /// Frames looked ahead by the rate control
+    pub fn rc_lookahead(self, frames: u32) -> Self {
+        self.set("rc-lookahead", frames)
+    }
+
+    /// Deblocking filter strength & threshold, from -6 to 6
+    pub fn deblock(self, strength: i8, threshold: i8) -> Self {
+        self.set("deblock", format!("{strength},{threshold}"))
+    }
+
+    /// Any other parameter, fails if `key` or `value` would break the `key=value:...` string
+    pub fn param(self, key: impl AsRef<str>, value: impl Display) -> anyhow::Result<Self> {
+        let (key, value) = (key.as_ref(), value.to_string());
+        check_param(key, &value)?;
+
+        Ok(self.set(key, value))
+    }
+
+    fn set(mut self, key: &str, value: impl Display) -> Self {
+        set_param(&mut self.params, key, value);
+
+        self
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.params.iter().find(|(existing, _)| existing == key).map(|(_, value)| value.as_str())
+    }
+
+    /// Check the values against what `encoder` accepts
+    pub fn validate(&self, encoder: X26xEncoder) -> anyhow::Result<()> {
+        let number = |key: &str| self.get(key).and_then(|value| value.parse::<i64>().ok());
+
+        if let (Some(keyint), Some(min_keyint)) = (number("keyint"), number("min-keyint")) {
+            if min_keyint > keyint {
+                anyhow::bail!("min-keyint ({min_keyint}) is bigger than keyint ({keyint})");
+            }
+        }
+
+        if number("aq-mode").is_some_and(|mode| mode > encoder.max_aq_mode() as i64) {
+            anyhow::bail!("{} only has aq-mode 0 to {}", encoder.codec(), encoder.max_aq_mode());
+        }
+
+        if let Some(deblock) = self.get("deblock") {
+            let valid = deblock.split(',').all(|value| value.parse::<i8>().is_ok_and(|value| (-6..=6).contains(&value)));
+
+            if !valid { anyhow::bail!("deblock values go from -6 to 6, got {deblock}") };
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for X26xParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", join_params(&self.params))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Av1Encoder {
+    /// `libsvtav1`, the fastest software encoder
+    SvtAv1,
+    /// `libaom-av1`, the reference encoder
+    Aom,
+    /// `librav1e`
+    Rav1e,
+}
+
+impl Av1Encoder {
+    pub fn codec(&self) -> &'static str {
+        match self {
+            Self::SvtAv1 => "libsvtav1",
+            Self::Aom => "libaom-av1",
+            Self::Rav1e => "librav1e",
+        }
+    }
+}
+
+/// Options of `libsvtav1`, see [`FFmpegBuilder::svtav1`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SvtAv1Options {
+    preset: Option<u8>,
+    crf: Option<u8>,
+    params: Vec<(String, String)>,
+}
+
+impl SvtAv1Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 0 (slowest) to 13 (fastest)
+    pub fn preset(mut self, preset: u8) -> Self {
+        self.preset = Some(preset);
+
+        self
+    }
+
+    /// 0 to 63, lower is better quality
+    pub fn crf(mut self, crf: u8) -> Self {
+        self.crf = Some(crf);
+
+        self
+    }
+
+    /// Synthesize film grain of this strength (up to 50) instead of encoding it, `film-grain` of `-svtav1-params`
+    pub fn film_grain(mut self, strength: u8) -> Self {
+        set_param(&mut self.params, "film-grain", strength);
+
+        self
+    }
+
+    /// Most frames between keyframes, `keyint` of `-svtav1-params`
+    pub fn keyint(mut self, frames: u32) -> Self {
+        set_param(&mut self.params, "keyint", frames);
+
+        self
+    }
+
+    /// Any other `-svtav1-params` parameter, e.g. `tune=0`
+    pub fn param(mut self, key: impl AsRef<str>, value: impl Display) -> anyhow::Result<Self> {
+        let (key, value) = (key.as_ref(), value.to_string());
+        check_param(key, &value)?;
+        set_param(&mut self.params, key, value);
+
+        Ok(self)
+    }
+
+    fn args(&self) -> anyhow::Result<Vec<String>> {
+        check_range("preset", self.preset, 0..=13)?;
+        check_range("crf", self.crf, 0..=63)?;
+
+        let mut args = Vec::new();
+        if let Some(preset) = self.preset { args.extend(["-preset".to_string(), preset.to_string()]) };
+        if let Some(crf) = self.crf { args.extend(["-crf".to_string(), crf.to_string()]) };
+        if !self.params.is_empty() { args.extend(["-svtav1-params".to_string(), join_params(&self.params)]) };
+
+        Ok(args)
+    }
+}
+
+/// Options of `libaom-av1`, see [`FFmpegBuilder::libaom_av1`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AomOptions {
+    cpu_used: Option<u8>,
+    crf: Option<u8>,
+    row_mt: bool,
+    tiles: Option<(u8, u8)>,
+}
+
+impl AomOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 0 (slowest) to 8 (fastest)
+    pub fn cpu_used(mut self, cpu_used: u8) -> Self {
+        self.cpu_used = Some(cpu_used);
+
+        self
+    }
+
+    /// 0 to 63, constant quality without a bitrate limit
+    pub fn crf(mut self, crf: u8) -> Self {
+        self.crf = Some(crf);
+
+        self
+    }
+
+    /// Encode rows of tiles in parallel, much faster on many cores
+    pub fn row_mt(mut self, row_mt: bool) -> Self {
+        self.row_mt = row_mt;
+
+        self
+    }
+
+    /// Split every frame into `columns`x`rows` tiles, needed for [`AomOptions::row_mt`] to do anything
+    pub fn tiles(mut self, columns: u8, rows: u8) -> Self {
+        self.tiles = Some((columns, rows));
+
+        self
+    }
+
+    fn args(&self) -> anyhow::Result<Vec<String>> {
+        check_range("cpu-used", self.cpu_used, 0..=8)?;
+        check_range("crf", self.crf, 0..=63)?;
+
+        let mut args = Vec::new();
+        if let Some(cpu_used) = self.cpu_used { args.extend(["-cpu-used".to_string(), cpu_used.to_string()]) };
+        // Without `-b:v 0` the crf is capped by the default bitrate
+        if let Some(crf) = self.crf { args.extend(["-crf".to_string(), crf.to_string(), "-b:v".to_string(), "0".to_string()]) };
+        if self.row_mt { args.extend(["-row-mt".to_string(), "1".to_string()]) };
+        if let Some((columns, rows)) = self.tiles { args.extend(["-tiles".to_string(), format!("{columns}x{rows}")]) };
+
+        Ok(args)
+    }
+}
+
+/// Options of `librav1e`, see [`FFmpegBuilder::rav1e`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rav1eOptions {
+    speed: Option<u8>,
+    qp: Option<u8>,
+    tiles: Option<u8>,
+}
+
+impl Rav1eOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 0 (slowest) to 10 (fastest)
+    pub fn speed(mut self, speed: u8) -> Self {
+        self.speed = Some(speed);
+
+        self
+    }
+
+    /// Constant quantizer, 0 to 255
+    pub fn qp(mut self, qp: u8) -> Self {
+        self.qp = Some(qp);
+
+        self
+    }
+
+    /// How many tiles to split every frame into for parallel encoding
+    pub fn tiles(mut self, tiles: u8) -> Self {
+        self.tiles = Some(tiles);
+
+        self
+    }
+
+    fn args(&self) -> anyhow::Result<Vec<String>> {
+        check_range("speed", self.speed, 0..=10)?;
+
+        let mut args = Vec::new();
+        if let Some(speed) = self.speed { args.extend(["-speed".to_string(), speed.to_string()]) };
+        if let Some(qp) = self.qp { args.extend(["-qp".to_string(), qp.to_string()]) };
+        if let Some(tiles) = self.tiles { args.extend(["-tiles".to_string(), tiles.to_string()]) };
+
+        Ok(args)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpusApplication {
+    /// Best for speech
+    Voip,
+    /// Best for music, the default
+    Audio,
+    /// Lowest latency at the cost of quality
+    LowDelay,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpusVbr {
+    /// Constant bitrate
+    Off,
+    /// Variable bitrate, the default
+    On,
+    /// Variable bitrate that never goes over the target, for streaming
+    Constrained,
+}
+
+/// Options of `libopus`, see [`FFmpegBuilder::libopus`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OpusOptions {
+    bitrate: Option<u32>,
+    vbr: Option<OpusVbr>,
+    application: Option<OpusApplication>,
+    frame_duration: Option<f32>,
+    compression_level: Option<u8>,
+}
+
+impl OpusOptions {
+    /// The frame durations libopus accepts, in milliseconds
+    const FRAME_DURATIONS: [f32; 9] = [2.5, 5.0, 10.0, 20.0, 40.0, 60.0, 80.0, 100.0, 120.0];
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// In bits per second, e.g. `96_000`
+    pub fn bitrate(mut self, bitrate: u32) -> Self {
+        self.bitrate = Some(bitrate);
+
+        self
+    }
+
+    pub fn vbr(mut self, vbr: OpusVbr) -> Self {
+        self.vbr = Some(vbr);
+
+        self
+    }
+
+    pub fn application(mut self, application: OpusApplication) -> Self {
+        self.application = Some(application);
+
+        self
+    }
+
+    /// In milliseconds, one of 2.5, 5, 10, 20, 40, 60, 80, 100 or 120, longer frames are more efficient for low bitrates
+    pub fn frame_duration(mut self, milliseconds: f32) -> Self {
+        self.frame_duration = Some(milliseconds);
+
+        self
+    }
+
+    /// 0 (fastest) to 10 (best quality)
+    pub fn compression_level(mut self, level: u8) -> Self {
+        self.compression_level = Some(level);
+
+        self
+    }
+
+    fn args(&self) -> anyhow::Result<Vec<String>> {
+        check_range("compression_level", self.compression_level, 0..=10)?;
+
+        if let Some(duration) = self.frame_duration.filter(|duration| !Self::FRAME_DURATIONS.contains(duration)) {
+            anyhow::bail!("libopus can't use {duration}ms frames, use one of {:?}", Self::FRAME_DURATIONS);
+        }
+
+        let mut args = Vec::new();
+        if let Some(bitrate) = self.bitrate { args.extend(["-b:a".to_string(), bitrate.to_string()]) };
+
+        if let Some(vbr) = self.vbr {
+            let vbr = match vbr {
+                OpusVbr::Off => "off",
+                OpusVbr::On => "on",
+                OpusVbr::Constrained => "constrained",
+            };
+
+            args.extend(["-vbr".to_string(), vbr.to_string()]);
+        }
+
+        if let Some(application) = self.application {
+            let application = match application {
+                OpusApplication::Voip => "voip",
+                OpusApplication::Audio => "audio",
+                OpusApplication::LowDelay => "lowdelay",
+            };
+
+            args.extend(["-application".to_string(), application.to_string()]);
+        }
+
+        if let Some(duration) = self.frame_duration { args.extend(["-frame_duration".to_string(), duration.to_string()]) };
+        if let Some(level) = self.compression_level { args.extend(["-compression_level".to_string(), level.to_string()]) };
+
+        Ok(args)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AacEncoder {
+    /// `libfdk_aac`, better quality at low bitrates but missing from most builds because of its license
+    FdkAac,
+    /// `aac`, the native encoder that every build has
+    Native,
+}
+
+impl AacEncoder {
+    pub fn codec(&self) -> &'static str {
+        match self {
+            Self::FdkAac => "libfdk_aac",
+            Self::Native => "aac",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AacProfile {
+    /// AAC-LC, plays everywhere
+    #[default]
+    Lc,
+    /// HE-AAC, for low bitrates, only with [`AacEncoder::FdkAac`]
+    He,
+    /// HE-AACv2, for very low bitrate stereo, only with [`AacEncoder::FdkAac`]
+    HeV2,
+}
+
+/// Options of the AAC encoders, see [`FFmpegBuilder::aac`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AacOptions {
+    bitrate: Option<u32>,
+    profile: AacProfile,
+    afterburner: Option<bool>,
+    vbr: Option<u8>,
+}
+
+impl AacOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// In bits per second, e.g. `128_000`
+    pub fn bitrate(mut self, bitrate: u32) -> Self {
+        self.bitrate = Some(bitrate);
+
+        self
+    }
+
+    pub fn profile(mut self, profile: AacProfile) -> Self {
+        self.profile = profile;
+
+        self
+    }
+
+    /// Slower but better quality, on by default in `libfdk_aac`, ignored by the native encoder
+    pub fn afterburner(mut self, afterburner: bool) -> Self {
+        self.afterburner = Some(afterburner);
+
+        self
+    }
+
+    /// `libfdk_aac` variable bitrate mode from 1 (lowest) to 5 (highest), the native encoder uses the bitrate instead
+    pub fn vbr(mut self, mode: u8) -> Self {
+        self.vbr = Some(mode);
+
+        self
+    }
+
+    fn args(&self, encoder: AacEncoder) -> anyhow::Result<Vec<String>> {
+        check_range("vbr", self.vbr, 1..=5)?;
+
+        let mut args = Vec::new();
+        if let Some(bitrate) = self.bitrate { args.extend(["-b:a".to_string(), bitrate.to_string()]) };
+
+        if encoder == AacEncoder::Native {
+            // The native encoder only does LC, the HE profiles fall back to it
+            return Ok(args);
+        }
+
+        let profile = match self.profile {
+            AacProfile::Lc => "aac_low",
+            AacProfile::He => "aac_he",
+            AacProfile::HeV2 => "aac_he_v2",
+        };
+
+        args.extend(["-profile:a".to_string(), profile.to_string()]);
+        if let Some(afterburner) = self.afterburner { args.extend(["-afterburner".to_string(), (afterburner as u8).to_string()]) };
+        if let Some(vbr) = self.vbr { args.extend(["-vbr".to_string(), vbr.to_string()]) };
+
+        Ok(args)
+    }
+}
+
+/// Names of the encoders in the output of `ffmpeg -encoders`
+fn parse_encoders(output: &str) -> Vec<String> {
+    output
+        .lines()
+        // Everything after the " ------" line, e.g. " V....D libsvtav1            SVT-AV1(Scalable Video Technology for AV1) encoder"
+        .skip_while(|line| !line.trim_start().starts_with("---"))
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(str::to_string)
+        .collect()
+}
+
+impl FFmpeg {
+    /// Every encoder this FFmpeg has, e.g. `libx264` or `aac`
+    pub fn encoders() -> anyhow::Result<Vec<String>> {
+        let program = Self::get_program()?.context("Can't find FFmpeg in your system")?;
+
+        let output = Command::new(program)
+            .args(["-hide_banner", "-encoders"])
+            .stdin(Stdio::null())
+            .output()?;
+
+        if !output.status.success() {
+            anyhow::bail!("FFmpeg exited with {}\n{}", output.status, String::from_utf8_lossy(&output.stderr).trim());
+        }
+
+        Ok(parse_encoders(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// The AV1 encoders this FFmpeg was built with, builds often have none or only some of them
+    pub fn av1_encoders() -> anyhow::Result<Vec<Av1Encoder>> {
+        let encoders = Self::encoders()?;
+
+        Ok([Av1Encoder::SvtAv1, Av1Encoder::Aom, Av1Encoder::Rav1e].into_iter().filter(|encoder| encoders.iter().any(|name| name == encoder.codec())).collect())
+    }
+
+    /// `libfdk_aac` if this FFmpeg was built with it, otherwise the native encoder
+    pub fn aac_encoder() -> anyhow::Result<AacEncoder> {
+        match Self::encoders()?.iter().any(|name| name == AacEncoder::FdkAac.codec()) {
+            true => Ok(AacEncoder::FdkAac),
+            false => Ok(AacEncoder::Native),
+        }
+    }
+}
+
+impl FFmpegBuilder<IO> {
+    /// Encode the video with `encoder` & `params`, sets the video codec
+    pub fn x26x_params(self, encoder: X26xEncoder, params: &X26xParams) -> anyhow::Result<Self> {
+        params.validate(encoder)?;
+
+        Ok(self.codec_video(encoder.codec()).args([encoder.params_option().to_string(), params.to_string()]))
+    }
+
+    /// Encode the video with `libsvtav1`, sets the video codec
+    pub fn svtav1(self, options: &SvtAv1Options) -> anyhow::Result<Self> {
+        Ok(self.codec_video(Av1Encoder::SvtAv1.codec()).args(options.args()?))
+    }
+
+    /// Encode the video with `libaom-av1`, sets the video codec
+    pub fn libaom_av1(self, options: &AomOptions) -> anyhow::Result<Self> {
+        Ok(self.codec_video(Av1Encoder::Aom.codec()).args(options.args()?))
+    }
+
+    /// Encode the video with `librav1e`, sets the video codec
+    pub fn rav1e(self, options: &Rav1eOptions) -> anyhow::Result<Self> {
+        Ok(self.codec_video(Av1Encoder::Rav1e.codec()).args(options.args()?))
+    }
+
+    /// Encode the audio with `libopus`, sets the audio codec
+    pub fn libopus(self, options: &OpusOptions) -> anyhow::Result<Self> {
+        Ok(self.codec_audio("libopus").args(options.args()?))
+    }
+
+    /// Encode the audio with `encoder`, sets the audio codec
+    ///
+    /// Options the encoder doesn't have are left out, use [`FFmpegBuilder::aac_auto`] to prefer `libfdk_aac` when it's there
+    pub fn aac(self, encoder: AacEncoder, options: &AacOptions) -> anyhow::Result<Self> {
+        Ok(self.codec_audio(encoder.codec()).args(options.args(encoder)?))
+    }
+
+    /// Same as [`FFmpegBuilder::aac`] with `libfdk_aac` if this FFmpeg has it, the native encoder otherwise
+    pub fn aac_auto(self, options: &AacOptions) -> anyhow::Result<Self> {
+        let encoder = FFmpeg::aac_encoder()?;
+
+        self.aac(encoder, options)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn x26x_param_string() -> anyhow::Result<()> {
+        let params = X26xParams::new()
+            .keyint(120)
+            .min_keyint(120)
+            .scenecut(0)
+            .aq_mode(3)
+            .psy_rd(1.0)
+            .deblock(-1, -1)
+            .param("no-open-gop", 1)?
+            .keyint(240);
+
+        assert_eq!(params.to_string(), "keyint=240:min-keyint=120:scenecut=0:aq-mode=3:psy-rd=1:deblock=-1,-1:no-open-gop=1");
+        assert!(params.validate(X26xEncoder::X264).is_ok());
+
+        assert!(params.clone().aq_mode(4).validate(X26xEncoder::X264).is_err());
+        assert!(params.clone().aq_mode(4).validate(X26xEncoder::X265).is_ok());
+        assert!(params.clone().min_keyint(300).validate(X26xEncoder::X265).is_err());
+        assert!(params.clone().deblock(-7, 0).validate(X26xEncoder::X264).is_err());
+        assert!(params.param("zones", "0,100,q=20:crf=18").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn av1_options() -> anyhow::Result<()> {
+        let svt = SvtAv1Options::new().preset(8).crf(35).film_grain(8).keyint(240);
+        assert_eq!(svt.args()?, ["-preset", "8", "-crf", "35", "-svtav1-params", "film-grain=8:keyint=240"]);
+        assert!(SvtAv1Options::new().crf(64).args().is_err());
+
+        let aom = AomOptions::new().cpu_used(6).crf(30).row_mt(true).tiles(2, 2);
+        assert_eq!(aom.args()?, ["-cpu-used", "6", "-crf", "30", "-b:v", "0", "-row-mt", "1", "-tiles", "2x2"]);
+
+        let encoders = "Encoders:\n V..... = Video\n ------\n V....D libaom-av1           libaom AV1 (codec av1)\n V..... libsvtav1            SVT-AV1(Scalable Video Technology for AV1) encoder (codec av1)\n A....D aac                  AAC (Advanced Audio Coding)\n";
+        assert_eq!(parse_encoders(encoders), ["libaom-av1", "libsvtav1", "aac"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn audio_options() -> anyhow::Result<()> {
+        let opus = OpusOptions::new().bitrate(24_000).vbr(OpusVbr::Constrained).application(OpusApplication::Voip).frame_duration(60.0);
+        assert_eq!(opus.args()?, ["-b:a", "24000", "-vbr", "constrained", "-application", "voip", "-frame_duration", "60"]);
+        assert!(OpusOptions::new().frame_duration(30.0).args().is_err());
+
+        let aac = AacOptions::new().bitrate(48_000).profile(AacProfile::HeV2).afterburner(true);
+        assert_eq!(aac.args(AacEncoder::FdkAac)?, ["-b:a", "48000", "-profile:a", "aac_he_v2", "-afterburner", "1"]);
+        assert_eq!(aac.args(AacEncoder::Native)?, ["-b:a", "48000"]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+//! Switch a live transcode to a backup input when the current one stalls, see [`Failover`]
+
+use std::{
+    process::ExitStatus,
+    sync::{atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering}, Arc},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use tokio::sync::mpsc::{error::TryRecvError, unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::{
+    health::{HealthEvent, HealthMonitor, HealthThresholds},
+    process,
+    stage::InputStage,
+    FFmpeg, FFmpegBuilder, Normal,
+};
+
+/// How long a stalled FFmpeg gets to exit after being interrupted before it's killed
+const STOP_GRACE: Duration = Duration::from_secs(3);
+
+/// Why [`Failover`] moved to another input
+#[derive(Debug)]
+pub enum SwitchReason {
+    /// The health monitor reported [`HealthEvent::Stalled`]
+    Stalled,
+    /// FFmpeg failed by itself, e.g. the source refused the connection
+    Failed(ExitStatus),
+}
+
+#[derive(Debug)]
+pub enum FailoverEvent {
+    /// `source` is the index of the input in [`Failover::new`]
+    Started { source: usize, pid: u32 },
+    Health { source: usize, event: HealthEvent },
+    Switching { from: usize, to: usize, reason: SwitchReason },
+    /// Every input failed one after another, waiting [`Failover::retry_delay`] before trying them again
+    Waiting { delay: Duration },
+    /// The last run finished successfully, or the failover was stopped
+    Finished { status: ExitStatus },
+}
+
+/// Runs the same outputs on a list of inputs, moving to the next input when the current one stalls or fails
+///
+/// The first input is the primary one, after the last input it starts over from the first
+pub struct Failover<F> {
+    sources: Vec<InputStage>,
+    outputs: F,
+    thresholds: HealthThresholds,
+    retry_delay: Duration,
+    max_rounds: Option<u32>,
+}
+
+impl<F> Failover<F>
+where
+    F: FnMut(FFmpegBuilder<Normal>) -> FFmpegBuilder<Normal> + Send + 'static,
+{
+    /// `outputs` adds the outputs (& anything else) to a builder that already has the current source as the first input
+    pub fn new(sources: impl IntoIterator<Item = InputStage>, outputs: F) -> Self {
+        Self { sources: sources.into_iter().collect(), outputs, thresholds: HealthThresholds::default(), retry_delay: Duration::from_secs(1), max_rounds: None }
+    }
+
+    /// What counts as stalled, see [`HealthThresholds::stall_timeout`]
+    pub fn thresholds(mut self, thresholds: HealthThresholds) -> Self {
+        self.thresholds = thresholds;
+
+        self
+    }
+
+    /// Delay after every input failed in a row, switching between inputs has no delay
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+
+        self
+    }
+
+    /// Give up with an error once every input failed `rounds` times in a row, an input that reports [`HealthEvent::Healthy`] starts over
+    ///
+    /// It never gives up by default
+    pub fn max_rounds(mut self, rounds: u32) -> Self {
+        self.max_rounds = Some(rounds);
+
+        self
+    }
+
+    /// Start running the first input in a background thread
+    pub fn start(self) -> anyhow::Result<(FailoverHandle, UnboundedReceiver<FailoverEvent>)> {
+        if self.sources.is_empty() {
+            anyhow::bail!("Failover needs at least one input");
+        }
+
+        let (event_tx, event_rx) = unbounded_channel();
+
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let pid = Arc::new(AtomicU32::new(0));
+        let source = Arc::new(AtomicUsize::new(0));
+
+        let thread = std::thread::spawn({
+            let (shutdown, pid, source) = (shutdown.clone(), pid.clone(), source.clone());
+
+            move || self.run(event_tx, shutdown, pid, source)
+        });
+
+        Ok((FailoverHandle { thread, shutdown, pid, source }, event_rx))
+    }
+
+    fn run(mut self, event_tx: UnboundedSender<FailoverEvent>, shutdown: Arc<AtomicBool>, current_pid: Arc<AtomicU32>, current_source: Arc<AtomicUsize>) -> anyhow::Result<ExitStatus> {
+        let mut source = 0;
+        let mut rotation = Rotation::new(self.sources.len(), self.max_rounds);
+
+        loop {
+            current_source.store(source, Ordering::SeqCst);
+
+            let (health_tx, mut health_rx) = unbounded_channel();
+            let builder = (self.outputs)(FFmpeg::try_new()?.add_input(self.sources[source].clone()));
+            let mut command = builder.start_with_health_monitor(HealthMonitor::with_thresholds(health_tx, self.thresholds.clone()))?;
+            let pid = command.pid();
+
+            current_pid.store(pid, Ordering::SeqCst);
+
+            // Stopped while this run was being started
+            if shutdown.load(Ordering::SeqCst) {
+                let _ = process::interrupt(pid);
+            }
+
+            let _ = event_tx.send(FailoverEvent::Started { source, pid });
+
+            let stalled = Arc::new(AtomicBool::new(false));
+            let healthy = Arc::new(AtomicBool::new(false));
+            let exited = Arc::new(AtomicBool::new(false));
+
+            // Polled because the progress (& the monitor with it) outlives FFmpeg if it never opened the progress pipe
+            let watcher = std::thread::spawn({
+                let (event_tx, stalled, healthy, exited, current_pid) = (event_tx.clone(), stalled.clone(), healthy.clone(), exited.clone(), current_pid.clone());
+
+                move || loop {
+                    let event = match health_rx.try_recv() {
+                        Ok(event) => event,
+                        Err(TryRecvError::Empty) if !exited.load(Ordering::SeqCst) => {
+                            std::thread::sleep(Duration::from_millis(50));
+                            continue;
+                        },
+                        Err(_) => break,
+                    };
+
+                    if event == HealthEvent::Healthy {
+                        healthy.store(true, Ordering::SeqCst);
+                    }
+
+                    if event == HealthEvent::Stalled && !stalled.swap(true, Ordering::SeqCst) {
+                        let _ = process::interrupt(pid);
+
+                        // A read stuck on a dead connection can ignore the interrupt
+                        let current_pid = current_pid.clone();
+                        std::thread::spawn(move || {
+                            std::thread::sleep(STOP_GRACE);
+
+                            if current_pid.load(Ordering::SeqCst) == pid { let _ = process::kill(pid); }
+                        });
+                    }
+
+                    let _ = event_tx.send(FailoverEvent::Health { source, event });
+                }
+            });
+
+            // Also joins the progress reader, even if FFmpeg failed before opening the progress FIFO
+            let status = command.wait()?;
+            current_pid.store(0, Ordering::SeqCst);
+            exited.store(true, Ordering::SeqCst);
+            drop(command);
+            let _ = watcher.join();
+
+            let stalled = stalled.load(Ordering::SeqCst);
+
+            if shutdown.load(Ordering::SeqCst) || (status.success() && !stalled) {
+                let _ = event_tx.send(FailoverEvent::Finished { status });
+
+                return Ok(status);
+            }
+
+            let reason = match stalled {
+                true => SwitchReason::Stalled,
+                false => SwitchReason::Failed(status),
+            };
+
+            // A source that recovered starts a new streak of failures
+            if healthy.load(Ordering::SeqCst) {
+                rotation.reset();
+            }
+
+            let (next, wait) = rotation.next(source)?;
+            let _ = event_tx.send(FailoverEvent::Switching { from: source, to: next, reason });
+
+            source = next;
+
+            if !wait { continue };
+
+            let _ = event_tx.send(FailoverEvent::Waiting { delay: self.retry_delay });
+
+            // Sleep in small steps so stopping doesn't wait for the whole delay
+            let mut slept = Duration::ZERO;
+            while slept < self.retry_delay {
+                if shutdown.load(Ordering::SeqCst) {
+                    let _ = event_tx.send(FailoverEvent::Finished { status });
+
+                    return Ok(status);
+                }
+
+                let step = (self.retry_delay - slept).min(Duration::from_millis(100));
+                std::thread::sleep(step);
+                slept += step;
+            }
+        }
+    }
+}
+
+/// Picks the input to switch to after one fails
+struct Rotation {
+    sources: usize,
+    /// Inputs that failed in a row
+    failures: usize,
+    rounds: u32,
+    max_rounds: Option<u32>,
+}
+
+impl Rotation {
+    fn new(sources: usize, max_rounds: Option<u32>) -> Self {
+        Self { sources, failures: 0, rounds: 0, max_rounds }
+    }
+
+    /// Forget the failures so far, once an input ran healthy
+    fn reset(&mut self) {
+        self.failures = 0;
+        self.rounds = 0;
+    }
+
+    /// The input after `source` failed, with `true` if every input failed in a row & it's time for [`Failover::retry_delay`]
+    fn next(&mut self, source: usize) -> anyhow::Result<(usize, bool)> {
+        let next = (source + 1) % self.sources;
+
+        self.failures += 1;
+        if self.failures < self.sources { return Ok((next, false)) };
+
+        self.failures = 0;
+        self.rounds += 1;
+
+        if self.max_rounds.is_some_and(|max| self.rounds >= max) {
+            anyhow::bail!("Every input failed {} time(s)", self.rounds);
+        }
+
+        Ok((next, true))
+    }
+}
+
+pub struct FailoverHandle {
+    thread: JoinHandle<anyhow::Result<ExitStatus>>,
+    shutdown: Arc<AtomicBool>,
+    pid: Arc<AtomicU32>,
+    source: Arc<AtomicUsize>,
+}
+
+impl FailoverHandle {
+    /// Pid of the currently running FFmpeg, [`Option::None`] while switching
+    pub fn pid(&self) -> Option<u32> {
+        match self.pid.load(Ordering::SeqCst) {
+            0 => None,
+            pid => Some(pid),
+        }
+    }
+
+    /// Index of the input being used
+    pub fn source(&self) -> usize {
+        self.source.load(Ordering::SeqCst)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Stop the current FFmpeg run & don't switch to another input
+    pub fn stop(self) -> anyhow::Result<ExitStatus> {
+        self.shutdown.store(true, Ordering::SeqCst);
+
+        if let Some(pid) = self.pid() {
+            process::interrupt(pid).or_else(|_| process::kill(pid))?;
+        }
+
+        self.join()
+    }
+
+    /// Wait until a run finishes successfully
+    pub fn join(self) -> anyhow::Result<ExitStatus> {
+        match self.thread.join() {
+            Ok(result) => result,
+            Err(_) => anyhow::bail!("The failover thread panicked"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn next_source() {
+        let mut rotation = Rotation::new(3, Some(2));
+
+        assert_eq!(rotation.next(0).unwrap(), (1, false));
+        assert_eq!(rotation.next(1).unwrap(), (2, false));
+        // Back to the primary input after a delay
+        assert_eq!(rotation.next(2).unwrap(), (0, true));
+
+        assert_eq!(rotation.next(0).unwrap(), (1, false));
+        assert_eq!(rotation.next(1).unwrap(), (2, false));
+        assert!(rotation.next(2).is_err());
+
+        let mut rotation = Rotation::new(1, None);
+
+        for _ in 0..10 {
+            assert_eq!(rotation.next(0).unwrap(), (0, true));
+        }
+
+        // Recovering in between doesn't count towards the rounds
+        let mut rotation = Rotation::new(2, Some(1));
+
+        assert_eq!(rotation.next(0).unwrap(), (1, false));
+        rotation.reset();
+        assert_eq!(rotation.next(1).unwrap(), (0, false));
+        rotation.reset();
+        assert_eq!(rotation.next(0).unwrap(), (1, false));
+        assert!(rotation.next(1).is_err());
+    }
+}
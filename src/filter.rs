@@ -0,0 +1,210 @@
+//! `-filter_complex` graphs built from typed filters, see [`FilterGraph`]
+//!
+//! Option values are escaped when the graph is rendered, so they can contain `:`, `,`, `'` or brackets
+
+use std::{fmt::Display, time::Duration};
+
+use crate::{compose::escape_filter_text, stream::StreamSelector, FFmpegBuilder, InputHandle, Normal, IO};
+
+/// A single filter, `name=value:key=value`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    name: String,
+    /// [`Option::None`] keys are positional
+    options: Vec<(Option<String>, String)>,
+}
+
+impl Filter {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into(), options: Vec::new() }
+    }
+
+    /// `key=value`, the value is escaped
+    pub fn option(mut self, key: impl Into<String>, value: impl Display) -> Self {
+        self.options.push((Some(key.into()), value.to_string()));
+
+        self
+    }
+
+    /// A value without a key, in the order the filter expects them, e.g. `scale=1280:720`
+    pub fn value(mut self, value: impl Display) -> Self {
+        self.options.push((None, value.to_string()));
+
+        self
+    }
+
+    /// `-1` or `-2` keep the aspect ratio, `-2` also keeps the size divisible by 2
+    pub fn scale(width: i32, height: i32) -> Self {
+        Self::new("scale").option("w", width).option("h", height)
+    }
+
+    /// Put the second input on top of the first one, `x` & `y` can be expressions like `main_w-overlay_w-10`
+    pub fn overlay(x: impl Display, y: impl Display) -> Self {
+        Self::new("overlay").option("x", x).option("y", y)
+    }
+
+    /// Join `segments` one after another, every segment has `video` video & `audio` audio pads in that order
+    pub fn concat(segments: usize, video: usize, audio: usize) -> Self {
+        Self::new("concat").option("n", segments).option("v", video).option("a", audio)
+    }
+
+    /// Mix `inputs` audio pads into one
+    pub fn amix(inputs: usize) -> Self {
+        Self::new("amix").option("inputs", inputs)
+    }
+
+    /// Transition from the first video to the second one starting at `offset` of the first, e.g. `fade` or `wipeleft`
+    pub fn xfade(transition: impl Into<String>, duration: Duration, offset: Duration) -> Self {
+        Self::new("xfade")
+            .option("transition", transition.into())
+            .option("duration", duration.as_secs_f64())
+            .option("offset", offset.as_secs_f64())
+    }
+}
+
+impl Display for Filter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+
+        for (index, (key, value)) in self.options.iter().enumerate() {
+            let separator = if index == 0 { '=' } else { ':' };
+
+            match key {
+                Some(key) => write!(f, "{separator}{key}={}", escape_filter_text(value))?,
+                None => write!(f, "{separator}{}", escape_filter_text(value))?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A labeled pad, either a stream of an input or the output of a chain
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Pad(String);
+
+impl Pad {
+    /// A stream of an input, `[1:a:0]`
+    pub fn input(input: InputHandle, stream: StreamSelector) -> Self {
+        Self(format!("{}:{stream}", input.0))
+    }
+
+    /// The label without brackets
+    pub fn label(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for Pad {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}]", self.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Chain {
+    inputs: Vec<Pad>,
+    filters: Vec<Filter>,
+    outputs: Vec<Pad>,
+}
+
+/// Chains of filters connected by their pads
+///
+/// ```
+/// use std::time::Duration;
+/// use essi_ffmpeg::{filter::{Filter, FilterGraph, Pad}, stream::StreamSelector, InputHandle};
+///
+/// let mut graph = FilterGraph::new();
+///
+/// let main = graph.chain([Pad::input(InputHandle(0), StreamSelector::Video(0))], [Filter::scale(1280, -2)]);
+/// let video = graph.chain([main, Pad::input(InputHandle(1), StreamSelector::Video(0))], [Filter::overlay("W-w-10", 10)]);
+///
+/// assert_eq!(graph.to_string(), "[0:v:0]scale=w=1280:h=-2[f0];[f0][1:v:0]overlay=x=W-w-10:y=10[f1]");
+/// assert_eq!(video.to_string(), "[f1]");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FilterGraph {
+    chains: Vec<Chain>,
+    labels: usize,
+}
+
+impl FilterGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `inputs` through `filters` one after another & get the output pad
+    pub fn chain(&mut self, inputs: impl IntoIterator<Item = Pad>, filters: impl IntoIterator<Item = Filter>) -> Pad {
+        self.chain_outputs(inputs, filters, 1).remove(0)
+    }
+
+    /// Same as [`FilterGraph::chain`] for filters with several outputs, e.g. `split` or `concat` with audio
+    pub fn chain_outputs(&mut self, inputs: impl IntoIterator<Item = Pad>, filters: impl IntoIterator<Item = Filter>, outputs: usize) -> Vec<Pad> {
+        let outputs = (0..outputs).map(|_| self.next_label()).collect::<Vec<_>>();
+
+        self.chains.push(Chain { inputs: inputs.into_iter().collect(), filters: filters.into_iter().collect(), outputs: outputs.clone() });
+
+        outputs
+    }
+
+    fn next_label(&mut self) -> Pad {
+        self.labels += 1;
+
+        Pad(format!("f{}", self.labels - 1))
+    }
+}
+
+impl Display for FilterGraph {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, chain) in self.chains.iter().enumerate() {
+            if index > 0 { write!(f, ";")? };
+
+            chain.inputs.iter().try_for_each(|pad| write!(f, "{pad}"))?;
+
+            for (index, filter) in chain.filters.iter().enumerate() {
+                if index > 0 { write!(f, ",")? };
+
+                write!(f, "{filter}")?;
+            }
+
+            chain.outputs.iter().try_for_each(|pad| write!(f, "{pad}"))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FFmpegBuilder<Normal> {
+    /// `-filter_complex` with the rendered `graph`, use [`FFmpegBuilder::filter_complex_script`] for very large graphs
+    pub fn filter_graph(self, graph: &FilterGraph) -> Self {
+        self.args(["-filter_complex".to_string(), graph.to_string()])
+    }
+}
+
+impl FFmpegBuilder<IO> {
+    /// Add the output of a filter graph chain to this output, `-map [f0]`
+    pub fn map_pad(self, pad: &Pad) -> Self {
+        self.args(["-map".to_string(), pad.to_string()])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn escaped_graph() {
+        let mut graph = FilterGraph::new();
+
+        let video = Pad::input(InputHandle(0), StreamSelector::Video(0));
+        let [first, second]: [Pad; 2] = graph.chain_outputs([video], [Filter::new("split")], 2).try_into().unwrap();
+        let first = graph.chain([first], [Filter::new("drawtext").option("text", "Time: 12:00, [live]").option("fontsize", 24)]);
+        let joined = graph.chain([first, second], [Filter::concat(2, 1, 0), Filter::new("format").value("yuv420p")]);
+
+        assert_eq!(
+            graph.to_string(),
+            r"[0:v:0]split[f0][f1];[f0]drawtext=text=Time\\: 12\\:00\, \[live\]:fontsize=24[f2];[f2][f1]concat=n=2:v=1:a=0,format=yuv420p[f3]",
+        );
+        assert_eq!(joined.label(), "f3");
+    }
+}
@@ -0,0 +1,504 @@
+use std::{io::{self, Read, Write}, ops::Range, path::{Path, PathBuf}, process::Stdio, thread::JoinHandle, time::Duration};
+
+use tokio::sync::mpsc::Receiver;
+
+use crate::{stderr::DEFAULT_RING_BUFFER_CAPACITY, FFmpeg, FFmpegBuilder, FFmpegCommand, FFmpegProgress, Normal, TempOutput, IO};
+
+/// Image format of extracted frames
+#[derive(Debug, Clone, Copy, Default)]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    /// `qscale` goes from 2 (best) to 31 (worst)
+    Jpeg { qscale: u8 },
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg { .. } => "jpg",
+        }
+    }
+
+    fn codec_args(&self) -> Vec<String> {
+        match self {
+            Self::Png => vec!["-c:v".to_string(), "png".to_string()],
+            Self::Jpeg { qscale } => vec!["-c:v".to_string(), "mjpeg".to_string(), "-q:v".to_string(), (*qscale).clamp(2, 31).to_string()],
+        }
+    }
+
+    /// Byte length of the first image in `data`, [`Option::None`] if it's incomplete or not this format
+    fn image_len(&self, data: &[u8]) -> Option<usize> {
+        let be_u32 = |at: usize| data.get(at..at + 4).map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()) as usize);
+        let be_u16 = |at: usize| data.get(at..at + 2).map(|bytes| u16::from_be_bytes(bytes.try_into().unwrap()) as usize);
+
+        match self {
+            // Signature, then [length, type, data, crc] chunks until IEND
+            Self::Png => {
+                if !data.starts_with(b"\x89PNG\r\n\x1a\n") { return None };
+
+                let mut at = 8;
+                loop {
+                    let len = be_u32(at)?;
+                    let kind = data.get(at + 4..at + 8)?;
+
+                    at += 12 + len;
+
+                    if kind == b"IEND" { return (at <= data.len()).then_some(at) };
+                }
+            },
+            // SOI, then segments until EOI, the entropy coded data after SOS has no length so it's scanned for the next marker
+            Self::Jpeg { .. } => {
+                if !data.starts_with(&[0xFF, 0xD8]) { return None };
+
+                let mut at = 2;
+                loop {
+                    if *data.get(at)? != 0xFF { return None };
+
+                    match *data.get(at + 1)? {
+                        0xFF => at += 1,
+                        0xD9 => return Some(at + 2),
+                        0x01 | 0xD0..=0xD7 => at += 2,
+                        0xDA => {
+                            at += 2 + be_u16(at + 2)?;
+
+                            // 0xFF00 is an escaped 0xFF & RSTn can appear in the middle
+                            while *data.get(at)? != 0xFF || matches!(*data.get(at + 1)?, 0x00 | 0xD0..=0xD7) {
+                                at += 1;
+                            }
+                        },
+                        _ => at += 2 + be_u16(at + 2)?,
+                    }
+                }
+            },
+        }
+    }
+
+    /// `%06d.png`, the pattern used for every numbered image in this module
+    fn numbered_pattern(&self) -> String {
+        format!("%06d.{}", self.extension())
+    }
+
+    /// Every numbered image that exists in `dir`, starting from 1
+    fn numbered_files(&self, dir: &Path) -> Vec<PathBuf> {
+        (1..)
+            .map(|index| dir.join(format!("{index:06}.{}", self.extension())))
+            .take_while(|path| path.exists())
+            .collect()
+    }
+
+    /// Split concatenated images, e.g. from `-f image2pipe`
+    fn split(&self, mut data: &[u8]) -> Vec<Vec<u8>> {
+        let mut images = Vec::new();
+
+        while let Some(len) = self.image_len(data) {
+            images.push(data[..len].to_vec());
+            data = &data[len..];
+        }
+
+        images
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScreenshotOptions {
+    /// One frame is taken every `interval`
+    pub interval: Duration,
+    pub format: ImageFormat,
+    /// Scale to this width, keeping the aspect ratio
+    pub width: Option<u32>,
+}
+
+impl Default for ScreenshotOptions {
+    fn default() -> Self {
+        Self { interval: Duration::from_secs(10), format: ImageFormat::Png, width: None }
+    }
+}
+
+/// Where [`FFmpeg::screenshots`] puts the images
+#[derive(Debug, Clone)]
+pub enum ScreenshotOutput {
+    /// Numbered files starting from 1, e.g. `000001.png`, the directory is created if it doesn't exist
+    Directory(PathBuf),
+    /// Read from FFmpeg stdout
+    Memory,
+}
+
+#[derive(Debug, Clone)]
+pub enum Screenshots {
+    Files(Vec<PathBuf>),
+    Images(Vec<Vec<u8>>),
+}
+
+/// A running [`FFmpeg::screenshots`]
+pub struct ScreenshotJob {
+    command: FFmpegCommand,
+    output: ScreenshotOutput,
+    format: ImageFormat,
+    reader: Option<JoinHandle<std::io::Result<Vec<u8>>>>,
+}
+
+impl ScreenshotJob {
+    pub fn command(&mut self) -> &mut FFmpegCommand {
+        &mut self.command
+    }
+
+    /// Wait for FFmpeg to finish & collect the screenshots
+    pub fn wait(mut self) -> anyhow::Result<Screenshots> {
+        self.command.wait_checked()?;
+
+        match self.output {
+            ScreenshotOutput::Directory(dir) => Ok(Screenshots::Files(self.format.numbered_files(&dir))),
+            ScreenshotOutput::Memory => {
+                let data = match self.reader.take().map(JoinHandle::join) {
+                    Some(Ok(data)) => data?,
+                    Some(Err(_)) => anyhow::bail!("The screenshot reader thread panicked"),
+                    None => Vec::new(),
+                };
+
+                Ok(Screenshots::Images(self.format.split(&data)))
+            },
+        }
+    }
+}
+
+impl FFmpeg {
+    /// Take a screenshot every [`ScreenshotOptions::interval`] of `input`, for building preview galleries
+    ///
+    /// The first screenshot is taken at the start, the n-th one at around `(n - 1) * interval`
+    pub fn screenshots(input: impl Into<PathBuf>, output: ScreenshotOutput, options: ScreenshotOptions, progress_rx: &mut Option<Receiver<FFmpegProgress>>) -> anyhow::Result<ScreenshotJob> {
+        let mut filter = format!("fps=1/{}", options.interval.as_secs_f64());
+
+        if let Some(width) = options.width {
+            filter.push_str(&format!(",scale={width}:-2"));
+        }
+
+        let builder = Self::quiet()?
+            .input_with_file(input.into())
+            .done()
+            .args(["-map", "0:v:0", "-vf", &filter])
+            .args(options.format.codec_args());
+
+        let builder = match &output {
+            ScreenshotOutput::Directory(dir) => {
+                std::fs::create_dir_all(dir)?;
+
+                builder
+                    .output_as_file(dir.join(options.format.numbered_pattern()))
+                    .args(["-start_number", "1"])
+                    .done()
+            },
+            ScreenshotOutput::Memory => builder.args(["-f", "image2pipe", "pipe:1"]),
+        };
+
+        // Collected so errors show up in wait_checked, & so FFmpeg never blocks on a full stderr pipe
+        let mut command = builder
+            .stderr_ring_buffer(DEFAULT_RING_BUFFER_CAPACITY)
+            .start_listen_progress(progress_rx)?;
+
+        let reader = command.take_stdout().filter(|_| matches!(output, ScreenshotOutput::Memory)).map(|mut stdout| {
+            std::thread::spawn(move || {
+                let mut data = Vec::new();
+                stdout.read_to_end(&mut data)?;
+
+                Ok(data)
+            })
+        });
+
+        Ok(ScreenshotJob { command, output, format: options.format, reader })
+    }
+
+    /// Extract the frames of `input` into `out_dir` as numbered images, e.g. `000001.png`
+    ///
+    /// The directory is created if it doesn't exist, existing images with the same names are overwritten
+    ///
+    /// Returns how many frames were extracted
+    pub fn extract_frames(input: impl Into<PathBuf>, out_dir: impl Into<PathBuf>, options: FrameExtractOptions) -> anyhow::Result<usize> {
+        let out_dir = out_dir.into();
+        std::fs::create_dir_all(&out_dir)?;
+
+        // Stats are left enabled, the frame count is read from the last one
+        let builder = Self::try_new()?.args(["-hide_banner", "-nostdin"]);
+
+        let mut command = options.extract_args(builder, input.into(), &out_dir).start_quiet()?;
+
+        command.wait_checked()?;
+
+        // frame=  250 fps=0.0 q=-0.0 Lsize=N/A time=00:00:10.00 bitrate=N/A speed=42x
+        // Counting the files instead would include leftovers from a previous extraction
+        let frames = command.recent_stderr()
+            .and_then(|stderr| {
+                let (_, stats) = stderr.rsplit_once("frame=")?;
+
+                stats.split_whitespace().next()?.parse::<usize>().ok()
+            });
+
+        Ok(frames.unwrap_or_else(|| options.format.numbered_files(&out_dir).len()))
+    }
+
+    /// The reverse of [`FFmpeg::extract_frames`], turn the numbered images in `dir` (e.g. `000001.png`) into a video
+    pub fn assemble_frames(dir: impl AsRef<Path>, format: ImageFormat, output: impl Into<PathBuf>, options: AssembleOptions) -> anyhow::Result<()> {
+        let builder = options.frames_input(Self::quiet()?, dir.as_ref(), format);
+
+        options.output_args(builder, output.into()).run_checked()?;
+
+        Ok(())
+    }
+
+    /// Turn in-memory images into a video, every image must be in a format FFmpeg can probe (e.g. PNG or JPEG)
+    pub fn assemble_images<I>(images: I, output: impl Into<PathBuf>, options: AssembleOptions) -> anyhow::Result<()>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let builder = Self::try_new()?
+            .args(["-hide_banner", "-nostats", "-f", "image2pipe"])
+            .args(["-framerate".to_string(), options.fps.to_string()])
+            .args(["-i", "pipe:0"]);
+
+        let mut command = options
+            .output_args(builder, output.into())
+            .stdout(Stdio::null())
+            .stderr_ring_buffer(DEFAULT_RING_BUFFER_CAPACITY)
+            .start()?;
+
+        if let Some(mut stdin) = command.take_stdin() {
+            for image in images {
+                // FFmpeg stopped reading, the reason is in wait_checked
+                if stdin.write_all(image.as_ref()).is_err() { break };
+            }
+        }
+
+        command.wait_checked()?;
+
+        Ok(())
+    }
+}
+
+/// See [`FFmpeg::assemble_frames`] & [`FFmpeg::assemble_images`]
+#[derive(Debug, Clone)]
+pub struct AssembleOptions {
+    pub fps: f64,
+    pub codec: String,
+    pub pixel_format: String,
+}
+
+impl Default for AssembleOptions {
+    fn default() -> Self {
+        Self { fps: 30.0, codec: "libx264".to_string(), pixel_format: "yuv420p".to_string() }
+    }
+}
+
+impl AssembleOptions {
+    /// The numbered images in `dir` as an input at [`AssembleOptions::fps`]
+    fn frames_input(&self, builder: FFmpegBuilder<Normal>, dir: &Path, format: ImageFormat) -> FFmpegBuilder<Normal> {
+        builder
+            .args(["-framerate".to_string(), self.fps.to_string()])
+            .input_with_file(dir.join(format.numbered_pattern()))
+            .done()
+    }
+
+    fn output_args(&self, builder: FFmpegBuilder<Normal>, output: PathBuf) -> FFmpegBuilder<Normal> {
+        let mut filter = String::new();
+
+        // Chroma subsampled formats need even dimensions, pad instead of failing on odd sized images
+        if ["420", "422", "411", "nv12", "nv21"].iter().any(|subsampled| self.pixel_format.contains(subsampled)) {
+            filter.push_str("pad=ceil(iw/2)*2:ceil(ih/2)*2,");
+        }
+
+        filter.push_str(&format!("format={}", self.pixel_format));
+
+        builder
+            .output_as_file(output)
+            .args(["-vf", &filter, "-c:v", &self.codec, "-pix_fmt", &self.pixel_format])
+            .done()
+    }
+}
+
+/// See [`FFmpeg::extract_frames`]
+#[derive(Debug, Clone, Default)]
+pub struct FrameExtractOptions {
+    pub format: ImageFormat,
+    /// [`Option::None`] to extract every frame
+    pub fps: Option<f64>,
+    /// Only extract the frames within this time range of the input
+    pub range: Option<Range<Duration>>,
+}
+
+impl FrameExtractOptions {
+    fn extract_args(&self, mut builder: FFmpegBuilder<Normal>, input: PathBuf, out_dir: &Path) -> FFmpegBuilder<Normal> {
+        if let Some(range) = &self.range {
+            builder = builder.args(["-ss".to_string(), range.start.as_secs_f64().to_string()]);
+        }
+
+        builder = builder
+            .input_with_file(input)
+            .done()
+            .args(["-map", "0:v:0"]);
+
+        if let Some(range) = &self.range {
+            builder = builder.args(["-t".to_string(), range.end.saturating_sub(range.start).as_secs_f64().to_string()]);
+        }
+
+        // Without the fps filter every decoded frame is written once, instead of being duplicated to a constant frame rate
+        builder = match self.fps {
+            Some(fps) => builder.args(["-vf".to_string(), format!("fps={fps}")]),
+            None => builder.args(["-fps_mode", "passthrough"]),
+        };
+
+        builder
+            .args(self.format.codec_args())
+            .output_as_file(out_dir.join(self.format.numbered_pattern()))
+            .args(["-start_number", "1"])
+            .done()
+    }
+}
+
+/// The latest frame of a running command, see [`FFmpegBuilder::output_live_snapshot`]
+#[derive(Debug)]
+pub struct LiveSnapshot {
+    /// Replaced by FFmpeg on every update, so it's only used for the path & the cleanup
+    file: TempOutput,
+}
+
+impl LiveSnapshot {
+    const FORMAT: ImageFormat = ImageFormat::Jpeg { qscale: 3 };
+
+    /// Where the JPEG is, it's replaced on every update
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    /// The latest JPEG, [`Option::None`] until the first one is written
+    pub fn latest(&self) -> io::Result<Option<Vec<u8>>> {
+        let mut data = match std::fs::read(self.path()) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self::FORMAT.image_len(&data).map(|len| {
+            data.truncate(len);
+            data
+        }))
+    }
+}
+
+impl FFmpegBuilder<Normal> {
+    /// Add an output that keeps a JPEG of the current frame, updated every `interval`, e.g. for monitoring a live ingest
+    ///
+    /// Like any other output the first video stream is used unless it's chosen with `-map`.
+    /// The image is deleted when `snapshot` is dropped
+    pub fn output_live_snapshot(self, snapshot: &mut Option<LiveSnapshot>, interval: Duration) -> io::Result<FFmpegBuilder<IO>> {
+        let file = TempOutput::create()?;
+        let path = file.path().to_path_buf();
+
+        *snapshot = Some(LiveSnapshot { file });
+
+        let rate = 1.0 / interval.as_secs_f64().max(0.001);
+
+        Ok(self
+            .output_as_file(path)
+            // Written to a temporary file & renamed, so a snapshot is never read half written
+            .args(["-f", "image2", "-update", "1", "-atomic_writing", "1", "-an", "-sn", "-dn"])
+            .args(["-r".to_string(), rate.to_string()])
+            .args(LiveSnapshot::FORMAT.codec_args()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_images() {
+        let png = [
+            b"\x89PNG\r\n\x1a\n".as_slice(),
+            &[0, 0, 0, 2], b"IHDR", &[1, 2], &[0; 4],
+            &[0, 0, 0, 0], b"IEND", &[0; 4],
+        ].concat();
+
+        let pngs = ImageFormat::Png.split(&[png.as_slice(), &png, &png[..10]].concat());
+        assert_eq!(pngs, vec![png.clone(), png]);
+
+        let jpeg = [
+            [0xFF, 0xD8].as_slice(),
+            // APP0 with an embedded EOI, skipped by its length
+            &[0xFF, 0xE0, 0, 4, 0xFF, 0xD9],
+            // SOS, followed by entropy coded data with an escaped 0xFF & a restart marker
+            &[0xFF, 0xDA, 0, 2, 0x12, 0xFF, 0x00, 0x34, 0xFF, 0xD0, 0x56],
+            &[0xFF, 0xD9],
+        ].concat();
+
+        let jpegs = ImageFormat::Jpeg { qscale: 2 }.split(&[jpeg.as_slice(), &jpeg].concat());
+        assert_eq!(jpegs, vec![jpeg.clone(), jpeg]);
+    }
+
+    #[test]
+    fn extract_frames_args() {
+        let options = FrameExtractOptions {
+            format: ImageFormat::Jpeg { qscale: 40 },
+            fps: Some(2.0),
+            range: Some(Duration::from_secs(10)..Duration::from_millis(12500)),
+        };
+
+        let builder = options.extract_args(FFmpeg::new_with_program("ffmpeg"), "in.mp4".into(), Path::new("frames"));
+
+        assert_eq!(builder.inner_args, [
+            "-ss", "10", "-i", "in.mp4", "-map", "0:v:0", "-t", "2.5", "-vf", "fps=2",
+            "-c:v", "mjpeg", "-q:v", "31", "-start_number", "1", "-y", "frames/%06d.jpg",
+        ]);
+
+        let builder = FrameExtractOptions::default().extract_args(FFmpeg::new_with_program("ffmpeg"), "in.mp4".into(), Path::new("frames"));
+
+        assert_eq!(builder.inner_args, [
+            "-i", "in.mp4", "-map", "0:v:0", "-fps_mode", "passthrough", "-c:v", "png", "-start_number", "1", "-y", "frames/%06d.png",
+        ]);
+    }
+
+    #[test]
+    fn assemble_frames_args() {
+        let options = AssembleOptions { fps: 24.0, codec: "libx264rgb".to_string(), pixel_format: "rgb24".to_string() };
+
+        let builder = options.frames_input(FFmpeg::new_with_program("ffmpeg"), Path::new("frames"), ImageFormat::Jpeg { qscale: 2 });
+        let builder = options.output_args(builder, "out.mp4".into());
+
+        assert_eq!(builder.inner_args, [
+            "-framerate", "24", "-i", "frames/%06d.jpg",
+            "-vf", "format=rgb24", "-c:v", "libx264rgb", "-pix_fmt", "rgb24", "-y", "out.mp4",
+        ]);
+
+        // Subsampled chroma needs even dimensions
+        let builder = AssembleOptions::default().output_args(FFmpeg::new_with_program("ffmpeg"), "out.mp4".into());
+
+        assert_eq!(builder.inner_args[..2], ["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2,format=yuv420p"]);
+    }
+
+    #[test]
+    fn live_snapshot_output() -> io::Result<()> {
+        let mut snapshot = None;
+
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("rtmp://localhost/live".into())
+            .done()
+            .output_as_file("recording.mkv".into())
+            .done()
+            .output_live_snapshot(&mut snapshot, Duration::from_secs(2))?
+            .done();
+
+        let snapshot = snapshot.unwrap();
+
+        assert_eq!(builder.inner_args[4..], [
+            "-f", "image2", "-update", "1", "-atomic_writing", "1", "-an", "-sn", "-dn",
+            "-r", "0.5", "-c:v", "mjpeg", "-q:v", "3",
+            "-y", snapshot.path().to_str().unwrap(),
+        ]);
+        assert_eq!(snapshot.latest()?, None);
+
+        std::fs::write(snapshot.path(), [0xFF, 0xD8, 0xFF, 0xD9])?;
+        assert_eq!(snapshot.latest()?, Some(vec![0xFF, 0xD8, 0xFF, 0xD9]));
+
+        Ok(())
+    }
+}
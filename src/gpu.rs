@@ -0,0 +1,160 @@
+//! Transcode without the frames leaving the GPU, see [`FFmpegBuilder::gpu_pipeline`]
+
+use crate::{FFmpegBuilder, IO};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gpu {
+    /// NVDEC, `scale_cuda` & NVENC
+    Nvidia,
+    /// Quick Sync Video
+    IntelQsv,
+    /// VA-API on Linux, AMD & Intel
+    Vaapi,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GpuCodec {
+    #[default]
+    H264,
+    Hevc,
+    Av1,
+}
+
+impl Gpu {
+    /// Decoder options of the input, the decoded frames stay in GPU memory
+    fn hwaccel_args(&self) -> &'static [&'static str] {
+        match self {
+            Gpu::Nvidia => &["-hwaccel", "cuda", "-hwaccel_output_format", "cuda"],
+            Gpu::IntelQsv => &["-hwaccel", "qsv", "-hwaccel_output_format", "qsv"],
+            Gpu::Vaapi => &["-hwaccel", "vaapi", "-hwaccel_device", "/dev/dri/renderD128", "-hwaccel_output_format", "vaapi"],
+        }
+    }
+
+    /// The suffix of the filters that work on frames of this GPU, e.g. `scale_cuda`
+    fn filter_suffix(&self) -> &'static str {
+        match self {
+            Gpu::Nvidia => "_cuda",
+            Gpu::IntelQsv => "_qsv",
+            Gpu::Vaapi => "_vaapi",
+        }
+    }
+
+    pub fn encoder(&self, codec: GpuCodec) -> &'static str {
+        match (self, codec) {
+            (Gpu::Nvidia, GpuCodec::H264) => "h264_nvenc",
+            (Gpu::Nvidia, GpuCodec::Hevc) => "hevc_nvenc",
+            (Gpu::Nvidia, GpuCodec::Av1) => "av1_nvenc",
+            (Gpu::IntelQsv, GpuCodec::H264) => "h264_qsv",
+            (Gpu::IntelQsv, GpuCodec::Hevc) => "hevc_qsv",
+            (Gpu::IntelQsv, GpuCodec::Av1) => "av1_qsv",
+            (Gpu::Vaapi, GpuCodec::H264) => "h264_vaapi",
+            (Gpu::Vaapi, GpuCodec::Hevc) => "hevc_vaapi",
+            (Gpu::Vaapi, GpuCodec::Av1) => "av1_vaapi",
+        }
+    }
+
+    /// Scale on the GPU, `-1` keeps the aspect ratio
+    pub fn scale_filter(&self, width: i32, height: i32) -> String {
+        match self {
+            Gpu::Nvidia => format!("scale_cuda=w={width}:h={height}"),
+            Gpu::IntelQsv => format!("scale_qsv=w={width}:h={height}"),
+            Gpu::Vaapi => format!("scale_vaapi=w={width}:h={height}"),
+        }
+    }
+
+    /// Whether `filter` (e.g. `scale_cuda=w=1280:h=720`) keeps the frames on this GPU
+    fn is_gpu_filter(&self, filter: &str) -> bool {
+        let name = filter.trim().split(['=', '@']).next().unwrap_or_default();
+
+        name.ends_with(self.filter_suffix()) || matches!(name, "null" | "hwupload" | "hwmap")
+    }
+}
+
+/// Options of [`FFmpegBuilder::gpu_pipeline`], made from a [`Gpu`] for H.264 without scaling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GpuPipeline {
+    pub gpu: Gpu,
+    pub codec: GpuCodec,
+    /// Scale on the GPU, `-1` keeps the aspect ratio
+    pub scale: Option<(i32, i32)>,
+}
+
+impl GpuPipeline {
+    pub fn new(gpu: Gpu) -> Self {
+        Self { gpu, codec: GpuCodec::default(), scale: None }
+    }
+
+    pub fn codec(mut self, codec: GpuCodec) -> Self {
+        self.codec = codec;
+
+        self
+    }
+
+    pub fn scale(mut self, width: i32, height: i32) -> Self {
+        self.scale = Some((width, height));
+
+        self
+    }
+}
+
+impl From<Gpu> for GpuPipeline {
+    fn from(gpu: Gpu) -> Self {
+        Self::new(gpu)
+    }
+}
+
+impl FFmpegBuilder<IO> {
+    /// Decode, filter & encode this output on the GPU, the last input before it is decoded with `-hwaccel`
+    ///
+    /// Fails if the output already has a filter that runs on the CPU, which would copy every frame back & forth.
+    /// Only add GPU filters (e.g. `scale_cuda`, `overlay_cuda`) with [`FFmpegBuilder::video_filter`] after this
+    pub fn gpu_pipeline(self, pipeline: impl Into<GpuPipeline>) -> anyhow::Result<Self> {
+        let GpuPipeline { gpu, codec, scale } = pipeline.into();
+
+        if let Some(chain) = self.output_option("-vf") {
+            let chain = chain.to_string_lossy();
+
+            if let Some(filter) = chain.split(',').find(|filter| !gpu.is_gpu_filter(filter)) {
+                anyhow::bail!("{:?} runs on the CPU, use a {} filter instead", filter.trim(), gpu.filter_suffix());
+            }
+        }
+
+        let builder = self.input_args(gpu.hwaccel_args()).codec_video(gpu.encoder(codec));
+
+        Ok(match scale {
+            Some((width, height)) => builder.video_filter(gpu.scale_filter(width, height)),
+            None => builder,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::FFmpeg;
+
+    #[test]
+    fn cuda_pipeline() -> anyhow::Result<()> {
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("in.mp4".into())
+            .done()
+            .output_as_file("out.mp4".into())
+            .gpu_pipeline(GpuPipeline::new(Gpu::Nvidia).codec(GpuCodec::Hevc).scale(-1, 720))?
+            .done();
+
+        assert_eq!(builder.inner_args, [
+            "-hwaccel", "cuda", "-hwaccel_output_format", "cuda", "-i", "in.mp4",
+            "-c:v", "hevc_nvenc", "-vf", "scale_cuda=w=-1:h=720", "-y", "out.mp4",
+        ]);
+
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("in.mp4".into())
+            .done()
+            .output_as_file("out.mp4".into())
+            .video_filter("yadif");
+
+        assert!(builder.gpu_pipeline(Gpu::Nvidia).is_err());
+
+        Ok(())
+    }
+}
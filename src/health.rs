@@ -0,0 +1,251 @@
+//! Health of live inputs (RTMP, SRT, UDP...), see [`FFmpegBuilder::start_with_health_monitor`]
+
+use std::{sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{sink::ProgressSink, stderr::DEFAULT_RING_BUFFER_CAPACITY, FFmpegBuilder, FFmpegCommand, FFmpegProgress, Normal};
+
+/// Stderr lines that mean the input is losing or mangling data
+const WARNINGS: &[&str] = &[
+    "Non-monotonous DTS",
+    "Packet corrupt",
+    "corrupt decoded frame",
+    "error while decoding",
+    "concealing",
+    "Circular buffer overrun",
+    "max delay reached",
+    "Connection timed out",
+    "RTMP_ReadPacket",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HealthEvent {
+    /// Keeping up with the input, also sent after recovering
+    Healthy,
+    Degraded { reason: DegradedReason },
+    /// No progress for [`HealthThresholds::stall_timeout`], e.g. the source stopped sending
+    Stalled,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DegradedReason {
+    /// Processing slower than real time, the live input is falling behind
+    SlowSpeed(f32),
+    /// Frames dropped since the last progress
+    DroppedFrames(usize),
+    /// Frames duplicated since the last progress, the input is missing frames
+    DuplicatedFrames(usize),
+    /// A stderr line about corrupt or lost data
+    Warning(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthThresholds {
+    /// Anything slower than this is degraded
+    pub min_speed: f32,
+    /// More dropped frames than this between two progress reports is degraded
+    pub max_dropped_frames: usize,
+    /// More duplicated frames than this between two progress reports is degraded
+    pub max_duplicated_frames: usize,
+    pub stall_timeout: Duration,
+    /// How long a stderr warning keeps the stream degraded
+    pub warning_cooldown: Duration,
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        Self {
+            min_speed: 0.95,
+            max_dropped_frames: 0,
+            max_duplicated_frames: 0,
+            stall_timeout: Duration::from_secs(10),
+            warning_cooldown: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Turns progress & warnings into health events, only changes are reported
+#[derive(Debug)]
+struct HealthTracker {
+    thresholds: HealthThresholds,
+    last: Option<HealthEvent>,
+    /// When the output time last moved forward
+    last_advance: Instant,
+    out_time: Option<usize>,
+    dropped: usize,
+    duplicated: usize,
+    warning: Option<(String, Instant)>,
+}
+
+impl HealthTracker {
+    fn new(thresholds: HealthThresholds, now: Instant) -> Self {
+        Self { thresholds, last: None, last_advance: now, out_time: None, dropped: 0, duplicated: 0, warning: None }
+    }
+
+    fn progress(&mut self, progress: &FFmpegProgress, now: Instant) -> Option<HealthEvent> {
+        if progress.out_time_us.is_some_and(|time| Some(time) > self.out_time) {
+            self.out_time = progress.out_time_us;
+            self.last_advance = now;
+        }
+
+        // The counts are totals since the start
+        let dropped = progress.drop_frames.unwrap_or(self.dropped).saturating_sub(self.dropped);
+        let duplicated = progress.dup_frames.unwrap_or(self.duplicated).saturating_sub(self.duplicated);
+        self.dropped += dropped;
+        self.duplicated += duplicated;
+
+        let reason = match progress.speed {
+            Some(speed) if speed < self.thresholds.min_speed => Some(DegradedReason::SlowSpeed(speed)),
+            _ if dropped > self.thresholds.max_dropped_frames => Some(DegradedReason::DroppedFrames(dropped)),
+            _ if duplicated > self.thresholds.max_duplicated_frames => Some(DegradedReason::DuplicatedFrames(duplicated)),
+            _ => self.warning.as_ref()
+                .filter(|(_, at)| now.duration_since(*at) < self.thresholds.warning_cooldown)
+                .map(|(line, _)| DegradedReason::Warning(line.clone())),
+        };
+
+        match reason {
+            Some(reason) => self.report(HealthEvent::Degraded { reason }),
+            None if self.is_stalled(now) => self.report(HealthEvent::Stalled),
+            None => self.report(HealthEvent::Healthy),
+        }
+    }
+
+    fn log(&mut self, line: &str, now: Instant) -> Option<HealthEvent> {
+        if !WARNINGS.iter().any(|warning| line.contains(warning)) { return None };
+
+        self.warning = Some((line.trim().to_string(), now));
+
+        // Stalled wins until the progress moves again
+        if self.last == Some(HealthEvent::Stalled) { return None };
+
+        self.report(HealthEvent::Degraded { reason: DegradedReason::Warning(line.trim().to_string()) })
+    }
+
+    /// The output time hasn't moved for too long
+    fn is_stalled(&self, now: Instant) -> bool {
+        now.duration_since(self.last_advance) >= self.thresholds.stall_timeout
+    }
+
+    fn check(&mut self, now: Instant) -> Option<HealthEvent> {
+        if !self.is_stalled(now) { return None };
+
+        self.report(HealthEvent::Stalled)
+    }
+
+    /// Only report the event if the health changed, a different reason is a change
+    fn report(&mut self, event: HealthEvent) -> Option<HealthEvent> {
+        let changed = match (&self.last, &event) {
+            // The amount of slowness or dropped frames changes on every progress
+            (Some(HealthEvent::Degraded { reason: last }), HealthEvent::Degraded { reason }) => std::mem::discriminant(last) != std::mem::discriminant(reason),
+            (last, event) => last.as_ref() != Some(event),
+        };
+
+        if !changed { return None };
+
+        self.last = Some(event.clone());
+
+        Some(event)
+    }
+}
+
+/// A [`ProgressSink`] that sends [`HealthEvent`]s, stalls are checked in a background thread
+pub struct HealthMonitor {
+    tracker: Arc<Mutex<HealthTracker>>,
+    tx: UnboundedSender<HealthEvent>,
+}
+
+impl HealthMonitor {
+    pub fn new(tx: UnboundedSender<HealthEvent>) -> Self {
+        Self::with_thresholds(tx, HealthThresholds::default())
+    }
+
+    pub fn with_thresholds(tx: UnboundedSender<HealthEvent>, thresholds: HealthThresholds) -> Self {
+        let interval = (thresholds.stall_timeout / 4).max(Duration::from_millis(100));
+        let tracker = Arc::new(Mutex::new(HealthTracker::new(thresholds, Instant::now())));
+
+        std::thread::spawn({
+            let tracker = Arc::downgrade(&tracker);
+            let tx = tx.clone();
+
+            // Until the monitor is dropped, when the progress is over
+            move || while let Some(tracker) = tracker.upgrade() {
+                if tx.is_closed() { break };
+
+                if let Some(event) = tracker.lock().ok().and_then(|mut tracker| tracker.check(Instant::now())) {
+                    let _ = tx.send(event);
+                }
+
+                drop(tracker);
+                std::thread::sleep(interval);
+            }
+        });
+
+        Self { tracker, tx }
+    }
+
+    fn send(&self, event: impl FnOnce(&mut HealthTracker) -> Option<HealthEvent>) {
+        if let Some(event) = self.tracker.lock().ok().and_then(|mut tracker| event(&mut tracker)) {
+            let _ = self.tx.send(event);
+        }
+    }
+}
+
+impl ProgressSink for HealthMonitor {
+    fn on_progress(&mut self, progress: FFmpegProgress) {
+        self.send(|tracker| tracker.progress(&progress, Instant::now()));
+    }
+
+    fn on_log(&mut self, line: &str) {
+        self.send(|tracker| tracker.log(line, Instant::now()));
+    }
+}
+
+impl FFmpegBuilder<Normal> {
+    /// Start a new FFmpeg child process & report the health of its inputs into `monitor`
+    ///
+    /// Stderr is collected for the warnings if it isn't already
+    pub fn start_with_health_monitor(mut self, monitor: HealthMonitor) -> anyhow::Result<FFmpegCommand> {
+        if self.start_options.stderr_ring_buffer.is_none() {
+            self = self.stderr_ring_buffer(DEFAULT_RING_BUFFER_CAPACITY);
+        }
+
+        self.start_with_progress_sink(monitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn health_changes() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let progress = |out_time: usize, speed: f32, drop_frames: usize| FFmpegProgress {
+            out_time_us: Some(out_time),
+            speed: Some(speed),
+            drop_frames: Some(drop_frames),
+            ..Default::default()
+        };
+
+        let mut tracker = HealthTracker::new(HealthThresholds::default(), start);
+
+        assert_eq!(tracker.progress(&progress(1_000_000, 1.0, 0), at(1)), Some(HealthEvent::Healthy));
+        assert_eq!(tracker.progress(&progress(2_000_000, 1.0, 0), at(2)), None);
+        assert_eq!(tracker.progress(&progress(3_000_000, 0.5, 0), at(3)), Some(HealthEvent::Degraded { reason: DegradedReason::SlowSpeed(0.5) }));
+        assert_eq!(tracker.progress(&progress(4_000_000, 0.6, 0), at(4)), None);
+        assert_eq!(tracker.progress(&progress(5_000_000, 1.0, 3), at(5)), Some(HealthEvent::Degraded { reason: DegradedReason::DroppedFrames(3) }));
+        assert_eq!(tracker.progress(&progress(6_000_000, 1.0, 3), at(6)), Some(HealthEvent::Healthy));
+
+        let warning = "[mpegts @ 0x55] Packet corrupt (stream = 0, dts = 1234).";
+        assert!(matches!(tracker.log(warning, at(7)), Some(HealthEvent::Degraded { reason: DegradedReason::Warning(_) })));
+        assert_eq!(tracker.progress(&progress(7_000_000, 1.0, 3), at(8)), None);
+        assert_eq!(tracker.progress(&progress(8_000_000, 1.0, 3), at(13)), Some(HealthEvent::Healthy));
+
+        assert_eq!(tracker.check(at(20)), None);
+        assert_eq!(tracker.check(at(23)), Some(HealthEvent::Stalled));
+        assert_eq!(tracker.progress(&progress(8_000_000, 1.0, 3), at(24)), None);
+        assert_eq!(tracker.progress(&progress(9_000_000, 1.0, 3), at(25)), Some(HealthEvent::Healthy));
+    }
+}
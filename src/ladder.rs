@@ -0,0 +1,277 @@
+use std::{path::{Path, PathBuf}, time::Duration};
+
+use crate::{probe::FFprobe, FFmpeg, FFmpegBuilder, IO};
+
+/// A quality of a [`Ladder`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rendition {
+    /// Scaled to fit inside, the aspect ratio of the source is kept
+    pub size: (u32, u32),
+    /// Video bitrate in kbps
+    pub video_bitrate: u32,
+    /// Audio bitrate in kbps
+    pub audio_bitrate: u32,
+    /// Video encoder, e.g. `libx264`, `libx265` or `h264_nvenc`
+    pub codec: String,
+}
+
+impl Rendition {
+    /// A `libx264` rendition with 128 kbps AAC audio
+    pub fn new(size: (u32, u32), video_bitrate: u32) -> Self {
+        Self { size, video_bitrate, audio_bitrate: 128, codec: "libx264".to_string() }
+    }
+
+    pub fn audio_bitrate(mut self, audio_bitrate: u32) -> Self {
+        self.audio_bitrate = audio_bitrate;
+
+        self
+    }
+
+    pub fn codec(mut self, codec: impl Into<String>) -> Self {
+        self.codec = codec.into();
+
+        self
+    }
+
+    /// e.g. `720p`, the name of its folder in HLS, see [`Ladder::rendition_names`]
+    pub fn name(&self) -> String {
+        format!("{}p", self.size.1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ManifestFormat {
+    /// `master.m3u8` with a playlist per rendition
+    #[default]
+    Hls,
+    /// `manifest.mpd`
+    Dash,
+}
+
+/// Encode a source into every rendition of an adaptive bitrate set in a single FFmpeg run, the source is only decoded once
+///
+/// There's no parallel mode with a FFmpeg per rendition, the manifest is written by the HLS/DASH muxer so every rendition has to be in the same run
+///
+/// ```no_run
+/// # use essi_ffmpeg::ladder::{Ladder, Rendition};
+/// let manifest = Ladder::new("source.mp4")
+///     .rendition(Rendition::new((1920, 1080), 5000))
+///     .rendition(Rendition::new((1280, 720), 2800))
+///     .rendition(Rendition::new((640, 360), 800).audio_bitrate(64))
+///     .render("out/")?;
+/// # anyhow::Ok(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Ladder {
+    source: PathBuf,
+    renditions: Vec<Rendition>,
+    format: ManifestFormat,
+    segment_duration: Duration,
+    audio: bool,
+}
+
+impl Ladder {
+    pub fn new(source: impl Into<PathBuf>) -> Self {
+        Self {
+            source: source.into(),
+            renditions: Vec::new(),
+            format: ManifestFormat::default(),
+            segment_duration: Duration::from_secs(6),
+            audio: true,
+        }
+    }
+
+    pub fn rendition(mut self, rendition: Rendition) -> Self {
+        self.renditions.push(rendition);
+
+        self
+    }
+
+    pub fn format(mut self, format: ManifestFormat) -> Self {
+        self.format = format;
+
+        self
+    }
+
+    /// Every rendition has a keyframe at the same times, so players can switch between them at any segment, 6 seconds by default
+    pub fn segment_duration(mut self, duration: Duration) -> Self {
+        self.segment_duration = duration;
+
+        self
+    }
+
+    /// Leave the audio out, [`Ladder::render`] does it automatically if the source doesn't have any
+    pub fn no_audio(mut self) -> Self {
+        self.audio = false;
+
+        self
+    }
+
+    /// Where the manifest is written in `out_dir`
+    pub fn manifest_path(&self, out_dir: impl AsRef<Path>) -> PathBuf {
+        match self.format {
+            ManifestFormat::Hls => out_dir.as_ref().join("master.m3u8"),
+            ManifestFormat::Dash => out_dir.as_ref().join("manifest.mpd"),
+        }
+    }
+
+    /// The folder of every rendition in HLS, [`Rendition::name`] with the index added when several renditions have the same height
+    pub fn rendition_names(&self) -> Vec<String> {
+        self.renditions
+            .iter()
+            .enumerate()
+            .map(|(index, rendition)| match self.renditions.iter().filter(|other| other.size.1 == rendition.size.1).count() {
+                1 => rendition.name(),
+                _ => format!("{}_{index}", rendition.name()),
+            })
+            .collect()
+    }
+
+    /// The `-filter_complex` graph, outputs `[v0]`, `[v1]`, ... for every rendition
+    pub fn filter_graph(&self) -> String {
+        let splits = (0..self.renditions.len()).map(|index| format!("[s{index}]")).collect::<Vec<_>>().concat();
+        let mut graph = vec![format!("[0:v]split={}{splits}", self.renditions.len())];
+
+        for (index, Rendition { size: (width, height), .. }) in self.renditions.iter().enumerate() {
+            graph.push(format!("[s{index}]scale=w={width}:h={height}:force_original_aspect_ratio=decrease:force_divisible_by=2[v{index}]"));
+        }
+
+        graph.join(";")
+    }
+
+    /// Everything after the filter graph, without the output path
+    fn output_args(&self, out_dir: &Path) -> Vec<String> {
+        let mut args = Vec::new();
+        // DASH shares a single audio stream between every rendition
+        let audio_streams = match (self.audio, self.format) {
+            (false, _) => 0,
+            (true, ManifestFormat::Hls) => self.renditions.len(),
+            (true, ManifestFormat::Dash) => 1,
+        };
+
+        for (index, rendition) in self.renditions.iter().enumerate() {
+            let bitrate = rendition.video_bitrate;
+
+            args.extend(["-map".to_string(), format!("[v{index}]")]);
+            args.extend([format!("-c:v:{index}"), rendition.codec.clone(), format!("-b:v:{index}"), format!("{bitrate}k")]);
+            // Capped at a bit more than the average, with 1.5 seconds of buffer
+            args.extend([format!("-maxrate:v:{index}"), format!("{}k", bitrate * 107 / 100), format!("-bufsize:v:{index}"), format!("{}k", bitrate * 3 / 2)]);
+        }
+
+        for index in 0..audio_streams {
+            args.extend(["-map".to_string(), "0:a:0".to_string()]);
+            args.extend([format!("-c:a:{index}"), "aac".to_string(), format!("-b:a:{index}"), format!("{}k", self.renditions[index].audio_bitrate)]);
+        }
+
+        let segment = self.segment_duration.as_secs_f64().to_string();
+
+        args.extend(["-force_key_frames".to_string(), format!("expr:gte(t,n_forced*{segment})")]);
+
+        match self.format {
+            ManifestFormat::Hls => {
+                let variants = self
+                    .rendition_names()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, name)| match audio_streams {
+                        0 => format!("v:{index},name:{name}"),
+                        _ => format!("v:{index},a:{index},name:{name}"),
+                    })
+                    .collect::<Vec<_>>();
+
+                args.extend(["-f", "hls", "-hls_time", &segment, "-hls_playlist_type", "vod"].map(String::from));
+                args.extend(["-hls_segment_filename".to_string(), out_dir.join("%v").join("segment_%05d.ts").display().to_string()]);
+                args.extend(["-master_pl_name".to_string(), "master.m3u8".to_string(), "-var_stream_map".to_string(), variants.join(" ")]);
+            },
+            ManifestFormat::Dash => {
+                let sets = match audio_streams {
+                    0 => "id=0,streams=v",
+                    _ => "id=0,streams=v id=1,streams=a",
+                };
+
+                args.extend(["-f", "dash", "-seg_duration", &segment, "-use_template", "1", "-use_timeline", "1", "-adaptation_sets", sets].map(String::from));
+            },
+        }
+
+        args
+    }
+
+    /// Everything needed for the renditions & the manifest, written to `out_dir`
+    pub fn build(&self, out_dir: impl AsRef<Path>) -> anyhow::Result<FFmpegBuilder<IO>> {
+        if self.renditions.is_empty() {
+            anyhow::bail!("A ladder needs at least 1 rendition");
+        }
+
+        let out_dir = out_dir.as_ref();
+
+        // The playlists are in a folder per rendition, the master playlist points to them
+        let output = match self.format {
+            ManifestFormat::Hls => out_dir.join("%v").join("index.m3u8"),
+            ManifestFormat::Dash => self.manifest_path(out_dir),
+        };
+
+        Ok(FFmpeg::quiet()?
+            .input_with_file(self.source.clone())
+            .done()
+            .args(["-filter_complex".to_string(), self.filter_graph()])
+            .args(self.output_args(out_dir))
+            .output_as_file(output))
+    }
+
+    /// Render every rendition & the manifest into `out_dir`, returns the path of the manifest
+    pub fn render(&self, out_dir: impl AsRef<Path>) -> anyhow::Result<PathBuf> {
+        let out_dir = out_dir.as_ref();
+
+        let has_audio = FFprobe::probe_raw(&self.source)?.streams.iter().any(|stream| stream.codec_type.as_deref() == Some("audio"));
+        let ladder = if self.audio && !has_audio { self.clone().no_audio() } else { self.clone() };
+
+        std::fs::create_dir_all(out_dir)?;
+
+        ladder
+            .build(out_dir)?
+            .done()
+            .run_checked()?;
+
+        Ok(ladder.manifest_path(out_dir))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hls_ladder_args() {
+        let ladder = Ladder::new("source.mp4")
+            .rendition(Rendition::new((1920, 1080), 5000))
+            .rendition(Rendition::new((640, 360), 800).audio_bitrate(64).codec("h264_nvenc"));
+
+        assert_eq!(
+            ladder.filter_graph(),
+            "[0:v]split=2[s0][s1];\
+            [s0]scale=w=1920:h=1080:force_original_aspect_ratio=decrease:force_divisible_by=2[v0];\
+            [s1]scale=w=640:h=360:force_original_aspect_ratio=decrease:force_divisible_by=2[v1]",
+        );
+
+        let args = ladder.output_args(Path::new("out"));
+
+        assert_eq!(args[..12], ["-map", "[v0]", "-c:v:0", "libx264", "-b:v:0", "5000k", "-maxrate:v:0", "5350k", "-bufsize:v:0", "7500k", "-map", "[v1]"]);
+        assert!(args.windows(6).any(|args| args == ["-map", "0:a:0", "-c:a:1", "aac", "-b:a:1", "64k"]));
+        assert_eq!(args.last().unwrap(), "v:0,a:0,name:1080p v:1,a:1,name:360p");
+
+        let args = ladder.format(ManifestFormat::Dash).no_audio().output_args(Path::new("out"));
+
+        assert!(!args.contains(&"0:a:0".to_string()));
+        assert_eq!(args.last().unwrap(), "id=0,streams=v");
+
+        // Same height with another codec, both can't write to 1080p/
+        let ladder = Ladder::new("source.mp4")
+            .rendition(Rendition::new((1920, 1080), 5000))
+            .rendition(Rendition::new((1920, 1080), 3500).codec("libx265"))
+            .rendition(Rendition::new((1280, 720), 2800))
+            .no_audio();
+
+        assert_eq!(ladder.rendition_names(), ["1080p_0", "1080p_1", "720p"]);
+        assert_eq!(ladder.output_args(Path::new("out")).last().unwrap(), "v:0,name:1080p_0 v:1,name:1080p_1 v:2,name:720p");
+    }
+}
@@ -0,0 +1,66 @@
+use std::{fmt::Display, time::Duration};
+
+use crate::compose::escape_filter_text;
+
+/// Generated media for [`crate::FFmpegBuilder::input_lavfi`], so nothing has to be shipped as sample assets
+///
+/// `duration` of [`Option::None`] generates forever, limit the output yourself in that case
+#[derive(Debug, Clone)]
+pub enum TestSource {
+    /// `testsrc`, a color pattern with a scrolling gradient & timestamp
+    TestSrc { size: (u32, u32), rate: u32, duration: Option<Duration> },
+    /// `testsrc2`, like `testsrc` but supports more pixel formats
+    TestSrc2 { size: (u32, u32), rate: u32, duration: Option<Duration> },
+    /// `smptebars`, SMPTE color bars
+    SmpteBars { size: (u32, u32), rate: u32, duration: Option<Duration> },
+    /// `color`, a single color, e.g. `black` or `#FF0000`
+    Color { color: String, size: (u32, u32), rate: u32, duration: Option<Duration> },
+    /// `sine`, a sine wave tone in Hz
+    SineWave { freq: u32, duration: Option<Duration> },
+    /// `anullsrc`, silent audio, e.g. `48000` & `stereo`
+    Silence { sample_rate: u32, channel_layout: String, duration: Option<Duration> },
+    /// Any other lavfi filter graph, e.g. `mandelbrot=size=640x480`, used as is without escaping
+    Custom(String),
+}
+
+impl Display for TestSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let duration = |duration: &Option<Duration>| duration.map(|duration| format!(":duration={}", duration.as_secs_f64())).unwrap_or_default();
+
+        match self {
+            Self::TestSrc { size: (width, height), rate, duration: d } => write!(f, "testsrc=size={width}x{height}:rate={rate}{}", duration(d)),
+            Self::TestSrc2 { size: (width, height), rate, duration: d } => write!(f, "testsrc2=size={width}x{height}:rate={rate}{}", duration(d)),
+            Self::SmpteBars { size: (width, height), rate, duration: d } => write!(f, "smptebars=size={width}x{height}:rate={rate}{}", duration(d)),
+            Self::Color { color, size: (width, height), rate, duration: d } => write!(f, "color=color={}:size={width}x{height}:rate={rate}{}", escape_filter_text(color), duration(d)),
+            Self::SineWave { freq, duration: d } => write!(f, "sine=frequency={freq}{}", duration(d)),
+            Self::Silence { sample_rate, channel_layout, duration: d } => write!(f, "anullsrc=sample_rate={sample_rate}:channel_layout={}{}", escape_filter_text(channel_layout), duration(d)),
+            Self::Custom(graph) => write!(f, "{graph}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn source_filters() {
+        let source = TestSource::TestSrc { size: (1280, 720), rate: 30, duration: Some(Duration::from_millis(2500)) };
+        assert_eq!(source.to_string(), "testsrc=size=1280x720:rate=30:duration=2.5");
+
+        let source = TestSource::SineWave { freq: 440, duration: None };
+        assert_eq!(source.to_string(), "sine=frequency=440");
+
+        let source = TestSource::Color { color: "red@0.5".to_string(), size: (64, 64), rate: 1, duration: None };
+        assert_eq!(source.to_string(), "color=color=red@0.5:size=64x64:rate=1");
+
+        // Values with separators stay a single option
+        let source = TestSource::Color { color: "0x000000:it's,odd".to_string(), size: (64, 64), rate: 1, duration: None };
+        assert_eq!(source.to_string(), r"color=color=0x000000\\:it\\\'s\,odd:size=64x64:rate=1");
+
+        let source = TestSource::Silence { sample_rate: 48000, channel_layout: "FL+FR".to_string(), duration: Some(Duration::from_secs(1)) };
+        assert_eq!(source.to_string(), "anullsrc=sample_rate=48000:channel_layout=FL+FR:duration=1");
+
+        assert_eq!(TestSource::Custom("mandelbrot=size=640x480".to_string()).to_string(), "mandelbrot=size=640x480");
+    }
+}
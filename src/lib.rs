@@ -1,31 +1,61 @@
-use std::{env::{current_exe, temp_dir}, ffi::OsStr, fs::{File, OpenOptions}, io::{Cursor, Read, Write}, marker::PhantomData, ops::AddAssign, path::PathBuf, process::{Child, ChildStderr, ChildStdin, ChildStdout, Command, ExitStatus, Stdio}};
+use std::{env::{current_exe, temp_dir}, ffi::{OsStr, OsString}, io::{Read, Write}, marker::PhantomData, ops::AddAssign, path::{Path, PathBuf}, process::{Child, ChildStderr, ChildStdin, ChildStdout, Command, ExitStatus, Stdio}, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, time::{Duration, Instant}};
 
 use anyhow::Context;
-use flate2::read::GzDecoder;
 use once_cell::sync::Lazy;
 use pipe::{Pipe, Piped};
 use rand::{distributions::Alphanumeric, Rng};
-use tokio::{sync::mpsc::{channel, Receiver, Sender}, task::JoinHandle};
-
+use tokio::{sync::mpsc::{channel, unbounded_channel, Receiver, Sender, UnboundedReceiver, UnboundedSender}, task::JoinHandle};
+
+pub mod analysis;
+pub mod attachments;
+pub mod audio;
+pub mod captions;
+pub mod capture;
+pub mod compose;
+pub mod concat;
+pub mod encoder;
+pub mod failover;
+pub mod filter;
+pub mod frames;
+pub mod gpu;
+pub mod health;
+pub mod ladder;
 pub mod pipe;
-
-/// https://github.com/eugeneware/ffmpeg-static/releases/tag/b6.0
-#[cfg(all(target_os = "windows", target_arch = "x86_64"))]
-const FFMPEG_URL: &str = "https://github.com/eugeneware/ffmpeg-static/releases/download/b6.0/ffmpeg-win32-x64.gz";
-
-#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
-const FFMPEG_URL: &str = "https://github.com/eugeneware/ffmpeg-static/releases/download/b6.0/ffmpeg-linux-x64.gz";
-
-#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
-const FFMPEG_URL: &str = "https://github.com/eugeneware/ffmpeg-static/releases/download/b6.0/ffmpeg-linux-arm64.gz";
-
-#[cfg(all(target_os = "macos", target_arch = "x86_64"))]
-const FFMPEG_URL: &str = "https://github.com/eugeneware/ffmpeg-static/releases/download/b6.0/ffmpeg-darwin-x64.gz";
-
-#[cfg(all(target_os = "macos", target_arch = "aarch64"))]
-const FFMPEG_URL: &str = "https://github.com/eugeneware/ffmpeg-static/releases/download/b6.0/ffmpeg-darwin-arm64.gz";
-
-static mut FFMPEG_DOWNLOAD_ROOT_DIR: Lazy<PathBuf> = Lazy::new(|| current_exe().expect("Can't get the current app path").parent().clone().expect("Can't get the current program folder.\nThis should never fail... I think").to_path_buf());
+pub mod play;
+pub mod probe;
+#[cfg(feature = "indicatif")]
+pub mod progress_bar;
+pub mod recording;
+pub mod recovery;
+pub mod seek;
+pub mod sink;
+pub mod slideshow;
+pub mod split;
+pub mod stage;
+pub mod stream;
+pub mod supervisor;
+pub mod timeline;
+pub mod timestamp;
+pub mod validate;
+pub mod video;
+mod archive;
+mod async_command;
+mod buffer;
+mod download;
+mod lavfi;
+mod process;
+mod stderr;
+mod temp;
+mod zmq;
+
+pub use async_command::AsyncFFmpegCommand;
+pub use buffer::OutputBuffer;
+pub use download::{ArchiveLayout, DownloadTarget, EugenewareSource, FFmpegDownloadSource};
+pub use lavfi::TestSource;
+pub use stderr::{FailureKind, FFmpegBenchmark, FFmpegBenchmarkTask};
+pub use temp::TempOutput;
+
+static mut FFMPEG_DOWNLOAD_ROOT_DIR: Lazy<PathBuf> = Lazy::new(|| current_exe().expect("Can't get the current app path").parent().expect("Can't get the current program folder.\nThis should never fail... I think").to_path_buf());
 
 #[derive(Debug)]
 pub enum FFmpegProgressStatus {
@@ -58,6 +88,29 @@ pub struct FFmpegProgress
     pub drop_frames: Option<usize>,
     pub speed: Option<f32>,
     pub progress: Option<FFmpegProgressStatus>,
+    /// From 0 to 100, only known with [`FFmpegBuilder::total_duration`]
+    pub percent: Option<f32>,
+    /// Time left at the current speed, only known with [`FFmpegBuilder::total_duration`]
+    pub eta: Option<Duration>,
+}
+
+impl FFmpegProgress {
+    /// Fill in the percent & eta from how long the whole output is
+    fn estimate(&mut self, total: Duration) {
+        if matches!(self.progress, Some(FFmpegProgressStatus::End)) {
+            self.percent = Some(100.0);
+            self.eta = Some(Duration::ZERO);
+
+            return;
+        }
+
+        let Some(done) = self.out_time_us.map(|us| Duration::from_micros(us as u64)) else { return };
+
+        if total.is_zero() { return };
+
+        self.percent = Some((done.as_secs_f32() / total.as_secs_f32() * 100.0).clamp(0.0, 100.0));
+        self.eta = self.speed.filter(|speed| *speed > 0.0).map(|speed| total.saturating_sub(done).div_f32(speed));
+    }
 }
 
 impl From<String> for FFmpegProgress {
@@ -75,13 +128,13 @@ impl From<String> for FFmpegProgress {
             match key {
                 "frame" => progress.frame = value.parse::<usize>().ok(),
                 "fps" => progress.fps = value.parse::<usize>().ok(),
-                "bitrate" => progress.bitrate = value.split_once("kbits").map(|(v, _)| v.parse::<f32>().ok()).flatten(),
+                "bitrate" => progress.bitrate = value.split_once("kbits").and_then(|(v, _)| v.parse::<f32>().ok()),
                 "total_size" => progress.total_size = value.parse::<usize>().ok(),
                 "out_time_us" => progress.out_time_us = value.parse::<usize>().ok(),
                 "out_time_ms" => progress.out_time_ms = value.parse::<usize>().ok(),
                 "dup_frames" => progress.dup_frames = value.parse::<usize>().ok(),
                 "drop_frames" => progress.drop_frames = value.parse::<usize>().ok(),
-                "speed" => progress.speed = value.split_once("x").map(|(v, _)| v.parse::<f32>().ok()).flatten(),
+                "speed" => progress.speed = value.split_once('x').and_then(|(v, _)| v.parse::<f32>().ok()),
                 "progress" => progress.progress = value.parse::<FFmpegProgressStatus>().ok(),
                 _ => {  }
             }
@@ -91,67 +144,500 @@ impl From<String> for FFmpegProgress {
     }
 }
 
+/// A sample of the FFmpeg process resource usage, see [`FFmpegCommand::resource_usage`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FFmpegResourceUsage {
+    /// CPU usage since the previous sample (or since the process started), 100% is one fully used core
+    ///
+    /// [`Option::None`] on the first sample of an attached process
+    pub cpu_percent: Option<f32>,
+    /// Total CPU time (user + system) spent by the process
+    pub cpu_time: Duration,
+    /// Resident set size in bytes
+    pub rss: u64,
+    /// Bytes read from storage
+    pub read_bytes: u64,
+    /// Bytes written to storage
+    pub write_bytes: u64,
+}
+
+/// How FFmpeg was stopped by [`FFmpegCommand::stop_with_timeout`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopOutcome {
+    /// It quit on "q" (or an interrupt), the outputs are finished
+    Quit,
+    /// It only stopped on SIGTERM (another interrupt on Windows), the outputs are likely finished
+    Terminated,
+    /// It had to be killed, the outputs are likely broken
+    Killed,
+}
+
+/// Keys that FFmpeg understands while it's running, see [`FFmpegCommand::send_key`]
+#[derive(Debug, Clone)]
+pub enum InteractiveCommand {
+    /// `q`
+    Quit,
+    /// `+`
+    IncreaseVerbosity,
+    /// `-`
+    DecreaseVerbosity,
+    /// `c`, send a command to the first filter matching `target`
+    FilterCommand {
+        target: String,
+        command: String,
+        arg: String,
+    },
+    /// `C`, send a command to every filter matching `target` (or `all`)
+    ///
+    /// Queued until `time` (in seconds) if provided, otherwise sent immediately
+    FilterCommandAll {
+        target: String,
+        time: Option<f64>,
+        command: String,
+        arg: String,
+    },
+    /// `D`
+    CycleDebugMode,
+    /// `h`, cycle between packet dump & hex dump
+    CyclePacketDump,
+    /// `s`
+    ToggleQpHistogram,
+    /// `?`
+    Help,
+}
+
+impl InteractiveCommand {
+    /// The raw bytes written into FFmpeg stdin
+    pub fn to_keystrokes(&self) -> Vec<u8> {
+        match self {
+            Self::Quit => b"q".to_vec(),
+            Self::IncreaseVerbosity => b"+".to_vec(),
+            Self::DecreaseVerbosity => b"-".to_vec(),
+            // FFmpeg reads "<target> <time> <command> <arg>" until a new line, -1 means now
+            Self::FilterCommand { target, command, arg } => format!("c{target} -1 {command} {arg}\n").into_bytes(),
+            Self::FilterCommandAll { target, time, command, arg } => format!("C{target} {} {command} {arg}\n", time.unwrap_or(-1.0)).into_bytes(),
+            Self::CycleDebugMode => b"D".to_vec(),
+            Self::CyclePacketDump => b"h".to_vec(),
+            Self::ToggleQpHistogram => b"s".to_vec(),
+            Self::Help => b"?".to_vec(),
+        }
+    }
+}
+
+/// FFmpeg exited with a non-zero status, returned by [`FFmpegCommand::wait_checked`]
+#[derive(Debug)]
+pub struct FFmpegError {
+    pub status: ExitStatus,
+    /// The last part of stderr, if it was collected with [`FFmpegBuilder::stderr_ring_buffer`]
+    pub stderr: Option<String>,
+}
+
+impl std::fmt::Display for FFmpegError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FFmpeg exited with {}", self.status)?;
+
+        if let Some(stderr) = self.stderr.as_deref().map(str::trim).filter(|stderr| !stderr.is_empty()) {
+            write!(f, "\n{stderr}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for FFmpegError { }
+
+impl FFmpegError {
+    /// Whether running FFmpeg again could succeed, based on the stderr messages
+    ///
+    /// Always [`FailureKind::Unknown`] without [`FFmpegBuilder::stderr_ring_buffer`]
+    pub fn failure_kind(&self) -> FailureKind {
+        self.stderr.as_deref().map_or(FailureKind::Unknown, stderr::classify_failure)
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.failure_kind() == FailureKind::Retryable
+    }
+}
+
+/// An output that isn't allowed to be overwritten already exists, see [`FFmpegBuilder::overwrite`]
+#[derive(Debug)]
+pub struct OutputExists {
+    pub path: PathBuf,
+}
+
+impl std::fmt::Display for OutputExists {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The output {:?} already exists", self.path)
+    }
+}
+
+impl std::error::Error for OutputExists { }
+
+/// A successful FFmpeg run, returned by [`FFmpegCommand::wait_checked`]
+#[derive(Debug)]
+pub struct FFmpegOutcome {
+    pub status: ExitStatus,
+    /// Enabled with [`FFmpegBuilder::benchmark`]
+    pub benchmark: Option<FFmpegBenchmark>,
+}
+
 pub struct FFmpegCommand {
-    inner_child: Child,
+    /// [`Option::None`] if the process is attached with [`FFmpegCommand::attach`]
+    inner_child: Option<Child>,
+    pid: u32,
+    drop_behavior: DropBehavior,
+    /// Used for computing the CPU usage between [`FFmpegCommand::resource_usage`] calls
+    last_cpu_sample: Option<(Duration, Instant)>,
+    zmq_address: Option<String>,
+    stderr_collector: Option<stderr::StderrCollector>,
+    /// Taken once [`timeline::TimelineEvent::Exited`] is sent
+    timeline: Option<UnboundedSender<timeline::TimelineEvent>>,
+    /// Deleted with the command, e.g. [`FFmpegBuilder::filter_complex_script`]
+    _temp_files: Vec<TempOutput>,
+    /// [`FFmpegBuilder::isolated_work_dir`], deleted with the command
+    work_dir: Option<temp::TempDir>,
+    /// Finished once FFmpeg exits, see [`FFmpegBuilder::start_with_progress_sink`]
+    progress_reader: Option<ProgressReader>,
 }
 
 impl FFmpegCommand {
+    /// Re-acquire control of a running FFmpeg process, usually one started with [`FFmpegBuilder::detached`]
+    ///
+    /// The attached process is not our child, so stdio is not available & it won't be killed on drop
+    pub fn attach(pid: u32) -> std::io::Result<Self> {
+        if !process::is_alive(pid) {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("No running process with pid {pid}")));
+        }
+
+        Ok(Self { inner_child: None, pid, drop_behavior: DropBehavior::Detach, last_cpu_sample: None, zmq_address: None, stderr_collector: None, timeline: None, _temp_files: Vec::new(), work_dir: None, progress_reader: None })
+    }
+
+    /// Same as [`FFmpegCommand::attach`], reading the pid from a file written by [`FFmpegBuilder::pid_file`]
+    pub fn attach_with_pid_file(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let pid = std::fs::read_to_string(path.as_ref())
+            .with_context(|| format!("Can't read the pid file {:?}", path.as_ref()))?
+            .trim()
+            .parse::<u32>()
+            .context("Invalid pid file")?;
+
+        Ok(Self::attach(pid)?)
+    }
+
+    /// OS assigned process identifier
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// The directory made by [`FFmpegBuilder::isolated_work_dir`], read the pass logs or reports from it before dropping the command
+    pub fn work_dir(&self) -> Option<&Path> {
+        self.work_dir.as_ref().map(temp::TempDir::path)
+    }
+
+    /// Sample the CPU, memory & IO usage of the FFmpeg process
+    ///
+    /// CPU percent is computed from the previous call, so call this periodically to keep it meaningful
+    pub fn resource_usage(&mut self) -> std::io::Result<FFmpegResourceUsage> {
+        let mut usage = process::resource_usage(self.pid)?;
+        let now = Instant::now();
+
+        if let Some((last_cpu_time, last_sampled)) = self.last_cpu_sample {
+            let elapsed = now.duration_since(last_sampled).as_secs_f32();
+
+            if elapsed > 0.0 {
+                usage.cpu_percent = Some(usage.cpu_time.saturating_sub(last_cpu_time).as_secs_f32() / elapsed * 100.0);
+            }
+        }
+
+        self.last_cpu_sample = Some((usage.cpu_time, now));
+
+        Ok(usage)
+    }
+
+    /// Gracefully stop FFmpeg & wait for it to finish writing the outputs
+    ///
+    /// Sends "q" if stdin is piped & not taken, otherwise FFmpeg is interrupted (SIGINT / CTRL_BREAK),
+    /// which FFmpeg handles the same way. Killed if neither is possible
     pub fn stop(mut self) -> std::io::Result<()> {
-        self.inner_child.stdin
-            .take().expect("Stdin has been taken")
-            .write(b"q")?;
-        
-        self.inner_child.wait()?;
+        self.request_quit()?;
+
+        self.wait()?;
         self.force_stop()?;
 
         Ok(())
     }
 
+    /// Same as [`FFmpegCommand::stop`] but gives FFmpeg at most `timeout` for every step,
+    /// then sends SIGTERM & finally kills it if it still hangs, e.g. on a dead network input
+    pub fn stop_with_timeout(mut self, timeout: Duration) -> std::io::Result<(StopOutcome, ExitStatus)> {
+        self.request_quit()?;
+
+        if let Some(status) = self.wait_timeout(timeout)? {
+            return Ok((StopOutcome::Quit, status));
+        }
+
+        let _ = process::terminate(self.pid);
+
+        if let Some(status) = self.wait_timeout(timeout)? {
+            return Ok((StopOutcome::Terminated, status));
+        }
+
+        self.kill()?;
+
+        Ok((StopOutcome::Killed, self.wait()?))
+    }
+
     pub fn force_stop(mut self) -> std::io::Result<()> {
-        self.inner_child.kill()?;
+        self.kill()
+    }
+
+    fn request_quit(&mut self) -> std::io::Result<()> {
+        match self.send_key(InteractiveCommand::Quit) {
+            // Closing stdin makes sure FFmpeg doesn't wait for more input
+            Ok(_) => drop(self.take_stdin()),
+            Err(_) => process::interrupt(self.pid).or_else(|_| process::kill(self.pid))?,
+        }
+
+        Ok(())
+    }
+
+    fn kill(&mut self) -> std::io::Result<()> {
+        match self.inner_child.as_mut() {
+            Some(child) => child.kill()?,
+            None => if process::is_alive(self.pid) { process::kill(self.pid)? },
+        }
 
         Ok(())
     }
     
+    /// For an attached process the real exit status can't be collected,
+    /// a successful status is returned once the process is gone
     pub fn wait(&mut self) -> std::io::Result<ExitStatus> {
-        self.inner_child.wait()
+        let status = match self.inner_child.as_mut() {
+            Some(child) => child.wait()?,
+            None => {
+                while process::is_alive(self.pid) {
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+
+                ExitStatus::default()
+            },
+        };
+
+        self.on_exit(status);
+
+        Ok(status)
+    }
+
+    /// Same as [`FFmpegCommand::wait`], the blocking wait runs in [`tokio::task::spawn_blocking`]
+    ///
+    /// Not cancel safe, dropping the future before it completes loses the process handle (the process keeps running).
+    /// Use [`FFmpegCommand::wait_timeout_async`] inside `select!`
+    pub async fn wait_async(&mut self) -> std::io::Result<ExitStatus> {
+        let Some(mut child) = self.inner_child.take() else {
+            let pid = self.pid;
+
+            return tokio::task::spawn_blocking(move || {
+                while process::is_alive(pid) {
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+
+                ExitStatus::default()
+            }).await.map_err(std::io::Error::other);
+        };
+
+        let (child, status) = tokio::task::spawn_blocking(move || {
+            let status = child.wait();
+
+            (child, status)
+        }).await.map_err(std::io::Error::other)?;
+
+        self.inner_child = Some(child);
+
+        if let Ok(status) = status {
+            self.on_exit(status);
+        }
+
+        status
+    }
+
+    /// Same as [`FFmpegCommand::stop`], the blocking stop runs in [`tokio::task::spawn_blocking`]
+    pub async fn stop_async(self) -> std::io::Result<()> {
+        tokio::task::spawn_blocking(move || self.stop()).await.map_err(std::io::Error::other)?
+    }
+
+    /// Check if FFmpeg has exited without blocking
+    pub fn try_wait(&mut self) -> std::io::Result<Option<ExitStatus>> {
+        let status = match self.inner_child.as_mut() {
+            Some(child) => child.try_wait()?,
+            None => (!process::is_alive(self.pid)).then(ExitStatus::default),
+        };
+
+        if let Some(status) = status {
+            self.on_exit(status);
+        }
+
+        Ok(status)
+    }
+
+    /// Called once FFmpeg is known to have exited
+    fn on_exit(&mut self, status: ExitStatus) {
+        if let Some(stderr_collector) = &self.stderr_collector {
+            stderr_collector.finish();
+        }
+
+        if let Some(timeline) = self.timeline.take() {
+            let _ = timeline.send(timeline::TimelineEvent::Exited { status });
+        }
+
+        if let Some(progress_reader) = self.progress_reader.take() {
+            progress_reader.finish();
+        }
+    }
+
+    /// Wait for FFmpeg to exit, at most for `timeout`
+    ///
+    /// Returns [`Option::None`] if FFmpeg is still running
+    pub fn wait_timeout(&mut self, timeout: Duration) -> std::io::Result<Option<ExitStatus>> {
+        let deadline = Instant::now() + timeout;
+        let mut interval = Duration::from_millis(1);
+
+        loop {
+            if let Some(status) = self.try_wait()? { return Ok(Some(status)) };
+
+            let now = Instant::now();
+            if now >= deadline { return Ok(None) };
+
+            std::thread::sleep(interval.min(deadline - now));
+            interval = (interval * 2).min(Duration::from_millis(100));
+        }
+    }
+
+    /// Same as [`FFmpegCommand::wait_timeout`], without blocking the async runtime
+    pub async fn wait_timeout_async(&mut self, timeout: Duration) -> std::io::Result<Option<ExitStatus>> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut interval = Duration::from_millis(1);
+
+        loop {
+            if let Some(status) = self.try_wait()? { return Ok(Some(status)) };
+
+            let now = tokio::time::Instant::now();
+            if now >= deadline { return Ok(None) };
+
+            tokio::time::sleep(interval.min(deadline - now)).await;
+            interval = (interval * 2).min(Duration::from_millis(100));
+        }
+    }
+
+    /// The last part of FFmpeg stderr, enabled with [`FFmpegBuilder::stderr_ring_buffer`]
+    ///
+    /// Useful for reporting why FFmpeg failed
+    pub fn recent_stderr(&self) -> Option<String> {
+        self.stderr_collector.as_ref().map(|stderr_collector| stderr_collector.contents())
+    }
+
+    /// Wait for FFmpeg to exit & turn a non-zero exit status into an [`FFmpegError`]
+    pub fn wait_checked(&mut self) -> anyhow::Result<FFmpegOutcome> {
+        let status = self.wait()?;
+
+        if !status.success() {
+            return Err(FFmpegError { status, stderr: self.recent_stderr() }.into());
+        }
+
+        Ok(FFmpegOutcome {
+            status,
+            benchmark: self.stderr_collector.as_ref().and_then(|stderr_collector| stderr_collector.benchmark()),
+        })
+    }
+
+    /// Send an interactive command through stdin
+    ///
+    /// Stdin must be piped & not taken
+    pub fn send_key(&mut self, command: InteractiveCommand) -> std::io::Result<()> {
+        let Some(stdin) = self.inner_child.as_mut().and_then(|child| child.stdin.as_mut()) else {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotConnected, "FFmpeg stdin is not available"));
+        };
+
+        stdin.write_all(&command.to_keystrokes())?;
+        stdin.flush()
+    }
+
+    /// Change a filter parameter while FFmpeg is running, e.g. `send_filter_command("volume", "volume", "0.5")`
+    ///
+    /// Goes through the zmq/azmq filter if [`FFmpegBuilder::zmq_address`] is set,
+    /// otherwise it's sent as [`InteractiveCommand::FilterCommand`] through stdin
+    pub fn send_filter_command(&mut self, target: impl AsRef<str>, command: impl AsRef<str>, arg: impl AsRef<str>) -> anyhow::Result<()> {
+        let (target, command, arg) = (target.as_ref(), command.as_ref(), arg.as_ref());
+
+        let Some(address) = &self.zmq_address else {
+            return Ok(self.send_key(InteractiveCommand::FilterCommand { target: target.to_owned(), command: command.to_owned(), arg: arg.to_owned() })?);
+        };
+
+        let reply = zmq::request(address, format!("{target} {command} {arg}").trim_end())?;
+
+        // The filter replies with "<error code> <error message>", 0 means success
+        match reply.split_once(' ').map(|(code, message)| (code.parse::<i32>(), message)) {
+            Some((Ok(0), _)) => Ok(()),
+            Some((_, message)) => anyhow::bail!("FFmpeg refused the filter command: {message}"),
+            None => anyhow::bail!("Unexpected reply from the zmq filter: {reply:?}"),
+        }
     }
 
     /// Used for piping input or command to FFmpeg 
     pub fn stdin(&self) -> &Option<ChildStdin> {
-        &self.inner_child.stdin
+        match self.inner_child.as_ref() {
+            Some(child) => &child.stdin,
+            None => &None,
+        }
     }
 
     /// Used for piping output from FFmpeg 
     pub fn stdout(&self) -> &Option<ChildStdout> {
-        &self.inner_child.stdout
+        match self.inner_child.as_ref() {
+            Some(child) => &child.stdout,
+            None => &None,
+        }
     }
 
     /// Used for piping log from FFmpeg 
     pub fn stderr(&self) -> &Option<ChildStderr> {
-        &self.inner_child.stderr
+        match self.inner_child.as_ref() {
+            Some(child) => &child.stderr,
+            None => &None,
+        }
     }
 
     /// Used for piping input or command to FFmpeg 
     pub fn take_stdin(&mut self) -> Option<ChildStdin> {
-        self.inner_child.stdin.take()
+        self.inner_child.as_mut()?.stdin.take()
     }
 
     /// Used for piping output from FFmpeg 
     pub fn take_stdout(&mut self) -> Option<ChildStdout> {
-        self.inner_child.stdout.take()
+        self.inner_child.as_mut()?.stdout.take()
     }
 
     /// Used for piping log from FFmpeg 
     pub fn take_stderr(&mut self) -> Option<ChildStderr> {
-        self.inner_child.stderr.take()
+        self.inner_child.as_mut()?.stderr.take()
     }
 }
 
 impl Drop for FFmpegCommand {
     fn drop(&mut self) {
-        // Make sure that there is no zombie process
-        let _ = self.inner_child.kill();
+        match self.drop_behavior {
+            // Make sure that there is no zombie process
+            DropBehavior::Kill => if self.kill().is_ok() { let _ = self.wait(); },
+            DropBehavior::GracefulStop { timeout } => {
+                if !matches!(self.try_wait(), Ok(None)) { return };
+
+                let _ = self.request_quit();
+
+                if !matches!(self.wait_timeout(timeout), Ok(Some(_))) {
+                    let _ = self.kill();
+                }
+            },
+            DropBehavior::Detach => { },
+        }
     }
 }
 
@@ -160,77 +646,667 @@ pub trait Mode { }
 pub struct Normal;
 impl Mode for Normal { }
 
+/// Configuring an output
 pub struct IO;
 impl Mode for IO { }
 
+/// Configuring an input, where options like `-c:v` mean something else than for outputs
+pub struct Input;
+impl Mode for Input { }
+
+/// Configuring an input or an output
+pub trait Section: Mode { }
+impl Section for IO { }
+impl Section for Input { }
+
+/// What happens to the FFmpeg process when [`FFmpegCommand`] is dropped, see [`FFmpegBuilder::on_drop`]
+#[derive(Debug, Clone, Copy, Default)]
+pub enum DropBehavior {
+    /// Kill it, the outputs might be left unfinished
+    #[default]
+    Kill,
+    /// Same as [`FFmpegCommand::stop`], killed if FFmpeg is still running after `timeout`
+    ///
+    /// Blocks the dropping thread for up to `timeout`
+    GracefulStop { timeout: Duration },
+    /// Let it keep running
+    Detach,
+}
+
+/// How FFmpeg sends the progress, see [`FFmpegBuilder::progress_transport`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProgressTransport {
+    /// A named pipe (FIFO on Unix)
+    #[default]
+    NamedPipe,
+    /// `-progress pipe:1`, stdout can't be used for an output
+    Stdout,
+    /// `-progress <temporary file>`, read as it grows
+    File,
+}
+
+/// Where the progress of a [`ProgressTransport`] is read from
+enum ProgressSource {
+    NamedPipe(Pipe),
+    Stdout,
+    File(PathBuf),
+}
+
+#[derive(Default)]
+struct StartOptions {
+    drop_behavior: DropBehavior,
+    detached: bool,
+    share_process_group: bool,
+    pid_file: Option<PathBuf>,
+    zmq_address: Option<String>,
+    stderr_ring_buffer: Option<usize>,
+    benchmark: bool,
+    temp_files: Vec<TempOutput>,
+    work_dir: Option<temp::TempDir>,
+    stderr_lines: Option<stderr::LineHandler>,
+    timeline: Option<UnboundedSender<timeline::TimelineEvent>>,
+    /// Written to stdin by [`FFmpegBuilder::input_memory`]
+    stdin_data: Option<Vec<u8>>,
+    /// Where stdout goes for [`FFmpegBuilder::output_to_vec`]
+    stdout_buffer: Option<std::sync::mpsc::Sender<std::io::Result<Vec<u8>>>>,
+    total_duration: Option<Duration>,
+    progress_transport: ProgressTransport,
+    /// Output options given while configuring an input, see [`FFmpegBuilder::output_args`]
+    pending_output_args: Vec<OsString>,
+}
+
 pub struct FFmpegBuilder<M: Mode + ?Sized> {
     inner_command: Command,
-    inner_args: Vec<String>,
+    inner_args: Vec<OsString>,
     inserting_offset: Option<usize>,
+    start_options: StartOptions,
     marker: PhantomData<M>
 }
 
+/// The nth input of a command, counted from 0, see [`FFmpegBuilder::insert_before_input`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InputHandle(pub usize);
+
+/// The nth output of a command, counted from 0, see [`FFmpegBuilder::insert_into_output`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OutputHandle(pub usize);
+
+/// Deliver every progress block (`key=value` lines ending with `progress=...`) of `reader` into `sink`
+fn read_progress<S: sink::ProgressSink>(reader: impl Read, sink: Arc<Mutex<S>>, timeline: Option<UnboundedSender<timeline::TimelineEvent>>, total_duration: Option<Duration>) {
+    let mut lines = std::io::BufRead::lines(std::io::BufReader::new(reader));
+    let mut block = String::new();
+
+    let mut has_ended = false;
+    let mut first = true;
+
+    // FFmpeg is gone without saying "end" if the lines run out, most likely crashed
+    while let Some(Ok(line)) = lines.next() {
+        block.push_str(&line);
+        block.push('\n');
+
+        let Some(status) = line.trim().strip_prefix("progress=") else { continue };
+        has_ended = status == "end";
+
+        if let Some(timeline) = timeline.as_ref().filter(|_| first) {
+            let _ = timeline.send(timeline::TimelineEvent::FirstProgress);
+            first = false;
+        }
+
+        let mut progress = FFmpegProgress::from(std::mem::take(&mut block));
+        if let Some(total) = total_duration { progress.estimate(total) };
+
+        let Ok(mut sink) = sink.lock() else { break };
+        sink.on_progress(progress);
+
+        if has_ended { break };
+    }
+
+    if let Ok(mut sink) = sink.lock() {
+        sink.on_finish(has_ended);
+    }
+}
+
+/// The thread running [`read_progress`]
+struct ProgressReader {
+    thread: std::thread::JoinHandle<()>,
+    /// The `-progress` FIFO, the thread is stuck opening it if FFmpeg exited without opening it
+    fifo: Option<PathBuf>,
+    /// Stops the [`pipe::TailReader`] of [`ProgressTransport::File`]
+    exited: Option<Arc<AtomicBool>>,
+}
+
+impl ProgressReader {
+    /// Wait for the rest of the progress once FFmpeg is gone
+    fn finish(self) {
+        if let Some(exited) = &self.exited { exited.store(true, Ordering::SeqCst) };
+
+        while !self.thread.is_finished() {
+            if let Some(fifo) = &self.fifo { pipe::release_reader(fifo) };
+
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let _ = self.thread.join();
+
+        if let Some(fifo) = &self.fifo { temp::remove(fifo) };
+    }
+}
+
+/// Every way to name stdout as an output, `-`, `pipe:`, `pipe:1` & `/dev/stdout`
+fn is_stdout(path: &OsStr) -> bool {
+    ["-", "pipe:", "pipe:1", "/dev/stdout"].iter().any(|stdout| path == *stdout)
+}
+
+/// Every way to name stdin as an input, `-`, `pipe:`, `pipe:0` & `/dev/stdin`
+fn is_stdin(path: &OsStr) -> bool {
+    ["-", "pipe:", "pipe:0", "/dev/stdin"].iter().any(|stdin| path == *stdin)
+}
+
+/// `-y` & `-n` are global options for FFmpeg, here they're put before every output path to know where the output starts
+fn is_output_marker(arg: &OsStr) -> bool {
+    arg == "-y" || arg == "-n"
+}
+
 impl<A: Mode> FFmpegBuilder<A> {
     fn into<B: Mode>(self) -> FFmpegBuilder<B> {
-        FFmpegBuilder { marker: PhantomData, inner_command: self.inner_command, inner_args: self.inner_args, inserting_offset: self.inserting_offset }
+        FFmpegBuilder { marker: PhantomData, inner_command: self.inner_command, inner_args: self.inner_args, inserting_offset: self.inserting_offset, start_options: self.start_options }
+    }
+
+    /// Index of the nth `-i` for inputs or `-y`/`-n` for outputs
+    fn nth_position(&self, marker: fn(&OsStr) -> bool, nth: usize) -> Option<usize> {
+        self.inner_args.iter().enumerate().filter(|(_, arg)| marker(arg)).nth(nth).map(|(index, _)| index)
+    }
+
+    fn insert_at<I, S>(mut self, at: usize, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let args = args.into_iter().map(|arg| arg.as_ref().to_owned()).collect::<Vec<_>>();
+        let count = args.len();
+
+        self.inner_args.splice(at..at, args);
+
+        // Keep pointing to the same input or output
+        if let Some(offset) = self.inserting_offset.as_mut().filter(|offset| at <= **offset) {
+            *offset += count;
+        }
+
+        self
+    }
+
+    /// Whether an output is written to stdout
+    fn has_stdout_output(&self) -> bool {
+        self.inner_args.windows(2).any(|args| is_output_marker(&args[0]) && is_stdout(&args[1]))
+    }
+
+    /// Whether an input is read from stdin
+    fn has_stdin_input(&self) -> bool {
+        self.inner_args.windows(2).any(|args| args[0] == "-i" && is_stdin(&args[1]))
+    }
+
+    /// Path of the first input
+    pub(crate) fn first_input(&self) -> Option<&OsStr> {
+        self.inner_args.iter().position(|arg| arg == "-i").and_then(|index| self.inner_args.get(index + 1)).map(OsString::as_os_str)
+    }
+
+    /// The input added last
+    pub fn last_input(&self) -> Option<InputHandle> {
+        self.inner_args.iter().filter(|arg| *arg == "-i").count().checked_sub(1).map(InputHandle)
+    }
+
+    /// The output added last
+    pub fn last_output(&self) -> Option<OutputHandle> {
+        self.inner_args.iter().filter(|arg| is_output_marker(arg)).count().checked_sub(1).map(OutputHandle)
+    }
+
+    /// Insert `arg` before everything else, where global options (e.g. `-loglevel`) go
+    pub fn insert_global_arg<S: AsRef<OsStr>>(self, arg: S) -> Self {
+        self.insert_at(0, [arg])
+    }
+
+    /// Same as [`FFmpegBuilder::insert_global_arg`] for multiple arguments
+    pub fn insert_global_args<I, S>(self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.insert_at(0, args)
+    }
+
+    /// Insert `args` right before the `-i` of `input`, after its other options
+    pub fn insert_before_input<I, S>(self, input: InputHandle, args: I) -> anyhow::Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let at = self.nth_position(|arg| arg == "-i", input.0).with_context(|| format!("There's no input {}", input.0))?;
+
+        Ok(self.insert_at(at, args))
+    }
+
+    /// Insert `args` as the last options of `output`, right before its path
+    pub fn insert_into_output<I, S>(self, output: OutputHandle, args: I) -> anyhow::Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let at = self.nth_position(is_output_marker, output.0).with_context(|| format!("There's no output {}", output.0))?;
+
+        Ok(self.insert_at(at, args))
     }
 }
 
 impl FFmpegBuilder<Normal> {
+    /// The arguments given to FFmpeg, with the outputs that can't be overwritten checked
+    ///
+    /// FFmpeg refuses to run with both `-y` & `-n`, so `-n` is only kept when every output has it
+    fn checked_args(&self) -> anyhow::Result<Vec<OsString>> {
+        let outputs = self.inner_args.iter().enumerate().filter(|(_, arg)| is_output_marker(arg)).collect::<Vec<_>>();
+
+        for (index, _) in outputs.iter().filter(|(_, marker)| *marker == "-n") {
+            let Some(path) = self.inner_args.get(index + 1).map(PathBuf::from) else { continue };
+
+            if path.is_file() {
+                return Err(OutputExists { path }.into());
+            }
+        }
+
+        // Nothing was added after them, they're left at the end like any trailing option
+        let args = self.inner_args.iter().chain(&self.start_options.pending_output_args);
+
+        if outputs.iter().all(|(_, marker)| *marker == "-n") {
+            return Ok(args.cloned().collect());
+        }
+
+        Ok(args.map(|arg| if arg == "-n" { "-y".into() } else { arg.clone() }).collect())
+    }
+
+    /// Point the builder at the output added next, after the output options given while configuring an input
+    pub(crate) fn begin_output(&mut self) {
+        let pending = std::mem::take(&mut self.start_options.pending_output_args);
+        self.inner_args.extend(pending);
+
+        self.inserting_offset = Some(self.inner_args.len());
+    }
+
     /// Start a new FFmpeg child process
+    ///
+    /// Fails with [`OutputExists`] if an output set with [`FFmpegBuilder::overwrite`] to `false` already exists
     pub fn start(&mut self) -> anyhow::Result<FFmpegCommand> {
-        self.inner_command.args(&self.inner_args);
+        self.inner_command.args(self.checked_args()?);
+
+        if self.start_options.detached {
+            process::detach(&mut self.inner_command);
+        } else {
+            process::prepare(&mut self.inner_command, self.start_options.share_process_group);
+        }
 
-        let inner_child = self.inner_command.spawn()?;
+        let mut inner_child = self.inner_command.spawn()?;
+        let pid = inner_child.id();
 
-        Ok(FFmpegCommand { inner_child })
+        process::spawned(pid, self.start_options.share_process_group && !self.start_options.detached);
+
+        if let Some(pid_file) = &self.start_options.pid_file {
+            if let Err(error) = std::fs::write(pid_file, pid.to_string()) {
+                // Nothing would be left to stop it otherwise
+                let _ = inner_child.kill();
+                let _ = inner_child.wait();
+
+                return Err(anyhow::Error::new(error).context(format!("Can't write the pid file {pid_file:?}")));
+            }
+        }
+
+        if let Some((data, mut stdin)) = self.start_options.stdin_data.take().and_then(|data| Some((data, inner_child.stdin.take()?))) {
+            // Fails once FFmpeg exits, there's nobody to report it to
+            std::thread::spawn(move || stdin.write_all(&data));
+        }
+
+        if let Some((tx, stdout)) = self.start_options.stdout_buffer.take().and_then(|tx| Some((tx, inner_child.stdout.take()?))) {
+            buffer::collect(stdout, tx);
+        }
+
+        let stderr_lines = self.stderr_line_handler();
+
+        let stderr_collector = match (self.start_options.stderr_ring_buffer, self.start_options.benchmark) {
+            (None, false) if self.start_options.timeline.is_none() => None,
+            (capacity, benchmark) => inner_child.stderr.take().map(|stderr| {
+                stderr::StderrCollector::spawn(stderr, capacity.unwrap_or(stderr::DEFAULT_RING_BUFFER_CAPACITY), benchmark, stderr_lines)
+            }),
+        };
+
+        if let Some(timeline) = &self.start_options.timeline {
+            let _ = timeline.send(timeline::TimelineEvent::Spawned { pid });
+        }
+
+        Ok(FFmpegCommand {
+            inner_child: Some(inner_child),
+            pid,
+            drop_behavior: self.start_options.drop_behavior,
+            last_cpu_sample: Some((Duration::ZERO, Instant::now())),
+            zmq_address: self.start_options.zmq_address.clone(),
+            stderr_collector,
+            timeline: self.start_options.timeline.take(),
+            _temp_files: std::mem::take(&mut self.start_options.temp_files),
+            work_dir: self.start_options.work_dir.take(),
+            progress_reader: None,
+        })
     }
 
-    /// Start a new FFmpeg child process & listen to the progress
-    pub fn start_listen_progress(mut self, progress_rx: &mut Option<Receiver<FFmpegProgress>>) -> anyhow::Result<FFmpegCommand> {
-        let progress_pipe = Pipe::create_pipe()?;
-        self.inner_args.extend(["-progress".to_owned(), progress_pipe.path().display().to_string()]);
+    /// Called with every stderr line, for [`FFmpegBuilder::start_with_progress_sink`] & [`FFmpegBuilder::timeline_events`]
+    pub(crate) fn stderr_line_handler(&mut self) -> Option<stderr::LineHandler> {
+        match (self.start_options.stderr_lines.take(), self.start_options.timeline.clone()) {
+            (None, None) => None,
+            (mut lines, timeline) => Some(Box::new(move |line: &str| {
+                if let Some(lines) = lines.as_mut() {
+                    lines(line);
+                }
+
+                if let Some((timeline, event)) = timeline.as_ref().and_then(|timeline| Some((timeline, timeline::parse_line(line)?))) {
+                    let _ = timeline.send(event);
+                }
+            })),
+        }
+    }
 
+    /// Start a new FFmpeg child process & listen to the progress
+    pub fn start_listen_progress(self, progress_rx: &mut Option<Receiver<FFmpegProgress>>) -> anyhow::Result<FFmpegCommand> {
         let (ffmpeg_progress_tx, ffmpeg_progress_rx) = channel(128);
 
         *progress_rx = Some(ffmpeg_progress_rx);
 
-        std::thread::spawn(move || {
-            let mut listener = progress_pipe.listen().unwrap();
+        self.start_with_progress_sink(sink::ChannelSink::new(ffmpeg_progress_tx))
+    }
 
-            let mut has_ended = false;
+    /// Start a new FFmpeg child process & get the progress as a [`sink::ProgressStream`]
+    pub fn start_progress_stream(self, progress: &mut Option<sink::ProgressStream>) -> anyhow::Result<FFmpegCommand> {
+        let (sink, stream) = sink::ProgressStream::new();
 
-            while !has_ended {
-                let mut progress_string = String::new();
+        *progress = Some(stream);
 
-                let mut buffer = [0u8; 1024];
-                let Ok(len) = listener.read(&mut buffer) else { continue };
+        self.start_with_progress_sink(sink)
+    }
 
-                progress_string.push_str(&String::from_utf8_lossy(&buffer[..len]).trim());
+    /// Start a new FFmpeg child process & call `on_progress` with the progress from a background thread
+    pub fn start_with_progress_callback(self, on_progress: impl FnMut(FFmpegProgress) + Send + 'static) -> anyhow::Result<FFmpegCommand> {
+        self.start_with_progress_sink(sink::CallbackSink::new(on_progress))
+    }
 
-                if progress_string.ends_with("end") { has_ended = true };
+    /// Start a new FFmpeg child process & deliver the progress into `sink`
+    ///
+    /// [`sink::ProgressSink::on_log`] only gets the stderr lines when they're collected, e.g. with [`FFmpegBuilder::stderr_ring_buffer`]
+    pub fn start_with_progress_sink(mut self, sink: impl sink::ProgressSink) -> anyhow::Result<FFmpegCommand> {
+        let sink = Arc::new(Mutex::new(sink));
+        let timeline = self.start_options.timeline.clone();
+        let total_duration = self.start_options.total_duration;
+
+        self.start_options.stderr_lines = Some(Box::new({
+            let sink = sink.clone();
+
+            move |line: &str| if let Ok(mut sink) = sink.lock() { sink.on_log(line) }
+        }));
+
+        match self.progress_source()? {
+            ProgressSource::NamedPipe(progress_pipe) => {
+                let fifo = progress_pipe.path().to_path_buf();
+
+                let thread = std::thread::spawn(move || {
+                    // Blocks until FFmpeg opens the pipe, or until it's released by ProgressReader::finish
+                    let Ok(listener) = progress_pipe.listen_reader() else {
+                        if let Ok(mut sink) = sink.lock() { sink.on_finish(false) };
+                        return;
+                    };
+
+                    read_progress(listener, sink, timeline, total_duration);
+                });
+
+                let progress_reader = ProgressReader { thread, fifo: Some(fifo), exited: None };
+
+                match self.start() {
+                    Ok(mut command) => {
+                        command.progress_reader = Some(progress_reader);
+
+                        Ok(command)
+                    },
+                    Err(error) => {
+                        progress_reader.finish();
+
+                        Err(error)
+                    },
+                }
+            },
+            ProgressSource::Stdout => {
+                let mut command = self.start()?;
+                let stdout = command.take_stdout().context("FFmpeg stdout isn't piped")?;
 
-                let ffmpeg_progress = FFmpegProgress::from(progress_string);
+                let thread = std::thread::spawn(move || read_progress(stdout, sink, timeline, total_duration));
+                command.progress_reader = Some(ProgressReader { thread, fifo: None, exited: None });
 
-                let ffmpeg_progress_tx = ffmpeg_progress_tx.clone();
-                std::thread::spawn(move || ffmpeg_progress_tx.blocking_send(ffmpeg_progress).unwrap());
-            }
-        });
+                Ok(command)
+            },
+            ProgressSource::File(path) => {
+                let mut command = self.start()?;
+                let exited = Arc::new(AtomicBool::new(false));
+                let tail = pipe::TailReader::open(&path, exited.clone())?;
+
+                let thread = std::thread::spawn(move || read_progress(tail, sink, timeline, total_duration));
+                command.progress_reader = Some(ProgressReader { thread, fifo: None, exited: Some(exited) });
+
+                Ok(command)
+            },
+        }
+    }
+
+    /// Add `-progress` for the [`ProgressTransport`] & set up where it's read from
+    fn progress_source(&mut self) -> anyhow::Result<ProgressSource> {
+        match self.start_options.progress_transport {
+            ProgressTransport::NamedPipe => {
+                let progress_pipe = Pipe::create_pipe()?;
+                self.inner_args.extend(["-progress".into(), progress_pipe.path().into()]);
+
+                Ok(ProgressSource::NamedPipe(progress_pipe))
+            },
+            ProgressTransport::Stdout => {
+                if self.start_options.stdout_buffer.is_some() || self.has_stdout_output() {
+                    anyhow::bail!("The progress can't be sent to stdout, an output is already using it");
+                }
+
+                self.inner_args.extend(["-progress".into(), "pipe:1".into()]);
+                self.inner_command.stdout(Stdio::piped());
+
+                Ok(ProgressSource::Stdout)
+            },
+            ProgressTransport::File => {
+                let file = TempOutput::create()?;
+                let path = file.path().to_path_buf();
+
+                self.inner_args.extend(["-progress".into(), path.clone().into()]);
+                self.start_options.temp_files.push(file);
+
+                Ok(ProgressSource::File(path))
+            },
+        }
+    }
+
+    /// Start FFmpeg without stdin & stdout, the end of stderr is kept for the errors, used by the one-shot helpers
+    pub(crate) fn start_quiet(self) -> anyhow::Result<FFmpegCommand> {
+        self
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr_ring_buffer(stderr::DEFAULT_RING_BUFFER_CAPACITY)
+            .start()
+    }
+
+    /// Same as [`FFmpegBuilder::start_quiet`] & wait for FFmpeg to finish successfully
+    pub(crate) fn run_checked(self) -> anyhow::Result<FFmpegOutcome> {
+        self.start_quiet()?.wait_checked()
+    }
 
-        self.start()
+    /// Run FFmpeg to completion & collect everything it wrote to stderr, used by the analysis helpers
+    pub(crate) fn run_collect_stderr(self) -> anyhow::Result<(ExitStatus, String)> {
+        let mut command = self
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .start()?;
+
+        // Metadata & file names in other encodings aren't valid UTF-8
+        let mut stderr = Vec::new();
+        if let Some(mut pipe) = command.take_stderr() {
+            pipe.read_to_end(&mut stderr)?;
+        }
+
+        Ok((command.wait()?, String::from_utf8_lossy(&stderr).into_owned()))
     }
 
     /// Inspect FFmpeg arguments
     pub fn inspect_args<F>(self, mut f: F) -> Self
     where
         Self: Sized,
-        F: FnMut(&Vec<String>),
+        F: FnMut(&Vec<OsString>),
     {
         f(&self.inner_args);
 
         self
     }
     
+    /// Let the FFmpeg process outlive this program, useful for long running restream daemons
+    ///
+    /// The process is started in a new session (process group on Windows) & uses [`DropBehavior::Detach`],
+    /// use [`FFmpegCommand::attach`] to control it again later
+    ///
+    /// Stdio is set to [`Stdio::null`] because nobody will be reading the pipes once we exit,
+    /// call [`FFmpegBuilder::stderr`] after this to log somewhere else
+    pub fn detached(mut self) -> Self {
+        self.start_options.detached = true;
+        self.start_options.drop_behavior = DropBehavior::Detach;
+
+        self.inner_command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+
+        self
+    }
+
+    /// Windows only, don't give FFmpeg its own process group, so CTRL_C in our console reaches it too
+    ///
+    /// FFmpeg can't be interrupted on its own then, stopping it falls back to killing it when "q" can't be sent,
+    /// ignored with [`FFmpegBuilder::detached`]
+    pub fn share_process_group(mut self) -> Self {
+        self.start_options.share_process_group = true;
+
+        self
+    }
+
+    /// What to do with the FFmpeg process when [`FFmpegCommand`] is dropped, defaults to [`DropBehavior::Kill`]
+    pub fn on_drop(mut self, behavior: DropBehavior) -> Self {
+        self.start_options.drop_behavior = behavior;
+
+        self
+    }
+
+    /// Write the pid of the started process into a file, to be used with [`FFmpegCommand::attach_with_pid_file`]
+    pub fn pid_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.start_options.pid_file = Some(path.into());
+
+        self
+    }
+
+    /// Send the [`timeline::TimelineEvent`]s of the command, parsed from stderr (like [`FFmpegBuilder::stderr_ring_buffer`]) & the process state
+    ///
+    /// [`timeline::TimelineEvent::FirstProgress`] needs [`FFmpegBuilder::start_listen_progress`] or [`FFmpegBuilder::start_with_progress_sink`],
+    /// [`timeline::TimelineEvent::Exited`] is sent once the exit is noticed, e.g. by [`FFmpegCommand::wait`]
+    pub fn timeline_events(mut self, events_rx: &mut Option<UnboundedReceiver<timeline::TimelineEvent>>) -> Self {
+        let (events_tx, rx) = unbounded_channel();
+
+        *events_rx = Some(rx);
+        self.start_options.timeline = Some(events_tx);
+        self.inner_command.stderr(Stdio::piped());
+
+        self
+    }
+
+    /// Add `-benchmark -benchmark_all`, the report is parsed from stderr & returned by [`FFmpegCommand::wait_checked`]
+    ///
+    /// The report is logged at the info level, so it's missing if the log level is lower than that
+    pub fn benchmark(mut self) -> Self {
+        self.start_options.benchmark = true;
+        self.inner_command.stderr(Stdio::piped());
+
+        self.inner_args.splice(0..0, ["-benchmark".into(), "-benchmark_all".into()]);
+
+        self
+    }
+
+    /// Same as `-filter_complex` but `graph` is written to a temporary file, for generated graphs too long for the command line
+    ///
+    /// The file is deleted when the [`FFmpegCommand`] is dropped
+    pub fn filter_complex_script(mut self, graph: impl AsRef<str>) -> std::io::Result<Self> {
+        let mut script = TempOutput::create()?;
+        script.write_all(graph.as_ref().as_bytes())?;
+
+        let path = script.path().to_path_buf();
+        self.start_options.temp_files.push(script);
+
+        Ok(self.args([OsStr::new("-filter_complex_script"), path.as_os_str()]))
+    }
+
+    /// Run FFmpeg inside a new temporary directory, deleted with everything in it when the [`FFmpegCommand`] is dropped
+    ///
+    /// Files FFmpeg writes next to itself (e.g. `ffmpeg2pass-0.log` of two pass encodes, `-report` logs & segment lists) go there,
+    /// so jobs running at the same time don't overwrite each other's. Relative paths resolve inside it too, use absolute paths for the inputs & outputs
+    pub fn isolated_work_dir(mut self) -> std::io::Result<Self> {
+        let dir = temp::TempDir::create()?;
+
+        self.inner_command.current_dir(dir.path());
+        self.start_options.work_dir = Some(dir);
+
+        Ok(self)
+    }
+
+    /// The directory made by [`FFmpegBuilder::isolated_work_dir`], e.g. to write a segment list into it
+    pub fn work_dir(&self) -> Option<&Path> {
+        self.start_options.work_dir.as_ref().map(temp::TempDir::path)
+    }
+
+    /// How long the output will be, so the progress has a [`FFmpegProgress::percent`] & [`FFmpegProgress::eta`]
+    ///
+    /// Use [`FFmpegBuilder::probe_total_duration`] to take it from the first input
+    pub fn total_duration(mut self, duration: Duration) -> Self {
+        self.start_options.total_duration = Some(duration);
+
+        self
+    }
+
+    /// How the progress gets from FFmpeg to [`FFmpegBuilder::start_listen_progress`] & the other progress listeners
+    ///
+    /// Defaults to [`ProgressTransport::NamedPipe`], the others avoid named pipes, e.g. where they misbehave
+    pub fn progress_transport(mut self, transport: ProgressTransport) -> Self {
+        self.start_options.progress_transport = transport;
+
+        self
+    }
+
+    /// Keep the last `capacity` bytes of FFmpeg stderr in memory, see [`FFmpegCommand::recent_stderr`]
+    ///
+    /// Stderr is read by a background thread, so [`FFmpegCommand::take_stderr`] will return [`Option::None`]
+    pub fn stderr_ring_buffer(mut self, capacity: usize) -> Self {
+        self.start_options.stderr_ring_buffer = Some(capacity);
+        self.inner_command.stderr(Stdio::piped());
+
+        self
+    }
+
+    /// `host:port` of a zmq/azmq filter in the filter graph, used by [`FFmpegCommand::send_filter_command`]
+    ///
+    /// The filter itself must be added by you, e.g. `-af azmq=bind_address=tcp\\://127.0.0.1\\:5555,volume=1`
+    pub fn zmq_address(mut self, address: impl Into<String>) -> Self {
+        self.start_options.zmq_address = Some(address.into());
+
+        self
+    }
+
     pub fn stdin(mut self, cfg: impl Into<Stdio>) -> Self {
         self.inner_command.stdin(cfg);
 
@@ -243,85 +1319,323 @@ impl FFmpegBuilder<Normal> {
         self
     }
 
-    pub fn stderr(mut self, cfg: impl Into<Stdio>) -> Self {
-        self.inner_command.stderr(cfg);
+    pub fn stderr(mut self, cfg: impl Into<Stdio>) -> Self {
+        self.inner_command.stderr(cfg);
+
+        self
+    }
+
+    pub fn input_with_pipe(mut self, pipe: &mut Option<Pipe>) -> anyhow::Result<FFmpegBuilder<Input>> {
+        self.inserting_offset = Some(self.inner_args.len());
+        
+        *pipe = Some(Pipe::create_pipe()?);
+
+        self.inner_args.extend(["-i".into(), pipe.as_ref().unwrap().path().into()]);
+
+        Ok(self.into())
+    }
+    
+    pub fn output_with_pipe(mut self, pipe: &mut Option<Pipe>) -> anyhow::Result<FFmpegBuilder<IO>> {
+        self.begin_output();
+        
+        *pipe = Some(Pipe::create_pipe()?);
+
+        self.inner_args.extend(["-y".into(), pipe.as_ref().unwrap().path().into()]);
+
+        Ok(self.into())
+    }
+
+    pub fn input_with_file(mut self, path: PathBuf) -> FFmpegBuilder<Input> {
+        self.inserting_offset = Some(self.inner_args.len());
+
+        self.inner_args.extend(["-i".into(), path.into()]);
+
+        self.into()
+    }
+
+    /// Use generated media as an input, `-f lavfi -i <source>`
+    pub fn input_lavfi(mut self, source: TestSource) -> FFmpegBuilder<Input> {
+        self.inserting_offset = Some(self.inner_args.len());
+
+        self.inner_args.extend(["-f".into(), "lavfi".into(), "-i".into(), source.to_string().into()]);
+
+        self.into()
+    }
+
+    pub fn output_as_file(mut self, path: PathBuf) -> FFmpegBuilder<IO> {
+        self.begin_output();
+
+        self.inner_args.extend(["-y".into(), path.into()]);
+
+        self.into()
+    }
+
+    /// Output to the segment muxer, `pattern` is the name of every segment, e.g. `chunk_%03d.mp4`
+    pub fn output_segments(self, pattern: PathBuf, options: split::SegmentOptions) -> FFmpegBuilder<IO> {
+        self.output_as_file(pattern).args(options.to_args())
+    }
+
+    /// Write `buffer` to a temporary file & use it as an input, the file is deleted when the [`FFmpegCommand`] is dropped
+    pub fn input(mut self, buffer: &[u8]) -> std::io::Result<FFmpegBuilder<Input>> {
+        let mut file = TempOutput::create()?;
+        file.write_all(buffer)?;
+
+        self.inserting_offset = Some(self.inner_args.len());
+
+        self.inner_args.extend(["-i".into(), file.path().into()]);
+        self.start_options.temp_files.push(file);
+
+        Ok(self.into())
+    }
+
+    /// Use `buffer` as an input without a temporary file, it's written to stdin (`-i pipe:0`) by a background thread
+    ///
+    /// Only one input can use stdin, the next ones fall back to [`FFmpegBuilder::input`].
+    /// Formats that need seeking (e.g. mp4 with the index at the end) can't be read from stdin, use [`FFmpegBuilder::input`] for them
+    ///
+    /// Fails if another input already reads stdin, e.g. `-i pipe:0` added by hand
+    ///
+    /// [`FFmpegCommand::stop`] interrupts FFmpeg instead of sending "q", since stdin is taken
+    pub fn input_memory(mut self, buffer: impl Into<Vec<u8>>) -> std::io::Result<FFmpegBuilder<Input>> {
+        if self.start_options.stdin_data.is_some() {
+            return self.input(&buffer.into());
+        }
+
+        if self.has_stdin_input() {
+            return Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, "Stdin is already used by another input"));
+        }
+
+        self.start_options.stdin_data = Some(buffer.into());
+        self.inner_command.stdin(Stdio::piped());
+
+        self.inserting_offset = Some(self.inner_args.len());
+        self.inner_args.extend(["-i".into(), "pipe:0".into()]);
+
+        Ok(self.into())
+    }
+
+    /// Collect this output in memory, it's written to stdout (`pipe:1`) & read by a background thread
+    ///
+    /// The format has to be set with [`FFmpegBuilder::format`] & must not need seeking (e.g. `matroska`, `mpegts` or mp4 with `-movflags frag_keyframe+empty_moov`).
+    /// Fails if stdout is already used by another output or by [`ProgressTransport::Stdout`]
+    pub fn output_to_vec(mut self, output: &mut Option<OutputBuffer>) -> anyhow::Result<FFmpegBuilder<IO>> {
+        if self.start_options.stdout_buffer.is_some() || self.has_stdout_output() {
+            anyhow::bail!("Only one output can be collected from stdout");
+        }
+
+        if self.start_options.progress_transport == ProgressTransport::Stdout {
+            anyhow::bail!("The progress is sent to stdout, the output can't use it");
+        }
+
+        let (buffer, tx) = OutputBuffer::new();
+
+        *output = Some(buffer);
+        self.start_options.stdout_buffer = Some(tx);
+        self.inner_command.stdout(Stdio::piped());
+
+        Ok(self.output_as_file("pipe:1".into()))
+    }
+
+    /// Output to a temporary file, which is deleted when `output` is dropped
+    ///
+    /// The file already exists, so [`FFmpegBuilder::overwrite`] can't be `false`
+    pub fn output(mut self, output: &mut Option<TempOutput>) -> std::io::Result<FFmpegBuilder<IO>> {
+        let temp = TempOutput::create()?;
+        let path = temp.path().to_path_buf();
+
+        *output = Some(temp);
+
+        self.begin_output();
+        
+        self.inner_args.extend(["-y".into(), path.into()]);
+
+        Ok(self.into())
+    }
+
+    /// Add custom argument to FFmpeg process
+    ///
+    /// Must take into consideration of where this argument is located
+    pub fn arg<S: AsRef<OsStr>>(mut self, arg: S) -> Self {
+        self.inner_args.push(arg.as_ref().to_owned());
+        
+        self
+    }
+
+    /// Add custom argument to FFmpeg process
+    ///
+    /// Must take into consideration of where this argument is located
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        for arg in args {
+            self = self.arg(arg);
+        }
+
+        self
+    }
+}
+
+impl FFmpegBuilder<IO> {
+    /// Set format
+    pub fn format(self, format: impl AsRef<str>) -> Self {
+        self.args(["-f", format.as_ref()])
+    }
+
+    /// Set audio codec
+    pub fn codec_audio(self, codec: impl AsRef<str>) -> Self {
+        self.args(["-c:a", codec.as_ref()])
+    }
+
+    /// Set video codec
+    pub fn codec_video(self, codec: impl AsRef<str>) -> Self {
+        self.args(["-c:v", codec.as_ref()])
+    }
+
+    /// Whether this output can be overwritten, it is by default
+    ///
+    /// [`FFmpegBuilder::start`] fails with [`OutputExists`] instead of starting if it can't & already exists
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        let marker = self.inserting_offset.and_then(|at| (at..self.inner_args.len()).find(|index| is_output_marker(&self.inner_args[*index])));
+
+        if let Some(marker) = marker {
+            self.inner_args[marker] = if overwrite { "-y" } else { "-n" }.into();
+        }
 
         self
     }
 
-    pub fn input_with_pipe(mut self, pipe: &mut Option<Pipe>) -> anyhow::Result<FFmpegBuilder<IO>> {
-        self.inserting_offset = Some(self.inner_args.len());
-        
-        *pipe = Some(Pipe::create_pipe()?);
-
-        self.inner_args.extend(["-i".to_string(), pipe.as_ref().unwrap().path().display().to_string()]);
+    /// Stop at the end of the shortest input, `-shortest`, e.g. for an image looped with [`FFmpegBuilder::loop_input`] under music
+    pub fn shortest(self) -> Self {
+        self.arg("-shortest")
+    }
 
-        Ok(self.into())
+    /// Stop writing this output after `duration`, `-t`
+    pub fn limit_duration(self, duration: Duration) -> Self {
+        self.args(["-t".to_string(), duration.as_secs_f64().to_string()])
     }
-    
-    pub fn output_with_pipe(mut self, pipe: &mut Option<Pipe>) -> anyhow::Result<FFmpegBuilder<IO>> {
-        self.inserting_offset = Some(self.inner_args.len());
-        
-        *pipe = Some(Pipe::create_pipe()?);
 
-        self.inner_args.extend(["-y".to_string(), pipe.as_ref().unwrap().path().display().to_string()]);
+    /// Stop writing this output once it reaches `bytes`, `-fs`
+    ///
+    /// The limit is checked before every packet, so the file ends up slightly smaller
+    pub fn limit_size(self, bytes: u64) -> Self {
+        self.args(["-fs".to_string(), bytes.to_string()])
+    }
 
-        Ok(self.into())
+    /// Stop at whichever limit comes first, e.g. a DVR capture of at most an hour or 2 GB
+    pub fn record_until(self, duration: Duration, bytes: u64) -> Self {
+        self.limit_duration(duration).limit_size(bytes)
     }
 
-    pub fn input_with_file(mut self, path: PathBuf) -> FFmpegBuilder<IO> {
-        self.inserting_offset = Some(self.inner_args.len());
+    /// Keep every stream of every input, FFmpeg only picks one video & one audio stream by default
+    ///
+    /// The output container has to support every codec, e.g. mp4 can't hold SubRip subtitles
+    pub fn map_all(self) -> Self {
+        (0..self.inputs_before()).fold(self, |builder, input| builder.args(["-map".to_string(), input.to_string()]))
+    }
 
-        self.inner_args.extend(["-i".to_string(), path.display().to_string()]);
+    /// How many inputs this output can use
+    pub(crate) fn inputs_before(&self) -> usize {
+        let at = self.inserting_offset.unwrap_or(self.inner_args.len());
 
-        self.into()
+        self.inner_args[..at].iter().filter(|arg| *arg == "-i").count()
     }
 
-    pub fn output_as_file(mut self, path: PathBuf) -> FFmpegBuilder<IO> {
-        self.inserting_offset = Some(self.inner_args.len());
-
-        self.inner_args.extend(["-y".to_string(), path.display().to_string()]);
+    /// Add `filter` to the end of the `-vf` chain of this output, e.g. `hflip`
+    pub fn video_filter(self, filter: impl AsRef<str>) -> Self {
+        self.append_filter("-vf", filter.as_ref())
+    }
 
-        self.into()
+    /// Add `filter` to the end of the `-af` chain of this output, e.g. `volume=0.5`
+    pub fn audio_filter(self, filter: impl AsRef<str>) -> Self {
+        self.append_filter("-af", filter.as_ref())
     }
 
-    pub fn input(mut self, buffer: &[u8]) -> std::io::Result<FFmpegBuilder<IO>> {
-        let path = random_temp_file();
+    /// The value of `option` if it was already given to this output, e.g. the `-vf` chain
+    pub(crate) fn output_option(&self, option: &str) -> Option<&OsStr> {
+        self.option_index(option).map(|index| self.inner_args[index + 1].as_os_str())
+    }
 
-        let mut file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
-        file.write(buffer)?;
+    /// FFmpeg only uses the last `option` of an output, so every filter has to be in the same chain
+    fn append_filter(mut self, option: &str, filter: &str) -> Self {
+        match self.option_index(option) {
+            Some(index) => {
+                let chain = &mut self.inner_args[index + 1];
+                chain.push(",");
+                chain.push(filter);
 
-        self.inserting_offset = Some(self.inner_args.len());
+                self
+            },
+            None => self.args([option, filter]),
+        }
+    }
+}
 
-        self.inner_args.extend(["-i".to_string(), path.display().to_string()]);
+impl FFmpegBuilder<Input> {
+    /// Force the input format instead of probing it, e.g. `rawvideo` or `flv`
+    pub fn force_format(mut self, format: impl AsRef<str>) -> Self {
+        let at = self.inserting_offset.unwrap_or(self.inner_args.len());
+        self.inner_args.splice(at..at, ["-f".into(), format.as_ref().into()]);
 
-        Ok(self.into())
+        self
     }
 
-    pub fn output(mut self, file: &mut Option<File>) -> std::io::Result<FFmpegBuilder<IO>> {
-        let path = random_temp_file();
-
-        *file = Some(OpenOptions::new().read(true).write(true).create_new(true).open(&path)?);
+    /// Force the audio decoder, e.g. `libopus` instead of the native `opus` decoder
+    pub fn decoder_audio(self, codec: impl AsRef<str>) -> Self {
+        self.args(["-c:a", codec.as_ref()])
+    }
 
-        self.inserting_offset = Some(self.inner_args.len());
-        
-        self.inner_args.extend(["-y".to_string(), path.display().to_string()]);
+    /// Force the video decoder, e.g. `h264_cuvid`
+    pub fn decoder_video(self, codec: impl AsRef<str>) -> Self {
+        self.args(["-c:v", codec.as_ref()])
+    }
 
-        Ok(self.into())
+    /// Read this input at `multiplier` times its native speed, `-readrate`, e.g. `1.0` to feed a live output in realtime (same as `-re`)
+    ///
+    /// FFmpeg stops reading a piped input while it's ahead, so the writer blocks instead of buffering
+    pub fn read_rate(self, multiplier: f64) -> Self {
+        self.args(["-readrate".to_string(), multiplier.to_string()])
     }
 
-    /// Add custom argument to FFmpeg process
+    /// Play this input again, e.g. a background video or a still image under music with [`FFmpegBuilder::shortest`]
     ///
-    /// Must take into consideration of where this argument is located
+    /// Still images can only loop forever, they're looped with `-loop 1` instead of `-stream_loop`
+    pub fn loop_input(self, count: LoopCount) -> anyhow::Result<Self> {
+        let at = self.inserting_offset.unwrap_or(self.inner_args.len());
+        let path = self.inner_args[at..].iter().position(|arg| arg == "-i").and_then(|index| self.inner_args.get(at + index + 1));
+
+        let image = path
+            .and_then(|path| Path::new(path).extension())
+            .is_some_and(|extension| ["png", "jpg", "jpeg", "bmp", "webp", "tif", "tiff"].iter().any(|image| extension.eq_ignore_ascii_case(image)));
+
+        Ok(match count {
+            LoopCount::Forever if image => self.args(["-loop", "1"]),
+            LoopCount::Repeat(_) if image => anyhow::bail!("Still images can only loop forever, limit the output duration instead"),
+            LoopCount::Forever => self.args(["-stream_loop", "-1"]),
+            LoopCount::Repeat(times) => self.args(["-stream_loop".to_string(), times.to_string()]),
+        })
+    }
+}
+
+/// How many times an input is played again, see [`FFmpegBuilder::loop_input`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopCount {
+    /// Until the output ends, limit it with `-t` or [`FFmpegBuilder::shortest`]
+    Forever,
+    /// Play it `n` more times, `Repeat(1)` plays it twice
+    Repeat(u32),
+}
+
+impl<M: Section> FFmpegBuilder<M> {
     pub fn arg<S: AsRef<OsStr>>(mut self, arg: S) -> Self {
-        self.inner_args.push(arg.as_ref().to_string_lossy().to_string());
+        self.inner_args.insert(self.inserting_offset.unwrap_or(self.inner_args.len()), arg.as_ref().to_owned());
+
+        if let Some(v) = self.inserting_offset.as_mut() { v.add_assign(1) };
         
         self
     }
 
-    /// Add custom argument to FFmpeg process
-    ///
-    /// Must take into consideration of where this argument is located
     pub fn args<I, S>(mut self, args: I) -> Self
     where
         I: IntoIterator<Item = S>,
@@ -333,43 +1647,76 @@ impl FFmpegBuilder<Normal> {
 
         self
     }
-}
 
-impl FFmpegBuilder<IO> {
-    /// Set format
-    pub fn format(mut self, format: impl AsRef<str>) -> Self {
-        let at = self.inserting_offset.unwrap_or(self.inner_args.len());
-        self.inner_args.splice(at..at, ["-f".to_string(), format.as_ref().to_string()]);
+    /// Index of `option` if it was already given to the input or output being configured
+    fn option_index(&self, option: &str) -> Option<usize> {
+        self.option_index_before(self.inserting_offset.unwrap_or(self.inner_args.len()), option)
+    }
 
-        self
+    /// Index of `option` in the options of the input or output whose `-i` or `-y`/`-n` is at `at`
+    fn option_index_before(&self, at: usize, option: &str) -> Option<usize> {
+        // Stop at the previous input or output
+        (0..at)
+            .rev()
+            .take_while(|index| self.inner_args[*index] != "-i" && !is_output_marker(&self.inner_args[*index]))
+            .find(|index| self.inner_args[*index] == option && index + 1 < at)
     }
 
-    /// Set audio codec
-    pub fn codec_audio(self, codec: impl AsRef<str>) -> Self {
-        self.args(["-c:a", codec.as_ref()])
+    /// Whether the options are going to an input, the section is the first `-i` or `-y`/`-n` after the offset
+    fn configuring_input(&self) -> bool {
+        let at = self.inserting_offset.unwrap_or(self.inner_args.len());
+
+        self.inner_args[at..].iter().find(|arg| *arg == "-i" || is_output_marker(arg)).is_some_and(|arg| arg == "-i")
     }
 
-    /// Set video codec
-    pub fn codec_video(self, codec: impl AsRef<str>) -> Self {
-        self.args(["-c:v", codec.as_ref()])
+    /// Add options to the input being configured, or to the last input before the output being configured
+    ///
+    /// Goes to the first input if there's no input yet
+    pub fn input_args<I, S>(self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        if self.configuring_input() { return self.args(args) };
+
+        let at = self.inserting_offset.unwrap_or(self.inner_args.len());
+        let at = self.inner_args[..at].iter().rposition(|arg| arg == "-i").unwrap_or(0);
+
+        self.insert_at(at, args)
     }
 
-    pub fn arg<S: AsRef<OsStr>>(mut self, arg: S) -> Self {
-        self.inner_args.insert(self.inserting_offset.unwrap_or(self.inner_args.len()), arg.as_ref().to_string_lossy().to_string());
+    /// FFmpeg only uses the last `-fflags` of an input, so add `flag` to the one that's already there
+    ///
+    /// Goes to the same input as [`FFmpegBuilder::input_args`]
+    fn add_format_flag(mut self, flag: &str) -> Self {
+        let at = self.inserting_offset.unwrap_or(self.inner_args.len());
 
-        self.inserting_offset.as_mut().map(|v| v.add_assign(1));
-        
-        self
+        let input = match self.configuring_input() {
+            true => Some(at),
+            false => self.inner_args[..at].iter().rposition(|arg| arg == "-i"),
+        };
+
+        match input.and_then(|input| self.option_index_before(input, "-fflags")) {
+            Some(index) => {
+                self.inner_args[index + 1].push(flag);
+
+                self
+            },
+            None => self.input_args(["-fflags", flag]),
+        }
     }
 
-    pub fn args<I, S>(mut self, args: I) -> Self
+    /// Add options to the output being configured
+    ///
+    /// When an input is being configured they're kept until the next output is added, even if more inputs are added first
+    pub fn output_args<I, S>(mut self, args: I) -> Self
     where
         I: IntoIterator<Item = S>,
         S: AsRef<OsStr>,
     {
-        for arg in args {
-            self = self.arg(arg);
-        }
+        if !self.configuring_input() { return self.args(args) };
+
+        self.start_options.pending_output_args.extend(args.into_iter().map(|arg| arg.as_ref().to_owned()));
 
         self
     }
@@ -389,18 +1736,69 @@ pub enum FFmpegDownloadProgress {
     Finished
 }
 
+/// How a download is verified before it's installed, see [`FFmpeg::auto_download_with_checksum`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Checksum {
+    /// The expected SHA-256 of the downloaded file, in hex
+    Sha256(String),
+    /// Fetch the expected SHA-256 from `<url>.sha256`, in the format of `sha256sum`
+    Sidecar,
+}
+
+/// Which binaries [`FFmpeg::auto_download_components`] fetches, e.g. `DownloadComponents::FFMPEG | DownloadComponents::FFPROBE`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DownloadComponents(u8);
+
+impl DownloadComponents {
+    pub const NONE: Self = Self(0);
+    pub const FFMPEG: Self = Self(1);
+    pub const FFPROBE: Self = Self(1 << 1);
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for DownloadComponents {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
 pub struct FFmpeg;
 
 impl FFmpeg {
     /// Uses [`FFmpeg::get_program`] to find the FFmpeg program
     ///
     /// Panic if doesn't exist
+    #[allow(clippy::new_ret_no_self)]
     pub fn new() -> FFmpegBuilder<Normal> {
         let program = Self::get_program().expect("Failed to find FFmpeg").expect("Can't find FFmpeg in your system");
         
         Self::new_with_program(program)
     }
 
+    /// Same as [`FFmpeg::new`], but returns an error instead of panicking
+    pub(crate) fn try_new() -> anyhow::Result<FFmpegBuilder<Normal>> {
+        let program = Self::get_program()?.context("Can't find FFmpeg in your system")?;
+
+        Ok(Self::new_with_program(program))
+    }
+
+    /// Same as [`FFmpeg::try_new`] without the banner, the stats & reading stdin, where the one-shot helpers start
+    pub(crate) fn quiet() -> anyhow::Result<FFmpegBuilder<Normal>> {
+        Ok(Self::try_new()?.args(["-hide_banner", "-nostdin", "-nostats"]))
+    }
+
+    /// Delete every temporary file & FIFO made so far, e.g. buffer inputs, [`TempOutput`] & segment lists, returns how many were deleted
+    ///
+    /// Happens automatically when the process exits normally, files used by running commands are deleted too
+    pub fn cleanup_temp() -> usize {
+        temp::cleanup()
+    }
+
     /// Must provide a valid FFmpeg program path
     pub fn new_with_program<S: AsRef<OsStr>>(program: S) -> FFmpegBuilder<Normal> {
         let mut inner_command = Command::new(program);
@@ -412,8 +1810,9 @@ impl FFmpeg {
         
         FFmpegBuilder {
             inner_command,
-            inner_args: vec![].into(),
+            inner_args: vec![],
             inserting_offset: Some(0),
+            start_options: StartOptions::default(),
             marker: PhantomData
         }
     }
@@ -436,9 +1835,7 @@ impl FFmpeg {
     
                 true
             },
-            Err(err) => match err.kind() {
-                _ => false
-            }
+            Err(_) => false,
         }
     }
 
@@ -453,6 +1850,11 @@ impl FFmpeg {
         Ok(Self::downloaded_ffmpeg_folder()?.join("ffmpeg"))
     }
 
+    /// Downloaded ffprobe executable, next to the downloaded FFmpeg
+    pub fn downloaded_ffprobe_path() -> anyhow::Result<PathBuf> {
+        Ok(Self::downloaded_ffmpeg_folder()?.join("ffprobe"))
+    }
+
     /// Check if FFmpeg is already downloaded
     ///
     /// Doesn't mean that it exist in the current environmant
@@ -479,15 +1881,17 @@ impl FFmpeg {
     /// Returns [`Option::None`] if FFmpeg alredy exist
     ///
     /// It is your responsibility for making sure that the download is succeed & finished!
-    pub fn auto_download() -> impl std::future::Future<Output = anyhow::Result<Option<(JoinHandle<Result<(), anyhow::Error>>, Receiver<FFmpegDownloadProgress>)>>> {
-        FFmpeg::auto_download_with_url(FFMPEG_URL)
+    pub async fn auto_download() -> anyhow::Result<Option<(JoinHandle<Result<(), anyhow::Error>>, Receiver<FFmpegDownloadProgress>)>> {
+        FFmpeg::auto_download_from(&EugenewareSource, DownloadComponents::FFMPEG).await
     }
 
-    /// Downloaded file must be compressed in GZIP archive that contains the single FFmpeg binary
+    /// Downloaded file must be a GZIP compressed FFmpeg binary, or a tar.gz, tar.xz or zip archive with an `ffmpeg` (or `ffmpeg.exe`) in it
     ///
     /// Consider looking at this
     /// https://github.com/eugeneware/ffmpeg-static/releases/tag/b6.0
     ///
+    /// Archives of full builds (e.g. https://github.com/BtbN/FFmpeg-Builds/releases) are searched for the binary, the one closest to the root wins
+    ///
     /// Returns the read channel for listening the download state & the thread handle
     ///
     /// Returns [`Option::None`] if FFmpeg alredy exist
@@ -496,62 +1900,135 @@ impl FFmpeg {
     pub async fn auto_download_with_url(url: &str) -> anyhow::Result<Option<(JoinHandle<Result<(), anyhow::Error>>, Receiver<FFmpegDownloadProgress>)>> {
         if Self::get_program()?.is_some() { return Ok(None) };
 
-        let mut response = reqwest::get(url).await?;
-        let length = response.content_length();
+        download_binaries(url, vec![("ffmpeg", Self::get_program)], None).await.map(Some)
+    }
+
+    /// Same as [`FFmpeg::auto_download_with_url`] but the download is checked against `checksum` first
+    ///
+    /// A corrupted or tampered download fails the returned handle & nothing is installed
+    pub async fn auto_download_with_checksum(url: &str, checksum: Checksum) -> anyhow::Result<Option<(JoinHandle<Result<(), anyhow::Error>>, Receiver<FFmpegDownloadProgress>)>> {
+        if Self::get_program()?.is_some() { return Ok(None) };
+
+        download_binaries(url, vec![("ffmpeg", Self::get_program)], Some(checksum)).await.map(Some)
+    }
 
-        let (progress_tx, progress_rx): (Sender<FFmpegDownloadProgress>, _) = channel(256);
+    /// Same as [`FFmpeg::auto_download_with_url`] but every binary in `components` is extracted from the same archive, e.g. ffmpeg & ffprobe from a BtbN or gyan.dev build
+    ///
+    /// Components that already exist are skipped, returns [`Option::None`] if every one of them exists
+    pub async fn auto_download_archive(url: &str, components: DownloadComponents, checksum: Option<Checksum>) -> anyhow::Result<Option<(JoinHandle<Result<(), anyhow::Error>>, Receiver<FFmpegDownloadProgress>)>> {
+        let missing = download::missing_binaries(components)?;
+        if missing.is_empty() { return Ok(None) };
 
-        let handle = tokio::task::spawn(async move {
-            let mut buffer = Vec::new();
+        download_binaries(url, missing.into_iter().map(|(_, binary)| binary).collect(), checksum).await.map(Some)
+    }
 
-            // SAFETY: we just don't care, this doesn't matter really
-            let _ = progress_tx.send(FFmpegDownloadProgress::Starting).await;
+    /// Same as [`FFmpeg::auto_download`] but also fetches the matching ffprobe with [`DownloadComponents::FFPROBE`]
+    ///
+    /// Components that already exist are skipped, returns [`Option::None`] if every one of them exists.
+    /// They're downloaded one after another, so [`FFmpegDownloadProgress::Downloading`] starts from 0 again for every component
+    pub async fn auto_download_components(components: DownloadComponents) -> anyhow::Result<Option<(JoinHandle<Result<(), anyhow::Error>>, Receiver<FFmpegDownloadProgress>)>> {
+        FFmpeg::auto_download_from(&EugenewareSource, components).await
+    }
+}
 
-            let mut downloaded = 0;
-            while let Some(chunk) = response.chunk().await? {
-                downloaded += chunk.len();
-                buffer.extend(chunk);
+/// A binary to install from a download, its name & how to check that it's usable afterwards
+pub(crate) type DownloadedBinary = (&'static str, fn() -> anyhow::Result<Option<String>>);
 
-                let length = match length {
-                    Some(length) => Some(((downloaded as f32 / length as f32) * 100.0) as usize),
-                    None => None,
-                };
+/// Download `url` & install `binaries` from it into [`FFmpeg::downloaded_ffmpeg_folder`]
+///
+/// The download can be a gzip of a single binary, a tar.gz, a tar.xz or a zip, it's checked against `checksum` before it's extracted
+pub(crate) async fn download_binaries(url: &str, binaries: Vec<DownloadedBinary>, checksum: Option<Checksum>) -> anyhow::Result<(JoinHandle<Result<(), anyhow::Error>>, Receiver<FFmpegDownloadProgress>)> {
+    // Fetched first, so a missing sidecar fails before anything is downloaded
+    let expected = match checksum {
+        Some(Checksum::Sha256(digest)) => Some(digest),
+        Some(Checksum::Sidecar) => {
+            let sidecar = reqwest::get(format!("{url}.sha256")).await?.error_for_status()?.text().await?;
 
-                // SAFETY: we just don't care, this doesn't matter really
-                let _ = progress_tx.send(FFmpegDownloadProgress::Downloading(length)).await;
-            }
+            Some(parse_sha256_sidecar(&sidecar)?)
+        },
+        None => None,
+    };
+
+    let mut response = reqwest::get(url).await?;
+    let length = response.content_length();
+
+    let (progress_tx, progress_rx): (Sender<FFmpegDownloadProgress>, _) = channel(256);
+
+    let handle = tokio::task::spawn(async move {
+        let mut buffer = Vec::new();
+
+        // SAFETY: we just don't care, this doesn't matter really
+        let _ = progress_tx.send(FFmpegDownloadProgress::Starting).await;
+
+        let mut downloaded = 0;
+        while let Some(chunk) = response.chunk().await? {
+            downloaded += chunk.len();
+            buffer.extend(chunk);
+
+            let length = length.map(|length| ((downloaded as f32 / length as f32) * 100.0) as usize);
 
             // SAFETY: we just don't care, this doesn't matter really
-            let _ = progress_tx.send(FFmpegDownloadProgress::Extracting).await;
+            let _ = progress_tx.send(FFmpegDownloadProgress::Downloading(length)).await;
+        }
+
+        // SAFETY: we just don't care, this doesn't matter really
+        let _ = progress_tx.send(FFmpegDownloadProgress::Extracting).await;
 
-            let mut gz = GzDecoder::new(Cursor::new(buffer));
+        let names = binaries.iter().map(|(name, _)| *name).collect::<Vec<_>>();
 
-            let mut binary = Vec::new();
-            gz.read_to_end(&mut binary)?;
+        if let Some(expected) = expected {
+            verify_sha256(&buffer, &expected).with_context(|| format!("Refusing to install {}", names.join(" & ")))?;
+        }
+
+        let extracted = archive::extract_binaries(&buffer, &names)?;
 
-            let output_path = Self::downloaded_ffmpeg_folder()?;
-            std::fs::create_dir_all(&output_path)?;
+        let output_path = FFmpeg::downloaded_ffmpeg_folder()?;
+        std::fs::create_dir_all(&output_path)?;
 
-            let ffmpeg_path = output_path.join("ffmpeg");
-            std::fs::write(&ffmpeg_path, binary)?;
+        for ((name, get_program), binary) in binaries.into_iter().zip(extracted) {
+            let binary_path = output_path.join(name);
+            std::fs::write(&binary_path, binary)?;
 
-            #[cfg(all(target_family = "unix"))]
+            #[cfg(target_family = "unix")]
             {
                 use std::os::unix::fs::PermissionsExt;
-                
-                std::fs::set_permissions(ffmpeg_path, std::fs::Permissions::from_mode(0o755))?;
+
+                std::fs::set_permissions(binary_path, std::fs::Permissions::from_mode(0o755))?;
             }
 
-            Self::get_program()?.context("Failed to download FFmpeg")?;
+            get_program()?.with_context(|| format!("Failed to download {name}"))?;
+        }
+
+        // SAFETY: we just don't care, this doesn't matter really
+        let _ = progress_tx.send(FFmpegDownloadProgress::Finished).await;
 
-            // SAFETY: we just don't care, this doesn't matter really
-            let _ = progress_tx.send(FFmpegDownloadProgress::Finished).await;
+        Ok::<(), anyhow::Error>(())
+    });
+
+    Ok((handle, progress_rx))
+}
+
+/// The digest of a `.sha256` file, either just the digest or `<digest>  <file name>` lines of `sha256sum`
+fn parse_sha256_sidecar(sidecar: &str) -> anyhow::Result<String> {
+    let digest = sidecar.split_whitespace().next().context("The checksum file is empty")?;
+
+    if digest.len() != 64 || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("{digest:?} isn't a SHA-256 digest");
+    }
+
+    Ok(digest.to_string())
+}
+
+fn verify_sha256(data: &[u8], expected: &str) -> anyhow::Result<()> {
+    use sha2::{Digest, Sha256};
 
-            Ok::<(), anyhow::Error>(())
-        });
+    let actual = format!("{:x}", Sha256::digest(data));
 
-        Ok(Some((handle, progress_rx)))
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        anyhow::bail!("SHA-256 mismatch, expected {} but the download is {actual}", expected.trim());
     }
+
+    Ok(())
 }
 
 pub(crate) fn random_string() -> String {
@@ -562,8 +2039,360 @@ pub(crate) fn random_string() -> String {
         .collect()
 }
 
+/// A new path in the temporary directory, deleted by [`FFmpeg::cleanup_temp`] or when the process exits
 pub(crate) fn random_temp_file() -> PathBuf {
     let name: String = random_string();
+    let path = temp_dir().join(name);
+
+    temp::register(&path);
+
+    path
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn positional_insertion() -> anyhow::Result<()> {
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("a.mp4".into())
+            .done()
+            .input_with_file("b.mp4".into())
+            .done()
+            .output_as_file("out.mkv".into())
+            .codec_video("libx264");
+
+        assert_eq!(builder.last_input(), Some(InputHandle(1)));
+
+        let output = builder.last_output().context("No output")?;
+        let builder = builder
+            .insert_global_args(["-loglevel", "error"])
+            .insert_before_input(InputHandle(0), ["-itsoffset", "2"])?
+            .insert_into_output(output, ["-movflags", "+faststart"])?
+            // Still goes to the output that was being configured
+            .codec_audio("aac")
+            .done();
+
+        assert_eq!(builder.inner_args, [
+            "-loglevel", "error", "-itsoffset", "2", "-i", "a.mp4", "-i", "b.mp4",
+            "-c:v", "libx264", "-movflags", "+faststart", "-c:a", "aac", "-y", "out.mkv",
+        ]);
+        assert!(builder.insert_into_output(OutputHandle(1), ["-an"]).is_err());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_paths() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = OsStr::from_bytes(b"caf\xe9.mp4");
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file(path.into())
+            .done()
+            .arg(path)
+            .output_as_file(path.into())
+            .done();
+
+        assert_eq!(builder.inner_args, [OsStr::new("-i"), path, path, OsStr::new("-y"), path]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stop_interrupts_without_stdin() -> anyhow::Result<()> {
+        use std::os::unix::process::ExitStatusExt;
+
+        let mut command = FFmpeg::new_with_program("sleep").arg("30").stdin(Stdio::null()).start()?;
+
+        assert!(command.send_key(InteractiveCommand::Quit).is_err());
+
+        command.request_quit()?;
+        let status = command.wait_timeout(Duration::from_secs(5))?.context("sleep wasn't interrupted")?;
+
+        assert_eq!(status.signal(), Some(nix::sys::signal::Signal::SIGINT as i32));
+
+        let started = Instant::now();
+        FFmpeg::new_with_program("sleep").arg("30").stdin(Stdio::null()).start()?.stop()?;
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn collect_non_utf8_stderr() -> anyhow::Result<()> {
+        let (status, stderr) = FFmpeg::new_with_program("sh").args(["-c", "printf 'title: caf\\351\\n' >&2"]).run_collect_stderr()?;
+
+        assert!(status.success());
+        assert_eq!(stderr, "title: caf\u{FFFD}\n");
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn progress_fifo_released_on_exit() -> anyhow::Result<()> {
+        struct FinishSink(Arc<AtomicBool>);
+
+        impl sink::ProgressSink for FinishSink {
+            fn on_progress(&mut self, _progress: FFmpegProgress) {}
+
+            fn on_finish(&mut self, _completed: bool) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let finished = Arc::new(AtomicBool::new(false));
+
+        // Exits without ever opening -progress, like FFmpeg failing to connect to its input
+        let mut command = FFmpeg::new_with_program("false").stdin(Stdio::null()).start_with_progress_sink(FinishSink(finished.clone()))?;
+        let fifo = command.progress_reader.as_ref().and_then(|reader| reader.fifo.clone()).context("There's no progress FIFO")?;
+
+        assert!(!command.wait()?.success());
+        assert!(finished.load(Ordering::SeqCst));
+        assert!(!fifo.exists());
+
+        Ok(())
+    }
 
-    temp_dir().join(name)
+    #[cfg(unix)]
+    #[test]
+    fn stop_with_timeout_escalation() -> anyhow::Result<()> {
+        // The traps are inherited by the exec'd sleep, so it ignores the signals the same way
+        let start = |ignored: &str| -> anyhow::Result<FFmpegCommand> {
+            let command = FFmpeg::new_with_program("sh")
+                .args(["-c", &format!("trap '' {ignored}; exec sleep 30")])
+                .stdin(Stdio::null())
+                .start()?;
+
+            // Give the shell time to set up the traps
+            std::thread::sleep(Duration::from_millis(300));
+
+            Ok(command)
+        };
+
+        let timeout = Duration::from_millis(300);
+
+        let (outcome, _) = FFmpeg::new_with_program("sleep").arg("30").stdin(Stdio::null()).start()?.stop_with_timeout(timeout)?;
+        assert_eq!(outcome, StopOutcome::Quit);
+
+        let (outcome, _) = start("INT")?.stop_with_timeout(timeout)?;
+        assert_eq!(outcome, StopOutcome::Terminated);
+
+        let started = Instant::now();
+        let (outcome, status) = start("INT TERM")?.stop_with_timeout(timeout)?;
+
+        assert_eq!(outcome, StopOutcome::Killed);
+        assert!(!status.success());
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        Ok(())
+    }
+
+    #[test]
+    fn memory_inputs() -> anyhow::Result<()> {
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_memory(b"first".to_vec())?
+            .force_format("mpegts")
+            .done()
+            .input_memory(b"second".to_vec())?
+            .done();
+
+        assert_eq!(builder.inner_args[..4], ["-f", "mpegts", "-i", "pipe:0"]);
+        assert_eq!(builder.start_options.stdin_data.as_deref(), Some(b"first".as_slice()));
+
+        // The second one doesn't fit into stdin, it's written to a file instead
+        assert_eq!(builder.inner_args[4], "-i");
+        assert!(Path::new(&builder.inner_args[5]).is_file());
+
+        let taken = FFmpeg::new_with_program("ffmpeg").input_with_file("-".into()).done();
+        assert_eq!(taken.input_memory(b"data".to_vec()).err().map(|error| error.kind()), Some(std::io::ErrorKind::AlreadyExists));
+
+        Ok(())
+    }
+
+    #[test]
+    fn vec_outputs() -> anyhow::Result<()> {
+        let mut buffer = None;
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("in.mp4".into())
+            .done()
+            .output_to_vec(&mut buffer)?
+            .format("matroska")
+            .done();
+
+        assert!(buffer.is_some());
+        assert_eq!(builder.inner_args[2..], ["-f", "matroska", "-y", "pipe:1"]);
+        assert!(builder.output_to_vec(&mut None).is_err());
+
+        let piped = FFmpeg::new_with_program("ffmpeg").output_as_file("-".into()).done();
+        assert!(piped.output_to_vec(&mut None).is_err());
+
+        let progress = FFmpeg::new_with_program("ffmpeg").progress_transport(ProgressTransport::Stdout);
+        assert!(progress.output_to_vec(&mut None).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn output_overwrite() -> anyhow::Result<()> {
+        let existing = random_temp_file();
+        std::fs::write(&existing, [])?;
+
+        let mut builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("a.mp4".into())
+            .done()
+            .output_as_file(existing.clone())
+            .overwrite(false)
+            .codec_video("libx264")
+            .done()
+            .output_as_file("out.mp4".into())
+            .done();
+
+        assert_eq!(builder.inner_args[2..5], ["-c:v", "libx264", "-n"]);
+        assert_eq!(builder.last_output(), Some(OutputHandle(1)));
+
+        let error = builder.start().err().unwrap();
+        assert_eq!(error.downcast_ref::<OutputExists>().map(|error| error.path.as_path()), Some(existing.as_path()));
+
+        let _ = std::fs::remove_file(&existing);
+
+        // -f comes before the marker
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .output_as_file("out.mp4".into())
+            .format("mp4")
+            .overwrite(false)
+            .codec_video("copy")
+            .done();
+
+        assert_eq!(builder.inner_args, ["-f", "mp4", "-c:v", "copy", "-n", "out.mp4"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn looped_inputs() -> anyhow::Result<()> {
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("cover.JPG".into())
+            .loop_input(LoopCount::Forever)?
+            .done()
+            .input_with_file("song.mp3".into())
+            .loop_input(LoopCount::Repeat(2))?
+            .done()
+            .output_as_file("out.mp4".into())
+            .shortest()
+            .done();
+
+        assert_eq!(builder.inner_args, [
+            "-loop", "1", "-i", "cover.JPG", "-stream_loop", "2", "-i", "song.mp3", "-shortest", "-y", "out.mp4",
+        ]);
+
+        let image = FFmpeg::new_with_program("ffmpeg").input_with_file("cover.png".into());
+        assert!(image.loop_input(LoopCount::Repeat(1)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn output_limits() {
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("rtmp://live/stream".into())
+            .done()
+            .output_as_file("dvr.ts".into())
+            .record_until(Duration::from_secs(3600), 2_000_000_000)
+            .done();
+
+        assert_eq!(builder.inner_args[2..], ["-t", "3600", "-fs", "2000000000", "-y", "dvr.ts"]);
+    }
+
+    #[test]
+    fn section_args() {
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_lavfi(TestSource::Custom("testsrc".to_string()))
+            .output_args(["-t", "5"])
+            .input_args(["-re"])
+            .done()
+            .output_as_file("out.mp4".into())
+            .input_args(["-stream_loop", "-1"])
+            .output_args(["-c:v", "libx264"])
+            .done();
+
+        assert_eq!(builder.inner_args, [
+            "-re", "-f", "lavfi", "-stream_loop", "-1", "-i", "testsrc",
+            "-t", "5", "-c:v", "libx264", "-y", "out.mp4",
+        ]);
+    }
+
+    #[test]
+    fn progress_estimate() {
+        let mut progress = FFmpegProgress::from("out_time_us=30000000\nspeed=2.0x\nprogress=continue".to_string());
+        progress.estimate(Duration::from_secs(120));
+
+        assert_eq!(progress.percent, Some(25.0));
+        assert_eq!(progress.eta, Some(Duration::from_secs(45)));
+
+        let mut progress = FFmpegProgress::from("out_time_us=119000000\nprogress=end".to_string());
+        progress.estimate(Duration::from_secs(120));
+
+        assert_eq!(progress.percent, Some(100.0));
+    }
+
+    #[test]
+    fn progress_transports() -> anyhow::Result<()> {
+        let builder = || FFmpeg::new_with_program("ffmpeg").input_with_file("in.mp4".into()).done().output_as_file("out.mkv".into()).done();
+
+        let mut named_pipe = builder();
+        let ProgressSource::NamedPipe(pipe) = named_pipe.progress_source()? else { panic!("Not a named pipe") };
+        assert_eq!(named_pipe.inner_args[4..], [OsStr::new("-progress"), pipe.path().as_os_str()]);
+
+        let mut stdout = builder().progress_transport(ProgressTransport::Stdout);
+        assert!(matches!(stdout.progress_source()?, ProgressSource::Stdout));
+        assert_eq!(stdout.inner_args[4..], ["-progress", "pipe:1"]);
+
+        let mut file = builder().progress_transport(ProgressTransport::File);
+        let ProgressSource::File(path) = file.progress_source()? else { panic!("Not a file") };
+        assert_eq!(file.inner_args[4..], [OsStr::new("-progress"), path.as_os_str()]);
+        assert!(path.is_file());
+
+        Ok(())
+    }
+
+    #[test]
+    fn stdout_progress_conflict() {
+        for stdout in ["-", "pipe:", "pipe:1", "/dev/stdout"] {
+            let mut builder = FFmpeg::new_with_program("ffmpeg")
+                .input_with_file("in.mp4".into())
+                .done()
+                .output_as_file(stdout.into())
+                .format("matroska")
+                .done()
+                .progress_transport(ProgressTransport::Stdout);
+
+            assert!(builder.progress_source().is_err(), "{stdout} isn't detected as stdout");
+        }
+
+        let mut builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("pipe:".into())
+            .done()
+            .output_as_file("out.mkv".into())
+            .done()
+            .progress_transport(ProgressTransport::Stdout);
+
+        assert!(builder.progress_source().is_ok());
+    }
+
+    #[test]
+    fn sha256_checksum() {
+        let digest = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+        assert!(verify_sha256(b"hello", digest).is_ok());
+        assert!(verify_sha256(b"hello", &digest.to_uppercase()).is_ok());
+        assert!(verify_sha256(b"hell0", digest).is_err());
+
+        assert_eq!(parse_sha256_sidecar(&format!("{digest}  ffmpeg-linux-x64.gz\n")).unwrap(), digest);
+        assert!(parse_sha256_sidecar("Not Found").is_err());
+    }
 }
@@ -1,4 +1,4 @@
-use std::{any::Any, io, path::{Path, PathBuf}};
+use std::{any::Any, io, path::{Path, PathBuf}, sync::{atomic::{AtomicBool, Ordering}, Arc}, time::{Duration, Instant}};
 
 #[cfg(windows)]
 mod windows;
@@ -32,6 +32,105 @@ impl Pipe {
     pub fn path(&self) -> &Path {
         self.path.as_path()
     }
+
+    /// Same as [`Piped::listen`], but only for reading, so the reader gets EOF once the writer is gone
+    pub(crate) fn listen_reader(self) -> anyhow::Result<impl io::Read> {
+        #[cfg(unix)]
+        return Ok(std::fs::File::open(self.path)?);
+
+        #[cfg(windows)]
+        return self.listen();
+    }
+}
+
+/// Unblocks [`Pipe::listen_reader`] when nobody is going to open the other end, e.g. FFmpeg exited before opening `-progress`
+///
+/// The reader gets EOF right away, unless the other end is already open
+pub(crate) fn release_reader(path: &Path) {
+    let _ = Pipe::connect_pipe_with_path(path);
+}
+
+/// Reads a file that's still being written, like `tail -f`, until `exited` is set by whoever waits for the writer
+pub(crate) struct TailReader {
+    file: std::fs::File,
+    exited: Arc<AtomicBool>,
+}
+
+impl TailReader {
+    pub(crate) fn open(path: &Path, exited: Arc<AtomicBool>) -> io::Result<Self> {
+        Ok(Self { file: std::fs::File::open(path)?, exited })
+    }
+}
+
+impl io::Read for TailReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let alive = !self.exited.load(Ordering::SeqCst);
+
+            match self.file.read(buf)? {
+                // Read once more after the process is gone, it might have written something since
+                0 if alive => std::thread::sleep(Duration::from_millis(100)),
+                len => return Ok(len),
+            }
+        }
+    }
+}
+
+/// Limits how fast a producer writes into a pipe, for inputs that can't be paced with [`crate::FFmpegBuilder::read_rate`] (e.g. raw frames)
+///
+/// Writes block until they fit in the rate, the first `burst` bytes aren't limited so FFmpeg can probe the input
+pub struct PacedWriter<W> {
+    inner: W,
+    bytes_per_second: u64,
+    burst: u64,
+    written: u64,
+    started: Option<Instant>,
+}
+
+impl<W: io::Write> PacedWriter<W> {
+    pub fn new(inner: W, bytes_per_second: u64) -> Self {
+        Self { inner, bytes_per_second: bytes_per_second.max(1), burst: 0, written: 0, started: None }
+    }
+
+    pub fn burst(mut self, bytes: u64) -> Self {
+        self.burst = bytes;
+
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// How long to wait before writing `len` more bytes
+    fn delay(&self, now: Instant, len: u64) -> Duration {
+        let Some(started) = self.started else { return Duration::ZERO };
+
+        let due = (self.written + len).saturating_sub(self.burst) as f64 / self.bytes_per_second as f64;
+
+        Duration::from_secs_f64(due).saturating_sub(now.duration_since(started))
+    }
+}
+
+impl<W: io::Write> io::Write for PacedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let now = Instant::now();
+        self.started.get_or_insert(now);
+
+        // Small writes so a big buffer doesn't go out all at once after the wait
+        let len = buf.len().min((self.bytes_per_second / 10).max(1) as usize);
+
+        std::thread::sleep(self.delay(now, len as u64));
+
+        let written = self.inner.write(&buf[..len])?;
+        self.written += written as u64;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
 }
 
 #[cfg(windows)]
@@ -74,7 +173,10 @@ impl Piped for Pipe {
 #[cfg(unix)]
 impl Piped for Pipe {
     fn create_pipe_with_name(name: String) -> anyhow::Result<Self> {
-        Self::create_pipe_with_path(std::env::temp_dir().join(format!("{name}.pipe")))
+        let path = std::env::temp_dir().join(format!("{name}.pipe"));
+        crate::temp::register(&path);
+
+        Self::create_pipe_with_path(path)
     }
 
     /// Will try to delete the file in path if it exists
@@ -134,7 +236,7 @@ mod test {
                 reader.read_exact(&mut buffer_test_data)?;
                 assert_eq!(static_test_data.as_bytes(), &buffer_test_data);
     
-                let mut buffer_random_test_data = Vec::new();
+                let mut buffer_random_test_data = Vec::<u8>::new();
 
                 loop {
                     let mut buffer = [0u8; 64];
@@ -154,11 +256,50 @@ mod test {
 
         let mut writer = Pipe::connect_pipe_with_name(pipe_name.clone())?;
 
-        writer.write(static_test_data.as_bytes())?;
-        writer.write(random_test_data.as_bytes())?;
+        writer.write_all(static_test_data.as_bytes())?;
+        writer.write_all(random_test_data.as_bytes())?;
 
         task.join().unwrap()?;
 
         Ok(())
     }
+
+    #[test]
+    fn paced_writing() -> io::Result<()> {
+        let mut writer = PacedWriter::new(Vec::new(), 10_000).burst(1_000);
+
+        let started = Instant::now();
+        writer.write_all(&[0u8; 3_000])?;
+
+        // 2000 bytes over the burst at 10 kB/s
+        assert!(started.elapsed() >= Duration::from_millis(190));
+        assert_eq!(writer.into_inner().len(), 3_000);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tail_until_exited() -> anyhow::Result<()> {
+        let path = crate::random_temp_file();
+        let mut file = std::fs::File::create(&path)?;
+        file.write_all(b"frame=1\n")?;
+
+        let exited = Arc::new(AtomicBool::new(false));
+        let mut tail = TailReader::open(&path, exited.clone())?;
+
+        let task = std::thread::spawn(move || {
+            let mut contents = String::new();
+            tail.read_to_string(&mut contents).map(|_| contents)
+        });
+
+        std::thread::sleep(Duration::from_millis(200));
+        file.write_all(b"progress=end\n")?;
+        exited.store(true, Ordering::SeqCst);
+
+        assert_eq!(task.join().unwrap()?, "frame=1\nprogress=end\n");
+
+        crate::temp::remove(&path);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,215 @@
+//! Everything related to ffplay
+
+use std::{ffi::OsStr, path::PathBuf, process::{Child, ChildStdin, Command, ExitStatus, Stdio}};
+
+use anyhow::Context;
+use tokio::{sync::mpsc::Receiver, task::JoinHandle};
+
+use crate::{download_binaries, FFmpeg, FFmpegBuilder, FFmpegCommand, FFmpegDownloadProgress, Normal, IO};
+
+pub struct FFplay;
+
+/// Options of the ffplay window
+#[derive(Debug, Clone)]
+pub struct PlayOptions {
+    pub title: Option<String>,
+    /// Window size, the video size if [`Option::None`]
+    pub size: Option<(u32, u32)>,
+    /// Close the window when the playback ends
+    pub auto_exit: bool,
+    /// Disable buffering, for live inputs like [`FFplay::play_command`]
+    pub low_latency: bool,
+}
+
+impl Default for PlayOptions {
+    fn default() -> Self {
+        Self {
+            title: None,
+            size: None,
+            auto_exit: true,
+            low_latency: false,
+        }
+    }
+}
+
+impl PlayOptions {
+    fn to_args(&self) -> Vec<String> {
+        let mut args = vec!["-hide_banner".to_string(), "-loglevel".to_string(), "error".to_string()];
+
+        if let Some(title) = &self.title {
+            args.extend(["-window_title".to_string(), title.clone()]);
+        }
+
+        if let Some((width, height)) = self.size {
+            args.extend(["-x".to_string(), width.to_string(), "-y".to_string(), height.to_string()]);
+        }
+
+        if self.auto_exit {
+            args.push("-autoexit".to_string());
+        }
+
+        if self.low_latency {
+            args.extend(["-fflags", "nobuffer", "-flags", "low_delay", "-framedrop"].map(String::from));
+        }
+
+        args
+    }
+}
+
+/// A running ffplay, killed when dropped
+pub struct Playback {
+    child: Child,
+}
+
+impl Playback {
+    /// Where the input is written to for [`FFplay::play_pipe`]
+    pub fn take_stdin(&mut self) -> Option<ChildStdin> {
+        self.child.stdin.take()
+    }
+
+    /// Wait until the window is closed or the playback ends with [`PlayOptions::auto_exit`]
+    pub fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        self.child.wait()
+    }
+
+    pub fn kill(&mut self) -> std::io::Result<()> {
+        self.child.kill()
+    }
+}
+
+impl Drop for Playback {
+    fn drop(&mut self) {
+        if matches!(self.child.try_wait(), Ok(None)) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+impl FFplay {
+    /// Check if ffplay is exist in the current environment
+    pub fn is_exist_in_env() -> bool {
+        Command::new("ffplay")
+            .arg("-version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok()
+    }
+
+    /// Downloaded ffplay executable, next to the downloaded FFmpeg
+    pub fn downloaded_ffplay_path() -> anyhow::Result<PathBuf> {
+        Ok(FFmpeg::downloaded_ffmpeg_folder()?.join("ffplay"))
+    }
+
+    /// Get the program string that can be used for [`Command::new`]
+    pub fn get_program() -> anyhow::Result<Option<String>> {
+        if Self::is_exist_in_env() { return Ok(Some("ffplay".to_string())) };
+
+        let path = Self::downloaded_ffplay_path()?;
+
+        Ok(path.exists().then(|| path.display().to_string()))
+    }
+
+    /// Same as [`FFmpeg::auto_download_with_url`] but for ffplay, there's no default URL because the static FFmpeg builds don't have ffplay
+    ///
+    /// Returns [`Option::None`] if ffplay alredy exist
+    pub async fn auto_download_with_url(url: &str) -> anyhow::Result<Option<(JoinHandle<Result<(), anyhow::Error>>, Receiver<FFmpegDownloadProgress>)>> {
+        if Self::get_program()?.is_some() { return Ok(None) };
+
+        download_binaries(url, vec![("ffplay", Self::get_program)], None).await.map(Some)
+    }
+
+    fn spawn(options: &PlayOptions, input_args: &[&OsStr], stdin: Stdio) -> anyhow::Result<Playback> {
+        let program = Self::get_program()?.context("Can't find ffplay in your system")?;
+
+        let child = Command::new(program)
+            .args(options.to_args())
+            .args(input_args)
+            .stdin(stdin)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        Ok(Playback { child })
+    }
+
+    /// Play a file or URL
+    pub fn play_file(input: impl AsRef<OsStr>, options: &PlayOptions) -> anyhow::Result<Playback> {
+        Self::spawn(options, &[input.as_ref()], Stdio::null())
+    }
+
+    /// Play whatever is written to [`Playback::take_stdin`], `format` is needed for formats that can't be probed (e.g. raw video)
+    pub fn play_pipe(format: Option<&str>, options: &PlayOptions) -> anyhow::Result<Playback> {
+        let mut args: Vec<&OsStr> = Vec::new();
+
+        if let Some(format) = format {
+            args.extend([OsStr::new("-f"), OsStr::new(format)]);
+        }
+
+        args.push(OsStr::new("pipe:0"));
+
+        Self::spawn(options, &args, Stdio::piped())
+    }
+
+    /// Play the stdout of a running FFmpeg command, which has to be written in a streamable format (e.g. `-f nut pipe:1`)
+    ///
+    /// The stdout is taken from `command`, so it can't be read by anything else
+    pub fn play_command(command: &mut FFmpegCommand, options: &PlayOptions) -> anyhow::Result<Playback> {
+        let stdout = command.take_stdout().context("The stdout of the FFmpeg command isn't piped or was already taken")?;
+
+        Self::spawn(options, &[OsStr::new("pipe:0")], Stdio::from(stdout))
+    }
+
+    /// Start `builder` & show what it's rendering, the output has to be made with [`FFmpegBuilder::output_with_preview`]
+    pub fn preview(builder: FFmpegBuilder<Normal>, options: &PlayOptions) -> anyhow::Result<(FFmpegCommand, Playback)> {
+        let mut command = builder.stdout(Stdio::piped()).start()?;
+        let playback = Self::play_command(&mut command, &PlayOptions { low_latency: true, ..options.clone() })?;
+
+        Ok((command, playback))
+    }
+}
+
+/// Escape the special characters of a tee muxer output
+fn escape_tee(output: &str) -> String {
+    output.chars().fold(String::with_capacity(output.len()), |mut escaped, c| {
+        if matches!(c, '\\' | '|' | '[' | ']') {
+            escaped.push('\\');
+        }
+
+        escaped.push(c);
+        escaped
+    })
+}
+
+impl FFmpegBuilder<Normal> {
+    /// Write to `path` & to stdout at the same time for a live preview, see [`FFplay::preview`]
+    ///
+    /// Uses the tee muxer, so the streams are only encoded once & have to be selected with `-map`, e.g. `-map 0:v -map 0:a?`
+    ///
+    /// The encoding keeps going if the preview is closed
+    pub fn output_with_preview(self, path: PathBuf) -> FFmpegBuilder<IO> {
+        let outputs = format!("{}|[f=nut:onfail=ignore]pipe:1", escape_tee(&path.display().to_string()));
+
+        self
+            // mp4 & friends need the codec headers out of band, which nut is fine with
+            .args(["-f", "tee", "-flags", "+global_header"])
+            .output_as_file(PathBuf::from(outputs))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tee_preview_output() {
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("in.mkv".into())
+            .done()
+            .output_with_preview(r"C:\renders\[final] a|b.mp4".into())
+            .done();
+
+        assert_eq!(builder.inner_args[2..], ["-f", "tee", "-flags", "+global_header", "-y", r"C:\\renders\\\[final\] a\|b.mp4|[f=nut:onfail=ignore]pipe:1"]);
+    }
+}
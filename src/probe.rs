@@ -0,0 +1,163 @@
+//! Everything related to ffprobe
+
+use std::{collections::HashMap, ffi::OsStr, process::{Command, Stdio}};
+
+use anyhow::Context;
+
+use crate::{stream::StreamSelector, FFmpeg, FFmpegBuilder, Normal, IO};
+
+mod info;
+pub mod schema;
+
+pub use info::{AudioInfo, MediaInfo, StreamInfo, StreamKind, VideoInfo};
+
+pub struct FFprobe;
+
+impl FFprobe {
+    /// Check if ffprobe is exist in the current environment
+    pub fn is_exist_in_env() -> bool {
+        Command::new("ffprobe")
+            .arg("-version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok()
+    }
+
+    /// Get the program string that can be used for [`Command::new`]
+    ///
+    /// Looks in the current environment first, then next to the downloaded FFmpeg
+    pub fn get_program() -> anyhow::Result<Option<String>> {
+        if Self::is_exist_in_env() { return Ok(Some("ffprobe".to_string())) };
+
+        let path = FFmpeg::downloaded_ffprobe_path()?;
+
+        Ok(path.exists().then(|| path.display().to_string()))
+    }
+
+    /// Run ffprobe with the format, streams & chapters sections & deserialize its output as is
+    pub fn probe_raw(input: impl AsRef<OsStr>) -> anyhow::Result<schema::FFprobeOutput> {
+        Self::run(["-show_format", "-show_streams", "-show_chapters"], input)
+    }
+
+    /// Typed info about the container & every stream of `input`
+    pub fn probe(input: impl AsRef<OsStr>) -> anyhow::Result<MediaInfo> {
+        Ok(Self::run(["-show_format", "-show_streams"], input)?.into())
+    }
+
+    /// The language tag of every stream of `input`, [`Option::None`] if it's untagged or `und`
+    ///
+    /// The selectors are the same for an output that maps every stream, e.g. with [`crate::FFmpegBuilder::map_all`]
+    pub fn stream_languages(input: impl AsRef<OsStr>) -> anyhow::Result<Vec<(StreamSelector, Option<String>)>> {
+        Ok(stream_languages(&Self::run(["-show_streams"], input)?.streams))
+    }
+
+    /// Run ffprobe with `args` (e.g. `-show_frames`) & deserialize its json output
+    pub(crate) fn run<I, S>(args: I, input: impl AsRef<OsStr>) -> anyhow::Result<schema::FFprobeOutput>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let program = Self::get_program()?.context("Can't find ffprobe in your system")?;
+
+        let output = Command::new(program)
+            .args(["-v", "error", "-of", "json"])
+            .args(args)
+            .arg(input)
+            .stdin(Stdio::null())
+            .output()?;
+
+        if !output.status.success() {
+            anyhow::bail!("ffprobe exited with {}\n{}", output.status, String::from_utf8_lossy(&output.stderr).trim());
+        }
+
+        Ok(serde_json::from_slice(&output.stdout)?)
+    }
+}
+
+impl FFmpegBuilder<IO> {
+    /// Probe the first input & let `build` pick the options of this output from what it contains
+    ///
+    /// ```no_run
+    /// # use essi_ffmpeg::FFmpeg;
+    /// let builder = FFmpeg::new()
+    ///     .input_with_file("input.mkv".into())
+    ///     .done()
+    ///     .output_as_file("output.mp4".into())
+    ///     .build_with_probe(|info, builder| {
+    ///         // Only scale down
+    ///         let builder = match info.dimensions() {
+    ///             Some((_, height)) if height <= 720 => builder,
+    ///             _ => builder.video_filter("scale=-2:720"),
+    ///         };
+    ///
+    ///         match info.audio().and_then(|audio| audio.codec.as_deref()) {
+    ///             Some("aac") => builder.codec_audio("copy"),
+    ///             _ => builder.codec_audio("aac"),
+    ///         }
+    ///     })?;
+    /// # anyhow::Ok(())
+    /// ```
+    pub fn build_with_probe(self, build: impl FnOnce(&MediaInfo, Self) -> Self) -> anyhow::Result<Self> {
+        let input = self.first_input().context("There's no input to probe")?;
+        let info = FFprobe::probe(input)?;
+
+        Ok(build(&info, self))
+    }
+}
+
+impl FFmpegBuilder<Normal> {
+    /// Use the duration of the first input for [`FFmpegBuilder::total_duration`]
+    ///
+    /// Trimmed outputs are shorter than the input, set their length with [`FFmpegBuilder::total_duration`] instead
+    pub fn probe_total_duration(self) -> anyhow::Result<Self> {
+        let input = self.first_input().context("There's no input to probe")?;
+        let duration = FFprobe::probe(input)?.duration.with_context(|| format!("Can't find the duration of {input:?}"))?;
+
+        Ok(self.total_duration(duration))
+    }
+}
+
+fn stream_languages(streams: &[schema::Stream]) -> Vec<(StreamSelector, Option<String>)> {
+    let mut counts = HashMap::new();
+
+    streams
+        .iter()
+        .filter_map(|stream| {
+            let codec_type = stream.codec_type.as_deref()?;
+
+            let nth = counts.entry(codec_type).or_insert(0);
+            let selector = StreamSelector::from_codec_type(codec_type, *nth)?;
+            *nth += 1;
+
+            let language = stream.tags.get("language").filter(|language| *language != "und").cloned();
+
+            Some((selector, language))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn languages_per_stream_type() {
+        let streams: Vec<schema::Stream> = serde_json::from_str(r#"[
+            { "codec_type": "video", "tags": { "language": "und" } },
+            { "codec_type": "audio", "tags": { "language": "jpn" } },
+            { "codec_type": "audio", "tags": { "language": "eng" } },
+            { "codec_type": "subtitle", "tags": { "language": "eng", "title": "Full" } },
+            { "codec_type": "subtitle" }
+        ]"#).unwrap();
+
+        assert_eq!(stream_languages(&streams), [
+            (StreamSelector::Video(0), None),
+            (StreamSelector::Audio(0), Some("jpn".to_string())),
+            (StreamSelector::Audio(1), Some("eng".to_string())),
+            (StreamSelector::Subtitle(0), Some("eng".to_string())),
+            (StreamSelector::Subtitle(1), None),
+        ]);
+        assert_eq!(StreamSelector::Audio(1).to_string(), "a:1");
+    }
+}
@@ -0,0 +1,226 @@
+//! Typed media info, parsed from the raw [`super::schema`] output
+
+use std::{collections::HashMap, time::Duration};
+
+use super::schema::{FFprobeOutput, Stream, Tags};
+use crate::stream::StreamSelector;
+
+/// What's inside of a media file, see [`super::FFprobe::probe`]
+#[derive(Debug, Clone, Default)]
+pub struct MediaInfo {
+    /// Every name of the container, e.g. `mov`, `mp4`, `m4a`, ...
+    pub format_names: Vec<String>,
+    pub duration: Option<Duration>,
+    /// Bytes
+    pub size: Option<u64>,
+    /// Bits per second
+    pub bit_rate: Option<u64>,
+    pub tags: Tags,
+    pub streams: Vec<StreamInfo>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamKind {
+    Video,
+    Audio,
+    Subtitle,
+    Data,
+    Attachment,
+    Unknown,
+}
+
+#[derive(Debug, Clone)]
+pub struct StreamInfo {
+    pub index: usize,
+    /// Counted the same way as an output that maps every stream, [`Option::None`] for an unknown kind
+    pub selector: Option<StreamSelector>,
+    pub kind: StreamKind,
+    /// e.g. `h264` or `aac`
+    pub codec: Option<String>,
+    pub codec_long_name: Option<String>,
+    pub profile: Option<String>,
+    pub duration: Option<Duration>,
+    /// Bits per second
+    pub bit_rate: Option<u64>,
+    /// [`Option::None`] if untagged or `und`
+    pub language: Option<String>,
+    pub is_default: bool,
+    /// A cover art, a video stream with a single image
+    pub is_attached_pic: bool,
+    pub tags: Tags,
+    pub video: Option<VideoInfo>,
+    pub audio: Option<AudioInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoInfo {
+    pub width: u32,
+    pub height: u32,
+    /// Average frames per second
+    pub frame_rate: Option<f64>,
+    pub pix_fmt: Option<String>,
+    /// Degrees of the display matrix, e.g. `-90` for a phone video shot in portrait
+    pub rotation: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioInfo {
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u32>,
+    /// e.g. `stereo` or `5.1(side)`
+    pub channel_layout: Option<String>,
+}
+
+impl MediaInfo {
+    /// The first video stream that isn't a cover art
+    pub fn video(&self) -> Option<&StreamInfo> {
+        self.streams.iter().find(|stream| stream.kind == StreamKind::Video && !stream.is_attached_pic)
+    }
+
+    pub fn audio(&self) -> Option<&StreamInfo> {
+        self.streams.iter().find(|stream| stream.kind == StreamKind::Audio)
+    }
+
+    /// Width & height of [`MediaInfo::video`]
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        self.video()?.video.as_ref().map(|video| (video.width, video.height))
+    }
+
+    pub fn streams_of(&self, kind: StreamKind) -> impl Iterator<Item = &StreamInfo> {
+        self.streams.iter().filter(move |stream| stream.kind == kind)
+    }
+}
+
+impl From<FFprobeOutput> for MediaInfo {
+    fn from(output: FFprobeOutput) -> Self {
+        let mut counts = HashMap::new();
+
+        let streams = output
+            .streams
+            .into_iter()
+            .map(|stream| {
+                let kind = match stream.codec_type.as_deref() {
+                    Some("video") => StreamKind::Video,
+                    Some("audio") => StreamKind::Audio,
+                    Some("subtitle") => StreamKind::Subtitle,
+                    Some("data") => StreamKind::Data,
+                    Some("attachment") => StreamKind::Attachment,
+                    _ => StreamKind::Unknown,
+                };
+
+                let selector = stream.codec_type.as_deref().and_then(|codec_type| {
+                    let nth = counts.entry(codec_type.to_string()).or_insert(0);
+                    let selector = StreamSelector::from_codec_type(codec_type, *nth)?;
+                    *nth += 1;
+
+                    Some(selector)
+                });
+
+                StreamInfo::new(stream, kind, selector)
+            })
+            .collect();
+
+        let format = output.format.unwrap_or_default();
+
+        Self {
+            format_names: format.format_name.map(|names| names.split(',').map(String::from).collect()).unwrap_or_default(),
+            duration: seconds(&format.duration),
+            size: format.size.and_then(|size| size.parse().ok()),
+            bit_rate: format.bit_rate.and_then(|bit_rate| bit_rate.parse().ok()),
+            tags: format.tags,
+            streams,
+        }
+    }
+}
+
+impl StreamInfo {
+    fn new(stream: Stream, kind: StreamKind, selector: Option<StreamSelector>) -> Self {
+        let video = match (kind, stream.width, stream.height) {
+            (StreamKind::Video, Some(width), Some(height)) => Some(VideoInfo {
+                width: width as u32,
+                height: height as u32,
+                frame_rate: rational(&stream.avg_frame_rate).or_else(|| rational(&stream.r_frame_rate)),
+                pix_fmt: stream.pix_fmt.clone(),
+                rotation: stream.side_data_list.iter().find_map(|side_data| side_data.rotation),
+            }),
+            _ => None,
+        };
+
+        let audio = (kind == StreamKind::Audio).then(|| AudioInfo {
+            sample_rate: stream.sample_rate.as_deref().and_then(|rate| rate.parse().ok()),
+            channels: stream.channels.map(|channels| channels as u32),
+            channel_layout: stream.channel_layout.clone(),
+        });
+
+        let disposition = stream.disposition.unwrap_or_default();
+        let tags = stream.tags;
+
+        Self {
+            index: stream.index as usize,
+            selector,
+            kind,
+            codec: stream.codec_name,
+            codec_long_name: stream.codec_long_name,
+            profile: stream.profile,
+            duration: seconds(&stream.duration),
+            bit_rate: stream.bit_rate.and_then(|bit_rate| bit_rate.parse().ok()),
+            language: tags.get("language").filter(|language| *language != "und").cloned(),
+            is_default: disposition.default == 1,
+            is_attached_pic: disposition.attached_pic == 1,
+            tags,
+            video,
+            audio,
+        }
+    }
+}
+
+fn seconds(value: &Option<String>) -> Option<Duration> {
+    value.as_deref()?.parse::<f64>().ok().filter(|seconds| seconds.is_finite() && *seconds >= 0.0).map(Duration::from_secs_f64)
+}
+
+/// `30000/1001`, [`Option::None`] for `0/0`
+fn rational(value: &Option<String>) -> Option<f64> {
+    let (num, den) = value.as_deref()?.split_once('/')?;
+    let (num, den) = (num.parse::<f64>().ok()?, den.parse::<f64>().ok()?);
+
+    (den != 0.0 && num != 0.0).then(|| num / den)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn typed_media_info() {
+        let output: FFprobeOutput = r#"{
+            "streams": [
+                { "index": 0, "codec_name": "h264", "codec_type": "video", "width": 1920, "height": 1080, "avg_frame_rate": "30000/1001", "r_frame_rate": "30000/1001", "bit_rate": "5000000", "disposition": { "default": 1 }, "side_data_list": [{ "side_data_type": "Display Matrix", "rotation": -90 }] },
+                { "index": 1, "codec_name": "aac", "codec_type": "audio", "sample_rate": "48000", "channels": 2, "channel_layout": "stereo", "tags": { "language": "jpn" } },
+                { "index": 2, "codec_name": "mjpeg", "codec_type": "video", "width": 600, "height": 600, "avg_frame_rate": "0/0", "disposition": { "attached_pic": 1 } }
+            ],
+            "format": { "format_name": "mov,mp4,m4a,3gp,3g2,mj2", "duration": "10.5", "size": "1048576", "bit_rate": "798915", "tags": { "title": "Sample" } }
+        }"#.parse().unwrap();
+
+        let info = MediaInfo::from(output);
+
+        assert_eq!(info.format_names[1], "mp4");
+        assert_eq!(info.duration, Some(Duration::from_millis(10500)));
+        assert_eq!(info.size, Some(1048576));
+        assert_eq!(info.tags["title"], "Sample");
+        assert_eq!(info.dimensions(), Some((1920, 1080)));
+
+        let video = info.video().unwrap().video.as_ref().unwrap();
+        assert!((video.frame_rate.unwrap() - 29.97).abs() < 0.01);
+        assert_eq!(video.rotation, Some(-90));
+
+        let audio = info.audio().unwrap();
+        assert_eq!(audio.selector, Some(StreamSelector::Audio(0)));
+        assert_eq!(audio.language.as_deref(), Some("jpn"));
+        assert_eq!(audio.audio.as_ref().unwrap().sample_rate, Some(48000));
+
+        let cover = &info.streams[2];
+        assert_eq!(cover.selector, Some(StreamSelector::Video(1)));
+        assert_eq!(cover.video.as_ref().unwrap().frame_rate, None);
+        assert_eq!(info.streams_of(StreamKind::Video).count(), 2);
+    }
+}
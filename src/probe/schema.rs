@@ -0,0 +1,437 @@
+//! Raw ffprobe JSON output, `ffprobe -of json -show_format -show_streams ...`
+//!
+//! The fields mirror what ffprobe writes as close as possible, so most of the numbers are still strings (e.g. `duration`, `bit_rate`)
+//!
+//! Every struct keeps the fields it doesn't know about in `extra`, so newer ffprobe versions never fail to deserialize
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+pub type Tags = BTreeMap<String, String>;
+
+/// The whole ffprobe output, sections that weren't requested are left empty
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FFprobeOutput {
+    /// `-show_program_version`
+    pub program_version: Option<ProgramVersion>,
+    /// `-show_library_versions`
+    pub library_versions: Vec<LibraryVersion>,
+    /// `-show_format`
+    pub format: Option<Format>,
+    /// `-show_streams`
+    pub streams: Vec<Stream>,
+    /// `-show_programs`
+    pub programs: Vec<Program>,
+    /// `-show_stream_groups`, ffprobe 7.0+
+    pub stream_groups: Vec<StreamGroup>,
+    /// `-show_chapters`
+    pub chapters: Vec<Chapter>,
+    /// `-show_packets`
+    pub packets: Vec<Packet>,
+    /// `-show_frames`
+    pub frames: Vec<Frame>,
+    /// `-show_error`
+    pub error: Option<ProbeError>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl std::str::FromStr for FFprobeOutput {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(serde_json::from_str(s)?)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProgramVersion {
+    pub version: Option<String>,
+    pub copyright: Option<String>,
+    pub compiler_ident: Option<String>,
+    pub configuration: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LibraryVersion {
+    pub name: Option<String>,
+    pub major: Option<i64>,
+    pub minor: Option<i64>,
+    pub micro: Option<i64>,
+    pub version: Option<i64>,
+    pub ident: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Format {
+    pub filename: Option<String>,
+    pub nb_streams: Option<i64>,
+    pub nb_programs: Option<i64>,
+    /// ffprobe 7.0+
+    pub nb_stream_groups: Option<i64>,
+    /// Comma separated, e.g. `mov,mp4,m4a,3gp,3g2,mj2`
+    pub format_name: Option<String>,
+    pub format_long_name: Option<String>,
+    /// Seconds
+    pub start_time: Option<String>,
+    /// Seconds
+    pub duration: Option<String>,
+    /// Bytes
+    pub size: Option<String>,
+    /// Bits per second
+    pub bit_rate: Option<String>,
+    pub probe_score: Option<i64>,
+    pub tags: Tags,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stream {
+    pub index: i64,
+    pub codec_name: Option<String>,
+    pub codec_long_name: Option<String>,
+    pub profile: Option<String>,
+    /// `video`, `audio`, `subtitle`, `data` or `attachment`
+    pub codec_type: Option<String>,
+    pub codec_tag_string: Option<String>,
+    pub codec_tag: Option<String>,
+
+    // Video
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub coded_width: Option<i64>,
+    pub coded_height: Option<i64>,
+    pub closed_captions: Option<i64>,
+    pub film_grain: Option<i64>,
+    pub has_b_frames: Option<i64>,
+    pub sample_aspect_ratio: Option<String>,
+    pub display_aspect_ratio: Option<String>,
+    pub pix_fmt: Option<String>,
+    pub level: Option<i64>,
+    pub color_range: Option<String>,
+    pub color_space: Option<String>,
+    pub color_transfer: Option<String>,
+    pub color_primaries: Option<String>,
+    pub chroma_location: Option<String>,
+    pub field_order: Option<String>,
+    pub refs: Option<i64>,
+    pub is_avc: Option<String>,
+    pub nal_length_size: Option<String>,
+
+    // Audio
+    pub sample_fmt: Option<String>,
+    pub sample_rate: Option<String>,
+    pub channels: Option<i64>,
+    pub channel_layout: Option<String>,
+    pub bits_per_sample: Option<i64>,
+    pub initial_padding: Option<i64>,
+
+    // Subtitle
+    pub dvb_teletext: Option<i64>,
+
+    /// Container specific stream id, e.g. `0x1`
+    pub id: Option<String>,
+    pub r_frame_rate: Option<String>,
+    pub avg_frame_rate: Option<String>,
+    pub time_base: Option<String>,
+    pub start_pts: Option<i64>,
+    pub start_time: Option<String>,
+    pub duration_ts: Option<i64>,
+    pub duration: Option<String>,
+    pub bit_rate: Option<String>,
+    pub max_bit_rate: Option<String>,
+    pub bits_per_raw_sample: Option<String>,
+    pub nb_frames: Option<String>,
+    /// `-count_frames`
+    pub nb_read_frames: Option<String>,
+    /// `-count_packets`
+    pub nb_read_packets: Option<String>,
+    pub extradata_size: Option<i64>,
+    /// `-show_data`
+    pub extradata: Option<String>,
+    pub extradata_hash: Option<String>,
+    pub disposition: Option<Disposition>,
+    pub tags: Tags,
+    pub side_data_list: Vec<SideData>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Every flag is either 0 or 1
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Disposition {
+    pub default: u8,
+    pub dub: u8,
+    pub original: u8,
+    pub comment: u8,
+    pub lyrics: u8,
+    pub karaoke: u8,
+    pub forced: u8,
+    pub hearing_impaired: u8,
+    pub visual_impaired: u8,
+    pub clean_effects: u8,
+    pub attached_pic: u8,
+    pub timed_thumbnails: u8,
+    pub non_diegetic: u8,
+    pub captions: u8,
+    pub descriptions: u8,
+    pub metadata: u8,
+    pub dependent: u8,
+    pub still_image: u8,
+    /// ffprobe 7.1+
+    pub multilayer: u8,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Stream, packet or frame side data
+///
+/// Only the common fields are typed, the rest depends on `side_data_type` & can be found in `extra`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SideData {
+    /// e.g. `Display Matrix`, `Mastering display metadata`, `Content light level metadata`, `DOVI configuration record`
+    pub side_data_type: Option<String>,
+
+    // Display Matrix
+    pub displaymatrix: Option<String>,
+    pub rotation: Option<i64>,
+
+    // Mastering display metadata, rationals like `34000/50000`
+    pub red_x: Option<String>,
+    pub red_y: Option<String>,
+    pub green_x: Option<String>,
+    pub green_y: Option<String>,
+    pub blue_x: Option<String>,
+    pub blue_y: Option<String>,
+    pub white_point_x: Option<String>,
+    pub white_point_y: Option<String>,
+    pub min_luminance: Option<String>,
+    pub max_luminance: Option<String>,
+
+    // Content light level metadata
+    pub max_content: Option<i64>,
+    pub max_average: Option<i64>,
+
+    // DOVI configuration record
+    pub dv_version_major: Option<i64>,
+    pub dv_version_minor: Option<i64>,
+    pub dv_profile: Option<i64>,
+    pub dv_level: Option<i64>,
+    pub rpu_present_flag: Option<i64>,
+    pub el_present_flag: Option<i64>,
+    pub bl_present_flag: Option<i64>,
+    pub dv_bl_signal_compatibility_id: Option<i64>,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Program {
+    pub program_id: Option<i64>,
+    pub program_num: Option<i64>,
+    pub nb_streams: Option<i64>,
+    pub pmt_pid: Option<i64>,
+    pub pcr_pid: Option<i64>,
+    pub start_pts: Option<i64>,
+    pub start_time: Option<String>,
+    pub end_pts: Option<i64>,
+    pub end_time: Option<String>,
+    pub tags: Tags,
+    pub streams: Vec<Stream>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamGroup {
+    pub index: i64,
+    pub id: Option<String>,
+    pub nb_streams: Option<i64>,
+    /// e.g. `IAMF Audio Element`, `Tile Grid`
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub disposition: Option<Disposition>,
+    pub tags: Tags,
+    pub streams: Vec<Stream>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Chapter {
+    pub id: i64,
+    pub time_base: Option<String>,
+    pub start: Option<i64>,
+    pub start_time: Option<String>,
+    pub end: Option<i64>,
+    pub end_time: Option<String>,
+    pub tags: Tags,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Packet {
+    pub codec_type: Option<String>,
+    pub stream_index: i64,
+    pub pts: Option<i64>,
+    pub pts_time: Option<String>,
+    pub dts: Option<i64>,
+    pub dts_time: Option<String>,
+    pub duration: Option<i64>,
+    pub duration_time: Option<String>,
+    pub size: Option<String>,
+    pub pos: Option<String>,
+    /// e.g. `K__`, `K` is a keyframe
+    pub flags: Option<String>,
+    pub side_data_list: Vec<SideData>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Frame {
+    pub media_type: Option<String>,
+    pub stream_index: i64,
+    pub key_frame: Option<i64>,
+    pub pts: Option<i64>,
+    pub pts_time: Option<String>,
+    pub pkt_dts: Option<i64>,
+    pub pkt_dts_time: Option<String>,
+    pub best_effort_timestamp: Option<i64>,
+    pub best_effort_timestamp_time: Option<String>,
+    pub duration: Option<i64>,
+    pub duration_time: Option<String>,
+    pub pkt_pos: Option<String>,
+    pub pkt_size: Option<String>,
+
+    // Video
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub crop_top: Option<i64>,
+    pub crop_bottom: Option<i64>,
+    pub crop_left: Option<i64>,
+    pub crop_right: Option<i64>,
+    pub pix_fmt: Option<String>,
+    pub sample_aspect_ratio: Option<String>,
+    /// `I`, `P`, `B`, ...
+    pub pict_type: Option<String>,
+    pub interlaced_frame: Option<i64>,
+    pub top_field_first: Option<i64>,
+    pub lossless: Option<i64>,
+    pub repeat_pict: Option<i64>,
+    pub color_range: Option<String>,
+    pub color_space: Option<String>,
+    pub color_primaries: Option<String>,
+    pub color_transfer: Option<String>,
+    pub chroma_location: Option<String>,
+
+    // Audio
+    pub sample_fmt: Option<String>,
+    pub nb_samples: Option<i64>,
+    pub channels: Option<i64>,
+    pub channel_layout: Option<String>,
+
+    pub tags: Tags,
+    pub side_data_list: Vec<SideData>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProbeError {
+    /// Negative AVERROR code
+    pub code: i64,
+    pub string: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deserialize_ffprobe_output() {
+        let output: FFprobeOutput = r#"{
+            "streams": [
+                {
+                    "index": 0,
+                    "codec_name": "h264",
+                    "profile": "High",
+                    "codec_type": "video",
+                    "width": 1920,
+                    "height": 1080,
+                    "closed_captions": 0,
+                    "r_frame_rate": "30/1",
+                    "duration": "10.000000",
+                    "bits_per_raw_sample": "8",
+                    "disposition": { "default": 1, "dub": 0, "some_future_flag": 1 },
+                    "tags": { "language": "und", "handler_name": "VideoHandler" },
+                    "side_data_list": [
+                        { "side_data_type": "Display Matrix", "displaymatrix": "\n00000000:            0       65536           0\n", "rotation": -90 },
+                        { "side_data_type": "Content light level metadata", "max_content": 1000, "max_average": 400 }
+                    ],
+                    "some_future_field": [1, 2, 3]
+                },
+                {
+                    "index": 1,
+                    "codec_type": "audio",
+                    "sample_rate": "48000",
+                    "channels": 2
+                }
+            ],
+            "format": {
+                "filename": "input.mp4",
+                "nb_streams": 2,
+                "format_name": "mov,mp4,m4a,3gp,3g2,mj2",
+                "duration": "10.010000",
+                "size": "1048576",
+                "probe_score": 100,
+                "tags": { "encoder": "Lavf60.3.100" }
+            },
+            "chapters": [
+                { "id": 0, "time_base": "1/1000", "start": 0, "start_time": "0.000000", "end": 5000, "end_time": "5.000000", "tags": { "title": "Intro" } }
+            ]
+        }"#.parse().unwrap();
+
+        let video = &output.streams[0];
+        assert_eq!(video.codec_name.as_deref(), Some("h264"));
+        assert_eq!(video.width, Some(1920));
+        assert_eq!(video.disposition.as_ref().unwrap().default, 1);
+        assert!(video.disposition.as_ref().unwrap().extra.contains_key("some_future_flag"));
+        assert_eq!(video.side_data_list[0].rotation, Some(-90));
+        assert_eq!(video.side_data_list[1].max_content, Some(1000));
+        assert!(video.extra.contains_key("some_future_field"));
+
+        assert_eq!(output.streams[1].channels, Some(2));
+
+        let format = output.format.unwrap();
+        assert_eq!(format.probe_score, Some(100));
+        assert_eq!(format.tags["encoder"], "Lavf60.3.100");
+
+        assert_eq!(output.chapters[0].tags["title"], "Intro");
+        assert!(output.error.is_none());
+    }
+}
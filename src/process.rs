@@ -0,0 +1,266 @@
+use std::{io, process::Command, time::Duration};
+
+use crate::FFmpegResourceUsage;
+
+#[cfg(unix)]
+use nix::{sys::signal::{self, Signal}, unistd::Pid};
+
+/// Make the spawned process outlive the current one
+///
+/// Unix: the process gets its own session, so it won't receive the signals meant for our terminal
+///
+/// Windows: the process gets its own process group & no console
+#[cfg(unix)]
+pub(crate) fn detach(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+
+    // SAFETY: setsid is async-signal-safe, nothing else is done between fork & exec
+    unsafe {
+        command.pre_exec(|| nix::unistd::setsid().map(|_| ()).map_err(io::Error::from));
+    }
+}
+
+/// Nothing to do, SIGINT can always reach our child
+#[cfg(unix)]
+pub(crate) fn prepare(_command: &mut Command, _shared_group: bool) {}
+
+#[cfg(unix)]
+pub(crate) fn spawned(_pid: u32, _shared_group: bool) {}
+
+/// Check if a process with the given pid is still running
+#[cfg(unix)]
+pub(crate) fn is_alive(pid: u32) -> bool {
+    // A zombie can still be signaled, but it's not running anymore
+    #[cfg(target_os = "linux")]
+    if let Ok(stat) = std::fs::read_to_string(format!("/proc/{pid}/stat")) {
+        if let Some((_, rest)) = stat.rsplit_once(')') {
+            return !rest.trim_start().starts_with('Z');
+        }
+    }
+
+    // Signal 0 doesn't send anything, it only checks if the process can be signaled
+    signal::kill(Pid::from_raw(pid as i32), None).is_ok()
+}
+
+/// Ask the process to quit, FFmpeg treats this the same way as "q"
+#[cfg(unix)]
+pub(crate) fn interrupt(pid: u32) -> io::Result<()> {
+    Ok(signal::kill(Pid::from_raw(pid as i32), Signal::SIGINT)?)
+}
+
+/// Ask the process to terminate, FFmpeg quits immediately but still tries to finish the outputs
+#[cfg(unix)]
+pub(crate) fn terminate(pid: u32) -> io::Result<()> {
+    Ok(signal::kill(Pid::from_raw(pid as i32), Signal::SIGTERM)?)
+}
+
+#[cfg(unix)]
+pub(crate) fn kill(pid: u32) -> io::Result<()> {
+    Ok(signal::kill(Pid::from_raw(pid as i32), Signal::SIGKILL)?)
+}
+
+/// Sample the resource usage of a process, `cpu_percent` is left for the caller to compute
+#[cfg(target_os = "linux")]
+pub(crate) fn resource_usage(pid: u32) -> io::Result<FFmpegResourceUsage> {
+    use nix::unistd::{sysconf, SysconfVar};
+
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat"))?;
+
+    // The process name can contain spaces, so only split what comes after it
+    // Index 0 is the 3rd field (state), see proc(5)
+    let fields = stat.rsplit_once(')').map(|(_, rest)| rest.split_whitespace().collect::<Vec<_>>()).unwrap_or_default();
+    let field = |index: usize| fields.get(index).and_then(|v| v.parse::<u64>().ok()).unwrap_or_default();
+
+    let clock_ticks = sysconf(SysconfVar::CLK_TCK).ok().flatten().unwrap_or(100) as u64;
+    let page_size = sysconf(SysconfVar::PAGE_SIZE).ok().flatten().unwrap_or(4096) as u64;
+
+    let cpu_ticks = field(11) + field(12);
+
+    // Might not be readable depending on the ptrace access mode
+    let io = std::fs::read_to_string(format!("/proc/{pid}/io")).unwrap_or_default();
+    let io_field = |name: &str| io.lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(':')?.trim().parse::<u64>().ok())
+        .unwrap_or_default();
+
+    Ok(FFmpegResourceUsage {
+        cpu_percent: None,
+        cpu_time: Duration::from_secs_f64(cpu_ticks as f64 / clock_ticks as f64),
+        rss: field(21) * page_size,
+        read_bytes: io_field("read_bytes"),
+        write_bytes: io_field("write_bytes"),
+    })
+}
+
+#[cfg(target_os = "macos")]
+#[allow(deprecated)] // mach_timebase_info, not worth a whole crate
+pub(crate) fn resource_usage(pid: u32) -> io::Result<FFmpegResourceUsage> {
+    use nix::libc;
+
+    let mut info: libc::rusage_info_v2 = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::proc_pid_rusage(pid as i32, libc::RUSAGE_INFO_V2, &mut info as *mut _ as *mut libc::rusage_info_t) };
+
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // CPU times are in mach absolute time units, which are not nanoseconds on Apple silicon
+    let mut timebase = libc::mach_timebase_info { numer: 1, denom: 1 };
+    unsafe { libc::mach_timebase_info(&mut timebase) };
+
+    let cpu_nanos = (info.ri_user_time + info.ri_system_time) as u128 * timebase.numer as u128 / timebase.denom.max(1) as u128;
+
+    Ok(FFmpegResourceUsage {
+        cpu_percent: None,
+        cpu_time: Duration::from_nanos(cpu_nanos as u64),
+        rss: info.ri_resident_size,
+        read_bytes: info.ri_diskio_bytesread,
+        write_bytes: info.ri_diskio_byteswritten,
+    })
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "macos"))))]
+pub(crate) fn resource_usage(_pid: u32) -> io::Result<FFmpegResourceUsage> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Resource usage is not supported on this platform"))
+}
+
+#[cfg(windows)]
+pub(crate) fn detach(command: &mut Command) {
+    use std::os::windows::process::CommandExt;
+
+    command.creation_flags(winapi::DETACHED_PROCESS | winapi::CREATE_NEW_PROCESS_GROUP);
+}
+
+/// Processes started with [`crate::FFmpegBuilder::share_process_group`], a CTRL_BREAK to them would reach our whole group
+#[cfg(windows)]
+static SHARED_GROUP: once_cell::sync::Lazy<std::sync::Mutex<std::collections::HashSet<u32>>> = once_cell::sync::Lazy::new(Default::default);
+
+/// CTRL_BREAK can only be sent to a whole process group, so give the process its own group for [`interrupt`]
+///
+/// It doesn't get the CTRL_C of our console anymore then, unless it shares our group
+#[cfg(windows)]
+pub(crate) fn prepare(command: &mut Command, shared_group: bool) {
+    use std::os::windows::process::CommandExt;
+
+    if !shared_group {
+        command.creation_flags(winapi::CREATE_NEW_PROCESS_GROUP);
+    }
+}
+
+/// Remember which processes can't get [`interrupt`], the pid might be reused from one that could
+#[cfg(windows)]
+pub(crate) fn spawned(pid: u32, shared_group: bool) {
+    let mut shared = SHARED_GROUP.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+
+    if shared_group {
+        shared.insert(pid);
+    } else {
+        shared.remove(&pid);
+    }
+}
+
+#[cfg(windows)]
+pub(crate) fn is_alive(pid: u32) -> bool {
+    const STILL_ACTIVE: u32 = 259;
+
+    let Ok(handle) = windows::ProcessHandle::open(pid, winapi::PROCESS_QUERY_LIMITED_INFORMATION) else { return false };
+
+    let mut exit_code = 0;
+    let ok = unsafe { kernel32::GetExitCodeProcess(handle.0, &mut exit_code) };
+
+    ok != 0 && exit_code == STILL_ACTIVE
+}
+
+/// Ask the process to quit, FFmpeg treats this the same way as "q"
+///
+/// Only works if the process shares our console & has its own process group, see [`prepare`]
+#[cfg(windows)]
+pub(crate) fn interrupt(pid: u32) -> io::Result<()> {
+    if SHARED_GROUP.lock().unwrap_or_else(std::sync::PoisonError::into_inner).contains(&pid) {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "The process shares our process group, it can't be interrupted alone"));
+    }
+
+    let ok = unsafe { kernel32::GenerateConsoleCtrlEvent(winapi::CTRL_BREAK_EVENT, pid) };
+
+    if ok != 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// There's no SIGTERM, FFmpeg quits immediately on a second CTRL_BREAK
+#[cfg(windows)]
+pub(crate) fn terminate(pid: u32) -> io::Result<()> {
+    interrupt(pid)
+}
+
+#[cfg(windows)]
+pub(crate) fn kill(pid: u32) -> io::Result<()> {
+    let handle = windows::ProcessHandle::open(pid, winapi::PROCESS_TERMINATE)?;
+
+    let ok = unsafe { kernel32::TerminateProcess(handle.0, 1) };
+
+    if ok != 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(windows)]
+pub(crate) fn resource_usage(pid: u32) -> io::Result<FFmpegResourceUsage> {
+    let handle = windows::ProcessHandle::open(pid, winapi::PROCESS_QUERY_LIMITED_INFORMATION)?;
+
+    let empty = winapi::FILETIME { dwLowDateTime: 0, dwHighDateTime: 0 };
+    let (mut creation, mut exit, mut kernel, mut user) = (empty, empty, empty, empty);
+    let mut memory: winapi::PROCESS_MEMORY_COUNTERS = unsafe { std::mem::zeroed() };
+    let mut io_counters: winapi::IO_COUNTERS = unsafe { std::mem::zeroed() };
+
+    memory.cb = std::mem::size_of::<winapi::PROCESS_MEMORY_COUNTERS>() as u32;
+
+    let ok = unsafe {
+        kernel32::GetProcessTimes(handle.0, &mut creation, &mut exit, &mut kernel, &mut user) != 0
+            && kernel32::K32GetProcessMemoryInfo(handle.0, &mut memory, memory.cb) != 0
+            && kernel32::GetProcessIoCounters(handle.0, &mut io_counters) != 0
+    };
+
+    if !ok {
+        return Err(io::Error::last_os_error());
+    }
+
+    // FILETIME is in 100 nanoseconds unit
+    let filetime = |time: winapi::FILETIME| ((time.dwHighDateTime as u64) << 32) | time.dwLowDateTime as u64;
+
+    Ok(FFmpegResourceUsage {
+        cpu_percent: None,
+        cpu_time: Duration::from_nanos((filetime(kernel) + filetime(user)) * 100),
+        rss: memory.WorkingSetSize as u64,
+        read_bytes: io_counters.ReadTransferCount,
+        write_bytes: io_counters.WriteTransferCount,
+    })
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::io;
+
+    pub(crate) struct ProcessHandle(pub(crate) winapi::HANDLE);
+
+    impl ProcessHandle {
+        pub(crate) fn open(pid: u32, access: winapi::DWORD) -> io::Result<Self> {
+            let handle = unsafe { kernel32::OpenProcess(access, 0, pid) };
+
+            if handle.is_null() {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(Self(handle))
+            }
+        }
+    }
+
+    impl Drop for ProcessHandle {
+        fn drop(&mut self) {
+            let _ = unsafe { kernel32::CloseHandle(self.0) };
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::{ffi::OsStr, time::Duration};
+
+use anyhow::Context;
+use indicatif::{ProgressBar, ProgressStyle};
+use tokio::sync::mpsc::Receiver;
+
+use crate::{probe::FFprobe, FFmpegProgress, FFmpegProgressStatus};
+
+/// Drives an indicatif [`ProgressBar`] from [`crate::FFmpegBuilder::start_listen_progress`]
+///
+/// The bar counts milliseconds of the output, it's a spinner if the duration is unknown
+pub struct ProgressBarAdapter {
+    bar: ProgressBar,
+    duration: Option<Duration>,
+}
+
+impl ProgressBarAdapter {
+    pub fn new(bar: ProgressBar) -> Self {
+        Self { bar, duration: None }
+    }
+
+    /// Use the duration of `input` as the length of the bar
+    pub fn probe_duration(self, input: impl AsRef<OsStr>) -> anyhow::Result<Self> {
+        let input = input.as_ref();
+        let duration = FFprobe::probe(input)?.duration.with_context(|| format!("Can't find the duration of {input:?}"))?;
+
+        Ok(self.duration(duration))
+    }
+
+    /// Length of the bar, e.g. the `-t` of the output
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+
+        self
+    }
+
+    pub fn bar(&self) -> &ProgressBar {
+        &self.bar
+    }
+
+    fn prepare(&self) {
+        match self.duration {
+            Some(duration) => {
+                self.bar.set_length(duration.as_millis() as u64);
+                self.bar.set_style(
+                    ProgressStyle::with_template("{wide_bar} {percent:>3}% {msg} eta {eta}")
+                        .unwrap_or_else(|_| ProgressStyle::default_bar()),
+                );
+            },
+            None => {
+                self.bar.set_style(ProgressStyle::with_template("{spinner} {msg}").unwrap_or_else(|_| ProgressStyle::default_spinner()));
+            },
+        }
+    }
+
+    /// Move the bar to `progress`
+    pub fn update(&self, progress: &FFmpegProgress) {
+        if let Some(out_time) = progress.out_time_us.map(|us| Duration::from_micros(us as u64)) {
+            self.bar.set_position(out_time.as_millis() as u64);
+
+            let speed = progress.speed.map(|speed| format!(" {speed:.2}x")).unwrap_or_default();
+            self.bar.set_message(format!("{}{speed}", format_time(out_time)));
+        }
+
+        if matches!(progress.progress, Some(FFmpegProgressStatus::End)) {
+            self.bar.finish();
+        }
+    }
+
+    /// Update the bar until FFmpeg is done
+    pub async fn drive(self, mut progress_rx: Receiver<FFmpegProgress>) {
+        self.prepare();
+
+        while let Some(progress) = progress_rx.recv().await {
+            self.update(&progress);
+        }
+
+        self.bar.finish();
+    }
+
+    /// Same as [`ProgressBarAdapter::drive`] but blocks the current thread, don't call it inside an async runtime
+    pub fn drive_blocking(self, mut progress_rx: Receiver<FFmpegProgress>) {
+        self.prepare();
+
+        while let Some(progress) = progress_rx.blocking_recv() {
+            self.update(&progress);
+        }
+
+        self.bar.finish();
+    }
+}
+
+/// `01:02:03`
+fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bar_position() {
+        let adapter = ProgressBarAdapter::new(ProgressBar::hidden()).duration(Duration::from_secs(10));
+        adapter.prepare();
+
+        assert_eq!(adapter.bar().length(), Some(10_000));
+
+        adapter.update(&FFmpegProgress::from("out_time_us=2500000\nspeed=1.5x\nprogress=continue".to_string()));
+
+        assert_eq!(adapter.bar().position(), 2500);
+        assert_eq!(adapter.bar().message(), "00:00:02 1.50x");
+        assert!(!adapter.bar().is_finished());
+
+        adapter.update(&FFmpegProgress::from("progress=end".to_string()));
+
+        assert!(adapter.bar().is_finished());
+        assert_eq!(format_time(Duration::from_secs(3723)), "01:02:03");
+    }
+}
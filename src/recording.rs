@@ -0,0 +1,304 @@
+//! Screen recording with pause & resume, see [`Recording`]
+
+use std::{path::{Path, PathBuf}, process::{ExitStatus, Stdio}, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex, MutexGuard, PoisonError}, thread::JoinHandle, time::{Duration, Instant}};
+
+use crate::{capture::{ScreenCapture, ScreenGrabber}, stderr::DEFAULT_RING_BUFFER_CAPACITY, FFmpeg, FFmpegCommand, FFmpegError, TempOutput};
+
+/// Options of a [`RecordingSession`]
+///
+/// Every pause ends the current part, the parts are joined into the output when the session is stopped
+#[derive(Debug, Clone)]
+pub struct Recording {
+    capture: ScreenCapture,
+    output: PathBuf,
+    video_codec: String,
+    max_duration: Option<Duration>,
+    split_size: Option<u64>,
+}
+
+impl Recording {
+    pub fn new(capture: ScreenCapture, output: impl Into<PathBuf>) -> Self {
+        Self { capture, output: output.into(), video_codec: "libx264".to_string(), max_duration: None, split_size: None }
+    }
+
+    /// `libx264` by default
+    pub fn video_codec(mut self, codec: impl Into<String>) -> Self {
+        self.video_codec = codec.into();
+
+        self
+    }
+
+    /// Stop recording once this much is recorded, pauses aren't counted
+    pub fn max_duration(mut self, duration: Duration) -> Self {
+        self.max_duration = Some(duration);
+
+        self
+    }
+
+    /// Start a new file every `bytes`, the files are numbered after the output, e.g. `recording_001.mp4`
+    pub fn split_size(mut self, bytes: u64) -> Self {
+        self.split_size = Some(bytes);
+
+        self
+    }
+
+    pub fn start(self) -> anyhow::Result<RecordingSession> {
+        RecordingSession::start(self)
+    }
+
+    /// Record into `part` until `duration` or `size` is reached
+    fn start_part(&self, part: &Path, duration: Option<Duration>, size: Option<u64>) -> anyhow::Result<FFmpegCommand> {
+        let mut builder = FFmpeg::try_new()?
+            .args(["-hide_banner", "-nostats"])
+            .input_screen(&self.capture)?
+            .done()
+            .output_as_file(part.to_path_buf())
+            // Stays playable if FFmpeg is killed
+            .format("matroska")
+            .codec_video(&self.video_codec)
+            .args(["-pix_fmt", "yuv420p"]);
+
+        if self.capture.grabber() == ScreenGrabber::DdaGrab {
+            builder = builder.video_filter("hwdownload,format=bgra");
+        }
+
+        if let Some(duration) = duration {
+            builder = builder.limit_duration(duration);
+        }
+
+        if let Some(size) = size {
+            builder = builder.limit_size(size);
+        }
+
+        builder
+            .done()
+            // Stopped with "q"
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr_ring_buffer(DEFAULT_RING_BUFFER_CAPACITY)
+            .start()
+    }
+}
+
+struct State {
+    /// The parts of every output file
+    chunks: Vec<Vec<TempOutput>>,
+    current: Option<(FFmpegCommand, Instant)>,
+    recorded: Duration,
+    paused: bool,
+    finished: bool,
+    error: Option<anyhow::Error>,
+}
+
+/// A running [`Recording`]
+///
+/// A new file is started in the background when [`Recording::split_size`] is reached
+pub struct RecordingSession {
+    recording: Arc<Recording>,
+    state: Arc<Mutex<State>>,
+    stopping: Arc<AtomicBool>,
+    watcher: Option<JoinHandle<()>>,
+}
+
+impl RecordingSession {
+    fn start(recording: Recording) -> anyhow::Result<Self> {
+        let recording = Arc::new(recording);
+        let state = Arc::new(Mutex::new(State {
+            chunks: vec![Vec::new()],
+            current: None,
+            recorded: Duration::ZERO,
+            paused: false,
+            finished: false,
+            error: None,
+        }));
+
+        start_next_part(&recording, &mut lock(&state))?;
+
+        let stopping = Arc::new(AtomicBool::new(false));
+
+        let watcher = std::thread::spawn({
+            let (recording, state, stopping) = (recording.clone(), state.clone(), stopping.clone());
+
+            move || while !stopping.load(Ordering::Relaxed) {
+                std::thread::sleep(Duration::from_millis(250));
+
+                let mut state = lock(&state);
+                let Some((command, _)) = state.current.as_mut() else { continue };
+
+                match command.try_wait() {
+                    Ok(Some(status)) => part_ended(&recording, &mut state, status),
+                    Ok(None) => continue,
+                    Err(err) => {
+                        state.error = Some(err.into());
+                        state.finished = true;
+                    },
+                }
+            }
+        });
+
+        Ok(Self { recording, state, stopping, watcher: Some(watcher) })
+    }
+
+    /// Stop recording until [`RecordingSession::resume`]
+    pub fn pause(&mut self) -> anyhow::Result<()> {
+        let mut state = lock(&self.state);
+
+        if state.paused || state.finished { return Ok(()) };
+
+        state.paused = true;
+
+        if let Some((command, started)) = state.current.take() {
+            state.recorded += started.elapsed();
+            command.stop()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn resume(&mut self) -> anyhow::Result<()> {
+        let mut state = lock(&self.state);
+
+        if !state.paused || state.finished { return Ok(()) };
+
+        state.paused = false;
+
+        start_next_part(&self.recording, &mut state)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        lock(&self.state).paused
+    }
+
+    /// The recording ended by itself, [`Recording::max_duration`] was reached or FFmpeg failed
+    pub fn is_finished(&self) -> bool {
+        lock(&self.state).finished
+    }
+
+    /// How much was recorded so far, without the pauses
+    pub fn recorded(&self) -> Duration {
+        let state = lock(&self.state);
+
+        state.recorded + state.current.as_ref().map(|(_, started)| started.elapsed()).unwrap_or_default()
+    }
+
+    /// Stop recording & join the parts, returns the recorded files
+    pub fn stop(mut self) -> anyhow::Result<Vec<PathBuf>> {
+        self.stopping.store(true, Ordering::Relaxed);
+
+        if let Some(watcher) = self.watcher.take() {
+            let _ = watcher.join();
+        }
+
+        let mut state = lock(&self.state);
+
+        if let Some((command, _)) = state.current.take() {
+            command.stop()?;
+        }
+
+        if let Some(err) = state.error.take() {
+            return Err(err);
+        }
+
+        let chunks = std::mem::take(&mut state.chunks).into_iter().filter(|parts| !parts.is_empty()).collect::<Vec<_>>();
+        let mut outputs = Vec::new();
+
+        for (index, parts) in chunks.iter().enumerate() {
+            let output = match self.recording.split_size {
+                Some(_) => numbered_path(&self.recording.output, index + 1),
+                None => self.recording.output.clone(),
+            };
+
+            FFmpeg::concat(parts.iter().map(|part| part.path().to_path_buf()), &output)?;
+
+            outputs.push(output);
+        }
+
+        Ok(outputs)
+    }
+}
+
+impl Drop for RecordingSession {
+    fn drop(&mut self) {
+        self.stopping.store(true, Ordering::Relaxed);
+    }
+}
+
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Continue the recording in a new part of the last file, with what's left of the duration & size
+fn start_next_part(recording: &Recording, state: &mut State) -> anyhow::Result<()> {
+    let remaining_duration = recording.max_duration.map(|max| max.saturating_sub(state.recorded));
+
+    if remaining_duration.is_some_and(|remaining| remaining.is_zero()) {
+        state.finished = true;
+
+        return Ok(());
+    }
+
+    let chunk = state.chunks.last_mut().expect("There's always a chunk");
+    let chunk_size = chunk.iter().filter_map(|part| part.file().metadata().ok()).map(|metadata| metadata.len()).sum::<u64>();
+    let remaining_size = recording.split_size.map(|size| size.saturating_sub(chunk_size).max(1));
+
+    let part = TempOutput::create()?;
+    let command = recording.start_part(part.path(), remaining_duration, remaining_size)?;
+
+    chunk.push(part);
+    state.current = Some((command, Instant::now()));
+
+    Ok(())
+}
+
+/// FFmpeg exited on its own, because of a limit or an error
+fn part_ended(recording: &Recording, state: &mut State, status: ExitStatus) {
+    let Some((command, started)) = state.current.take() else { return };
+
+    state.recorded += started.elapsed();
+
+    if !status.success() {
+        state.error = Some(FFmpegError { status, stderr: command.recent_stderr() }.into());
+        state.finished = true;
+
+        return;
+    }
+
+    let reached_duration = recording.max_duration.is_some_and(|max| state.recorded + Duration::from_millis(500) >= max);
+
+    // Anything else that stops FFmpeg cleanly, e.g. the captured window was closed, ends the recording too
+    if reached_duration || recording.split_size.is_none() {
+        state.finished = true;
+
+        return;
+    }
+
+    state.chunks.push(Vec::new());
+
+    if let Err(err) = start_next_part(recording, state) {
+        state.error = Some(err);
+        state.finished = true;
+    }
+}
+
+/// `recording.mp4` into `recording_001.mp4`
+fn numbered_path(output: &Path, number: usize) -> PathBuf {
+    let stem = output.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+
+    let name = match output.extension() {
+        Some(extension) => format!("{stem}_{number:03}.{}", extension.to_string_lossy()),
+        None => format!("{stem}_{number:03}"),
+    };
+
+    output.with_file_name(name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_file_names() {
+        assert_eq!(numbered_path(Path::new("out/recording.mp4"), 1), Path::new("out/recording_001.mp4"));
+        assert_eq!(numbered_path(Path::new("recording"), 12), Path::new("recording_012"));
+    }
+}
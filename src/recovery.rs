@@ -0,0 +1,139 @@
+use std::fmt::Display;
+
+use crate::{FFmpegBuilder, Input};
+
+/// How strictly the decoder checks the bitstream, `-err_detect`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorDetection {
+    /// Keep decoding through errors, the usual choice for damaged files
+    IgnoreErrors,
+    /// Only reject what a careful encoder would never write
+    Careful,
+    /// Reject everything that isn't compliant with the spec
+    Compliant,
+    /// Reject anything a sane encoder wouldn't write
+    Aggressive,
+    /// Stop decoding on the first error
+    Explode,
+}
+
+impl ErrorDetection {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ErrorDetection::IgnoreErrors => "ignore_err",
+            ErrorDetection::Careful => "careful",
+            ErrorDetection::Compliant => "compliant",
+            ErrorDetection::Aggressive => "aggressive",
+            ErrorDetection::Explode => "explode",
+        }
+    }
+}
+
+/// How the decoder patches up damaged parts of a frame, `-ec`
+///
+/// [`Default`] is what FFmpeg does on its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Concealment {
+    /// Fill damaged blocks by guessing their motion from the blocks around them
+    pub guess_motion_vectors: bool,
+    /// Deblock the concealed blocks so the edges are less visible
+    pub deblock: bool,
+    /// Prefer the previous frame over the blocks around it, better for mostly static video
+    pub favor_inter: bool,
+}
+
+impl Default for Concealment {
+    fn default() -> Self {
+        Self { guess_motion_vectors: true, deblock: true, favor_inter: false }
+    }
+}
+
+impl Display for Concealment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let flags = [(self.guess_motion_vectors, "guess_mvs"), (self.deblock, "deblock"), (self.favor_inter, "favor_inter")]
+            .into_iter()
+            .filter_map(|(enabled, flag)| enabled.then_some(flag))
+            .collect::<Vec<_>>();
+
+        // No flag turns the concealment off
+        match flags.is_empty() {
+            true => write!(f, "0"),
+            false => write!(f, "{}", flags.join("+")),
+        }
+    }
+}
+
+/// Options for decoding damaged inputs, e.g. to salvage a recording that was cut off or has corrupted packets
+///
+/// See [`FFmpegBuilder::decode_recovery`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeRecovery {
+    /// `-err_detect` of the decoders
+    pub error_detection: Option<ErrorDetection>,
+    /// Drop packets the demuxer flags as corrupt instead of decoding them, `-fflags +discardcorrupt`
+    pub discard_corrupt: bool,
+    /// `-ec` of the decoders
+    pub concealment: Option<Concealment>,
+}
+
+impl DecodeRecovery {
+    /// Decode through every error & conceal as much as possible, keeps every frame but damaged ones show smeared blocks
+    pub fn conceal() -> Self {
+        Self {
+            error_detection: Some(ErrorDetection::IgnoreErrors),
+            discard_corrupt: false,
+            concealment: Some(Concealment { favor_inter: true, ..Default::default() }),
+        }
+    }
+
+    /// Decode through every error but drop corrupt packets, skips the damaged frames instead of showing them
+    pub fn discard() -> Self {
+        Self { error_detection: Some(ErrorDetection::IgnoreErrors), discard_corrupt: true, concealment: None }
+    }
+}
+
+impl FFmpegBuilder<Input> {
+    /// Decode this input with `recovery`, the `-fflags` of the input are kept
+    pub fn decode_recovery(self, recovery: &DecodeRecovery) -> Self {
+        let mut builder = self;
+
+        if let Some(detection) = recovery.error_detection {
+            builder = builder.args(["-err_detect", detection.as_str()]);
+        }
+
+        if recovery.discard_corrupt {
+            builder = builder.add_format_flag("+discardcorrupt");
+        }
+
+        match recovery.concealment {
+            Some(concealment) => builder.args(["-ec".to_string(), concealment.to_string()]),
+            None => builder,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::FFmpeg;
+
+    #[test]
+    fn decode_recovery_args() {
+        assert_eq!(Concealment::default().to_string(), "guess_mvs+deblock");
+        assert_eq!(Concealment { guess_motion_vectors: false, deblock: false, favor_inter: false }.to_string(), "0");
+
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("broken.mp4".into())
+            .decode_recovery(&DecodeRecovery::conceal())
+            .done()
+            .input_with_file("broken.ts".into())
+            .args(["-fflags", "+genpts"])
+            .decode_recovery(&DecodeRecovery::discard())
+            .done();
+
+        assert_eq!(builder.inner_args, [
+            "-err_detect", "ignore_err", "-ec", "guess_mvs+deblock+favor_inter", "-i", "broken.mp4",
+            "-fflags", "+genpts+discardcorrupt", "-err_detect", "ignore_err", "-i", "broken.ts",
+        ]);
+    }
+}
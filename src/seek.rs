@@ -0,0 +1,143 @@
+use std::time::Duration;
+
+use crate::{FFmpegBuilder, Input, Section};
+
+/// Where the `-ss` of [`FFmpegBuilder::seek_mode`] goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekMode {
+    /// `-ss` before `-i`, jumps to the keyframe before the position
+    ///
+    /// Re-encoded streams are still decoded up to the exact position, stream copies start at the keyframe
+    Fast(Duration),
+    /// `-ss` after `-i`, decodes everything from the start, slow but exact even for inputs that can't be seeked properly
+    Accurate(Duration),
+    /// Jump to `coarse` before `-i`, then decode for `fine` more, e.g. jumping to a few seconds before the position
+    Split { coarse: Duration, fine: Duration },
+}
+
+impl SeekMode {
+    /// `-ss` of the input & of the output
+    fn positions(&self) -> (Option<Duration>, Option<Duration>) {
+        match *self {
+            SeekMode::Fast(position) => (Some(position), None),
+            SeekMode::Accurate(position) => (None, Some(position)),
+            SeekMode::Split { coarse, fine } => (Some(coarse), Some(fine)),
+        }
+    }
+}
+
+impl FFmpegBuilder<Input> {
+    /// Start this input at a position
+    ///
+    /// The `-ss` after `-i` is an option of the output, it goes to the next output added (even if more inputs are added first)
+    /// & applies to every input of it
+    pub fn seek_mode(self, mode: SeekMode) -> Self {
+        let (input, output) = mode.positions();
+
+        let builder = match input {
+            Some(position) => self.input_args(["-ss".to_string(), seconds(position)]),
+            None => self,
+        };
+
+        match output {
+            Some(position) => builder.output_args(["-ss".to_string(), seconds(position)]),
+            None => builder,
+        }
+    }
+}
+
+fn seconds(position: Duration) -> String {
+    position.as_secs_f64().to_string()
+}
+
+impl<M: Section> FFmpegBuilder<M> {
+    /// Start reading the input at `position`, `-ss` before `-i`, same as [`SeekMode::Fast`]
+    ///
+    /// From an output it goes to the last input before it
+    pub fn seek(self, position: Duration) -> Self {
+        self.input_args(["-ss".to_string(), seconds(position)])
+    }
+
+    /// Drop everything before `position` of the output, `-ss` after `-i`, same as [`SeekMode::Accurate`]
+    ///
+    /// From an input it goes to the next output added
+    pub fn seek_output(self, position: Duration) -> Self {
+        self.output_args(["-ss".to_string(), seconds(position)])
+    }
+
+    /// Stop reading the input at `position` of the input, `-to` before `-i`
+    ///
+    /// Unlike `-to` of an output it isn't shifted by [`FFmpegBuilder::seek`], so `seek(10s).to(15s)` is a 5 second clip.
+    /// Limit the length of the output with [`FFmpegBuilder::duration`] instead
+    pub fn to(self, position: Duration) -> Self {
+        self.input_args(["-to".to_string(), seconds(position)])
+    }
+
+    /// Only write `duration` of the output, `-t` after `-i`, same as [`FFmpegBuilder::limit_duration`]
+    ///
+    /// From an input it goes to the next output added
+    pub fn duration(self, duration: Duration) -> Self {
+        self.output_args(["-t".to_string(), seconds(duration)])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::FFmpeg;
+
+    #[test]
+    fn seek_placement() {
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("in.mp4".into())
+            .seek_mode(SeekMode::Split { coarse: Duration::from_secs(60), fine: Duration::from_millis(2500) })
+            .done()
+            .output_as_file("out.mp4".into())
+            .done();
+
+        assert_eq!(builder.inner_args, ["-ss", "60", "-i", "in.mp4", "-ss", "2.5", "-y", "out.mp4"]);
+    }
+
+    #[test]
+    fn accurate_seek_before_more_inputs() {
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("in.mp4".into())
+            .seek_mode(SeekMode::Accurate(Duration::from_secs(3)))
+            .done()
+            .input_with_file("music.mp3".into())
+            .done()
+            .output_as_file("out.mp4".into())
+            .codec_video("libx264")
+            .done();
+
+        assert_eq!(builder.inner_args, ["-i", "in.mp4", "-i", "music.mp3", "-ss", "3", "-c:v", "libx264", "-y", "out.mp4"]);
+        assert_eq!(builder.output_stage(crate::OutputHandle(0)).unwrap().option("-ss"), Some(std::ffi::OsStr::new("3")));
+    }
+
+    #[test]
+    fn trim_placement() {
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("in.mp4".into())
+            .done()
+            .output_as_file("out.mp4".into())
+            .limit_duration(Duration::from_secs(5))
+            .seek(Duration::from_secs(10))
+            .to(Duration::from_millis(20500))
+            .done();
+
+        assert_eq!(builder.inner_args, ["-ss", "10", "-to", "20.5", "-i", "in.mp4", "-t", "5", "-y", "out.mp4"]);
+
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("in.mp4".into())
+            .seek(Duration::from_secs(10))
+            .duration(Duration::from_millis(1500))
+            .done()
+            .output_as_file("a.mp4".into())
+            .done()
+            .output_as_file("b.mp4".into())
+            .duration(Duration::from_secs(5))
+            .done();
+
+        assert_eq!(builder.inner_args, ["-ss", "10", "-i", "in.mp4", "-t", "1.5", "-y", "a.mp4", "-t", "5", "-y", "b.mp4"]);
+    }
+}
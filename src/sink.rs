@@ -0,0 +1,120 @@
+//! Where the progress of [`crate::FFmpegBuilder::start_with_progress_sink`] is delivered
+
+use std::{pin::Pin, task::{Context, Poll}};
+
+use tokio::sync::mpsc::{unbounded_channel, Sender, UnboundedReceiver, UnboundedSender};
+
+use crate::FFmpegProgress;
+
+/// Receives the progress of a running FFmpeg, called from a background thread
+pub trait ProgressSink: Send + 'static {
+    fn on_progress(&mut self, progress: FFmpegProgress);
+
+    /// A line FFmpeg wrote to stderr
+    fn on_log(&mut self, _line: &str) {}
+
+    /// The progress is over, `completed` is `false` if FFmpeg stopped without reporting the end, e.g. it crashed
+    fn on_finish(&mut self, _completed: bool) {}
+}
+
+/// Send the progress into a channel, what [`crate::FFmpegBuilder::start_listen_progress`] uses
+pub struct ChannelSink {
+    tx: Sender<FFmpegProgress>,
+}
+
+impl ChannelSink {
+    pub fn new(tx: Sender<FFmpegProgress>) -> Self {
+        Self { tx }
+    }
+}
+
+impl ProgressSink for ChannelSink {
+    fn on_progress(&mut self, progress: FFmpegProgress) {
+        let tx = self.tx.clone();
+
+        // Don't hold FFmpeg back if the receiver is slow
+        std::thread::spawn(move || tx.blocking_send(progress));
+    }
+}
+
+/// The progress of [`crate::FFmpegBuilder::start_progress_stream`] as a [`futures_core::Stream`]
+///
+/// Ends once FFmpeg stops reporting progress, works with the `tokio_stream::StreamExt` adaptors like `throttle`
+pub struct ProgressStream {
+    rx: UnboundedReceiver<FFmpegProgress>,
+}
+
+impl ProgressStream {
+    pub(crate) fn new() -> (StreamSink, Self) {
+        let (tx, rx) = unbounded_channel();
+
+        (StreamSink { tx }, Self { rx })
+    }
+}
+
+impl futures_core::Stream for ProgressStream {
+    type Item = FFmpegProgress;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+/// Feeds a [`ProgressStream`], unbounded so the progress stays in order without holding FFmpeg back
+pub(crate) struct StreamSink {
+    tx: UnboundedSender<FFmpegProgress>,
+}
+
+impl ProgressSink for StreamSink {
+    fn on_progress(&mut self, progress: FFmpegProgress) {
+        let _ = self.tx.send(progress);
+    }
+}
+
+/// Call a closure with every progress
+pub struct CallbackSink<F> {
+    on_progress: F,
+}
+
+impl<F: FnMut(FFmpegProgress) + Send + 'static> CallbackSink<F> {
+    pub fn new(on_progress: F) -> Self {
+        Self { on_progress }
+    }
+}
+
+impl<F: FnMut(FFmpegProgress) + Send + 'static> ProgressSink for CallbackSink<F> {
+    fn on_progress(&mut self, progress: FFmpegProgress) {
+        (self.on_progress)(progress)
+    }
+}
+
+/// Emit the progress as `tracing` events, under the `essi_ffmpeg` target
+///
+/// Progress is logged at the debug level, FFmpeg logs at the info level
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingSink;
+
+#[cfg(feature = "tracing")]
+impl ProgressSink for TracingSink {
+    fn on_progress(&mut self, progress: FFmpegProgress) {
+        tracing::debug!(
+            target: "essi_ffmpeg",
+            frame = progress.frame,
+            out_time_us = progress.out_time_us,
+            speed = progress.speed,
+            "progress",
+        );
+    }
+
+    fn on_log(&mut self, line: &str) {
+        tracing::info!(target: "essi_ffmpeg", "{line}");
+    }
+
+    fn on_finish(&mut self, completed: bool) {
+        match completed {
+            true => tracing::debug!(target: "essi_ffmpeg", "finished"),
+            false => tracing::warn!(target: "essi_ffmpeg", "FFmpeg stopped without finishing"),
+        }
+    }
+}
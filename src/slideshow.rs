@@ -0,0 +1,207 @@
+use std::{path::PathBuf, time::Duration};
+
+use crate::{FFmpeg, FFmpegBuilder, IO};
+
+/// Turn images into a video, each shown for its own duration
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use essi_ffmpeg::slideshow::Slideshow;
+/// Slideshow::new((1920, 1080))
+///     .slide("intro.png", Duration::from_secs(3))
+///     .slide("product.jpg", Duration::from_secs(5))
+///     .crossfade(Duration::from_millis(500))
+///     .background_audio("music.mp3")
+///     .render("slideshow.mp4")?;
+/// # anyhow::Ok(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Slideshow {
+    slides: Vec<(PathBuf, Duration)>,
+    size: (u32, u32),
+    fps: u32,
+    crossfade: Option<Duration>,
+    transition: String,
+    audio: Option<PathBuf>,
+}
+
+impl Slideshow {
+    /// Every image is scaled to fit `size`, padded with black bars if the aspect ratio is different
+    pub fn new(size: (u32, u32)) -> Self {
+        Self {
+            slides: Vec::new(),
+            size,
+            fps: 30,
+            crossfade: None,
+            transition: "fade".to_string(),
+            audio: None,
+        }
+    }
+
+    pub fn slide(mut self, image: impl Into<PathBuf>, duration: Duration) -> Self {
+        self.slides.push((image.into(), duration));
+
+        self
+    }
+
+    pub fn fps(mut self, fps: u32) -> Self {
+        self.fps = fps;
+
+        self
+    }
+
+    /// Blend every slide into the next one, the transition starts when the slide duration ends
+    pub fn crossfade(mut self, duration: Duration) -> Self {
+        self.crossfade = Some(duration);
+
+        self
+    }
+
+    /// xfade transition used by [`Slideshow::crossfade`], defaults to `fade`
+    ///
+    /// e.g. `wipeleft`, `slideup`, `circleopen`, `dissolve`
+    pub fn transition(mut self, transition: impl Into<String>) -> Self {
+        self.transition = transition.into();
+
+        self
+    }
+
+    /// Looped or trimmed to the length of the slideshow
+    pub fn background_audio(mut self, audio: impl Into<PathBuf>) -> Self {
+        self.audio = Some(audio.into());
+
+        self
+    }
+
+    /// Total length of the slideshow, crossfades overlap the next slide so they don't add to it
+    pub fn duration(&self) -> Duration {
+        self.slides.iter().map(|(_, duration)| *duration).sum()
+    }
+
+    /// Input length of the slide at `index`, extended so the crossfade doesn't eat into the next slide
+    fn input_duration(&self, index: usize) -> Duration {
+        let duration = self.slides[index].1;
+
+        match self.crossfade {
+            Some(crossfade) if index + 1 < self.slides.len() => duration + crossfade,
+            _ => duration,
+        }
+    }
+
+    /// The `-filter_complex` graph, outputs `[v]` & `[a]` if there's background audio
+    ///
+    /// Inputs are expected to be every slide in order, then the background audio
+    pub fn filter_graph(&self) -> String {
+        let (width, height) = self.size;
+        let mut graph = Vec::new();
+
+        for index in 0..self.slides.len() {
+            graph.push(format!(
+                "[{index}:v]scale={width}:{height}:force_original_aspect_ratio=decrease,pad={width}:{height}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={},format=yuv420p[s{index}]",
+                self.fps,
+            ));
+        }
+
+        match (self.slides.len(), self.crossfade) {
+            (0, _) => { },
+            (1, _) => graph.push("[s0]null[v]".to_string()),
+            (count, Some(crossfade)) => {
+                let mut offset = Duration::ZERO;
+                let mut previous = "s0".to_string();
+
+                for index in 1..count {
+                    offset += self.slides[index - 1].1;
+
+                    let output = if index + 1 == count { "v".to_string() } else { format!("x{index}") };
+
+                    graph.push(format!(
+                        "[{previous}][s{index}]xfade=transition={}:duration={}:offset={}[{output}]",
+                        self.transition,
+                        crossfade.as_secs_f64(),
+                        offset.as_secs_f64(),
+                    ));
+
+                    previous = output;
+                }
+            },
+            (count, None) => {
+                let inputs = (0..count).map(|index| format!("[s{index}]")).collect::<Vec<_>>().concat();
+
+                graph.push(format!("{inputs}concat=n={count}:v=1:a=0[v]"));
+            },
+        }
+
+        if self.audio.is_some() {
+            graph.push(format!("[{}:a]atrim=duration={},asetpts=PTS-STARTPTS[a]", self.slides.len(), self.duration().as_secs_f64()));
+        }
+
+        graph.join(";")
+    }
+
+    /// Everything needed for the slideshow, continue with the output options (e.g. [`FFmpegBuilder::codec_video`])
+    pub fn build(&self, output: impl Into<PathBuf>) -> anyhow::Result<FFmpegBuilder<IO>> {
+        if self.slides.is_empty() {
+            anyhow::bail!("A slideshow needs at least 1 slide");
+        }
+
+        let mut builder = FFmpeg::quiet()?;
+
+        for (index, (image, _)) in self.slides.iter().enumerate() {
+            builder = builder
+                .args(["-loop", "1", "-framerate", &self.fps.to_string(), "-t", &self.input_duration(index).as_secs_f64().to_string()])
+                .input_with_file(image.clone())
+                .done();
+        }
+
+        if let Some(audio) = &self.audio {
+            builder = builder
+                .args(["-stream_loop", "-1"])
+                .input_with_file(audio.clone())
+                .done();
+        }
+
+        builder = builder.args(["-filter_complex", &self.filter_graph(), "-map", "[v]"]);
+
+        if self.audio.is_some() {
+            builder = builder.args(["-map", "[a]"]);
+        }
+
+        Ok(builder.output_as_file(output.into()))
+    }
+
+    /// Render the slideshow & wait for it to finish
+    pub fn render(&self, output: impl Into<PathBuf>) -> anyhow::Result<()> {
+        self.build(output)?
+            .done()
+            .run_checked()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn crossfade_filter_graph() {
+        let slideshow = Slideshow::new((1280, 720))
+            .slide("a.png", Duration::from_secs(3))
+            .slide("b.png", Duration::from_secs(4))
+            .slide("c.png", Duration::from_secs(2))
+            .crossfade(Duration::from_millis(500))
+            .background_audio("music.mp3");
+
+        let graph = slideshow.filter_graph();
+        let chains = graph.split(';').collect::<Vec<_>>();
+
+        assert_eq!(chains.len(), 6);
+        assert!(chains[0].starts_with("[0:v]scale=1280:720:force_original_aspect_ratio=decrease,"));
+        assert_eq!(chains[3], "[s0][s1]xfade=transition=fade:duration=0.5:offset=3[x1]");
+        assert_eq!(chains[4], "[x1][s2]xfade=transition=fade:duration=0.5:offset=7[v]");
+        assert_eq!(chains[5], "[3:a]atrim=duration=9,asetpts=PTS-STARTPTS[a]");
+
+        assert_eq!(slideshow.input_duration(0), Duration::from_millis(3500));
+        assert_eq!(slideshow.input_duration(2), Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,332 @@
+use std::{ffi::OsString, path::{Path, PathBuf}, time::Duration};
+
+use crate::{analysis::{Silence, SilenceParams}, probe::FFprobe, random_temp_file, FFmpeg, FFmpegBuilder, Normal};
+
+/// Where the segment muxer cuts, see [`SegmentOptions`]
+#[derive(Debug, Clone)]
+pub enum SegmentSplit {
+    /// `-segment_time`, e.g. every 10 minutes
+    Every(Duration),
+    /// `-segment_times`, at these points of the input
+    At(Vec<Duration>),
+    /// `-segment_frames`, at these frame numbers
+    AtFrames(Vec<usize>),
+}
+
+/// Typed options of the segment muxer, used with [`crate::FFmpegBuilder::output_segments`]
+///
+/// Video is only cut on keyframes, so segments can be a bit longer than asked
+#[derive(Debug, Clone)]
+pub struct SegmentOptions {
+    pub split: SegmentSplit,
+    /// `-reset_timestamps`, start every segment from 0, needed to play them one by one
+    pub reset_timestamps: bool,
+    /// `-segment_format`, guessed from the output name if [`Option::None`]
+    pub format: Option<String>,
+    /// `-strftime`, expand the output name as a strftime pattern (e.g. `%Y-%m-%d_%H-%M-%S.mp4`) instead of a segment number
+    pub strftime: bool,
+    /// `-segment_list`, a file listing every segment that was written
+    pub list: Option<PathBuf>,
+    /// `-segment_start_number`
+    pub start_number: Option<usize>,
+}
+
+impl SegmentOptions {
+    pub fn every(duration: Duration) -> Self {
+        Self {
+            split: SegmentSplit::Every(duration),
+            reset_timestamps: true,
+            format: None,
+            strftime: false,
+            list: None,
+            start_number: None,
+        }
+    }
+
+    pub fn at(times: impl IntoIterator<Item = Duration>) -> Self {
+        Self { split: SegmentSplit::At(times.into_iter().collect()), ..Self::every(Duration::ZERO) }
+    }
+
+    pub(crate) fn to_args(&self) -> Vec<OsString> {
+        let mut args: Vec<OsString> = vec!["-f".into(), "segment".into()];
+
+        let join = |values: Vec<String>| values.join(",");
+
+        match &self.split {
+            SegmentSplit::Every(duration) => args.extend(["-segment_time".into(), duration.as_secs_f64().to_string().into()]),
+            SegmentSplit::At(times) => args.extend(["-segment_times".into(), join(times.iter().map(|time| time.as_secs_f64().to_string()).collect()).into()]),
+            SegmentSplit::AtFrames(frames) => args.extend(["-segment_frames".into(), join(frames.iter().map(usize::to_string).collect()).into()]),
+        }
+
+        if self.reset_timestamps {
+            args.extend(["-reset_timestamps".into(), "1".into()]);
+        }
+
+        if let Some(format) = &self.format {
+            args.extend(["-segment_format".into(), format.into()]);
+        }
+
+        if self.strftime {
+            args.extend(["-strftime".into(), "1".into()]);
+        }
+
+        if let Some(list) = &self.list {
+            args.extend(["-segment_list".into(), list.into()]);
+        }
+
+        if let Some(start_number) = self.start_number {
+            args.extend(["-segment_start_number".into(), start_number.to_string().into()]);
+        }
+
+        args
+    }
+}
+
+/// Make a chapter title usable as a file name on every platform
+fn sanitize_file_name(name: &str) -> String {
+    let sanitized = name
+        .chars()
+        .map(|c| if c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*') { '_' } else { c })
+        .collect::<String>();
+
+    // Windows doesn't allow a trailing dot or space
+    sanitized.trim().trim_end_matches('.').to_string()
+}
+
+/// Replace `{index}` & `{title}` in `template`
+///
+/// The index starts from 1 & is zero padded to the width of `count`
+fn render_template(template: &str, index: usize, count: usize, title: &str) -> String {
+    let width = count.to_string().len().max(2);
+
+    template
+        .replace("{index}", &format!("{index:0width$}"))
+        .replace("{title}", title)
+}
+
+impl FFmpeg {
+    /// Cut `input` into one file per chapter, without re-encoding
+    ///
+    /// `out_template` is a path with `{index}` & `{title}` in it, e.g. `out/{index} - {title}.m4a`,
+    /// chapters without a title are named `Chapter <index>`
+    ///
+    /// The cuts happen on the nearest keyframe because the streams are copied
+    ///
+    /// Returns the created files in chapter order
+    pub fn split_by_chapters(input: impl Into<PathBuf>, out_template: impl AsRef<Path>) -> anyhow::Result<Vec<PathBuf>> {
+        let input = input.into();
+        let template = out_template.as_ref().to_string_lossy().to_string();
+
+        let chapters = FFprobe::probe_raw(&input)?.chapters;
+
+        if chapters.is_empty() {
+            anyhow::bail!("{input:?} doesn't have any chapter");
+        }
+
+        let mut outputs = Vec::with_capacity(chapters.len());
+
+        for (index, chapter) in chapters.iter().enumerate() {
+            let index = index + 1;
+
+            let title = chapter.tags.get("title").map(|title| sanitize_file_name(title)).filter(|title| !title.is_empty());
+            let title = title.unwrap_or_else(|| format!("Chapter {index}"));
+
+            let output = PathBuf::from(render_template(&template, index, chapters.len(), &title));
+
+            if let Some(parent) = output.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            let mut builder = Self::quiet()?;
+
+            if let Some(start) = &chapter.start_time {
+                builder = builder.args(["-ss", start]);
+            }
+
+            if let Some(end) = &chapter.end_time {
+                builder = builder.args(["-to", end]);
+            }
+
+            builder
+                .input_with_file(input.clone())
+                .done()
+                // Every output would have all of the chapters otherwise
+                .args(["-map", "0", "-c", "copy", "-map_chapters", "-1"])
+                .output_as_file(output.clone())
+                .done()
+                .run_checked()?;
+
+            outputs.push(output);
+        }
+
+        Ok(outputs)
+    }
+
+    /// Cut `input` into segments without re-encoding, e.g. splitting a recording into 10 minute chunks
+    ///
+    /// `pattern` is the name of every segment, e.g. `out/chunk_%03d.mp4`
+    ///
+    /// Returns the written segments in order
+    pub fn split_into_segments(input: impl Into<PathBuf>, pattern: impl Into<PathBuf>, options: SegmentOptions) -> anyhow::Result<Vec<PathBuf>> {
+        let builder = Self::quiet()?
+            .input_with_file(input.into())
+            .done()
+            .args(["-map", "0", "-c", "copy"]);
+
+        run_segments(builder, pattern.into(), options)
+    }
+
+    /// Cut the first audio stream of `input` into one file per spoken segment, the silence between them is dropped
+    ///
+    /// `pattern` is the name of every segment, e.g. `out/%03d.wav`, the audio is encoded with the default encoder of its extension
+    pub fn split_on_silence(input: impl Into<PathBuf>, pattern: impl Into<PathBuf>, params: SilenceParams) -> anyhow::Result<Vec<SpeechSegment>> {
+        let input = input.into();
+
+        let (silences, duration) = Self::silences_and_duration(input.clone(), &params)?;
+        let ranges = speech_ranges(&silences, duration, &params);
+
+        if ranges.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let select = ranges
+            .iter()
+            .map(|(start, end)| match end {
+                Some(end) => format!("between(t,{},{})", start.as_secs_f64(), end.as_secs_f64()),
+                None => format!("gte(t,{})", start.as_secs_f64()),
+            })
+            .collect::<Vec<_>>()
+            .join("+");
+
+        // The speech is joined back to back, so the cuts are at the end of every range on the new timeline
+        let cuts = ranges
+            .iter()
+            .filter_map(|(start, end)| Some(end.as_ref()?.saturating_sub(*start)))
+            .scan(Duration::ZERO, |total, length| { *total += length; Some(*total) })
+            .take(ranges.len() - 1);
+
+        let builder = Self::quiet()?
+            .input_with_file(input)
+            .done()
+            .args(["-map", "0:a:0", "-af", &format!("aselect='{select}',asetpts=N/SR/TB")]);
+
+        let segments = run_segments(builder, pattern.into(), SegmentOptions::at(cuts))?;
+
+        Ok(segments.into_iter().zip(ranges).map(|(path, (start, end))| SpeechSegment { path, start, end }).collect())
+    }
+}
+
+/// A file written by [`FFmpeg::split_on_silence`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpeechSegment {
+    pub path: PathBuf,
+    /// Where the segment starts in the input
+    pub start: Duration,
+    /// Where the segment ends in the input, [`Option::None`] if it runs to the end & the input duration is unknown
+    pub end: Option<Duration>,
+}
+
+/// The parts between `silences`, padded & merged if the padding overlaps
+fn speech_ranges(silences: &[Silence], duration: Option<Duration>, params: &SilenceParams) -> Vec<(Duration, Option<Duration>)> {
+    let mut ranges = Vec::new();
+    let mut start = Some(Duration::ZERO);
+
+    for silence in silences {
+        if let Some(start) = start.filter(|start| *start < silence.start) {
+            ranges.push((start, Some(silence.start)));
+        }
+
+        start = silence.end;
+    }
+
+    if let Some(start) = start {
+        match duration {
+            Some(duration) if start < duration => ranges.push((start, Some(duration))),
+            Some(_) => { },
+            None => ranges.push((start, None)),
+        }
+    }
+
+    ranges.retain(|(start, end)| end.map_or(true, |end| end.saturating_sub(*start) >= params.min_speech));
+
+    let mut padded: Vec<(Duration, Option<Duration>)> = Vec::with_capacity(ranges.len());
+
+    for (start, end) in ranges {
+        let start = start.saturating_sub(params.padding);
+        let end = end.map(|end| (end + params.padding).min(duration.unwrap_or(Duration::MAX)));
+
+        match padded.last_mut() {
+            Some((_, last_end)) if last_end.is_some_and(|last_end| last_end >= start) => *last_end = end,
+            _ => padded.push((start, end)),
+        }
+    }
+
+    padded
+}
+
+/// Output `builder` to the segment muxer & return the written segments
+fn run_segments(builder: FFmpegBuilder<Normal>, pattern: PathBuf, mut options: SegmentOptions) -> anyhow::Result<Vec<PathBuf>> {
+    let directory = pattern.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    if !directory.as_os_str().is_empty() {
+        std::fs::create_dir_all(&directory)?;
+    }
+
+    // The list is the only way to know the names when strftime is used
+    let temp_list = options.list.is_none().then(random_temp_file);
+    let list = options.list.get_or_insert_with(|| temp_list.clone().unwrap()).clone();
+
+    let result = builder
+        .output_segments(pattern, options)
+        .done()
+        .run_checked();
+
+    let segments = std::fs::read_to_string(list);
+
+    if let Some(temp_list) = temp_list {
+        crate::temp::remove(&temp_list);
+    }
+
+    result?;
+
+    // The list only has the file names
+    Ok(segments?.lines().filter(|line| !line.is_empty()).map(|segment| directory.join(segment)).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn chapter_file_names() {
+        assert_eq!(sanitize_file_name("Part 1: The \"Beginning\"?"), "Part 1_ The _Beginning__");
+        assert_eq!(sanitize_file_name("AC/DC \\ live... "), "AC_DC _ live");
+        assert_eq!(render_template("out/{index} - {title}.m4a", 3, 12, "Intro"), "out/03 - Intro.m4a");
+        assert_eq!(render_template("{index}.mkv", 7, 120, "Intro"), "007.mkv");
+    }
+
+    #[test]
+    fn segment_args() {
+        let options = SegmentOptions { format: Some("mpegts".to_string()), ..SegmentOptions::at([Duration::from_secs(90), Duration::from_millis(200500)]) };
+
+        assert_eq!(options.to_args(), ["-f", "segment", "-segment_times", "90,200.5", "-reset_timestamps", "1", "-segment_format", "mpegts"]);
+    }
+
+    #[test]
+    fn speech_ranges_between_silences() {
+        let seconds = Duration::from_secs_f64;
+        let silences = [
+            Silence { start: Duration::ZERO, end: Some(seconds(1.0)) },
+            Silence { start: seconds(5.0), end: Some(seconds(5.3)) },
+            Silence { start: seconds(5.35), end: Some(seconds(8.0)) },
+            Silence { start: seconds(10.0), end: Some(seconds(12.0)) },
+        ];
+        let params = SilenceParams { padding: seconds(0.25), ..Default::default() };
+
+        assert_eq!(speech_ranges(&silences, Some(seconds(12.0)), &params), [
+            (seconds(0.75), Some(seconds(5.25))),
+            (seconds(7.75), Some(seconds(10.25))),
+        ]);
+        assert_eq!(speech_ranges(&silences[..1], None, &params), [(seconds(0.75), None)]);
+    }
+}
@@ -0,0 +1,384 @@
+//! Inputs & outputs as values, see [`FFmpegBuilder::add_input`] & [`FFmpegBuilder::add_output`]
+//!
+//! The stages of a builder can be read back & changed later by their handle, in any order
+
+use std::{ffi::{OsStr, OsString}, path::PathBuf, time::Duration};
+
+use anyhow::Context;
+
+use crate::{is_output_marker, FFmpegBuilder, InputHandle, Mode, Normal, OutputHandle, TestSource};
+
+/// Options that apply to the whole command, they aren't part of any stage. `true` if they take a value
+const GLOBAL_OPTIONS: &[(&str, bool)] = &[
+    ("-hide_banner", false),
+    ("-nostdin", false),
+    ("-nostats", false),
+    ("-stats", false),
+    ("-benchmark", false),
+    ("-benchmark_all", false),
+    ("-report", false),
+    ("-loglevel", true),
+    ("-v", true),
+    ("-progress", true),
+    ("-stats_period", true),
+    ("-filter_complex", true),
+    ("-filter_complex_script", true),
+    ("-filter_threads", true),
+    ("-init_hw_device", true),
+    ("-filter_hw_device", true),
+];
+
+/// The value of the last `name` in `options`
+fn find_option<'a>(options: &'a [OsString], name: &str) -> Option<&'a OsStr> {
+    options.iter().rposition(|option| option == name).and_then(|index| options.get(index + 1)).map(OsString::as_os_str)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputStage {
+    options: Vec<OsString>,
+    source: OsString,
+}
+
+impl InputStage {
+    /// A file, URL or device
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Self { options: Vec::new(), source: path.into().into_os_string() }
+    }
+
+    /// Generated media, `-f lavfi -i <source>`
+    pub fn lavfi(source: TestSource) -> Self {
+        Self::file(source.to_string()).format("lavfi")
+    }
+
+    /// Force the format instead of probing it, `-f`
+    pub fn format(self, format: impl AsRef<str>) -> Self {
+        self.args(["-f", format.as_ref()])
+    }
+
+    /// Start reading at `position`, `-ss`
+    pub fn seek(self, position: Duration) -> Self {
+        self.args(["-ss".to_string(), position.as_secs_f64().to_string()])
+    }
+
+    /// Only read `duration` of the input, `-t`
+    pub fn duration(self, duration: Duration) -> Self {
+        self.args(["-t".to_string(), duration.as_secs_f64().to_string()])
+    }
+
+    /// Read at `multiplier` times the native speed, `-readrate`
+    pub fn read_rate(self, multiplier: f64) -> Self {
+        self.args(["-readrate".to_string(), multiplier.to_string()])
+    }
+
+    pub fn arg<S: AsRef<OsStr>>(mut self, arg: S) -> Self {
+        self.options.push(arg.as_ref().to_owned());
+
+        self
+    }
+
+    pub fn args<I, S>(self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        args.into_iter().fold(self, Self::arg)
+    }
+
+    /// What's given to `-i`
+    pub fn source(&self) -> &OsStr {
+        &self.source
+    }
+
+    pub fn options(&self) -> &[OsString] {
+        &self.options
+    }
+
+    /// The value of `name`, e.g. `option("-f")`
+    pub fn option(&self, name: &str) -> Option<&OsStr> {
+        find_option(&self.options, name)
+    }
+
+    fn into_args(self) -> Vec<OsString> {
+        self.options.into_iter().chain(["-i".into(), self.source]).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputStage {
+    options: Vec<OsString>,
+    target: OsString,
+    overwrite: bool,
+}
+
+impl OutputStage {
+    /// A file or URL, overwritten if it exists
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Self { options: Vec::new(), target: path.into().into_os_string(), overwrite: true }
+    }
+
+    /// `-f`
+    pub fn format(self, format: impl AsRef<str>) -> Self {
+        self.args(["-f", format.as_ref()])
+    }
+
+    /// `-c:v`
+    pub fn codec_video(self, codec: impl AsRef<str>) -> Self {
+        self.args(["-c:v", codec.as_ref()])
+    }
+
+    /// `-c:a`
+    pub fn codec_audio(self, codec: impl AsRef<str>) -> Self {
+        self.args(["-c:a", codec.as_ref()])
+    }
+
+    /// Add a stream to this output, e.g. `0:v:0` or a filter graph label like `[out]`
+    pub fn map(self, stream: impl AsRef<str>) -> Self {
+        self.args(["-map", stream.as_ref()])
+    }
+
+    /// Skip the first `position` of the output, `-ss`
+    pub fn seek(self, position: Duration) -> Self {
+        self.args(["-ss".to_string(), position.as_secs_f64().to_string()])
+    }
+
+    /// Stop writing after `duration`, `-t`
+    pub fn duration(self, duration: Duration) -> Self {
+        self.args(["-t".to_string(), duration.as_secs_f64().to_string()])
+    }
+
+    /// Same as [`FFmpegBuilder::overwrite`]
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+
+        self
+    }
+
+    pub fn arg<S: AsRef<OsStr>>(mut self, arg: S) -> Self {
+        self.options.push(arg.as_ref().to_owned());
+
+        self
+    }
+
+    pub fn args<I, S>(self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        args.into_iter().fold(self, Self::arg)
+    }
+
+    pub fn target(&self) -> &OsStr {
+        &self.target
+    }
+
+    pub fn options(&self) -> &[OsString] {
+        &self.options
+    }
+
+    /// The value of `name`, e.g. `option("-c:v")`
+    pub fn option(&self, name: &str) -> Option<&OsStr> {
+        find_option(&self.options, name)
+    }
+
+    pub fn is_overwritten(&self) -> bool {
+        self.overwrite
+    }
+
+    fn into_args(self) -> Vec<OsString> {
+        let marker = if self.overwrite { "-y" } else { "-n" };
+
+        self.options.into_iter().chain([marker.into(), self.target]).collect()
+    }
+}
+
+/// Where a stage is in the arguments
+struct StageRange {
+    /// Global options that are mixed in with the options of the stage
+    globals: Vec<OsString>,
+    options: Vec<OsString>,
+    /// From the first option to the path
+    start: usize,
+    marker: usize,
+}
+
+impl<A: Mode> FFmpegBuilder<A> {
+    /// The arguments of the stage ending with the `nth` `marker`, options start after the previous stage
+    fn stage_range(&self, marker: fn(&OsStr) -> bool, nth: usize) -> Option<StageRange> {
+        let at = self.nth_position(marker, nth)?;
+
+        let start = (0..at)
+            .rev()
+            .find(|index| (self.inner_args[*index] == "-i" || is_output_marker(&self.inner_args[*index])) && index + 1 < at)
+            .map_or(0, |previous| previous + 2);
+
+        let (mut globals, mut options) = (Vec::new(), Vec::new());
+        let mut args = self.inner_args[start..at].iter();
+
+        while let Some(arg) = args.next() {
+            match GLOBAL_OPTIONS.iter().find(|(name, _)| arg == *name) {
+                Some((_, true)) => globals.extend([Some(arg.clone()), args.next().cloned()].into_iter().flatten()),
+                Some((_, false)) => globals.push(arg.clone()),
+                None => options.push(arg.clone()),
+            }
+        }
+
+        Some(StageRange { globals, options, start, marker: at })
+    }
+
+    /// The options & path of `input`
+    pub fn input_stage(&self, input: InputHandle) -> Option<InputStage> {
+        let range = self.stage_range(|arg| arg == "-i", input.0)?;
+
+        Some(InputStage { options: range.options, source: self.inner_args.get(range.marker + 1)?.clone() })
+    }
+
+    /// The options & path of `output`
+    pub fn output_stage(&self, output: OutputHandle) -> Option<OutputStage> {
+        let range = self.stage_range(is_output_marker, output.0)?;
+
+        Some(OutputStage {
+            options: range.options,
+            target: self.inner_args.get(range.marker + 1)?.clone(),
+            overwrite: self.inner_args[range.marker] == "-y",
+        })
+    }
+
+    pub fn input_stages(&self) -> Vec<InputStage> {
+        (0..).map(InputHandle).map_while(|input| self.input_stage(input)).collect()
+    }
+
+    pub fn output_stages(&self) -> Vec<OutputStage> {
+        (0..).map(OutputHandle).map_while(|output| self.output_stage(output)).collect()
+    }
+
+    /// Replace the arguments of a stage, the global options in it are kept in front
+    fn replace_stage(mut self, range: StageRange, args: Vec<OsString>) -> Self {
+        let end = range.marker + 2;
+        let replacement = range.globals.into_iter().chain(args).collect::<Vec<_>>();
+        let added = replacement.len() as isize - (end - range.start) as isize;
+        // The stage always ends with its `-i` or `-y`/`-n` & the path
+        let marker = range.start + replacement.len() - 2;
+
+        self.inner_args.splice(range.start..end, replacement);
+
+        // Keep pointing to the same input or output
+        match self.inserting_offset.as_mut() {
+            Some(offset) if *offset >= end => *offset = (*offset as isize + added) as usize,
+            Some(offset) if *offset >= range.start => *offset = marker,
+            _ => { },
+        }
+
+        self
+    }
+
+    /// Change the options of `input`, e.g. add a seek to an input added earlier
+    pub fn with_input(self, input: InputHandle, change: impl FnOnce(InputStage) -> InputStage) -> anyhow::Result<Self> {
+        let stage = self.input_stage(input).with_context(|| format!("There's no input {}", input.0))?;
+        let range = self.stage_range(|arg| arg == "-i", input.0).expect("The stage exists");
+
+        Ok(self.replace_stage(range, change(stage).into_args()))
+    }
+
+    /// Change the options of `output`
+    pub fn with_output(self, output: OutputHandle, change: impl FnOnce(OutputStage) -> OutputStage) -> anyhow::Result<Self> {
+        let stage = self.output_stage(output).with_context(|| format!("There's no output {}", output.0))?;
+        let range = self.stage_range(is_output_marker, output.0).expect("The stage exists");
+
+        Ok(self.replace_stage(range, change(stage).into_args()))
+    }
+}
+
+impl FFmpegBuilder<Normal> {
+    /// Add an input, it always goes before the outputs so inputs & outputs can be added in any order
+    pub fn add_input(self, input: InputStage) -> Self {
+        let Some(first_output) = self.nth_position(is_output_marker, 0) else {
+            return self.args(input.into_args());
+        };
+
+        // Right after the last input, before the options of the first output
+        let at = self.inner_args[..first_output].iter().rposition(|arg| arg == "-i").map_or(0, |index| index + 2);
+
+        self.insert_at(at, input.into_args())
+    }
+
+    pub fn add_output(mut self, output: OutputStage) -> Self {
+        self.begin_output();
+
+        self.args(output.into_args())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::FFmpeg;
+
+    #[test]
+    fn stages_in_any_order() -> anyhow::Result<()> {
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .args(["-hide_banner", "-loglevel", "error"])
+            .add_input(InputStage::file("live.flv").format("flv"))
+            .add_output(OutputStage::file("out.mp4").codec_video("libx264").map("0:v:0").map("1:a:0"))
+            .add_input(InputStage::file("music.mp3").seek(Duration::from_secs(30)))
+            .with_input(InputHandle(0), |input| input.read_rate(1.0))?
+            .with_output(OutputHandle(0), |output| output.overwrite(false))?;
+
+        assert_eq!(builder.inner_args, [
+            "-hide_banner", "-loglevel", "error",
+            "-f", "flv", "-readrate", "1", "-i", "live.flv",
+            "-ss", "30", "-i", "music.mp3",
+            "-c:v", "libx264", "-map", "0:v:0", "-map", "1:a:0", "-n", "out.mp4",
+        ]);
+
+        let input = builder.input_stage(InputHandle(0)).unwrap();
+        assert_eq!(input.option("-f"), Some(OsStr::new("flv")));
+        assert_eq!(input.source(), "live.flv");
+
+        let outputs = builder.output_stages();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].option("-c:v"), Some(OsStr::new("libx264")));
+        assert!(!outputs[0].is_overwritten());
+
+        assert!(builder.with_input(InputHandle(2), |input| input).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn stages_of_chained_builders() {
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("in.mkv".into())
+            .done()
+            .args(["-filter_complex", "[0:v]scale=1280:-2[v]"])
+            .output_as_file("out.mp4".into())
+            .args(["-map", "[v]"])
+            .done();
+
+        let output = builder.output_stage(OutputHandle(0)).unwrap();
+        assert_eq!(output.options(), ["-map", "[v]"]);
+    }
+
+    #[test]
+    fn builder_calls_after_changing_the_current_stage() -> anyhow::Result<()> {
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("a.mp4".into())
+            .done()
+            .output_as_file("out.mp4".into())
+            .with_output(OutputHandle(0), |output| output.codec_video("libx264"))?
+            .overwrite(false)
+            .codec_audio("aac")
+            .done();
+
+        assert_eq!(builder.inner_args, ["-i", "a.mp4", "-c:v", "libx264", "-c:a", "aac", "-n", "out.mp4"]);
+
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_lavfi(TestSource::Custom("testsrc".to_string()))
+            .with_input(InputHandle(0), |input| input.read_rate(1.0))?
+            .args(["-t", "5"])
+            .done();
+
+        assert_eq!(builder.inner_args, ["-f", "lavfi", "-readrate", "1", "-t", "5", "-i", "testsrc"]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,330 @@
+use std::{collections::{BTreeMap, VecDeque}, io::Read, process::ChildStderr, sync::{Arc, Mutex}, thread::JoinHandle, time::Duration};
+
+use tokio::io::AsyncReadExt;
+
+/// Used when something needs stderr but no ring buffer capacity was given
+pub(crate) const DEFAULT_RING_BUFFER_CAPACITY: usize = 64 * 1024;
+
+pub(crate) type LineHandler = Box<dyn FnMut(&str) + Send>;
+
+/// Reads FFmpeg stderr from a background thread (or task for [`crate::AsyncFFmpegCommand`]), keeping the last `capacity` bytes
+/// & parsing the lines that the crate cares about
+pub(crate) struct StderrCollector {
+    inner: Arc<Mutex<Collected>>,
+    reader: Mutex<Option<Reader>>,
+}
+
+enum Reader {
+    Thread(JoinHandle<()>),
+    Task(tokio::task::JoinHandle<()>),
+}
+
+struct Collected {
+    buffer: VecDeque<u8>,
+    capacity: usize,
+    truncated: bool,
+    benchmark: Option<FFmpegBenchmark>,
+    /// The line being read
+    line: Vec<u8>,
+    on_line: Option<LineHandler>,
+}
+
+impl Collected {
+    fn new(capacity: usize, benchmark: bool, on_line: Option<LineHandler>) -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(Self {
+            buffer: VecDeque::with_capacity(capacity),
+            capacity,
+            truncated: false,
+            benchmark: benchmark.then(FFmpegBenchmark::default),
+            line: Vec::new(),
+            on_line,
+        }))
+    }
+
+    fn push(&mut self, chunk: &[u8]) {
+        self.buffer.extend(chunk);
+
+        let overflow = self.buffer.len().saturating_sub(self.capacity);
+        if overflow > 0 {
+            self.buffer.drain(..overflow);
+            self.truncated = true;
+        }
+
+        if self.benchmark.is_none() && self.on_line.is_none() { return };
+
+        // The stats line is updated with \r
+        for &byte in chunk {
+            if byte == b'\n' || byte == b'\r' {
+                let text = String::from_utf8_lossy(&self.line);
+
+                if let Some(benchmark) = self.benchmark.as_mut() {
+                    benchmark.parse_line(&text);
+                }
+
+                if let Some(on_line) = self.on_line.as_mut().filter(|_| !text.trim().is_empty()) {
+                    on_line(&text);
+                }
+
+                self.line.clear();
+            } else {
+                self.line.push(byte);
+            }
+        }
+    }
+}
+
+impl StderrCollector {
+    /// `on_line` is called with every line, e.g. for [`crate::sink::ProgressSink::on_log`]
+    pub(crate) fn spawn(mut stderr: ChildStderr, capacity: usize, benchmark: bool, on_line: Option<LineHandler>) -> Self {
+        let inner = Collected::new(capacity, benchmark, on_line);
+
+        let reader = std::thread::spawn({
+            let inner = inner.clone();
+
+            move || {
+                let mut chunk = [0u8; 4096];
+
+                while let Ok(len @ 1..) = stderr.read(&mut chunk) {
+                    let Ok(mut inner) = inner.lock() else { break };
+
+                    inner.push(&chunk[..len]);
+                }
+            }
+        });
+
+        Self { inner, reader: Mutex::new(Some(Reader::Thread(reader))) }
+    }
+
+    /// Same as [`StderrCollector::spawn`] with a tokio task, has to be called inside a tokio runtime
+    pub(crate) fn spawn_async(mut stderr: tokio::process::ChildStderr, capacity: usize, benchmark: bool, on_line: Option<LineHandler>) -> Self {
+        let inner = Collected::new(capacity, benchmark, on_line);
+
+        let reader = tokio::spawn({
+            let inner = inner.clone();
+
+            async move {
+                let mut chunk = [0u8; 4096];
+
+                while let Ok(len @ 1..) = stderr.read(&mut chunk).await {
+                    let Ok(mut inner) = inner.lock() else { break };
+
+                    inner.push(&chunk[..len]);
+                }
+            }
+        });
+
+        Self { inner, reader: Mutex::new(Some(Reader::Task(reader))) }
+    }
+
+    fn take_reader(&self) -> Option<Reader> {
+        self.reader.lock().ok().and_then(|mut reader| reader.take())
+    }
+
+    /// Wait until everything FFmpeg wrote is collected, only call this after FFmpeg has exited
+    pub(crate) fn finish(&self) {
+        if let Some(Reader::Thread(reader)) = self.take_reader() {
+            let _ = reader.join();
+        }
+    }
+
+    /// Same as [`StderrCollector::finish`] for [`StderrCollector::spawn_async`]
+    pub(crate) async fn finish_async(&self) {
+        if let Some(Reader::Task(reader)) = self.take_reader() {
+            let _ = reader.await;
+        }
+    }
+
+    /// Everything that's currently in the buffer
+    ///
+    /// The first partial line is skipped once older output has been dropped
+    pub(crate) fn contents(&self) -> String {
+        let Ok(inner) = self.inner.lock() else { return String::new() };
+
+        let (front, back) = inner.buffer.as_slices();
+        let mut bytes = [front, back].concat();
+
+        if inner.truncated {
+            let start = bytes.iter().position(|&byte| byte == b'\n').map_or(0, |newline| newline + 1);
+            bytes.drain(..start);
+        }
+
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    pub(crate) fn benchmark(&self) -> Option<FFmpegBenchmark> {
+        self.inner.lock().ok()?.benchmark.clone()
+    }
+}
+
+/// Parsed `-benchmark` & `-benchmark_all` report, see [`crate::FFmpegBuilder::benchmark`]
+#[derive(Debug, Clone, Default)]
+pub struct FFmpegBenchmark {
+    /// User CPU time
+    pub utime: Option<Duration>,
+    /// System CPU time
+    pub stime: Option<Duration>,
+    /// Real (wall clock) time
+    pub rtime: Option<Duration>,
+    /// Peak memory usage in bytes
+    pub maxrss: Option<u64>,
+    /// Time spent per task (e.g. "decode_video 0.0", "encode_video 0.0"), summed across all of the frames
+    pub tasks: BTreeMap<String, FFmpegBenchmarkTask>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FFmpegBenchmarkTask {
+    pub user: Duration,
+    pub sys: Duration,
+    pub real: Duration,
+    /// How many times the task was reported
+    pub count: usize,
+}
+
+impl FFmpegBenchmark {
+    fn parse_line(&mut self, line: &str) {
+        let Some((_, report)) = line.split_once("bench: ") else { return };
+
+        // bench: utime=0.123s stime=0.045s rtime=0.234s
+        // bench: maxrss=12345KiB
+        if report.contains('=') {
+            for (key, value) in report.split_whitespace().filter_map(|kv| kv.split_once('=')) {
+                let seconds = || value.trim_end_matches('s').parse::<f64>().ok().map(Duration::from_secs_f64);
+
+                match key {
+                    "utime" => self.utime = seconds(),
+                    "stime" => self.stime = seconds(),
+                    "rtime" => self.rtime = seconds(),
+                    "maxrss" => self.maxrss = value.trim_end_matches(char::is_alphabetic).parse::<u64>().ok().map(|kib| kib * 1024),
+                    _ => { }
+                }
+            }
+
+            return;
+        }
+
+        // bench:      123 user        4 sys      130 real decode_video 0.0
+        let mut words = report.split_whitespace();
+        let mut micros = |unit: &str| {
+            let value = words.next()?.parse::<u64>().ok()?;
+            (words.next()? == unit).then(|| Duration::from_micros(value))
+        };
+
+        let (Some(user), Some(sys), Some(real)) = (micros("user"), micros("sys"), micros("real")) else { return };
+        let task = words.collect::<Vec<_>>().join(" ");
+
+        let entry = self.tasks.entry(task).or_default();
+        entry.user += user;
+        entry.sys += sys;
+        entry.real += real;
+        entry.count += 1;
+    }
+}
+
+/// Whether running FFmpeg again could succeed, see [`crate::FFmpegError::failure_kind`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// Most likely transient, e.g. a network timeout or a connection reset
+    Retryable,
+    /// Fails the same way every time, e.g. invalid data or an unsupported codec
+    Permanent,
+    /// Nothing in stderr explains the failure
+    Unknown,
+}
+
+/// Messages of transient failures, lowercase
+const RETRYABLE: &[&str] = &[
+    "connection timed out",
+    "operation timed out",
+    "connection reset by peer",
+    "connection refused",
+    "network is unreachable",
+    "no route to host",
+    "temporary failure in name resolution",
+    "resource temporarily unavailable",
+    "input/output error",
+    "broken pipe",
+    "server returned 408",
+    "server returned 429",
+    "server returned 5",
+];
+
+/// Messages of failures that won't go away by retrying, lowercase
+const PERMANENT: &[&str] = &[
+    "invalid data found when processing input",
+    "no such file or directory",
+    "permission denied",
+    "unknown encoder",
+    "unknown decoder",
+    "decoder not found",
+    "encoder not found",
+    "not currently supported in container",
+    "could not find tag for codec",
+    "unrecognized option",
+    "option not found",
+    "invalid argument",
+    "no space left on device",
+    "server returned 4",
+];
+
+/// The first line of `stderr` that explains the failure decides, later errors are usually caused by it
+pub(crate) fn classify_failure(stderr: &str) -> FailureKind {
+    stderr
+        .lines()
+        .map(str::to_lowercase)
+        .find_map(|line| {
+            if RETRYABLE.iter().any(|pattern| line.contains(pattern)) {
+                Some(FailureKind::Retryable)
+            } else if PERMANENT.iter().any(|pattern| line.contains(pattern)) {
+                Some(FailureKind::Permanent)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(FailureKind::Unknown)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn benchmark_parsing() {
+        let mut benchmark = FFmpegBenchmark::default();
+
+        for line in [
+            "bench:      120 user        4 sys      130 real decode_video 0.0",
+            "bench:       80 user        6 sys       90 real decode_video 0.0",
+            "bench:     1000 user       10 sys     1200 real encode_video 0.0",
+            "frame=  250 fps=0.0 q=-1.0 Lsize=     410kB time=00:00:10.00 bitrate= 335.9kbits/s speed=  42x",
+            "bench: utime=0.123s stime=0.045s rtime=0.234s",
+            "bench: maxrss=12345KiB",
+        ] {
+            benchmark.parse_line(line);
+        }
+
+        assert_eq!(benchmark.utime, Some(Duration::from_millis(123)));
+        assert_eq!(benchmark.stime, Some(Duration::from_millis(45)));
+        assert_eq!(benchmark.rtime, Some(Duration::from_millis(234)));
+        assert_eq!(benchmark.maxrss, Some(12345 * 1024));
+
+        let decode = benchmark.tasks["decode_video 0.0"];
+        assert_eq!(decode.user, Duration::from_micros(200));
+        assert_eq!(decode.real, Duration::from_micros(220));
+        assert_eq!(decode.count, 2);
+
+        assert_eq!(benchmark.tasks["encode_video 0.0"].count, 1);
+        assert_eq!(benchmark.tasks.len(), 2);
+    }
+
+    #[test]
+    fn failure_classification() {
+        let reset = "[tls @ 0x5581] Error in the pull function.\n[https @ 0x5581] Connection reset by peer\nhttps://example.com/live.m3u8: Invalid data found when processing input\n";
+        assert_eq!(classify_failure(reset), FailureKind::Retryable);
+
+        assert_eq!(classify_failure("[https @ 0x55] HTTP error 503 Service Unavailable\nServer returned 5XX Server Error reply"), FailureKind::Retryable);
+        assert_eq!(classify_failure("[in#0 @ 0x55] Error opening input: Server returned 404 Not Found"), FailureKind::Permanent);
+        assert_eq!(classify_failure("Unknown encoder 'libfdk_aac'"), FailureKind::Permanent);
+        assert_eq!(classify_failure("input.mp4: Invalid data found when processing input"), FailureKind::Permanent);
+        assert_eq!(classify_failure("Conversion failed!"), FailureKind::Unknown);
+    }
+}
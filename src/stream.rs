@@ -0,0 +1,247 @@
+use std::fmt::Display;
+
+use crate::{probe::StreamKind, FFmpegBuilder, InputHandle, IO};
+
+/// A stream of an output, counted the same way as FFmpeg stream specifiers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StreamSelector {
+    /// By index across every stream, `0`
+    Index(usize),
+    /// The nth video stream, `v:0`
+    Video(usize),
+    /// The nth audio stream, `a:0`
+    Audio(usize),
+    /// The nth subtitle stream, `s:0`
+    Subtitle(usize),
+    /// The nth data stream, `d:0`
+    Data(usize),
+    /// The nth attachment, `t:0`
+    Attachment(usize),
+}
+
+impl StreamSelector {
+    /// The selector of the nth stream of `codec_type` as reported by ffprobe, e.g. `audio`
+    pub fn from_codec_type(codec_type: &str, nth: usize) -> Option<Self> {
+        Some(match codec_type {
+            "video" => Self::Video(nth),
+            "audio" => Self::Audio(nth),
+            "subtitle" => Self::Subtitle(nth),
+            "data" => Self::Data(nth),
+            "attachment" => Self::Attachment(nth),
+            _ => return None,
+        })
+    }
+
+    /// The selector of the nth stream of `kind`, [`Option::None`] for [`StreamKind::Unknown`]
+    pub fn of_kind(kind: StreamKind, nth: usize) -> Option<Self> {
+        Some(match kind {
+            StreamKind::Video => Self::Video(nth),
+            StreamKind::Audio => Self::Audio(nth),
+            StreamKind::Subtitle => Self::Subtitle(nth),
+            StreamKind::Data => Self::Data(nth),
+            StreamKind::Attachment => Self::Attachment(nth),
+            StreamKind::Unknown => return None,
+        })
+    }
+}
+
+impl Display for StreamSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamSelector::Index(index) => write!(f, "{index}"),
+            StreamSelector::Video(index) => write!(f, "v:{index}"),
+            StreamSelector::Audio(index) => write!(f, "a:{index}"),
+            StreamSelector::Subtitle(index) => write!(f, "s:{index}"),
+            StreamSelector::Data(index) => write!(f, "d:{index}"),
+            StreamSelector::Attachment(index) => write!(f, "t:{index}"),
+        }
+    }
+}
+
+/// A bitstream filter, changes how packets are stored without re-encoding them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Bsf {
+    /// `h264_mp4toannexb`, H.264 from mp4/mkv into MPEG-TS or raw `.h264`
+    H264Mp4ToAnnexB,
+    /// `hevc_mp4toannexb`, same as [`Bsf::H264Mp4ToAnnexB`] for HEVC
+    HevcMp4ToAnnexB,
+    /// `aac_adtstoasc`, AAC from MPEG-TS or raw `.aac` into mp4/mov
+    AacAdtsToAsc,
+    /// `extract_extradata`, copy the codec headers out of the packets
+    ExtractExtradata,
+    /// Any other bitstream filter, e.g. `setts=pts=PTS-STARTPTS`
+    Custom(String),
+}
+
+impl Display for Bsf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Bsf::H264Mp4ToAnnexB => write!(f, "h264_mp4toannexb"),
+            Bsf::HevcMp4ToAnnexB => write!(f, "hevc_mp4toannexb"),
+            Bsf::AacAdtsToAsc => write!(f, "aac_adtstoasc"),
+            Bsf::ExtractExtradata => write!(f, "extract_extradata"),
+            Bsf::Custom(bsf) => write!(f, "{bsf}"),
+        }
+    }
+}
+
+impl FFmpegBuilder<IO> {
+    /// Tag a stream of this output with an ISO 639-2 language code, e.g. `eng` or `jpn`
+    pub fn language(self, stream: StreamSelector, language: impl AsRef<str>) -> Self {
+        self.args([format!("-metadata:s:{stream}"), format!("language={}", language.as_ref())])
+    }
+
+    /// Take the global metadata of this output from `input` instead of the first input, `-map_metadata`
+    pub fn copy_metadata_from(self, input: InputHandle) -> anyhow::Result<Self> {
+        self.map_from("-map_metadata", input)
+    }
+
+    /// Take the chapters of this output from `input` instead of the first input with chapters, `-map_chapters`
+    pub fn copy_chapters_from(self, input: InputHandle) -> anyhow::Result<Self> {
+        self.map_from("-map_chapters", input)
+    }
+
+    /// Don't copy the global metadata of any input, `-map_metadata -1`
+    pub fn no_metadata(self) -> Self {
+        self.args(["-map_metadata", "-1"])
+    }
+
+    /// Don't copy the chapters of any input, `-map_chapters -1`
+    pub fn no_chapters(self) -> Self {
+        self.args(["-map_chapters", "-1"])
+    }
+
+    fn map_from(self, option: &str, input: InputHandle) -> anyhow::Result<Self> {
+        self.check_input(option, input)?;
+
+        Ok(self.args([option.to_string(), input.0.to_string()]))
+    }
+
+    fn check_input(&self, option: &str, input: InputHandle) -> anyhow::Result<()> {
+        let inputs = self.inputs_before();
+
+        if input.0 >= inputs {
+            anyhow::bail!("{option} uses input {} but this output only has {inputs} inputs", input.0);
+        }
+
+        Ok(())
+    }
+
+    /// Add a stream of the first input to this output, `-map 0:v:0`
+    ///
+    /// Once anything is mapped FFmpeg doesn't pick any stream by itself, every stream of the output has to be mapped
+    pub fn map_stream(self, stream: StreamSelector) -> anyhow::Result<Self> {
+        self.map_input(InputHandle(0), stream)
+    }
+
+    /// Add a stream of `input` to this output, `map_input_stream(InputHandle(1), StreamKind::Audio, 2)` is `-map 1:a:2`
+    pub fn map_input_stream(self, input: InputHandle, kind: StreamKind, nth: usize) -> anyhow::Result<Self> {
+        let stream = StreamSelector::of_kind(kind, nth).ok_or_else(|| anyhow::anyhow!("Can't map a stream of an unknown kind"))?;
+
+        self.map_input(input, stream)
+    }
+
+    /// Add a stream of `input` to this output, `-map 1:a:0`
+    pub fn map_input(self, input: InputHandle, stream: StreamSelector) -> anyhow::Result<Self> {
+        self.check_input("-map", input)?;
+
+        Ok(self.args(["-map".to_string(), format!("{}:{stream}", input.0)]))
+    }
+
+    /// Same as [`FFmpegBuilder::map_input`] but FFmpeg doesn't fail if the stream doesn't exist, `-map 0:s:0?`
+    pub fn map_input_optional(self, input: InputHandle, stream: StreamSelector) -> anyhow::Result<Self> {
+        self.check_input("-map", input)?;
+
+        Ok(self.args(["-map".to_string(), format!("{}:{stream}?", input.0)]))
+    }
+
+    /// Remove a stream of `input` that was mapped before, e.g. after [`FFmpegBuilder::map_all`], `-map -0:s:0`
+    pub fn negative_map(self, input: InputHandle, stream: StreamSelector) -> anyhow::Result<Self> {
+        self.check_input("-map", input)?;
+
+        Ok(self.args(["-map".to_string(), format!("-{}:{stream}", input.0)]))
+    }
+
+    /// Filter the packets of a stream of this output, `-bsf:v:0 h264_mp4toannexb`, only works with stream copy or after encoding
+    pub fn bitstream_filter(self, stream: StreamSelector, bsf: Bsf) -> Self {
+        self.args([format!("-bsf:{stream}"), bsf.to_string()])
+    }
+
+    /// Only keep video, `-an -sn -dn`
+    ///
+    /// These also drop streams selected with `-map`, e.g. after [`FFmpegBuilder::map_all`]
+    pub fn video_only(self) -> Self {
+        self.args(["-an", "-sn", "-dn"])
+    }
+
+    /// Only keep audio, `-vn -sn -dn`, e.g. to extract the soundtrack without the cover art
+    pub fn audio_only(self) -> Self {
+        self.args(["-vn", "-sn", "-dn"])
+    }
+
+    /// `-sn`, subtitles often can't be converted to the codecs of the output container
+    pub fn drop_subtitles(self) -> Self {
+        self.arg("-sn")
+    }
+
+    /// `-dn`, e.g. timecode or GPS tracks of camera footage
+    pub fn drop_data_streams(self) -> Self {
+        self.arg("-dn")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::FFmpeg;
+
+    #[test]
+    fn stream_maps() -> anyhow::Result<()> {
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("video.mkv".into())
+            .done()
+            .input_with_file("dub.mka".into())
+            .done()
+            .output_as_file("out.mkv".into())
+            .map_all()
+            .negative_map(InputHandle(0), StreamSelector::Subtitle(1))?
+            .map_input_stream(InputHandle(1), StreamKind::Audio, 2)?
+            .map_input_optional(InputHandle(1), StreamSelector::Subtitle(0))?;
+
+        let builder = builder.done();
+
+        assert_eq!(builder.inner_args[4..], [
+            "-map", "0", "-map", "1", "-map", "-0:s:1", "-map", "1:a:2", "-map", "1:s:0?", "-y", "out.mkv",
+        ]);
+
+        let single = FFmpeg::new_with_program("ffmpeg").input_with_file("video.mkv".into()).done().output_as_file("out.mkv".into());
+        assert!(single.map_input_stream(InputHandle(1), StreamKind::Audio, 0).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn metadata_sources() -> anyhow::Result<()> {
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("video.mkv".into())
+            .done()
+            .input_with_file("tags.mka".into())
+            .done()
+            .output_as_file("out.mkv".into())
+            .copy_metadata_from(InputHandle(1))?
+            .no_chapters();
+
+        assert!(builder.copy_chapters_from(InputHandle(2)).is_err());
+
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("video.mkv".into())
+            .done()
+            .output_as_file("out.mkv".into())
+            .copy_chapters_from(InputHandle(0))?
+            .done();
+
+        assert_eq!(builder.inner_args[2..], ["-map_chapters", "0", "-y", "out.mkv"]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+use std::{io::{BufRead, BufReader}, process::{ExitStatus, Stdio}, sync::{atomic::{AtomicBool, AtomicU32, Ordering}, Arc}, thread::JoinHandle, time::Duration};
+
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::{process, FFmpegBuilder, FFmpegProgress, Normal};
+
+/// When & how often [`FFmpegSupervisor`] restarts FFmpeg
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    /// [`Option::None`] to restart forever
+    pub max_retries: Option<usize>,
+    /// Delay before the first restart
+    pub initial_backoff: Duration,
+    /// The delay is multiplied by this on every restart
+    pub backoff_multiplier: f32,
+    /// Upper bound of the delay
+    pub max_backoff: Duration,
+    /// Don't restart when FFmpeg exits successfully
+    pub only_on_failure: bool,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: None,
+            initial_backoff: Duration::from_secs(1),
+            backoff_multiplier: 2.0,
+            max_backoff: Duration::from_secs(30),
+            only_on_failure: true,
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Delay before the given restart, starting from 1
+    pub fn backoff(&self, restart: usize) -> Duration {
+        let multiplier = self.backoff_multiplier.max(1.0).powi(restart.saturating_sub(1).min(i32::MAX as usize) as i32);
+
+        // Computed as f32 first, an infinite multiplier would make Duration panic
+        Duration::from_secs_f32((self.initial_backoff.as_secs_f32() * multiplier).min(self.max_backoff.as_secs_f32()))
+    }
+}
+
+/// Everything that happens across all of the supervised FFmpeg runs
+#[derive(Debug)]
+pub enum FFmpegSupervisorEvent {
+    /// `attempt` starts from 0, every restart increments it
+    Started { attempt: usize, pid: u32 },
+    Progress(FFmpegProgress),
+    /// A single line from FFmpeg stderr
+    Log(String),
+    Exited { attempt: usize, status: ExitStatus },
+    Restarting { attempt: usize, delay: Duration },
+    /// The restart policy doesn't allow another restart
+    GaveUp,
+    /// Stopped with [`FFmpegSupervisorHandle::stop`], or FFmpeg exited successfully with [`RestartPolicy::only_on_failure`]
+    Stopped,
+    /// FFmpeg couldn't be started or waited for, nothing is restarted after this
+    Failed(String),
+}
+
+/// Keeps FFmpeg running according to a [`RestartPolicy`], for 24/7 live transcoding
+///
+/// A [`crate::FFmpegCommand`] can't be started twice, so the supervisor takes a function that builds a fresh builder on every (re)start
+pub struct FFmpegSupervisor<F> {
+    builder: F,
+    policy: RestartPolicy,
+}
+
+impl<F> FFmpegSupervisor<F>
+where
+    F: FnMut() -> FFmpegBuilder<Normal> + Send + 'static,
+{
+    pub fn new(builder: F) -> Self {
+        Self { builder, policy: RestartPolicy::default() }
+    }
+
+    pub fn restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.policy = policy;
+
+        self
+    }
+
+    /// Start supervising in a background thread
+    ///
+    /// Stderr of the built commands is always piped, it is forwarded as [`FFmpegSupervisorEvent::Log`]
+    ///
+    /// The event channel is unbounded so a slow reader never stalls FFmpeg
+    pub fn start(self) -> (FFmpegSupervisorHandle, UnboundedReceiver<FFmpegSupervisorEvent>) {
+        let (event_tx, event_rx) = unbounded_channel();
+
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let pid = Arc::new(AtomicU32::new(0));
+
+        let thread = std::thread::spawn({
+            let shutdown = shutdown.clone();
+            let pid = pid.clone();
+
+            move || self.supervise(event_tx, shutdown, pid)
+        });
+
+        (FFmpegSupervisorHandle { thread, shutdown, pid }, event_rx)
+    }
+
+    /// Every way out ends with [`FFmpegSupervisorEvent::GaveUp`], [`FFmpegSupervisorEvent::Stopped`] or [`FFmpegSupervisorEvent::Failed`]
+    fn supervise(self, event_tx: UnboundedSender<FFmpegSupervisorEvent>, shutdown: Arc<AtomicBool>, current_pid: Arc<AtomicU32>) -> anyhow::Result<ExitStatus> {
+        let (result, event) = match self.restart_loop(&event_tx, shutdown, current_pid) {
+            Ok((status, event)) => (Ok(status), event),
+            Err(error) => {
+                let event = FFmpegSupervisorEvent::Failed(format!("{error:#}"));
+
+                (Err(error), event)
+            },
+        };
+
+        // SAFETY: we just don't care, nobody is listening anymore
+        let _ = event_tx.send(event);
+
+        result
+    }
+
+    /// Returns the status of the last run & the event saying why it ended
+    fn restart_loop(mut self, event_tx: &UnboundedSender<FFmpegSupervisorEvent>, shutdown: Arc<AtomicBool>, current_pid: Arc<AtomicU32>) -> anyhow::Result<(ExitStatus, FFmpegSupervisorEvent)> {
+        let mut attempt = 0;
+
+        loop {
+            let mut progress_rx = None;
+            let mut command = (self.builder)()
+                .stderr(Stdio::piped())
+                .start_listen_progress(&mut progress_rx)?;
+
+            current_pid.store(command.pid(), Ordering::SeqCst);
+
+            // Stopped while this run was being started
+            if shutdown.load(Ordering::SeqCst) {
+                let _ = process::interrupt(command.pid());
+            }
+
+            // SAFETY: we just don't care, nobody is listening anymore
+            let _ = event_tx.send(FFmpegSupervisorEvent::Started { attempt, pid: command.pid() });
+
+            if let Some(mut progress_rx) = progress_rx {
+                let event_tx = event_tx.clone();
+
+                std::thread::spawn(move || {
+                    while let Some(progress) = progress_rx.blocking_recv() {
+                        let _ = event_tx.send(FFmpegSupervisorEvent::Progress(progress));
+                    }
+                });
+            }
+
+            let log_forwarder = command.take_stderr().map(|stderr| {
+                let event_tx = event_tx.clone();
+
+                std::thread::spawn(move || {
+                    for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                        let _ = event_tx.send(FFmpegSupervisorEvent::Log(line));
+                    }
+                })
+            });
+
+            let status = command.wait()?;
+            current_pid.store(0, Ordering::SeqCst);
+
+            // Make sure the logs are sent before the exit event
+            if let Some(log_forwarder) = log_forwarder {
+                let _ = log_forwarder.join();
+            }
+
+            let _ = event_tx.send(FFmpegSupervisorEvent::Exited { attempt, status });
+
+            if shutdown.load(Ordering::SeqCst) || (status.success() && self.policy.only_on_failure) {
+                return Ok((status, FFmpegSupervisorEvent::Stopped));
+            }
+
+            if self.policy.max_retries.is_some_and(|max_retries| attempt >= max_retries) {
+                return Ok((status, FFmpegSupervisorEvent::GaveUp));
+            }
+
+            attempt += 1;
+
+            let delay = self.policy.backoff(attempt);
+            let _ = event_tx.send(FFmpegSupervisorEvent::Restarting { attempt, delay });
+
+            // Sleep in small steps so stopping doesn't wait for the whole backoff
+            let mut slept = Duration::ZERO;
+            while slept < delay {
+                if shutdown.load(Ordering::SeqCst) { return Ok((status, FFmpegSupervisorEvent::Stopped)) };
+
+                let step = (delay - slept).min(Duration::from_millis(100));
+                std::thread::sleep(step);
+                slept += step;
+            }
+        }
+    }
+}
+
+pub struct FFmpegSupervisorHandle {
+    thread: JoinHandle<anyhow::Result<ExitStatus>>,
+    shutdown: Arc<AtomicBool>,
+    pid: Arc<AtomicU32>,
+}
+
+impl FFmpegSupervisorHandle {
+    /// Pid of the currently running FFmpeg, [`Option::None`] while waiting to restart
+    pub fn pid(&self) -> Option<u32> {
+        match self.pid.load(Ordering::SeqCst) {
+            0 => None,
+            pid => Some(pid),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Stop the current FFmpeg run & don't restart it again
+    ///
+    /// Returns the exit status of the last run
+    pub fn stop(self) -> anyhow::Result<ExitStatus> {
+        self.shutdown.store(true, Ordering::SeqCst);
+
+        if let Some(pid) = self.pid() {
+            process::interrupt(pid).or_else(|_| process::kill(pid))?;
+        }
+
+        self.join()
+    }
+
+    /// Wait until the restart policy gives up
+    ///
+    /// Returns the exit status of the last run
+    pub fn join(self) -> anyhow::Result<ExitStatus> {
+        match self.thread.join() {
+            Ok(result) => result,
+            Err(_) => anyhow::bail!("The supervisor thread panicked"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::FFmpeg;
+
+    #[test]
+    fn backoff_growth() {
+        let policy = RestartPolicy { initial_backoff: Duration::from_millis(500), max_backoff: Duration::from_secs(3), ..Default::default() };
+
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(2));
+        assert_eq!(policy.backoff(4), Duration::from_secs(3));
+        assert_eq!(policy.backoff(usize::MAX), Duration::from_secs(3));
+
+        // Never shrinks
+        let policy = RestartPolicy { backoff_multiplier: 0.5, ..policy };
+        assert_eq!(policy.backoff(5), Duration::from_millis(500));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn gives_up_after_max_retries() {
+        let policy = RestartPolicy { max_retries: Some(2), initial_backoff: Duration::from_millis(1), ..Default::default() };
+        let (handle, mut events) = FFmpegSupervisor::new(|| FFmpeg::new_with_program("false")).restart_policy(policy).start();
+
+        let mut restarts = Vec::new();
+
+        let last = loop {
+            match events.blocking_recv().expect("The supervisor stopped without a final event") {
+                FFmpegSupervisorEvent::Restarting { attempt, .. } => restarts.push(attempt),
+                event @ (FFmpegSupervisorEvent::GaveUp | FFmpegSupervisorEvent::Stopped | FFmpegSupervisorEvent::Failed(_)) => break event,
+                _ => { },
+            }
+        };
+
+        assert!(matches!(last, FFmpegSupervisorEvent::GaveUp));
+        assert_eq!(restarts, [1, 2]);
+        assert!(!handle.join().unwrap().success());
+    }
+
+    #[test]
+    fn reports_start_failures() {
+        let (handle, mut events) = FFmpegSupervisor::new(|| FFmpeg::new_with_program("/nonexistent/ffmpeg")).start();
+
+        assert!(matches!(events.blocking_recv(), Some(FFmpegSupervisorEvent::Failed(_))));
+        assert!(handle.join().is_err());
+    }
+}
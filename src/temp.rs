@@ -0,0 +1,167 @@
+use std::{collections::HashSet, fs::{File, OpenOptions}, io::{Read, Seek, SeekFrom, Write}, path::{Path, PathBuf}, sync::{Mutex, PoisonError}};
+
+use once_cell::sync::Lazy;
+
+use crate::random_temp_file;
+
+/// Every temporary file & FIFO that wasn't deleted yet, see [`crate::FFmpeg::cleanup_temp`]
+static REGISTRY: Lazy<Mutex<HashSet<PathBuf>>> = Lazy::new(|| {
+    extern "C" {
+        fn atexit(callback: extern "C" fn()) -> std::os::raw::c_int;
+    }
+
+    extern "C" fn cleanup_on_exit() {
+        cleanup();
+    }
+
+    // Runs when main returns or on `std::process::exit`, not when the process is killed
+    let _ = unsafe { atexit(cleanup_on_exit) };
+
+    Mutex::default()
+});
+
+pub(crate) fn register(path: &Path) {
+    REGISTRY.lock().unwrap_or_else(PoisonError::into_inner).insert(path.to_path_buf());
+}
+
+pub(crate) fn forget(path: &Path) {
+    REGISTRY.lock().unwrap_or_else(PoisonError::into_inner).remove(path);
+}
+
+/// Delete `path` & stop tracking it
+pub(crate) fn remove(path: &Path) {
+    forget(path);
+
+    let _ = std::fs::remove_file(path);
+}
+
+/// Delete every tracked file & directory, returns how many were deleted
+pub(crate) fn cleanup() -> usize {
+    let paths = std::mem::take(&mut *REGISTRY.lock().unwrap_or_else(PoisonError::into_inner));
+
+    paths.iter().filter(|path| std::fs::remove_file(path).is_ok() || std::fs::remove_dir_all(path).is_ok()).count()
+}
+
+/// A temporary file, e.g. the output made by [`crate::FFmpegBuilder::output`]
+///
+/// The file is deleted when dropped, unless it's kept with [`TempOutput::keep`]
+#[derive(Debug)]
+pub struct TempOutput {
+    file: File,
+    /// Empty once kept
+    path: PathBuf,
+}
+
+impl TempOutput {
+    pub(crate) fn create() -> std::io::Result<Self> {
+        let path = random_temp_file();
+        let file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+
+        Ok(Self { file, path })
+    }
+
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    pub fn file_mut(&mut self) -> &mut File {
+        &mut self.file
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Don't delete the file, e.g. to move it somewhere else
+    pub fn keep(mut self) -> PathBuf {
+        forget(&self.path);
+
+        std::mem::take(&mut self.path)
+    }
+}
+
+impl Read for TempOutput {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Write for TempOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for TempOutput {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+impl Drop for TempOutput {
+    fn drop(&mut self) {
+        if !self.path.as_os_str().is_empty() {
+            remove(&self.path);
+        }
+    }
+}
+
+/// A temporary directory, deleted with everything in it when dropped, see [`crate::FFmpegBuilder::isolated_work_dir`]
+#[derive(Debug)]
+pub(crate) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub(crate) fn create() -> std::io::Result<Self> {
+        let path = random_temp_file();
+        std::fs::create_dir(&path)?;
+
+        Ok(Self { path })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        forget(&self.path);
+
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn temp_output_cleanup() -> std::io::Result<()> {
+        let temp = TempOutput::create()?;
+        let path = temp.path().to_path_buf();
+        assert!(REGISTRY.lock().unwrap().contains(&path));
+
+        drop(temp);
+        assert!(!path.exists() && !REGISTRY.lock().unwrap().contains(&path));
+
+        let path = TempOutput::create()?.keep();
+        assert!(path.exists());
+
+        std::fs::remove_file(path)?;
+
+        let dir = TempDir::create()?;
+        let path = dir.path().to_path_buf();
+        std::fs::write(path.join("ffmpeg2pass-0.log"), "")?;
+
+        drop(dir);
+        assert!(!path.exists() && !REGISTRY.lock().unwrap().contains(&path));
+
+        Ok(())
+    }
+}
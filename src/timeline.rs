@@ -0,0 +1,65 @@
+//! What a running FFmpeg is doing, see [`crate::FFmpegBuilder::timeline_events`]
+
+use std::{path::PathBuf, process::ExitStatus};
+
+/// A phase of a running FFmpeg, in the order they usually happen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimelineEvent {
+    Spawned { pid: u32 },
+    /// An output file is opened, every output is reported before anything is encoded
+    OutputOpened { index: usize, path: PathBuf },
+    /// A line of the stream mapping, e.g. `Stream #0:0 -> #0:0 (h264 (native) -> hevc (libx265))`
+    StreamMapped { input: (usize, usize), output: (usize, usize), description: String },
+    /// The first progress report, only sent when the progress is listened to
+    FirstProgress,
+    Exited { status: ExitStatus },
+}
+
+/// The event of a stderr line, if there's any
+pub(crate) fn parse_line(line: &str) -> Option<TimelineEvent> {
+    // Output #0, mp4, to 'out.mp4':
+    if let Some(rest) = line.strip_prefix("Output #") {
+        let (index, rest) = rest.split_once(',')?;
+        let path = rest.split_once(" to '")?.1.strip_suffix("':")?;
+
+        return Some(TimelineEvent::OutputOpened { index: index.parse().ok()?, path: PathBuf::from(path) });
+    }
+
+    //   Stream #0:1 -> #0:1 (aac (native) -> opus (libopus))
+    let mapping = line.trim_start().strip_prefix("Stream #")?;
+    let (input, rest) = mapping.split_once(" -> #")?;
+    let (output, description) = rest.split_once(' ').unwrap_or((rest, ""));
+
+    let pair = |pair: &str| {
+        let (file, stream) = pair.split_once(':')?;
+
+        Some((file.parse().ok()?, stream.parse().ok()?))
+    };
+
+    Some(TimelineEvent::StreamMapped {
+        input: pair(input)?,
+        output: pair(output)?,
+        description: description.strip_prefix('(').and_then(|description| description.strip_suffix(')')).unwrap_or(description).to_string(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stderr_events() {
+        assert_eq!(parse_line("Output #1, matroska, to 'C:\\out dir\\it's.mkv':"), Some(TimelineEvent::OutputOpened { index: 1, path: PathBuf::from("C:\\out dir\\it's.mkv") }));
+        assert_eq!(parse_line("  Stream #0:0 -> #0:0 (h264 (native) -> hevc (libx265))"), Some(TimelineEvent::StreamMapped {
+            input: (0, 0),
+            output: (0, 0),
+            description: "h264 (native) -> hevc (libx265)".to_string(),
+        }));
+        assert_eq!(parse_line("  Stream #1:2 -> #0:1 (copy)"), Some(TimelineEvent::StreamMapped { input: (1, 2), output: (0, 1), description: "copy".to_string() }));
+
+        // Filter graph inputs & the stream info aren't mappings
+        assert_eq!(parse_line("  Stream #0:0 (h264) -> scale:default"), None);
+        assert_eq!(parse_line("  Stream #0:0(und): Video: h264 (High), yuv420p, 1920x1080"), None);
+        assert_eq!(parse_line("Input #0, mov,mp4,m4a,3gp,3g2,mj2, from 'in.mp4':"), None);
+    }
+}
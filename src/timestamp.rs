@@ -0,0 +1,156 @@
+use std::time::Duration;
+
+use crate::{FFmpegBuilder, Section};
+
+/// What the muxer does with timestamps below zero, `-avoid_negative_ts`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NegativeTimestamps {
+    /// Let the muxer decide, only shifts for formats that can't store them
+    Auto,
+    /// Shift so the first timestamp is 0, the usual fix for cuts that start with a B-frame
+    MakeZero,
+    /// Shift only as far as needed for every timestamp to be 0 or above
+    MakeNonNegative,
+    /// Write them as they are
+    Disabled,
+}
+
+impl NegativeTimestamps {
+    fn as_str(&self) -> &'static str {
+        match self {
+            NegativeTimestamps::Auto => "auto",
+            NegativeTimestamps::MakeZero => "make_zero",
+            NegativeTimestamps::MakeNonNegative => "make_non_negative",
+            NegativeTimestamps::Disabled => "disabled",
+        }
+    }
+}
+
+/// Timestamp fixes for an input & output, see [`FFmpegBuilder::timestamp_policy`]
+///
+/// Start from one of the presets instead of copying flags around, every field is off by default
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TimestampPolicy {
+    /// Make up the missing presentation timestamps of the input, `-fflags +genpts`, e.g. raw h264 or old AVI files
+    pub generate_pts: bool,
+    /// `-avoid_negative_ts` of the output
+    pub negative: Option<NegativeTimestamps>,
+    /// Keep the timestamps of the input instead of starting the output at 0, `-copyts`
+    pub copy_timestamps: bool,
+    /// With `copy_timestamps`, still start at 0 but keep the gaps between streams, `-start_at_zero`
+    pub start_at_zero: bool,
+    /// Maximum demux-decode delay the muxer allows, `-muxdelay`
+    pub mux_delay: Option<Duration>,
+    /// Initial demux-decode delay the muxer assumes, `-muxpreload`
+    pub mux_preload: Option<Duration>,
+}
+
+impl TimestampPolicy {
+    /// Remux of an input with broken or missing timestamps, e.g. a stream copy cut that doesn't start on a keyframe
+    pub fn repair() -> Self {
+        Self { generate_pts: true, negative: Some(NegativeTimestamps::MakeZero), ..Default::default() }
+    }
+
+    /// Keep the original timing (e.g. to line up with external subtitles or other outputs) but still start at 0
+    pub fn preserve() -> Self {
+        Self { copy_timestamps: true, start_at_zero: true, ..Default::default() }
+    }
+
+    /// No muxing delay, for live MPEG-TS & UDP outputs where the default 0.7s shows up as latency
+    pub fn low_latency() -> Self {
+        Self { mux_delay: Some(Duration::ZERO), mux_preload: Some(Duration::ZERO), ..Default::default() }
+    }
+
+    fn global_args(&self) -> Vec<&'static str> {
+        let mut args = Vec::new();
+
+        // -start_at_zero does nothing on its own
+        if self.copy_timestamps || self.start_at_zero {
+            args.push("-copyts");
+        }
+
+        if self.start_at_zero {
+            args.push("-start_at_zero");
+        }
+
+        args
+    }
+
+    fn output_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if let Some(negative) = self.negative {
+            args.extend(["-avoid_negative_ts".to_string(), negative.as_str().to_string()]);
+        }
+
+        if let Some(delay) = self.mux_delay {
+            args.extend(["-muxdelay".to_string(), delay.as_secs_f64().to_string()]);
+        }
+
+        if let Some(preload) = self.mux_preload {
+            args.extend(["-muxpreload".to_string(), preload.as_secs_f64().to_string()]);
+        }
+
+        args
+    }
+}
+
+impl<M: Section> FFmpegBuilder<M> {
+    /// Apply `policy`, the input options go to the input being configured (or the last one before the output)
+    ///
+    /// `+genpts` is added to the `-fflags` the input already has, e.g. from [`FFmpegBuilder::decode_recovery`]
+    ///
+    /// `-copyts` & `-start_at_zero` are global, so they affect every input & output
+    pub fn timestamp_policy(self, policy: &TimestampPolicy) -> Self {
+        let builder = match policy.generate_pts {
+            true => self.add_format_flag("+genpts"),
+            false => self,
+        };
+
+        let global = policy.global_args().into_iter().filter(|arg| !builder.inner_args.iter().any(|existing| existing == arg)).collect::<Vec<_>>();
+
+        builder
+            .insert_global_args(global)
+            .output_args(policy.output_args())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{recovery::DecodeRecovery, FFmpeg};
+
+    #[test]
+    fn timestamp_policy_placement() {
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("in.h264".into())
+            .done()
+            .output_as_file("out.mp4".into())
+            .timestamp_policy(&TimestampPolicy::repair())
+            .timestamp_policy(&TimestampPolicy::preserve())
+            .done()
+            .output_as_file("out.ts".into())
+            .timestamp_policy(&TimestampPolicy { copy_timestamps: true, ..TimestampPolicy::low_latency() })
+            .done();
+
+        assert_eq!(builder.inner_args, [
+            "-copyts", "-start_at_zero", "-fflags", "+genpts", "-i", "in.h264",
+            "-avoid_negative_ts", "make_zero", "-y", "out.mp4",
+            "-muxdelay", "0", "-muxpreload", "0", "-y", "out.ts",
+        ]);
+
+        // Only the last -fflags of an input counts
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .input_with_file("broken.h264".into())
+            .decode_recovery(&DecodeRecovery::discard())
+            .done()
+            .output_as_file("out.mp4".into())
+            .timestamp_policy(&TimestampPolicy::repair())
+            .done();
+
+        assert_eq!(builder.inner_args, [
+            "-err_detect", "ignore_err", "-fflags", "+discardcorrupt+genpts", "-i", "broken.h264",
+            "-avoid_negative_ts", "make_zero", "-y", "out.mp4",
+        ]);
+    }
+}
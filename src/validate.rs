@@ -0,0 +1,198 @@
+use std::{collections::BTreeSet, path::PathBuf, process::ExitStatus, time::Duration};
+
+use crate::{FFmpeg};
+
+/// Result of [`FFmpeg::validate`] & [`FFmpeg::validate_quick`]
+#[derive(Debug, Clone)]
+pub struct ValidationReport {
+    pub status: ExitStatus,
+    /// The quick check was killed after [`QuickCheckOptions::timeout`], the result is inconclusive
+    pub timed_out: bool,
+    /// Everything FFmpeg logged at the error level, in order
+    pub errors: Vec<ValidationError>,
+    /// Input stream indices that errors were reported for
+    ///
+    /// Errors logged by the decoder itself don't always say which stream they're from, so this might be empty even if `errors` is not
+    pub corrupt_streams: BTreeSet<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ValidationError {
+    /// Roughly where the error happened, based on the last progress report before it
+    pub position: Option<Duration>,
+    /// Input stream index, if FFmpeg mentioned it
+    pub stream: Option<usize>,
+    pub message: String,
+}
+
+impl ValidationReport {
+    /// FFmpeg exited successfully in time & didn't log any error
+    pub fn is_valid(&self) -> bool {
+        self.status.success() && !self.timed_out && self.errors.is_empty()
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.errors.len()
+    }
+
+    pub fn first_error_position(&self) -> Option<Duration> {
+        self.errors.iter().find_map(|error| error.position)
+    }
+
+    fn parse(status: ExitStatus, stderr: &str) -> Self {
+        let mut report = ValidationReport { status, timed_out: false, errors: Vec::new(), corrupt_streams: BTreeSet::new() };
+        let mut position = None;
+
+        for line in stderr.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            // Progress is written into stderr as well, see FFmpeg::validate
+            // It's missing from FFmpeg::validate_quick, so the position is always None there
+            if let Some((key, value)) = line.split_once('=').filter(|(key, _)| key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')) {
+                if key == "out_time_us" {
+                    if let Ok(micros) = value.parse::<u64>() {
+                        position = Some(Duration::from_micros(micros));
+                    }
+                }
+
+                continue;
+            }
+
+            let stream = stream_index(line);
+
+            if let Some(stream) = stream {
+                report.corrupt_streams.insert(stream);
+            }
+
+            report.errors.push(ValidationError { position, stream, message: line.to_string() });
+        }
+
+        report
+    }
+}
+
+/// `[vist#0:1/h264 @ 0x...]` or `Error while decoding stream #0:1`
+///
+/// Only the stream index is returned, [`FFmpeg::validate_quick`] opens the same file as 2 inputs
+fn stream_index(line: &str) -> Option<usize> {
+    line.match_indices('#').find_map(|(at, _)| {
+        let (input, rest) = line[at + 1..].split_once(':')?;
+        let stream = rest.chars().take_while(char::is_ascii_digit).collect::<String>();
+
+        if input.is_empty() || !input.chars().all(|c| c.is_ascii_digit()) { return None };
+
+        stream.parse::<usize>().ok()
+    })
+}
+
+/// How much of the input [`FFmpeg::validate_quick`] decodes
+#[derive(Debug, Clone, Copy)]
+pub struct QuickCheckOptions {
+    /// Decoded from the start
+    pub head: Duration,
+    /// Decoded from the end, [`Duration::ZERO`] to skip it
+    pub tail: Duration,
+    /// FFmpeg is killed after this, so the check never takes longer regardless of the input
+    pub timeout: Duration,
+}
+
+impl Default for QuickCheckOptions {
+    fn default() -> Self {
+        Self {
+            head: Duration::from_secs(5),
+            tail: Duration::from_secs(5),
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl FFmpeg {
+    /// Decode every stream of `input` to find corrupted data, for screening uploads
+    ///
+    /// This decodes the whole input, so it takes about as long as transcoding it
+    ///
+    /// Returns an error only if FFmpeg can't be started, a file that can't be opened is reported as invalid
+    pub fn validate(input: impl Into<PathBuf>) -> anyhow::Result<ValidationReport> {
+        let (status, stderr) = Self::quiet()?
+            .args(["-v", "error", "-progress", "pipe:2", "-stats_period", "0.5"])
+            .input_with_file(input.into())
+            .done()
+            .args(["-map", "0", "-f", "null", "-"])
+            .run_collect_stderr()?;
+
+        Ok(ValidationReport::parse(status, &stderr))
+    }
+
+    /// A faster alternative to [`FFmpeg::validate`], only opens the container & decodes the start & end of `input`
+    ///
+    /// Catches broken headers & truncated files, but not corruption in the middle
+    pub fn validate_quick(input: impl Into<PathBuf>, options: QuickCheckOptions) -> anyhow::Result<ValidationReport> {
+        let input = input.into();
+
+        let mut builder = Self::quiet()?
+            .args(["-v", "error"])
+            .args(["-t".to_string(), options.head.as_secs_f64().to_string()])
+            .input_with_file(input.clone())
+            .done()
+            .args(["-map", "0"]);
+
+        // -sseof seeks relative to the end, the whole file is decoded if it's shorter than that
+        if !options.tail.is_zero() {
+            builder = builder
+                .args(["-sseof".to_string(), format!("-{}", options.tail.as_secs_f64())])
+                .input_with_file(input)
+                .done()
+                .args(["-map", "1"]);
+        }
+
+        let mut command = builder
+            .args(["-f", "null", "-"])
+            .start_quiet()?;
+
+        let (status, timed_out) = match command.wait_timeout(options.timeout)? {
+            Some(status) => (status, false),
+            None => {
+                command.kill()?;
+
+                (command.wait()?, true)
+            },
+        };
+
+        let mut report = ValidationReport::parse(status, &command.recent_stderr().unwrap_or_default());
+        report.timed_out = timed_out;
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn validation_report_parsing() {
+        let report = ValidationReport::parse(ExitStatus::default(), "\
+frame=0
+out_time_us=0
+progress=continue
+frame=120
+out_time_us=4000000
+out_time=00:00:04.000000
+progress=continue
+[h264 @ 0x55d0c1a2b3c0] error while decoding MB 12 34, bytestream -5
+[vist#0:0/h264 @ 0x55d0c1a2c100] Error submitting packet to decoder: Invalid data found when processing input
+out_time_us=8500000
+Error while decoding stream #0:1: Invalid data found when processing input
+progress=end
+");
+
+        assert!(!report.is_valid());
+        assert_eq!(report.error_count(), 3);
+        assert_eq!(report.first_error_position(), Some(Duration::from_secs(4)));
+        assert_eq!(report.errors[0].stream, None);
+        assert_eq!(report.errors[1].stream, Some(0));
+        assert_eq!(report.errors[2].stream, Some(1));
+        assert_eq!(report.errors[2].position, Some(Duration::from_millis(8500)));
+        assert_eq!(report.corrupt_streams, BTreeSet::from([0, 1]));
+
+        assert!(ValidationReport::parse(ExitStatus::default(), "out_time_us=100\nprogress=end\n").is_valid());
+    }
+}
@@ -0,0 +1,728 @@
+use std::{ffi::OsStr, fmt::Display, path::{Path, PathBuf}, str::FromStr, time::Duration};
+
+use anyhow::Context;
+
+use crate::{probe::{schema::{SideData, Stream}, FFprobe}, FFmpeg, FFmpegBuilder, FFmpegError, IO};
+
+/// e.g. 16:9, see [`FFmpegBuilder::display_aspect_ratio`] & [`FFmpegBuilder::sample_aspect_ratio`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AspectRatio {
+    pub num: u32,
+    pub den: u32,
+}
+
+impl AspectRatio {
+    pub const SQUARE: Self = Self::new(1, 1);
+    pub const STANDARD: Self = Self::new(4, 3);
+    pub const WIDESCREEN: Self = Self::new(16, 9);
+
+    pub const fn new(num: u32, den: u32) -> Self {
+        Self { num, den }
+    }
+}
+
+impl Display for AspectRatio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.num, self.den)
+    }
+}
+
+/// Parses `16:9` or `16/9`
+impl FromStr for AspectRatio {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (num, den) = s.split_once([':', '/']).ok_or_else(|| anyhow::anyhow!("{s:?} isn't an aspect ratio like 16:9"))?;
+        let (num, den) = (num.trim().parse()?, den.trim().parse()?);
+
+        if num == 0 || den == 0 {
+            anyhow::bail!("{s:?} isn't a valid aspect ratio");
+        }
+
+        Ok(Self::new(num, den))
+    }
+}
+
+/// How missing frames are made up by [`FpsConversion::Interpolate`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InterpolationMode {
+    /// Repeat frames
+    Duplicate,
+    /// Blend the frames around it, fast but ghosts on motion
+    Blend,
+    /// Motion compensated interpolation, smooth but very slow
+    #[default]
+    MotionCompensated,
+}
+
+/// See [`FFmpegBuilder::fps`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FpsConversion {
+    /// Drop or duplicate frames to reach the frame rate
+    Drop(f64),
+    /// Make up new frames, e.g. for a smooth 24 to 60 fps conversion
+    Interpolate { target: f64, mode: InterpolationMode },
+}
+
+impl FpsConversion {
+    fn filter(&self) -> String {
+        match self {
+            FpsConversion::Drop(fps) => format!("fps={fps}"),
+            FpsConversion::Interpolate { target, mode } => {
+                let mode = match mode {
+                    InterpolationMode::Duplicate => "mi_mode=dup",
+                    InterpolationMode::Blend => "mi_mode=blend",
+                    InterpolationMode::MotionCompensated => "mi_mode=mci:mc_mode=aobmc:me_mode=bidir:vsbmc=1",
+                };
+
+                format!("minterpolate=fps={target}:{mode}")
+            },
+        }
+    }
+}
+
+/// The exact rate of `fps`, e.g. `30000/1001` for 29.97 instead of the `2997/100` FFmpeg would read
+fn exact_frame_rate(fps: f64) -> (u32, u32) {
+    let ntsc = fps * 1.001;
+
+    if fps.fract() != 0.0 && (ntsc - ntsc.round()).abs() < 0.01 {
+        return (ntsc.round() as u32 * 1000, 1001);
+    }
+
+    if fps.fract() == 0.0 {
+        return (fps as u32, 1);
+    }
+
+    ((fps * 1000.0).round() as u32, 1000)
+}
+
+/// mp4 & mov timescale that fits every common frame rate, so every frame gets the same duration
+const MP4_TIMESCALE: u32 = 90000;
+
+/// Chromaticity coordinates (x, y) & luminance of the display a HDR10 video was mastered on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MasteringDisplay {
+    pub red: (f64, f64),
+    pub green: (f64, f64),
+    pub blue: (f64, f64),
+    pub white_point: (f64, f64),
+    /// In cd/m²
+    pub max_luminance: f64,
+    /// In cd/m²
+    pub min_luminance: f64,
+}
+
+impl MasteringDisplay {
+    /// Display P3 with a D65 white point & 1000 nits, the most common one
+    pub const P3_D65_1000: Self = Self {
+        red: (0.68, 0.32),
+        green: (0.265, 0.69),
+        blue: (0.15, 0.06),
+        white_point: (0.3127, 0.329),
+        max_luminance: 1000.0,
+        min_luminance: 0.0001,
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentLightLevel {
+    /// MaxCLL, the brightest pixel in cd/m²
+    pub max_cll: u32,
+    /// MaxFALL, the brightest frame on average in cd/m²
+    pub max_fall: u32,
+}
+
+/// HDR10 static metadata, see [`FFmpegBuilder::hdr10`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Hdr10 {
+    pub mastering_display: Option<MasteringDisplay>,
+    pub content_light_level: Option<ContentLightLevel>,
+}
+
+impl Hdr10 {
+    /// The metadata of a probed HDR10 stream, to keep it when transcoding
+    ///
+    /// [`Option::None`] if the stream isn't PQ (`smpte2084`), e.g. SDR or HLG
+    pub fn from_stream(stream: &Stream) -> Option<Self> {
+        if stream.color_transfer.as_deref() != Some("smpte2084") {
+            return None;
+        }
+
+        // Rationals like `34000/50000`
+        let rational = |value: &Option<String>| {
+            let value = value.as_deref()?;
+
+            match value.split_once('/') {
+                Some((num, den)) => Some(num.parse::<f64>().ok()? / den.parse::<f64>().ok()?),
+                None => value.parse::<f64>().ok(),
+            }
+        };
+
+        let mut metadata = Self::default();
+
+        for side_data in &stream.side_data_list {
+            match side_data.side_data_type.as_deref() {
+                Some("Mastering display metadata") => {
+                    let point = |x, y| Some((rational(x)?, rational(y)?));
+
+                    metadata.mastering_display = (|| Some(MasteringDisplay {
+                        red: point(&side_data.red_x, &side_data.red_y)?,
+                        green: point(&side_data.green_x, &side_data.green_y)?,
+                        blue: point(&side_data.blue_x, &side_data.blue_y)?,
+                        white_point: point(&side_data.white_point_x, &side_data.white_point_y)?,
+                        max_luminance: rational(&side_data.max_luminance)?,
+                        min_luminance: rational(&side_data.min_luminance)?,
+                    }))();
+                },
+                Some("Content light level metadata") => {
+                    metadata.content_light_level = Some(ContentLightLevel {
+                        max_cll: side_data.max_content.unwrap_or_default().try_into().unwrap_or_default(),
+                        max_fall: side_data.max_average.unwrap_or_default().try_into().unwrap_or_default(),
+                    });
+                },
+                _ => { },
+            }
+        }
+
+        Some(metadata)
+    }
+
+    /// `-x265-params`, x265 wants the coordinates in 0.00002 units & the luminance in 0.0001 units
+    fn x265_params(&self) -> String {
+        let mut params = vec!["hdr10=1".to_string(), "repeat-headers=1".to_string()];
+
+        if let Some(display) = &self.mastering_display {
+            let point = |(x, y): (f64, f64)| format!("({},{})", (x * 50000.0).round(), (y * 50000.0).round());
+
+            params.push(format!(
+                "master-display=G{}B{}R{}WP{}L({},{})",
+                point(display.green),
+                point(display.blue),
+                point(display.red),
+                point(display.white_point),
+                (display.max_luminance * 10000.0).round(),
+                (display.min_luminance * 10000.0).round(),
+            ));
+        }
+
+        if let Some(level) = &self.content_light_level {
+            params.push(format!("max-cll={},{}", level.max_cll, level.max_fall));
+        }
+
+        params.join(":")
+    }
+
+    /// `-svtav1-params`, SVT-AV1 takes the values as is
+    fn svtav1_params(&self) -> String {
+        let mut params = vec!["enable-hdr=1".to_string()];
+
+        if let Some(display) = &self.mastering_display {
+            let point = |(x, y): (f64, f64)| format!("({x:.4},{y:.4})");
+
+            params.push(format!(
+                "mastering-display=G{}B{}R{}WP{}L({:.4},{:.4})",
+                point(display.green),
+                point(display.blue),
+                point(display.red),
+                point(display.white_point),
+                display.max_luminance,
+                display.min_luminance,
+            ));
+        }
+
+        if let Some(level) = &self.content_light_level {
+            params.push(format!("content-light={},{}", level.max_cll, level.max_fall));
+        }
+
+        params.join(":")
+    }
+}
+
+/// Dolby Vision configuration of a stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DolbyVision {
+    /// e.g. 5, 7 or 8
+    pub profile: u8,
+    pub level: u8,
+    /// Whether the dynamic metadata (RPU) is present
+    pub rpu: bool,
+    /// Whether there's an enhancement layer, profile 7 only
+    pub enhancement_layer: bool,
+    /// What the base layer is compatible with, e.g. 1 for HDR10, 4 for HLG
+    pub compatibility_id: u8,
+}
+
+/// Dynamic HDR metadata of the first video stream, see [`DynamicHdr::probe`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DynamicHdr {
+    pub dolby_vision: Option<DolbyVision>,
+    /// HDR10+ (SMPTE ST 2094-40)
+    pub hdr10_plus: bool,
+}
+
+/// Containers FFmpeg can write the Dolby Vision configuration into
+const DOLBY_VISION_CONTAINERS: [&str; 5] = ["mp4", "m4v", "mov", "mkv", "ts"];
+
+impl DynamicHdr {
+    /// Look for Dolby Vision in the stream & HDR10+ in the first frame of the first video stream of `input`
+    pub fn probe(input: impl AsRef<OsStr>) -> anyhow::Result<Self> {
+        let probe = FFprobe::run(["-select_streams", "v:0", "-show_streams", "-show_frames", "-read_intervals", "%+#1"], input)?;
+
+        let stream_side_data = probe.streams.iter().flat_map(|stream| &stream.side_data_list);
+        let frame_side_data = probe.frames.iter().flat_map(|frame| &frame.side_data_list);
+
+        Ok(Self::from_side_data(stream_side_data.chain(frame_side_data)))
+    }
+
+    fn from_side_data<'a>(side_data: impl IntoIterator<Item = &'a SideData>) -> Self {
+        let mut hdr = Self::default();
+
+        let number = |value: Option<i64>| value.and_then(|value| u8::try_from(value).ok()).unwrap_or_default();
+
+        for side_data in side_data {
+            match side_data.side_data_type.as_deref() {
+                Some("DOVI configuration record") => {
+                    hdr.dolby_vision = Some(DolbyVision {
+                        profile: number(side_data.dv_profile),
+                        level: number(side_data.dv_level),
+                        rpu: side_data.rpu_present_flag == Some(1),
+                        enhancement_layer: side_data.el_present_flag == Some(1),
+                        compatibility_id: number(side_data.dv_bl_signal_compatibility_id),
+                    });
+                },
+                Some(kind) if kind.contains("HDR10+") || kind.contains("SMPTE2094-40") => hdr.hdr10_plus = true,
+                _ => { },
+            }
+        }
+
+        hdr
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dolby_vision.is_none() && !self.hdr10_plus
+    }
+
+    /// What would be lost by copying into `output`, based on its extension
+    ///
+    /// HDR10+ is part of the HEVC & AV1 bitstream, so it's kept in every container
+    pub fn warnings_for(&self, output: impl AsRef<Path>) -> Vec<String> {
+        let extension = output.as_ref().extension().map(|extension| extension.to_string_lossy().to_lowercase()).unwrap_or_default();
+
+        let mut warnings = Vec::new();
+
+        if self.dolby_vision.is_some() && !DOLBY_VISION_CONTAINERS.contains(&extension.as_str()) {
+            warnings.push(format!("{extension:?} can't carry the Dolby Vision configuration, players will fall back to the base layer"));
+        }
+
+        warnings
+    }
+}
+
+/// How often a keyframe is put in the video, see [`FFmpegBuilder::keyframe_interval`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyframeSpec {
+    /// Every n seconds of the output, works with any frame rate
+    Seconds(f64),
+    /// Every n frames, a fixed GOP
+    Frames(u32),
+}
+
+/// Where [`FFmpegBuilder::force_key_frames`] puts keyframes
+#[derive(Debug, Clone, PartialEq)]
+pub enum ForcedKeyframes {
+    /// Whenever the expression is non zero, e.g. `gte(t,n_forced*2)`
+    Expr(String),
+    /// At these times of the output
+    Timestamps(Vec<Duration>),
+    /// At the start of every chapter
+    Chapters,
+}
+
+impl Display for ForcedKeyframes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Expr(expr) => write!(f, "expr:{expr}"),
+            Self::Timestamps(timestamps) => write!(f, "{}", timestamps.iter().map(|timestamp| timestamp.as_secs_f64().to_string()).collect::<Vec<_>>().join(",")),
+            Self::Chapters => write!(f, "chapters"),
+        }
+    }
+}
+
+/// Encoders that can write HDR10 metadata
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HdrEncoder {
+    /// `libx265`
+    X265,
+    /// `libsvtav1`
+    SvtAv1,
+}
+
+/// What [`FFmpeg::visualize_motion`] draws over the video, with the codecview filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MotionVisualization {
+    /// Forward motion vectors of P-frames
+    pub forward_p: bool,
+    /// Forward motion vectors of B-frames
+    pub forward_b: bool,
+    /// Backward motion vectors of B-frames
+    pub backward_b: bool,
+    /// Macroblock partitioning, FFmpeg 4.4+
+    pub blocks: bool,
+    /// Quantization parameter of every block as a shade of gray
+    pub qp: bool,
+}
+
+impl Default for MotionVisualization {
+    fn default() -> Self {
+        Self { forward_p: true, forward_b: true, backward_b: true, blocks: false, qp: false }
+    }
+}
+
+impl MotionVisualization {
+    fn filter(&self) -> String {
+        let vectors = [(self.forward_p, "pf"), (self.forward_b, "bf"), (self.backward_b, "bb")]
+            .into_iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, kind)| kind)
+            .collect::<Vec<_>>();
+
+        let mut options = Vec::new();
+
+        if !vectors.is_empty() {
+            options.push(format!("mv={}", vectors.join("+")));
+        }
+
+        if self.blocks {
+            options.push("block=1".to_string());
+        }
+
+        if self.qp {
+            options.push("qp=1".to_string());
+        }
+
+        format!("codecview={}", options.join(":"))
+    }
+}
+
+/// A rectangle of the video to keep, see [`FFmpeg::detect_crop`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CropRect {
+    pub width: u32,
+    pub height: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl CropRect {
+    /// The crop filter
+    pub fn filter(&self) -> String {
+        format!("crop={}:{}:{}:{}", self.width, self.height, self.x, self.y)
+    }
+
+    /// The rectangle suggested the most by the cropdetect lines of `stderr`
+    ///
+    /// The most common one is used instead of the biggest one, so dark scenes & logos in the bars don't change it
+    fn most_detected(stderr: &str) -> Option<Self> {
+        let mut counts: Vec<(CropRect, usize)> = Vec::new();
+
+        // [Parsed_cropdetect_0 @ 0x5612a4b0] x1:0 x2:1919 y1:140 y2:939 w:1920 h:800 x:0 y:140 pts:1001 t:0.041708 crop=1920:800:0:140
+        let rects = stderr.lines().filter(|line| line.contains("Parsed_cropdetect")).filter_map(|line| {
+            let (_, crop) = line.rsplit_once("crop=")?;
+            let mut values = crop.trim().split(':').map(|value| value.parse::<u32>().ok());
+
+            Some(CropRect { width: values.next()??, height: values.next()??, x: values.next()??, y: values.next()?? })
+        });
+
+        for rect in rects.filter(|rect| rect.width > 0 && rect.height > 0) {
+            match counts.iter_mut().find(|(counted, _)| *counted == rect) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((rect, 1)),
+            }
+        }
+
+        // The first one wins a tie
+        counts.into_iter().rev().max_by_key(|(_, count)| *count).map(|(rect, _)| rect)
+    }
+}
+
+impl FFmpeg {
+    /// Find the black bars around the first video stream of `input` with the cropdetect filter
+    ///
+    /// Only keyframes are decoded, which samples the whole video quickly
+    pub fn detect_crop(input: impl Into<PathBuf>) -> anyhow::Result<CropRect> {
+        let (status, stderr) = Self::quiet()?
+            .input_with_file(input.into())
+            .input_args(["-skip_frame", "nokey"])
+            .done()
+            .args(["-map", "0:v:0", "-vf", "cropdetect=round=2", "-f", "null", "-"])
+            .run_collect_stderr()?;
+
+        if !status.success() {
+            return Err(FFmpegError { status, stderr: Some(stderr) }.into());
+        }
+
+        CropRect::most_detected(&stderr).context("cropdetect didn't find anything to keep, the video might be completely black")
+    }
+
+    /// Draw the motion vectors & block decisions of the video of `input` into `output`, for codec tuning & teaching
+    ///
+    /// Only decoders that export them show anything, e.g. H.264, MPEG-2 & MPEG-4 Part 2 but not hardware decoders
+    pub fn visualize_motion(input: impl Into<PathBuf>, output: impl Into<PathBuf>, options: MotionVisualization) -> anyhow::Result<()> {
+        let mut builder = Self::quiet()?
+            .input_with_file(input.into())
+            .input_args(["-flags2", "+export_mvs"]);
+
+        if options.qp {
+            builder = builder.input_args(["-export_side_data", "+venc_params"]);
+        }
+
+        builder
+            .done()
+            .output_as_file(output.into())
+            .args(["-map", "0:v:0", "-map", "0:a?", "-c:a", "copy"])
+            .video_filter(options.filter())
+            .done()
+            .run_checked()?;
+
+        Ok(())
+    }
+}
+
+impl FFmpegBuilder<IO> {
+    /// Encode the video as HDR10 (10 bit, BT.2020, PQ) with `metadata`, sets the video codec
+    ///
+    /// Encoders don't copy the metadata of the input on their own, get it with [`Hdr10::from_stream`]
+    pub fn hdr10(self, encoder: HdrEncoder, metadata: &Hdr10) -> Self {
+        let (codec, params_option, params) = match encoder {
+            HdrEncoder::X265 => ("libx265", "-x265-params", metadata.x265_params()),
+            HdrEncoder::SvtAv1 => ("libsvtav1", "-svtav1-params", metadata.svtav1_params()),
+        };
+
+        self
+            .codec_video(codec)
+            .args(["-pix_fmt", "yuv420p10le", "-color_primaries", "bt2020", "-color_trc", "smpte2084", "-colorspace", "bt2020nc"])
+            .args([params_option, &params])
+    }
+
+    /// Copy the video as is, keeping the Dolby Vision configuration & HDR10+ metadata from [`DynamicHdr::probe`]
+    ///
+    /// Check [`DynamicHdr::warnings_for`] for the output first, re-encoding drops both
+    pub fn copy_dynamic_hdr(self, hdr: &DynamicHdr) -> Self {
+        let builder = self.codec_video("copy");
+
+        // The dvcC & dvvC boxes of mp4 are written only in unofficial mode
+        if hdr.dolby_vision.is_some() {
+            return builder.args(["-strict", "unofficial"]);
+        }
+
+        builder
+    }
+
+    /// Tag the video as HLG (10 bit, BT.2020, ARIB STD-B67), HLG doesn't need any extra metadata
+    pub fn hlg(self) -> Self {
+        self.args(["-pix_fmt", "yuv420p10le", "-color_primaries", "bt2020", "-color_trc", "arib-std-b67", "-colorspace", "bt2020nc"])
+    }
+
+    /// Convert the frame rate of the video, the output is constant frame rate even if the input is variable (e.g. phone recordings)
+    pub fn fps(self, conversion: FpsConversion) -> Self {
+        self
+            .video_filter(conversion.filter())
+            // The muxer would drop or duplicate frames again to follow the input timestamps otherwise
+            .args(["-fps_mode", "cfr"])
+    }
+
+    /// Make the video constant frame rate at `fps` without changing its speed, for VFR screen & phone recordings that break editors
+    ///
+    /// NTSC rates (23.976, 29.97, 59.94) are written as the exact `x/1001`.
+    /// Matroska, WebM & FLV keep the input timestamps by default, so the frame rate mode is always forced.
+    /// mp4 & mov get a timescale the rate divides evenly, matroska & WebM store milliseconds so 29.97 still alternates between 33 & 34 ms
+    pub fn constant_frame_rate(self, fps: f64) -> Self {
+        let (num, den) = exact_frame_rate(fps);
+        let rate = format!("{num}/{den}");
+
+        let builder = self
+            .video_filter(format!("fps={rate}"))
+            .args(["-fps_mode", "cfr"])
+            // Containers without timestamps (e.g. raw h264 & y4m) only store this
+            .args(["-r", &rate]);
+
+        if builder.output_muxer().is_some_and(|muxer| ["mp4", "mov", "m4v"].contains(&muxer.as_str())) && (MP4_TIMESCALE * den).checked_rem(num) == Some(0) {
+            return builder.args(["-video_track_timescale".to_string(), MP4_TIMESCALE.to_string()]);
+        }
+
+        builder
+    }
+
+    /// The muxer of this output from its `-f` or the extension of the target
+    fn output_muxer(&self) -> Option<String> {
+        if let Some(format) = self.output_option("-f") {
+            return Some(format.to_string_lossy().to_lowercase());
+        }
+
+        let at = self.inserting_offset?;
+
+        self.inner_args.get(at + 1)
+            .and_then(|target| Path::new(target).extension())
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+    }
+
+    /// Put keyframes at a fixed interval & nowhere else, so segments of HLS & DASH renditions line up
+    ///
+    /// Scene cut detection is turned off, otherwise the encoder adds keyframes on scene changes
+    pub fn keyframe_interval(self, spec: KeyframeSpec) -> Self {
+        let builder = self.args(["-sc_threshold", "0"]);
+
+        match spec {
+            KeyframeSpec::Seconds(seconds) => builder.force_key_frames(ForcedKeyframes::Expr(format!("gte(t,n_forced*{seconds})"))),
+            KeyframeSpec::Frames(frames) => builder.args(["-g".to_string(), frames.to_string(), "-keyint_min".to_string(), frames.to_string()]),
+        }
+    }
+
+    /// Make the encoder put keyframes at `keyframes`, on top of the ones it picks
+    pub fn force_key_frames(self, keyframes: ForcedKeyframes) -> Self {
+        self.args(["-force_key_frames".to_string(), keyframes.to_string()])
+    }
+
+    /// Set the shape of the whole picture, the pixels are stretched to match it
+    pub fn display_aspect_ratio(self, ratio: AspectRatio) -> Self {
+        self.video_filter(format!("setdar={ratio}"))
+    }
+
+    /// Set the shape of a single pixel, [`AspectRatio::SQUARE`] for most videos
+    pub fn sample_aspect_ratio(self, ratio: AspectRatio) -> Self {
+        self.video_filter(format!("setsar={ratio}"))
+    }
+
+    /// Resample non square pixels (e.g. DV & DVD sources) into square ones, so it isn't shown stretched by players that ignore the aspect ratio
+    pub fn fix_anamorphic(self) -> Self {
+        self.video_filter("scale=trunc(iw*sar/2)*2:ih,setsar=1")
+    }
+
+    /// Remove the black bars around the video with [`FFmpeg::detect_crop`] on the first input
+    pub fn apply_auto_crop(self) -> anyhow::Result<Self> {
+        let input = self.first_input().context("apply_auto_crop needs an input to detect the bars of")?;
+        let rect = FFmpeg::detect_crop(input)?;
+
+        Ok(self.video_filter(rect.filter()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn aspect_ratio_and_fps() {
+        assert_eq!("16:9".parse::<AspectRatio>().unwrap(), AspectRatio::WIDESCREEN);
+        assert_eq!(" 4 / 3".parse::<AspectRatio>().unwrap().to_string(), "4/3");
+        assert!("16x9".parse::<AspectRatio>().is_err());
+        assert!("1:0".parse::<AspectRatio>().is_err());
+
+        assert_eq!(FpsConversion::Drop(29.97).filter(), "fps=29.97");
+        assert_eq!(FpsConversion::Interpolate { target: 60.0, mode: InterpolationMode::Blend }.filter(), "minterpolate=fps=60:mi_mode=blend");
+    }
+
+    #[test]
+    fn keyframe_options() {
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .output_as_file("a.mp4".into())
+            .keyframe_interval(KeyframeSpec::Seconds(2.0))
+            .done()
+            .output_as_file("b.mp4".into())
+            .keyframe_interval(KeyframeSpec::Frames(48))
+            .force_key_frames(ForcedKeyframes::Timestamps(vec![Duration::from_millis(1500), Duration::from_secs(10)]))
+            .done();
+
+        assert_eq!(builder.inner_args, [
+            "-sc_threshold", "0", "-force_key_frames", "expr:gte(t,n_forced*2)", "-y", "a.mp4",
+            "-sc_threshold", "0", "-g", "48", "-keyint_min", "48", "-force_key_frames", "1.5,10", "-y", "b.mp4",
+        ]);
+    }
+
+    #[test]
+    fn constant_frame_rate() {
+        assert_eq!(exact_frame_rate(29.97), (30000, 1001));
+        assert_eq!(exact_frame_rate(23.976), (24000, 1001));
+        assert_eq!(exact_frame_rate(25.0), (25, 1));
+        assert_eq!(exact_frame_rate(12.5), (12500, 1000));
+
+        let builder = FFmpeg::new_with_program("ffmpeg")
+            .output_as_file("a.mp4".into())
+            .constant_frame_rate(29.97)
+            .done()
+            .output_as_file("b.mkv".into())
+            .constant_frame_rate(30.0)
+            .done();
+
+        assert_eq!(builder.inner_args, [
+            "-vf", "fps=30000/1001", "-fps_mode", "cfr", "-r", "30000/1001", "-video_track_timescale", "90000", "-y", "a.mp4",
+            "-vf", "fps=30/1", "-fps_mode", "cfr", "-r", "30/1", "-y", "b.mkv",
+        ]);
+    }
+
+    #[test]
+    fn motion_visualization_filter() {
+        assert_eq!(MotionVisualization::default().filter(), "codecview=mv=pf+bf+bb");
+        assert_eq!(MotionVisualization { forward_p: false, forward_b: false, blocks: true, qp: true, ..Default::default() }.filter(), "codecview=mv=bb:block=1:qp=1");
+    }
+
+    #[test]
+    fn crop_detection() {
+        let stderr = "\
+[Parsed_cropdetect_0 @ 0x5612a4b0] x1:0 x2:1919 y1:140 y2:939 w:1920 h:800 x:0 y:140 pts:0 t:0.000000 crop=1920:800:0:140
+[Parsed_cropdetect_0 @ 0x5612a4b0] x1:0 x2:1919 y1:300 y2:700 w:1920 h:400 x:0 y:300 pts:1001 t:2.002000 crop=1920:400:0:300
+[Parsed_cropdetect_0 @ 0x5612a4b0] x1:0 x2:1919 y1:140 y2:939 w:1920 h:800 x:0 y:140 pts:2002 t:4.004000 crop=1920:800:0:140
+[Parsed_cropdetect_0 @ 0x5612a4b0] x1:1919 x2:0 y1:1079 y2:0 w:-1904 h:-1072 x:1912 y:1076 pts:3003 t:6.006000 crop=-1904:-1072:1912:1076
+";
+
+        let rect = CropRect::most_detected(stderr).unwrap();
+
+        assert_eq!(rect, CropRect { width: 1920, height: 800, x: 0, y: 140 });
+        assert_eq!(rect.filter(), "crop=1920:800:0:140");
+        assert_eq!(CropRect::most_detected("[Parsed_cropdetect_0 @ 0x5612a4b0] crop=-1904:-1072:1912:1076"), None);
+    }
+
+    #[test]
+    fn hdr10_encoder_params() {
+        let stream: Stream = serde_json::from_str(r#"{
+            "color_transfer": "smpte2084",
+            "side_data_list": [
+                {
+                    "side_data_type": "Mastering display metadata",
+                    "red_x": "34000/50000", "red_y": "16000/50000",
+                    "green_x": "13250/50000", "green_y": "34500/50000",
+                    "blue_x": "7500/50000", "blue_y": "3000/50000",
+                    "white_point_x": "15635/50000", "white_point_y": "16450/50000",
+                    "min_luminance": "1/10000", "max_luminance": "10000000/10000"
+                },
+                { "side_data_type": "Content light level metadata", "max_content": 1000, "max_average": 400 }
+            ]
+        }"#).unwrap();
+
+        let metadata = Hdr10::from_stream(&stream).unwrap();
+
+        assert_eq!(
+            metadata.x265_params(),
+            "hdr10=1:repeat-headers=1:master-display=G(13250,34500)B(7500,3000)R(34000,16000)WP(15635,16450)L(10000000,1):max-cll=1000,400",
+        );
+        assert_eq!(
+            metadata.svtav1_params(),
+            "enable-hdr=1:mastering-display=G(0.2650,0.6900)B(0.1500,0.0600)R(0.6800,0.3200)WP(0.3127,0.3290)L(1000.0000,0.0001):content-light=1000,400",
+        );
+    }
+
+    #[test]
+    fn dynamic_hdr_detection() {
+        let side_data: Vec<SideData> = serde_json::from_str(r#"[
+            { "side_data_type": "DOVI configuration record", "dv_version_major": 1, "dv_profile": 8, "dv_level": 6, "rpu_present_flag": 1, "el_present_flag": 0, "bl_present_flag": 1, "dv_bl_signal_compatibility_id": 1 },
+            { "side_data_type": "HDR Dynamic Metadata SMPTE2094-40 (HDR10+)", "application version": 1 }
+        ]"#).unwrap();
+
+        let hdr = DynamicHdr::from_side_data(&side_data);
+
+        assert_eq!(hdr.dolby_vision, Some(DolbyVision { profile: 8, level: 6, rpu: true, enhancement_layer: false, compatibility_id: 1 }));
+        assert!(hdr.hdr10_plus);
+        assert!(hdr.warnings_for("out.MKV").is_empty());
+        assert_eq!(hdr.warnings_for("out.webm").len(), 1);
+    }
+}
@@ -0,0 +1,224 @@
+//! Just enough of ZMTP 3.0 to talk to the FFmpeg zmq/azmq filter as a REQ socket,
+//! so we don't need to link against libzmq
+//!
+//! https://rfc.zeromq.org/spec/23/
+
+use std::{io::{Read, Write}, net::TcpStream, time::Duration};
+
+const FLAG_MORE: u8 = 0x01;
+const FLAG_LONG: u8 = 0x02;
+const FLAG_COMMAND: u8 = 0x04;
+
+/// The replies of the filter are a few words, anything bigger than this is a broken or hostile peer
+const MAX_FRAME_LEN: u64 = 1024 * 1024;
+
+/// Send a single request & wait for the reply
+pub(crate) fn request(address: &str, message: &str) -> anyhow::Result<String> {
+    let mut stream = TcpStream::connect(address)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    stream.set_nodelay(true)?;
+
+    handshake(&mut stream)?;
+
+    // REQ sockets prefix every message with an empty delimiter frame
+    write_frame(&mut stream, FLAG_MORE, &[])?;
+    write_frame(&mut stream, 0, message.as_bytes())?;
+
+    // Skip the delimiter & any command frame, the last frame is the reply
+    let reply = loop {
+        let (flags, body) = read_frame(&mut stream)?;
+
+        if flags & (FLAG_COMMAND | FLAG_MORE) == 0 { break body };
+    };
+
+    Ok(String::from_utf8_lossy(&reply).into_owned())
+}
+
+fn handshake(stream: &mut TcpStream) -> anyhow::Result<()> {
+    let mut greeting = [0u8; 64];
+
+    // Signature
+    greeting[0] = 0xFF;
+    greeting[9] = 0x7F;
+    // Version 3.0
+    greeting[10] = 3;
+    greeting[11] = 0;
+    // Mechanism, as-server & filler stay zeroed
+    greeting[12..16].copy_from_slice(b"NULL");
+
+    stream.write_all(&greeting)?;
+
+    let mut peer_greeting = [0u8; 64];
+    stream.read_exact(&mut peer_greeting)?;
+
+    if peer_greeting[0] != 0xFF || peer_greeting[9] != 0x7F || peer_greeting[10] < 3 {
+        anyhow::bail!("The peer doesn't speak ZMTP 3");
+    }
+
+    let mut ready = Vec::new();
+    ready.push(5);
+    ready.extend(b"READY");
+    ready.push(11);
+    ready.extend(b"Socket-Type");
+    ready.extend(3u32.to_be_bytes());
+    ready.extend(b"REQ");
+
+    write_frame(stream, FLAG_COMMAND, &ready)?;
+
+    let (flags, body) = read_frame(stream)?;
+
+    if flags & FLAG_COMMAND == 0 || !body.get(1..6).is_some_and(|name| name == b"READY") {
+        anyhow::bail!("The peer refused the ZMTP handshake: {}", String::from_utf8_lossy(&body));
+    }
+
+    Ok(())
+}
+
+fn write_frame(stream: &mut TcpStream, flags: u8, body: &[u8]) -> std::io::Result<()> {
+    let mut frame = Vec::with_capacity(body.len() + 9);
+
+    match u8::try_from(body.len()) {
+        Ok(len) => frame.extend([flags, len]),
+        Err(_) => {
+            frame.push(flags | FLAG_LONG);
+            frame.extend((body.len() as u64).to_be_bytes());
+        },
+    }
+
+    frame.extend(body);
+
+    stream.write_all(&frame)
+}
+
+fn read_frame(stream: &mut TcpStream) -> std::io::Result<(u8, Vec<u8>)> {
+    let mut flags = [0u8; 1];
+    stream.read_exact(&mut flags)?;
+
+    let len = if flags[0] & FLAG_LONG != 0 {
+        let mut len = [0u8; 8];
+        stream.read_exact(&mut len)?;
+
+        u64::from_be_bytes(len)
+    } else {
+        let mut len = [0u8; 1];
+        stream.read_exact(&mut len)?;
+
+        len[0] as u64
+    };
+
+    if len > MAX_FRAME_LEN {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("The ZMTP frame is {len} bytes, more than {MAX_FRAME_LEN}")));
+    }
+
+    let mut body = vec![0u8; len as usize];
+    stream.read_exact(&mut body)?;
+
+    Ok((flags[0], body))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{net::TcpListener, thread::JoinHandle};
+
+    use super::*;
+
+    /// A connected pair of streams, the second one is accepted by `server` on its own thread
+    fn serve<T: Send + 'static>(server: impl FnOnce(TcpStream) -> T + Send + 'static) -> anyhow::Result<(String, JoinHandle<T>)> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?.to_string();
+
+        let handle = std::thread::spawn(move || server(listener.accept().expect("No client").0));
+
+        Ok((address, handle))
+    }
+
+    fn greeting() -> [u8; 64] {
+        let mut greeting = [0u8; 64];
+        greeting[0] = 0xFF;
+        greeting[9] = 0x7F;
+        greeting[10] = 3;
+        greeting[12..16].copy_from_slice(b"NULL");
+
+        greeting
+    }
+
+    #[test]
+    fn frame_round_trip() -> anyhow::Result<()> {
+        let (address, server) = serve(|mut stream| {
+            (0..3).map(|_| read_frame(&mut stream).expect("Can't read the frame")).collect::<Vec<_>>()
+        })?;
+
+        let mut client = TcpStream::connect(address)?;
+        write_frame(&mut client, FLAG_MORE, &[])?;
+        write_frame(&mut client, 0, b"volume volume 0.5")?;
+        write_frame(&mut client, FLAG_COMMAND, &[7; 300])?;
+
+        let frames = server.join().unwrap();
+
+        assert_eq!(frames[0], (FLAG_MORE, Vec::new()));
+        assert_eq!(frames[1], (0, b"volume volume 0.5".to_vec()));
+        assert_eq!(frames[2], (FLAG_COMMAND | FLAG_LONG, vec![7; 300]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn oversized_frame() -> anyhow::Result<()> {
+        let (address, server) = serve(|mut stream| read_frame(&mut stream).map_err(|error| error.kind()))?;
+
+        let mut client = TcpStream::connect(address)?;
+        client.write_all(&[FLAG_LONG])?;
+        client.write_all(&u64::MAX.to_be_bytes())?;
+
+        assert_eq!(server.join().unwrap(), Err(std::io::ErrorKind::InvalidData));
+
+        Ok(())
+    }
+
+    #[test]
+    fn request_reply() -> anyhow::Result<()> {
+        let (address, server) = serve(|mut stream| {
+            let mut peer_greeting = [0u8; 64];
+            stream.read_exact(&mut peer_greeting).unwrap();
+            stream.write_all(&greeting()).unwrap();
+
+            let (flags, ready) = read_frame(&mut stream).unwrap();
+            assert_eq!(flags, FLAG_COMMAND);
+            assert!(ready.ends_with(b"Socket-Type\0\0\0\x03REQ"));
+
+            write_frame(&mut stream, FLAG_COMMAND, b"\x05READY").unwrap();
+
+            let request = [read_frame(&mut stream).unwrap(), read_frame(&mut stream).unwrap()];
+
+            write_frame(&mut stream, FLAG_MORE, &[]).unwrap();
+            write_frame(&mut stream, 0, b"0 Success").unwrap();
+
+            request
+        })?;
+
+        assert_eq!(request(&address, "volume volume 0.5")?, "0 Success");
+        assert_eq!(server.join().unwrap()[1], (0, b"volume volume 0.5".to_vec()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejected_greeting() -> anyhow::Result<()> {
+        let (address, server) = serve(|mut stream| {
+            let mut peer_greeting = [0u8; 64];
+            stream.read_exact(&mut peer_greeting).unwrap();
+
+            // ZMTP 2
+            let mut greeting = greeting();
+            greeting[10] = 1;
+            stream.write_all(&greeting).unwrap();
+        })?;
+
+        let error = request(&address, "volume volume 0.5").unwrap_err();
+        server.join().unwrap();
+
+        assert_eq!(error.to_string(), "The peer doesn't speak ZMTP 3");
+
+        Ok(())
+    }
+}